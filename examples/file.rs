@@ -12,6 +12,10 @@ struct Opt {
     #[arg(short, long)]
     mime: bool,
 
+    /// Additional libmagic flags to set, such as `symlink` or `no-check-text`.
+    #[arg(short, long = "flag")]
+    flags: Vec<Flag>,
+
     #[arg(required=true, num_args=1..)]
     paths: Vec<PathBuf>,
 }
@@ -20,6 +24,7 @@ fn main() -> anyhow::Result<()> {
     let Opt {
         compressed,
         mime,
+        flags,
         paths,
     } = Opt::parse();
 
@@ -30,12 +35,13 @@ fn main() -> anyhow::Result<()> {
     if mime {
         config = config.set_flag(Flag::Mime);
     }
+    config = config.set_flags(flags);
     let pool = config.build_pool()?;
 
     // Let's parallelise for fun, since we have a thread-safe pool available.
     for res in paths
         .into_par_iter()
-        .map(|path| anyhow::Ok((path.clone(), pool.handle()?.file(path)?)))
+        .map(|path| anyhow::Ok((path.clone(), pool.file(&path)?)))
         .collect::<Vec<_>>()
         .into_iter()
     {
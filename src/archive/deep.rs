@@ -0,0 +1,212 @@
+use std::{io::Read, ops::ControlFlow};
+
+use super::Member;
+use crate::{Detection, Error, Handle};
+
+/// The limits on how much work [`Handle::deep_detect`] does within nested archives.
+///
+/// Archives can decompress to many times their own size, and can contain themselves, either
+/// directly or by way of other archives. Every budget is shared across the whole tree, so an input
+/// can't exceed them by spreading its content across many archives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeepBudget {
+    max_bytes: u64,
+    max_depth: usize,
+    max_entries: usize,
+}
+
+impl DeepBudget {
+    /// Creates the default budget, which allows 256 MiB of decompressed content across 10,000
+    /// members, nested up to 8 archives deep.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the total number of bytes that can be decompressed from the members of archives.
+    ///
+    /// Each member is held in memory while it's examined, so this also bounds the memory used.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Sets how many levels of archives are descended into. A value of zero only performs
+    /// detection on the input itself.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the total number of members that are examined, including any that can't be read.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+}
+
+impl Default for DeepBudget {
+    fn default() -> Self {
+        Self {
+            max_bytes: 256 * 1024 * 1024,
+            max_depth: 8,
+            max_entries: 10_000,
+        }
+    }
+}
+
+/// The limit of a [`DeepBudget`] that stopped an archive from being fully examined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BudgetLimit {
+    /// Decompressing the next member would have exceeded [`DeepBudget::with_max_bytes`].
+    Bytes,
+
+    /// The archive was nested deeper than [`DeepBudget::with_max_depth`].
+    Depth,
+
+    /// [`DeepBudget::with_max_entries`] members had already been examined.
+    Entries,
+}
+
+/// The result of [`Handle::deep_detect`]: the detection of an input, and of each member if the
+/// input is an archive.
+#[derive(Debug)]
+pub struct DeepDetection {
+    /// The detection of the input itself.
+    pub detection: Detection,
+
+    /// The name and detection of each file within the archive, in the order that they appear,
+    /// which is empty if the input isn't an archive that can be read.
+    ///
+    /// Errors for individual members are returned as [`Error::Detect`], with the member name as
+    /// the path, and errors reading the archive itself as [`Error::Archive`].
+    pub members: Vec<Result<(String, DeepDetection), Error>>,
+
+    /// The limit that was reached before every member could be examined, if any.
+    pub truncated: Option<BudgetLimit>,
+}
+
+/// The archive formats that can be descended into, identified by their content.
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    #[cfg(feature = "tar")]
+    Tar,
+    #[cfg(feature = "zip")]
+    Zip,
+}
+
+impl Format {
+    fn detect(buf: &[u8]) -> Option<Self> {
+        #[cfg(feature = "zip")]
+        if buf.starts_with(b"PK\x03\x04") || buf.starts_with(b"PK\x05\x06") {
+            return Some(Self::Zip);
+        }
+
+        #[cfg(feature = "tar")]
+        if super::tar::is_archive(buf) {
+            return Some(Self::Tar);
+        }
+
+        None
+    }
+
+    fn for_each(
+        self,
+        buf: &[u8],
+        visit: impl FnMut(Member<'_>) -> ControlFlow<()>,
+    ) -> Result<(), Error> {
+        match self {
+            #[cfg(feature = "tar")]
+            Self::Tar => super::tar::for_each(buf, visit),
+            #[cfg(feature = "zip")]
+            Self::Zip => super::zip::for_each(std::io::Cursor::new(buf), visit),
+        }
+    }
+}
+
+/// What's left of the budget, shared across the whole tree.
+struct Remaining {
+    bytes: u64,
+    entries: usize,
+}
+
+pub(crate) fn detect(
+    handle: &mut Handle,
+    buf: &[u8],
+    budget: DeepBudget,
+) -> Result<DeepDetection, Error> {
+    let mut remaining = Remaining {
+        bytes: budget.max_bytes,
+        entries: budget.max_entries,
+    };
+
+    descend(handle, buf, budget.max_depth, &mut remaining)
+}
+
+fn descend(
+    handle: &mut Handle,
+    buf: &[u8],
+    depth: usize,
+    remaining: &mut Remaining,
+) -> Result<DeepDetection, Error> {
+    let mut tree = DeepDetection {
+        detection: Detection::from(handle.buffer(buf)?),
+        members: Vec::new(),
+        truncated: None,
+    };
+
+    let Some(format) = Format::detect(buf) else {
+        return Ok(tree);
+    };
+    if depth == 0 {
+        tree.truncated = Some(BudgetLimit::Depth);
+        return Ok(tree);
+    }
+
+    let result = format.for_each(buf, |member| {
+        // Members that can't be read still count, or an archive full of them could produce an
+        // unbounded number of errors.
+        if remaining.entries == 0 {
+            tree.truncated = Some(BudgetLimit::Entries);
+            return ControlFlow::Break(());
+        }
+        remaining.entries -= 1;
+
+        let (name, file) = match member {
+            Ok(member) => member,
+            Err(e) => {
+                tree.members.push(Err(e));
+                return ControlFlow::Continue(());
+            }
+        };
+
+        // Reading one byte more than the budget allows distinguishes a member that exactly
+        // exhausts it from one that exceeds it.
+        let mut content = Vec::new();
+        let result = file
+            .take(remaining.bytes.saturating_add(1))
+            .read_to_end(&mut content)
+            .map_err(Error::ReadInput);
+        if content.len() as u64 > remaining.bytes {
+            remaining.bytes = 0;
+            tree.truncated = Some(BudgetLimit::Bytes);
+            return ControlFlow::Break(());
+        }
+        remaining.bytes -= content.len() as u64;
+
+        let child = result
+            .and_then(|_| descend(handle, &content, depth - 1, remaining))
+            .map_err(|source| Error::Detect {
+                path: name.clone().into(),
+                source: Box::new(source),
+            });
+        tree.members.push(child.map(|child| (name, child)));
+
+        ControlFlow::Continue(())
+    });
+    if let Err(e) = result {
+        tree.members.push(Err(e));
+    }
+
+    Ok(tree)
+}
@@ -0,0 +1,22 @@
+//! Detection of the members of archives, each format behind a feature of the same name.
+
+use std::io::Read;
+
+use crate::Error;
+
+/// The default number of bytes read from the start of each member for detection.
+pub(crate) const DEFAULT_PREFIX_LEN: usize = 256 * 1024;
+
+pub(crate) mod deep;
+#[cfg(feature = "tar")]
+pub(crate) mod tar;
+#[cfg(feature = "zip")]
+pub(crate) mod zip;
+
+#[cfg(feature = "zip")]
+pub(crate) fn archive_error(e: impl std::error::Error + Send + Sync + 'static) -> Error {
+    Error::Archive(std::io::Error::other(e))
+}
+
+/// A file within an archive, as its name and a reader over its content, or an error reading it.
+pub(crate) type Member<'a> = Result<(String, &'a mut dyn Read), Error>;
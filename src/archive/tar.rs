@@ -0,0 +1,149 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    ops::ControlFlow,
+};
+
+use tar::Archive;
+
+use super::{DEFAULT_PREFIX_LEN, Member};
+use crate::{Detection, Error, Handle};
+
+/// The compression formats that can wrap a tar archive, identified by their magic numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    fn detect(prefix: &[u8]) -> Option<Self> {
+        if prefix.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if prefix.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::Xz)
+        } else if prefix.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the name of the format, and the feature that enables support for it.
+    fn describe(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Gzip => ("gzip", "flate2"),
+            Self::Xz => ("xz", "xz2"),
+            Self::Zstd => ("zstd", "zstd"),
+        }
+    }
+}
+
+/// Wraps the reader in a decoder if the archive is compressed.
+fn decompress<'r, R: Read + 'r>(reader: R) -> Result<Box<dyn Read + 'r>, Error> {
+    let mut reader = BufReader::new(reader);
+    let compression = Compression::detect(reader.fill_buf().map_err(Error::ReadInput)?);
+
+    Ok(match compression {
+        None => Box::new(reader),
+
+        #[cfg(feature = "flate2")]
+        Some(Compression::Gzip) => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+
+        #[cfg(feature = "xz2")]
+        Some(Compression::Xz) => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+
+        #[cfg(feature = "zstd")]
+        Some(Compression::Zstd) => {
+            Box::new(zstd::stream::read::Decoder::with_buffer(reader).map_err(Error::Archive)?)
+        }
+
+        #[allow(unreachable_patterns)]
+        Some(compression) => {
+            let (name, feature) = compression.describe();
+            return Err(Error::Archive(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("archive is {name} compressed, which requires the {feature} feature"),
+            )));
+        }
+    })
+}
+
+/// The name of a file within an archive, and the result of detection on its content.
+pub(crate) type Entry = Result<(String, Detection), Error>;
+
+pub(crate) fn entries<R: Read>(handle: &mut Handle, reader: R) -> Result<Vec<Entry>, Error> {
+    let mut buf = Vec::new();
+    let mut results = Vec::new();
+    for_each(reader, |member| {
+        results.push(member.and_then(|(name, file)| {
+            buf.clear();
+            file.take(DEFAULT_PREFIX_LEN as u64)
+                .read_to_end(&mut buf)
+                .map_err(Error::ReadInput)
+                .and_then(|_| handle.buffer(&buf))
+                .map(|description| (name.clone(), Detection::from(description)))
+                .map_err(|source| Error::Detect {
+                    path: name.into(),
+                    source: Box::new(source),
+                })
+        }));
+
+        ControlFlow::Continue(())
+    })?;
+
+    Ok(results)
+}
+
+/// Returns `true` if the buffer contains a tar archive, possibly compressed in a format that's
+/// enabled.
+pub(crate) fn is_archive(buf: &[u8]) -> bool {
+    // Only ustar and GNU archives have a magic number, in the first header.
+    let mut header = Vec::new();
+    decompress(buf)
+        .and_then(|reader| {
+            reader
+                .take(512)
+                .read_to_end(&mut header)
+                .map_err(Error::ReadInput)
+        })
+        .is_ok_and(|_| header.get(257..262) == Some(b"ustar"))
+}
+
+/// Calls `visit` with each regular file in the archive, in order, until it breaks.
+///
+/// Errors reading an entry are passed to `visit`, and end the archive if the header itself
+/// couldn't be read.
+pub(crate) fn for_each<R: Read>(
+    reader: R,
+    mut visit: impl FnMut(Member<'_>) -> ControlFlow<()>,
+) -> Result<(), Error> {
+    let mut archive = Archive::new(decompress(reader)?);
+    for entry in archive.entries().map_err(Error::Archive)? {
+        // Once a header can't be read, there's no way to find the next entry.
+        let mut entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let _ = visit(Err(Error::Archive(e)));
+                break;
+            }
+        };
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        // The rest of the entry is skipped when the next entry is read.
+        let member = match entry.path() {
+            Ok(path) => Ok((
+                path.to_string_lossy().into_owned(),
+                &mut entry as &mut dyn Read,
+            )),
+            Err(e) => Err(Error::Archive(e)),
+        };
+        if visit(member).is_break() {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::{
+    fmt::Debug,
+    io::{Read, Seek},
+    ops::ControlFlow,
+};
+
+use zip::ZipArchive;
+
+use super::{DEFAULT_PREFIX_LEN, Member, archive_error};
+use crate::{BYTES_MAX, Detection, Error, Handle};
+
+/// An iterator over the members of a ZIP archive, as returned by
+/// [`Handle::zip_entries`][crate::Handle::zip_entries].
+///
+/// This yields the name of each member along with the result of detection on the start of its
+/// decompressed content. Directories are skipped.
+pub struct ZipEntries<'h, R> {
+    archive: ZipArchive<R>,
+    buf: Vec<u8>,
+    handle: &'h mut Handle,
+    index: usize,
+    prefix_len: usize,
+}
+
+impl<'h, R: Read + Seek> ZipEntries<'h, R> {
+    pub(crate) fn new(handle: &'h mut Handle, reader: R) -> Result<Self, Error> {
+        Ok(Self {
+            archive: ZipArchive::new(reader).map_err(archive_error)?,
+            buf: Vec::new(),
+            handle,
+            index: 0,
+            prefix_len: DEFAULT_PREFIX_LEN,
+        })
+    }
+
+    /// Sets the number of bytes decompressed from the start of each member for detection.
+    ///
+    /// By default, 256 KiB is read, which is enough for libmagic to identify almost every format,
+    /// while bounding the work done on each member of an archive that decompresses to far more
+    /// than its own size. The length is clamped between 1 and [`BYTES_MAX`].
+    pub fn with_prefix_len(mut self, len: usize) -> Self {
+        self.prefix_len = len.clamp(1, BYTES_MAX);
+        self
+    }
+}
+
+impl<R: Read + Seek> Iterator for ZipEntries<'_, R> {
+    type Item = Result<(String, Detection), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.archive.len() {
+            let index = self.index;
+            self.index += 1;
+
+            // Members that can't be opened, such as those that are encrypted, are still
+            // attributed to their name if the central directory has one.
+            let name = self.archive.name_for_index(index).map(str::to_string);
+            let error = |name: Option<String>, source| match name {
+                Some(name) => Error::Detect {
+                    path: name.into(),
+                    source: Box::new(source),
+                },
+                None => source,
+            };
+
+            let mut member = match self.archive.by_index(index) {
+                Ok(member) => member,
+                Err(e) => return Some(Err(error(name, archive_error(e)))),
+            };
+            if member.is_dir() {
+                continue;
+            }
+            let name = member.name().to_string();
+
+            self.buf.clear();
+            let result = (&mut member)
+                .take(self.prefix_len as u64)
+                .read_to_end(&mut self.buf)
+                .map_err(Error::ReadInput)
+                .and_then(|_| self.handle.buffer(&self.buf))
+                .map(Detection::from)
+                .map_err(|source| error(Some(name.clone()), source));
+
+            return Some(result.map(|detection| (name, detection)));
+        }
+
+        None
+    }
+}
+
+impl<R> Debug for ZipEntries<'_, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZipEntries")
+            .field("index", &self.index)
+            .field("prefix_len", &self.prefix_len)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Calls `visit` with each file in the archive, in order, until it breaks.
+///
+/// Members that can't be opened are passed to `visit` as errors, attributed to their name if the
+/// central directory has one.
+pub(crate) fn for_each<R: Read + Seek>(
+    reader: R,
+    mut visit: impl FnMut(Member<'_>) -> ControlFlow<()>,
+) -> Result<(), Error> {
+    let mut archive = ZipArchive::new(reader).map_err(archive_error)?;
+    for index in 0..archive.len() {
+        let name = archive.name_for_index(index).map(str::to_string);
+        let flow = match archive.by_index(index) {
+            Ok(member) if member.is_dir() => continue,
+            Ok(mut member) => {
+                let name = member.name().to_string();
+                visit(Ok((name, &mut member)))
+            }
+            Err(e) => visit(Err(match name {
+                Some(name) => Error::Detect {
+                    path: name.into(),
+                    source: Box::new(archive_error(e)),
+                },
+                None => archive_error(e),
+            })),
+        };
+
+        if flow.is_break() {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::{fs::File, io::Read, path::Path};
+
+use crate::{BYTES_MAX, Detection, Error, Pool};
+
+/// A file type detection backend.
+///
+/// [`Pool`] implements this trait using libmagic. Code that only needs to classify content can
+/// accept a `&dyn Backend` (or a generic `B: Backend`) instead of a pool, which allows the
+/// backend to be chosen at runtime: for example, falling back to the pure Rust `InferBackend`
+/// (available with the `infer` feature) when libmagic can't be loaded with the `dlopen` feature,
+/// or comparing the results of different backends.
+///
+/// Note that different backends return different descriptions for the same content; libmagic's
+/// descriptions depend on the flags that the pool was configured with, whereas `InferBackend`
+/// only returns MIME types.
+pub trait Backend: Send + Sync {
+    /// Returns a short, human readable name for the backend, such as `libmagic`.
+    fn name(&self) -> &'static str;
+
+    /// Performs detection on the given buffer.
+    fn buffer(&self, buf: &[u8]) -> Result<Detection, Error>;
+
+    /// Performs detection on the given file.
+    ///
+    /// By default, this reads up to [`BYTES_MAX`] bytes from the start of the file and calls
+    /// [`Backend::buffer`]. Any error is wrapped in [`Error::Detect`], which includes the path.
+    fn file(&self, path: &Path) -> Result<Detection, Error> {
+        let mut buf = Vec::new();
+        File::open(path)
+            .and_then(|file| file.take(BYTES_MAX as u64).read_to_end(&mut buf))
+            .map_err(Error::ReadInput)
+            .and_then(|_| self.buffer(&buf))
+            .map_err(|source| Error::Detect {
+                path: path.to_path_buf(),
+                source: Box::new(source),
+            })
+    }
+}
+
+impl Backend for Pool {
+    fn name(&self) -> &'static str {
+        "libmagic"
+    }
+
+    fn buffer(&self, buf: &[u8]) -> Result<Detection, Error> {
+        Pool::buffer(self, buf).map(Detection::from)
+    }
+
+    fn file(&self, path: &Path) -> Result<Detection, Error> {
+        Pool::file(self, path).map(Detection::from)
+    }
+}
+
+/// A pure Rust [`Backend`] that uses the [`infer`] crate to detect MIME types from magic numbers.
+///
+/// This recognises far fewer types than libmagic, and doesn't attempt to identify text, but
+/// requires neither libmagic nor a magic database. Content that isn't recognised is described as
+/// `application/octet-stream`, and empty content as `application/x-empty`, matching libmagic with
+/// [`Flag::MimeType`][crate::Flag::MimeType].
+#[cfg(feature = "infer")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InferBackend;
+
+#[cfg(feature = "infer")]
+impl Backend for InferBackend {
+    fn name(&self) -> &'static str {
+        "infer"
+    }
+
+    fn buffer(&self, buf: &[u8]) -> Result<Detection, Error> {
+        Ok(Detection::new(match infer::get(buf) {
+            Some(kind) => kind.mime_type(),
+            None if buf.is_empty() => "application/x-empty",
+            None => "application/octet-stream",
+        }))
+    }
+}
@@ -0,0 +1,63 @@
+//! A standalone detection daemon that serves [`mojique::Daemon`] requests on a Unix socket.
+//!
+//! Usage: `mojique-daemon --socket PATH`
+//!
+//! The daemon uses the system magic database to return MIME types. Since clients pass open file
+//! descriptors rather than paths, the daemon should be run as an unprivileged user with no access
+//! to the files being detected.
+
+use std::{os::unix::net::UnixListener, path::PathBuf, process::ExitCode};
+
+use mojique::{Config, Daemon, DefaultConfig, Flag};
+
+const USAGE: &str = "usage: mojique-daemon --socket PATH";
+
+fn main() -> ExitCode {
+    let mut socket: Option<PathBuf> = None;
+
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--socket") => match args.next() {
+                Some(value) => socket = Some(value.into()),
+                None => {
+                    eprintln!("missing value for --socket\n{USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            Some("-h" | "--help") => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ => {
+                eprintln!("unexpected argument: {}\n{USAGE}", arg.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let Some(socket) = socket else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    match run(socket) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mojique-daemon: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(socket: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+
+    let listener = UnixListener::bind(&socket)?;
+    eprintln!("listening on {}", socket.display());
+    Daemon::new(pool).serve(listener)?;
+
+    Ok(())
+}
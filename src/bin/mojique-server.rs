@@ -0,0 +1,74 @@
+//! A standalone HTTP sidecar that exposes detection via [`mojique::Server`].
+//!
+//! Usage: `mojique-server [--listen ADDR] [--root DIR]`
+//!
+//! The server listens on `127.0.0.1:8080` by default, and uses the system magic database to
+//! return MIME types. `GET /detect?path=` is only enabled if `--root` is given.
+
+use std::{net::SocketAddr, path::PathBuf, process::ExitCode};
+
+use mojique::{Config, DefaultConfig, Flag, Server};
+use tokio::net::TcpListener;
+
+const USAGE: &str = "usage: mojique-server [--listen ADDR] [--root DIR]";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut listen = SocketAddr::from(([127, 0, 0, 1], 8080));
+    let mut root: Option<PathBuf> = None;
+
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        let value = match arg.to_str() {
+            Some("--listen" | "--root") => args.next(),
+            Some("-h" | "--help") => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ => {
+                eprintln!("unexpected argument: {}\n{USAGE}", arg.display());
+                return ExitCode::FAILURE;
+            }
+        };
+        let Some(value) = value else {
+            eprintln!("missing value for {}\n{USAGE}", arg.display());
+            return ExitCode::FAILURE;
+        };
+
+        if arg == "--root" {
+            root = Some(value.into());
+        } else {
+            match value.to_str().and_then(|value| value.parse().ok()) {
+                Some(addr) => listen = addr,
+                None => {
+                    eprintln!("invalid listen address: {}", value.display());
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    }
+
+    match run(listen, root).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mojique-server: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(listen: SocketAddr, root: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+    let mut server = Server::new(pool);
+    if let Some(root) = root {
+        server = server.with_root(root);
+    }
+
+    let listener = TcpListener::bind(listen).await?;
+    eprintln!("listening on {}", listener.local_addr()?);
+    server.serve(listener).await?;
+
+    Ok(())
+}
@@ -0,0 +1,579 @@
+//! A command line tool that mirrors the common options of `file(1)`, using a [`mojique::Pool`].
+//!
+//! Usage: `mojique [OPTIONS] FILE...`
+//!
+//! Each file is described on its own line, prefixed by its path unless `--brief` is given. A path
+//! of `-` reads from stdin. Files that can't be described are reported on stderr, and the
+//! remaining files are still described, but the exit status is non-zero.
+//!
+//! Paths can also be read from a file with `--files-from`, one per line, or `--files0-from`,
+//! terminated by NUL bytes as written by `find -print0`; either can be `-` to read paths from
+//! stdin. Paths given as arguments are described first, and lists are read as they're described,
+//! so there's no limit on their length.
+//!
+//! `--recursive` scans every path as a directory tree with a [`mojique::Scanner`], using
+//! `--threads` worker threads, which defaults to one per CPU. Results are written as each file is
+//! described, so they aren't in any particular order. While the output is redirected, the number
+//! of files scanned so far is shown on stderr, if it's a terminal, as a progress bar if the
+//! `indicatif` feature is enabled.
+//!
+//! `--expect` gives a MIME type that every file must match, such as `text/plain` or `image/*`, and
+//! can be repeated to allow several. Files that don't match any are reported on stderr, and the
+//! exit status is non-zero, which allows the tool to be used as a check in CI or pre-commit hooks.
+//! Since patterns can only match MIME types, `--mime-type` is implied unless `--mime` is given.
+//!
+//! `--format json`, `jsonl`, or `csv` writes the results as per [`mojique::JsonLinesWriter`] and
+//! [`mojique::CsvWriter`], with `json` wrapping the same objects in an array. The JSON formats
+//! include files that couldn't be described as objects with an `error`, rather than reporting them
+//! on stderr. `--print0` separates the path and description, and terminates each description, with
+//! a NUL byte rather than `: ` and a newline, so the output can be split reliably by `xargs -0`.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fs::File,
+    io::{self, BufRead, BufReader, IsTerminal, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+#[cfg(not(feature = "indicatif"))]
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use mojique::{
+    Config, CsvWriter, DefaultConfig, Detection, Error, FileConfig, Flag, JsonLinesWriter, Pool,
+    ScanProgress, ScanSink, Scanner,
+};
+
+const USAGE: &str = "usage: mojique [OPTIONS] FILE...
+
+options:
+  -b, --brief             don't prefix each description with the path
+  -i, --mime              output MIME type and encoding strings
+      --mime-type         output only the MIME type
+      --mime-encoding     output only the MIME encoding
+      --extension         output valid extensions for each file
+  -k, --keep-going        don't stop at the first match
+  -z, --uncompress        try to look inside compressed files
+  -L, --dereference       follow symlinks
+  -h, --no-dereference    don't follow symlinks (default)
+  -m, --magic-file LIST   use the colon-separated list of magic files instead of the default
+  -f, --files-from FILE   read paths to describe from FILE, one per line, or stdin if FILE is -
+      --files0-from FILE  read NUL-terminated paths to describe from FILE, or stdin if FILE is -
+  -r, --recursive         describe every file within directories, in parallel
+      --threads N         use N worker threads with --recursive (default: one per CPU)
+      --expect PATTERN    fail unless every file matches a MIME type pattern, such as image/*
+      --format FORMAT     output text (default), json, jsonl, or csv
+  -0, --print0            separate and terminate text output with NUL bytes
+      --help              display this help and exit";
+
+/// The formats that results can be written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Json,
+    JsonLines,
+    #[default]
+    Text,
+}
+
+/// The options given on the command line.
+#[derive(Debug, Default)]
+struct Options {
+    brief: bool,
+    expect: Vec<String>,
+    flags: Vec<Flag>,
+    format: Format,
+    lists: Vec<(PathBuf, u8)>,
+    magic: Vec<PathBuf>,
+    paths: Vec<PathBuf>,
+    print0: bool,
+    recursive: bool,
+    threads: Option<usize>,
+}
+
+impl Options {
+    /// Parses the arguments, returning `None` if help was requested.
+    fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Option<Self>, String> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let Some(arg_str) = arg.to_str() else {
+                options.paths.push(arg.into());
+                continue;
+            };
+
+            if arg_str == "--" {
+                options.paths.extend(args.by_ref().map(PathBuf::from));
+            } else if let Some(long) = arg_str.strip_prefix("--") {
+                let (name, value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(OsString::from(value))),
+                    None => (long, None),
+                };
+                if name == "help" {
+                    return Ok(None);
+                }
+
+                let takes_value = matches!(
+                    name,
+                    "expect" | "files-from" | "files0-from" | "format" | "magic-file" | "threads"
+                );
+                let value = match (takes_value, value) {
+                    (true, Some(value)) => Some(value),
+                    (true, None) => Some(
+                        args.next()
+                            .ok_or_else(|| format!("missing value for --{name}"))?,
+                    ),
+                    (false, Some(_)) => return Err(format!("--{name} doesn't take a value")),
+                    (false, None) => None,
+                };
+                options.apply(name, value)?;
+            } else if let Some(shorts) = arg_str.strip_prefix('-')
+                && !shorts.is_empty()
+            {
+                for (i, short) in shorts.char_indices() {
+                    let name = match short {
+                        '0' => "print0",
+                        'b' => "brief",
+                        'i' => "mime",
+                        'k' => "keep-going",
+                        'z' => "uncompress",
+                        'r' => "recursive",
+                        'L' => "dereference",
+                        'h' => "no-dereference",
+                        'f' | 'm' => {
+                            // The value is either the rest of this argument, or the next one.
+                            let rest = &shorts[i + 1..];
+                            let value = if rest.is_empty() {
+                                args.next()
+                                    .ok_or_else(|| format!("missing value for -{short}"))?
+                            } else {
+                                rest.into()
+                            };
+                            let name = if short == 'f' {
+                                "files-from"
+                            } else {
+                                "magic-file"
+                            };
+                            options.apply(name, Some(value))?;
+                            break;
+                        }
+                        _ => return Err(format!("unknown option: -{short}")),
+                    };
+                    options.apply(name, None)?;
+                }
+            } else {
+                options.paths.push(arg.into());
+            }
+        }
+
+        if options.paths.is_empty() && options.lists.is_empty() {
+            return Err("no files given".into());
+        }
+        let stdin = Path::new("-");
+        if options
+            .lists
+            .iter()
+            .filter(|(list, _)| list == stdin)
+            .count()
+            + usize::from(options.paths.iter().any(|path| path == stdin))
+            > 1
+        {
+            return Err("stdin can only be read once".into());
+        }
+        if options.recursive && options.paths.iter().any(|path| path == stdin) {
+            return Err("stdin can't be scanned recursively".into());
+        }
+        if options.threads.is_some() && !options.recursive {
+            return Err("--threads can only be used with --recursive".into());
+        }
+        if !options.expect.is_empty()
+            && !options.flags.contains(&Flag::Mime)
+            && !options.flags.contains(&Flag::MimeType)
+        {
+            options.flags.push(Flag::MimeType);
+        }
+        if options.print0 && options.format != Format::Text {
+            return Err("--print0 can only be used with the text format".into());
+        }
+
+        Ok(Some(options))
+    }
+
+    /// Applies a single option, by its long name.
+    fn apply(&mut self, name: &str, value: Option<OsString>) -> Result<(), String> {
+        match name {
+            "brief" => self.brief = true,
+            "mime" => self.flags.push(Flag::Mime),
+            "mime-type" => self.flags.push(Flag::MimeType),
+            "mime-encoding" => self.flags.push(Flag::MimeEncoding),
+            #[cfg(feature = "v5-23")]
+            "extension" => self.flags.push(Flag::Extension),
+            "keep-going" => self.flags.push(Flag::Continue),
+            "uncompress" => self.flags.push(Flag::Compress),
+            "dereference" => self.flags.push(Flag::Symlink),
+            "no-dereference" => self.flags.retain(|flag| *flag != Flag::Symlink),
+            "format" => {
+                self.format = match value.as_ref().and_then(|value| value.to_str()) {
+                    Some("csv") => Format::Csv,
+                    Some("json") => Format::Json,
+                    Some("jsonl") => Format::JsonLines,
+                    Some("text") => Format::Text,
+                    _ => return Err("--format must be text, json, jsonl, or csv".into()),
+                }
+            }
+            "expect" => self.expect.push(
+                value
+                    .unwrap_or_default()
+                    .into_string()
+                    .map_err(|_| "--expect patterns must be valid UTF-8")?,
+            ),
+            "files-from" => self.lists.push((value.unwrap_or_default().into(), b'\n')),
+            "files0-from" => self.lists.push((value.unwrap_or_default().into(), b'\0')),
+            "print0" => self.print0 = true,
+            "recursive" => self.recursive = true,
+            "threads" => {
+                let threads = value
+                    .as_ref()
+                    .and_then(|value| value.to_str()?.parse().ok());
+                match threads {
+                    Some(threads) if threads > 0 => self.threads = Some(threads),
+                    _ => return Err("--threads must be a positive number".into()),
+                }
+            }
+            "magic-file" => {
+                let value = value.unwrap_or_default();
+                self.magic.extend(
+                    value
+                        .to_str()
+                        .ok_or("magic file paths must be valid UTF-8")?
+                        .split(':')
+                        .filter(|path| !path.is_empty())
+                        .map(PathBuf::from),
+                );
+            }
+            _ => return Err(format!("unknown option: --{name}")),
+        }
+
+        Ok(())
+    }
+
+    fn build_pool(&self) -> Result<Pool, mojique::Error> {
+        if self.magic.is_empty() {
+            DefaultConfig::default()
+                .set_flags(self.flags.clone())
+                .build_pool()
+        } else {
+            FileConfig::from_iter(&self.magic)
+                .set_flags(self.flags.clone())
+                .build_pool()
+        }
+    }
+
+    /// Returns the sink that results are written to, in the chosen format.
+    fn sink<'a>(&self, out: impl Write + 'a) -> Box<dyn ScanSink + 'a> {
+        match self.format {
+            Format::Csv => Box::new(CsvWriter::new(out)),
+            Format::Json => Box::new(JsonArray::new(out)),
+            Format::JsonLines => Box::new(JsonLinesWriter::new(out)),
+            Format::Text => Box::new(Text {
+                brief: self.brief,
+                out,
+                print0: self.print0,
+            }),
+        }
+    }
+}
+
+/// Writes results in the same format as `file(1)`.
+struct Text<W> {
+    brief: bool,
+    out: W,
+    print0: bool,
+}
+
+impl<W: Write> ScanSink for Text<W> {
+    fn record(&mut self, path: &Path, result: &Result<Detection, Error>) -> io::Result<()> {
+        let Ok(detection) = result else {
+            return Ok(());
+        };
+
+        let (separator, terminator) = if self.print0 {
+            ("\0", "\0")
+        } else {
+            (": ", "\n")
+        };
+        if !self.brief {
+            write!(self.out, "{}{separator}", path.display())?;
+        }
+        write!(self.out, "{detection}{terminator}")
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Writes results as a JSON array of the objects written by [`JsonLinesWriter`].
+struct JsonArray<W> {
+    out: W,
+    records: usize,
+}
+
+impl<W: Write> JsonArray<W> {
+    fn new(out: W) -> Self {
+        Self { out, records: 0 }
+    }
+}
+
+impl<W: Write> ScanSink for JsonArray<W> {
+    fn record(&mut self, path: &Path, result: &Result<Detection, Error>) -> io::Result<()> {
+        let mut line = JsonLinesWriter::new(Vec::new());
+        line.record(path, result)?;
+        let line = line.into_inner();
+
+        self.out.write_all(if self.records == 0 {
+            b"[\n  "
+        } else {
+            b",\n  "
+        })?;
+        self.out.write_all(line.trim_ascii_end())?;
+        self.records += 1;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out
+            .write_all(if self.records == 0 { b"[]\n" } else { b"\n]\n" })?;
+        self.out.flush()
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args_os().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(options) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("mojique: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Reports the progress of a recursive scan on stderr.
+#[derive(Clone)]
+enum Progress {
+    Hidden,
+
+    /// A progress bar, drawn by `indicatif`.
+    #[cfg(feature = "indicatif")]
+    Bar(indicatif::ProgressBar),
+
+    /// A single line of counts, along with when it was last drawn.
+    #[cfg(not(feature = "indicatif"))]
+    Line(Arc<Mutex<Instant>>),
+}
+
+impl Progress {
+    /// How often the line of counts is redrawn.
+    #[cfg(not(feature = "indicatif"))]
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Creates a reporter, which only reports anything if stderr is a terminal and stdout isn't,
+    /// since results written to the same terminal already show the progress.
+    fn new(enabled: bool) -> Self {
+        if !enabled || !io::stderr().is_terminal() || io::stdout().is_terminal() {
+            return Self::Hidden;
+        }
+
+        #[cfg(feature = "indicatif")]
+        {
+            let style = indicatif::ProgressStyle::with_template(
+                "{spinner} [{elapsed_precise}] {bar:40} {pos}/{len} files, {msg}",
+            )
+            .expect("progress template is valid");
+            Self::Bar(indicatif::ProgressBar::new(0).with_style(style))
+        }
+
+        #[cfg(not(feature = "indicatif"))]
+        Self::Line(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    /// Updates the progress. This is called by the scanner threads.
+    fn update(&self, progress: &ScanProgress) {
+        match self {
+            Self::Hidden => {}
+
+            #[cfg(feature = "indicatif")]
+            Self::Bar(bar) => {
+                bar.set_length(progress.discovered);
+                bar.set_position(progress.scanned);
+                bar.set_message(format!(
+                    "{}, {} errors",
+                    indicatif::HumanBytes(progress.bytes),
+                    progress.errors
+                ));
+            }
+
+            // If another thread is already drawing, there's no need to draw again.
+            #[cfg(not(feature = "indicatif"))]
+            Self::Line(last_drawn) => {
+                if let Ok(mut last_drawn) = last_drawn.try_lock()
+                    && last_drawn.elapsed() >= Self::INTERVAL
+                {
+                    eprint!(
+                        "\r\x1b[K{} of {} files, {} bytes, {} errors",
+                        progress.scanned, progress.discovered, progress.bytes, progress.errors
+                    );
+                    *last_drawn = Instant::now();
+                }
+            }
+        }
+    }
+
+    /// Hides the progress while `f` writes to stderr.
+    fn suspend(&self, f: impl FnOnce()) {
+        match self {
+            Self::Hidden => f(),
+
+            #[cfg(feature = "indicatif")]
+            Self::Bar(bar) => bar.suspend(f),
+
+            // The line is redrawn by the next update.
+            #[cfg(not(feature = "indicatif"))]
+            Self::Line(last_drawn) => {
+                let _last_drawn = last_drawn.lock().unwrap_or_else(PoisonError::into_inner);
+                eprint!("\r\x1b[K");
+                f();
+            }
+        }
+    }
+
+    /// Removes the progress once the scan is complete.
+    fn finish(&self) {
+        match self {
+            Self::Hidden => {}
+
+            #[cfg(feature = "indicatif")]
+            Self::Bar(bar) => bar.finish_and_clear(),
+
+            #[cfg(not(feature = "indicatif"))]
+            Self::Line(_) => self.suspend(|| {}),
+        }
+    }
+}
+
+/// Calls `f` with each path within the given lists, in order.
+fn for_each_listed(
+    lists: &[(PathBuf, u8)],
+    mut f: impl FnMut(&Path) -> io::Result<()>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (list, delimiter) in lists {
+        let reader: Box<dyn BufRead> = if list == Path::new("-") {
+            Box::new(io::stdin().lock())
+        } else {
+            let file = File::open(list)
+                .map_err(|e| format!("reading paths from {}: {e}", list.display()))?;
+            Box::new(BufReader::new(file))
+        };
+
+        for path in reader.split(*delimiter) {
+            let path = path.map_err(|e| format!("reading paths from {}: {e}", list.display()))?;
+            if !path.is_empty() {
+                f(Path::new(OsStr::from_bytes(&path)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Describes every path, returning `false` if any couldn't be described.
+fn run(options: Options) -> Result<bool, Box<dyn std::error::Error>> {
+    let pool = options.build_pool()?;
+
+    // The JSON formats include errors in the output, but the others can only report them.
+    let report_errors = matches!(options.format, Format::Csv | Format::Text);
+
+    let mut sink = options.sink(io::stdout().lock());
+    let progress = Progress::new(options.recursive);
+    let mut success = true;
+    let mut record = |path: &Path, result: Result<Detection, Error>| {
+        if let Err(e) = &result {
+            if report_errors {
+                progress.suspend(|| eprintln!("mojique: {e}"));
+            }
+            success = false;
+        }
+        if let Ok(detection) = &result
+            && !options.expect.is_empty()
+            && !options
+                .expect
+                .iter()
+                .any(|pattern| detection.matches(pattern))
+        {
+            progress.suspend(|| {
+                eprintln!(
+                    "mojique: {}: {detection} doesn't match any expected type",
+                    path.display()
+                )
+            });
+            success = false;
+        }
+        sink.record(path, &result)
+    };
+
+    if options.recursive {
+        let mut roots = options.paths.clone();
+        for_each_listed(&options.lists, |path| {
+            roots.push(path.to_path_buf());
+            Ok(())
+        })?;
+
+        let reporter = progress.clone();
+        let mut scanner = Scanner::new(pool)
+            .with_roots(roots)
+            .with_progress(move |progress| reporter.update(progress));
+        if let Some(threads) = options.threads {
+            scanner = scanner.with_threads(threads);
+        }
+        for (path, result) in scanner.scan()? {
+            record(&path, result)?;
+        }
+    } else {
+        let mut handle = pool.handle()?;
+        let mut describe = |path: &Path| {
+            let result = if path == Path::new("-") {
+                handle.read(io::stdin().lock())
+            } else {
+                handle.file(path)
+            };
+            record(path, result.map(Detection::from))
+        };
+
+        for path in &options.paths {
+            describe(path)?;
+        }
+        for_each_listed(&options.lists, describe)?;
+    }
+
+    progress.finish();
+    sink.finish()?;
+
+    Ok(success)
+}
@@ -0,0 +1,129 @@
+use std::{
+    fmt::Debug,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use http_body::{Body, Frame, SizeHint};
+
+/// An [`http_body::Body`] returned by [`Pool::sniff_body`][crate::Pool::sniff_body].
+///
+/// This yields the data that was buffered for detection, followed by the rest of the original
+/// body, so it can be forwarded unchanged.
+pub struct ReplayBody<B> {
+    prefix: Option<Bytes>,
+    trailers: Option<Frame<Bytes>>,
+    body: B,
+}
+
+impl<B> ReplayBody<B> {
+    /// Returns the data that was buffered for detection but not yet yielded, along with the
+    /// original body.
+    ///
+    /// If the original body ended with trailers while the prefix was being buffered, the
+    /// trailers are lost.
+    pub fn into_parts(self) -> (Option<Bytes>, B) {
+        (self.prefix, self.body)
+    }
+}
+
+impl<B> Debug for ReplayBody<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplayBody")
+            .field("prefix", &self.prefix.as_ref().map(Bytes::len))
+            .field("trailers", &self.trailers.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B> Body for ReplayBody<B>
+where
+    B: Body + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        if let Some(prefix) = self.prefix.take() {
+            return Poll::Ready(Some(Ok(Frame::data(prefix))));
+        }
+        if let Some(trailers) = self.trailers.take() {
+            return Poll::Ready(Some(Ok(trailers)));
+        }
+
+        Pin::new(&mut self.body)
+            .poll_frame(cx)
+            .map(|frame| frame.map(|frame| frame.map(|frame| frame.map_data(into_bytes))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.prefix.is_none() && self.trailers.is_none() && self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let prefix = self.prefix.as_ref().map_or(0, |prefix| prefix.len() as u64);
+        let inner = self.body.size_hint();
+
+        let mut hint = SizeHint::new();
+        hint.set_lower(inner.lower().saturating_add(prefix));
+        if let Some(upper) = inner.upper().and_then(|upper| upper.checked_add(prefix)) {
+            hint.set_upper(upper);
+        }
+        hint
+    }
+}
+
+/// Reads whole data frames from the body until at least [`BYTES_MAX`][crate::BYTES_MAX] bytes
+/// have been read or the body ends, and returns a [`ReplayBody`] that will yield them again.
+///
+/// The prefix is also returned separately, so that detection can be performed on it.
+pub(crate) async fn read_prefix<B>(mut body: B) -> Result<(Bytes, ReplayBody<B>), B::Error>
+where
+    B: Body + Unpin,
+{
+    let mut chunks = Vec::new();
+    let mut len = 0;
+    let mut trailers = None;
+    while len < crate::BYTES_MAX {
+        match std::future::poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+            Some(frame) => match frame?.into_data() {
+                Ok(data) => {
+                    let chunk = into_bytes(data);
+                    len += chunk.len();
+                    chunks.push(chunk);
+                }
+                Err(frame) => {
+                    // Trailers are always the last frame, so there's nothing left to buffer.
+                    if frame.is_trailers() {
+                        trailers = Some(frame.map_data(into_bytes));
+                        break;
+                    }
+                }
+            },
+            None => break,
+        }
+    }
+
+    let prefix: Bytes = if chunks.len() == 1 {
+        chunks.swap_remove(0)
+    } else {
+        chunks.concat().into()
+    };
+
+    Ok((
+        prefix.clone(),
+        ReplayBody {
+            prefix: (!prefix.is_empty()).then_some(prefix),
+            trailers,
+            body,
+        },
+    ))
+}
+
+fn into_bytes(mut data: impl Buf) -> Bytes {
+    data.copy_to_bytes(data.remaining())
+}
@@ -0,0 +1,289 @@
+use std::{
+    fmt::Debug,
+    hash::{BuildHasher, RandomState},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{Backend, Detection, Error, Pool};
+
+/// The default number of detections held by a [`CachedPool`].
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// A [`Pool`] that caches detections in memory, so that identical inputs are only ever passed to
+/// libmagic once.
+///
+/// Buffers are keyed by a hash of their content and their length, so the same content is only
+/// classified once regardless of where it came from. Files are keyed by their path, along with the
+/// device, inode, size, and modification time from their metadata, so a file that is modified or
+/// replaced is classified again. Symlinks and special files, such as FIFOs and devices, are never
+/// cached.
+///
+/// The least recently used detection is evicted once the cache is full. If the `moka` feature is
+/// enabled, the cache is provided by [`moka`][moka], which scales better under heavy concurrent
+/// use, but evicts in a slightly different order.
+///
+/// Errors are never cached. Clones of a `CachedPool` share the same cache.
+///
+/// The content hash is keyed randomly for each cache, so collisions can't be engineered by
+/// whoever provides the content, but they are still possible in principle. Callers that can't
+/// tolerate the occasional wrong result shouldn't use a cache.
+///
+/// [moka]: https://crates.io/crates/moka
+#[derive(Clone)]
+pub struct CachedPool {
+    pool: Pool,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    hasher: RandomState,
+    store: Store,
+}
+
+impl CachedPool {
+    /// Creates a cache around the given pool, holding up to 1024 detections.
+    pub fn new(pool: Pool) -> Self {
+        Self::with_store(pool, DEFAULT_CAPACITY)
+    }
+
+    /// Sets the maximum number of detections held by the cache, replacing the cache.
+    ///
+    /// A capacity of zero disables caching.
+    pub fn with_capacity(self, capacity: usize) -> Self {
+        Self::with_store(self.pool, capacity)
+    }
+
+    fn with_store(pool: Pool, capacity: usize) -> Self {
+        Self {
+            pool,
+            shared: Arc::new(Shared {
+                hasher: RandomState::new(),
+                store: Store::new(capacity),
+            }),
+        }
+    }
+
+    /// Returns the underlying pool.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    /// Performs detection on the given buffer, returning the cached detection if the same content
+    /// has been seen before.
+    pub fn buffer(&self, buf: &[u8]) -> Result<Detection, Error> {
+        let key = Key::Buffer {
+            hash: self.shared.hasher.hash_one(buf),
+            len: buf.len(),
+        };
+
+        self.cached(key, || self.pool.buffer(buf))
+    }
+
+    /// Performs detection on the given file, returning the cached detection if the file hasn't
+    /// changed since it was last seen.
+    pub fn file(&self, path: impl AsRef<Path>) -> Result<Detection, Error> {
+        let path = path.as_ref();
+
+        // Anything that can't be cached, including paths that can't be read, is left to libmagic,
+        // which will describe or report it as usual.
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_file() => {
+                let key = Key::File {
+                    path: path.to_path_buf(),
+                    dev: metadata.dev(),
+                    ino: metadata.ino(),
+                    size: metadata.size(),
+                    mtime: (metadata.mtime(), metadata.mtime_nsec()),
+                };
+
+                self.cached(key, || self.pool.file(path))
+            }
+            _ => self.pool.file(path).map(Detection::from),
+        }
+    }
+
+    /// Returns the number of detections currently held by the cache.
+    pub fn len(&self) -> usize {
+        self.shared.store.len()
+    }
+
+    /// Returns `true` if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every detection from the cache.
+    ///
+    /// This should be called if the pool is reloaded with different magic databases.
+    pub fn clear(&self) {
+        self.shared.store.clear()
+    }
+
+    fn cached<F>(&self, key: Key, detect: F) -> Result<Detection, Error>
+    where
+        F: FnOnce() -> Result<String, Error>,
+    {
+        if let Some(detection) = self.shared.store.get(&key) {
+            return Ok(detection);
+        }
+
+        let detection = Detection::from(detect()?);
+        self.shared.store.insert(key, detection.clone());
+
+        Ok(detection)
+    }
+}
+
+impl Debug for CachedPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedPool")
+            .field("pool", &self.pool)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl Backend for CachedPool {
+    fn name(&self) -> &'static str {
+        "libmagic"
+    }
+
+    fn buffer(&self, buf: &[u8]) -> Result<Detection, Error> {
+        CachedPool::buffer(self, buf)
+    }
+
+    fn file(&self, path: &Path) -> Result<Detection, Error> {
+        CachedPool::file(self, path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Buffer {
+        hash: u64,
+        len: usize,
+    },
+    File {
+        path: PathBuf,
+        dev: u64,
+        ino: u64,
+        size: u64,
+        mtime: (i64, i64),
+    },
+}
+
+#[cfg(feature = "moka")]
+struct Store(moka::sync::Cache<Key, Detection>);
+
+#[cfg(feature = "moka")]
+impl Store {
+    fn new(capacity: usize) -> Self {
+        Self(moka::sync::Cache::new(capacity as u64))
+    }
+
+    fn get(&self, key: &Key) -> Option<Detection> {
+        self.0.get(key)
+    }
+
+    fn insert(&self, key: Key, detection: Detection) {
+        self.0.insert(key, detection)
+    }
+
+    fn len(&self) -> usize {
+        // moka updates its counts lazily, so pending work has to be applied first.
+        self.0.run_pending_tasks();
+        self.0.entry_count() as usize
+    }
+
+    fn clear(&self) {
+        self.0.invalidate_all()
+    }
+}
+
+#[cfg(not(feature = "moka"))]
+struct Store(std::sync::Mutex<Lru>);
+
+#[cfg(not(feature = "moka"))]
+impl Store {
+    fn new(capacity: usize) -> Self {
+        Self(std::sync::Mutex::new(Lru {
+            capacity,
+            entries: Default::default(),
+            order: Default::default(),
+            tick: 0,
+        }))
+    }
+
+    fn get(&self, key: &Key) -> Option<Detection> {
+        self.lock().get(key)
+    }
+
+    fn insert(&self, key: Key, detection: Detection) {
+        self.lock().insert(key, detection)
+    }
+
+    fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    fn clear(&self) {
+        let mut lru = self.lock();
+        lru.entries.clear();
+        lru.order.clear();
+    }
+
+    // Nothing can be left half updated by a panic while the lock is held, so a poisoned lock is
+    // safe to recover.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Lru> {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// A least recently used cache.
+///
+/// Each entry records the tick at which it was last used, and `order` maps ticks back to keys,
+/// so the least recently used entry is always the first in `order`.
+#[cfg(not(feature = "moka"))]
+struct Lru {
+    capacity: usize,
+    entries: std::collections::HashMap<Key, (Detection, u64)>,
+    order: std::collections::BTreeMap<u64, Key>,
+    tick: u64,
+}
+
+#[cfg(not(feature = "moka"))]
+impl Lru {
+    fn get(&mut self, key: &Key) -> Option<Detection> {
+        let (detection, used) = self.entries.get_mut(key)?;
+        self.order.remove(used);
+        self.tick += 1;
+        *used = self.tick;
+        self.order.insert(self.tick, key.clone());
+
+        Some(detection.clone())
+    }
+
+    fn insert(&mut self, key: Key, detection: Detection) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let Some((_, used)) = self.entries.remove(&key) {
+            self.order.remove(&used);
+        }
+        while self.entries.len() >= self.capacity {
+            let Some((_, evicted)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&evicted);
+        }
+
+        self.tick += 1;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (detection, self.tick));
+    }
+}
@@ -0,0 +1,44 @@
+use std::ffi::c_int;
+
+use crate::{Flag, FlagSet};
+
+/// The capabilities of the libmagic library that is actually in use at runtime.
+///
+/// The `v5-*` features determine which flags exist at compile time, but the libmagic that ends up
+/// being linked or loaded may be older than the one that mojique was built against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    version: c_int,
+    flags: FlagSet,
+}
+
+impl Capabilities {
+    /// Returns the libmagic version, in the same form as [`crate::version`].
+    pub fn version(&self) -> c_int {
+        self.version
+    }
+
+    /// Returns every flag that libmagic supports.
+    pub fn flags(&self) -> FlagSet {
+        self.flags
+    }
+
+    /// Returns true if libmagic supports the given flag.
+    pub fn supports(&self, flag: Flag) -> bool {
+        self.flags.contains(flag)
+    }
+}
+
+/// Returns the capabilities of the libmagic library in use.
+pub fn capabilities() -> Capabilities {
+    let version = crate::version();
+
+    Capabilities {
+        version,
+        flags: Flag::SINGLE
+            .iter()
+            .copied()
+            .filter(|flag| flag.min_version() <= version)
+            .collect(),
+    }
+}
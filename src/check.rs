@@ -0,0 +1,169 @@
+use std::{
+    ffi::{CStr, c_int},
+    fmt::Display,
+    io::{Read, Write},
+    os::fd::{AsFd, AsRawFd},
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{Error, handle::Cookie, sys::magic_check};
+
+/// A warning emitted by libmagic while checking a magic database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckWarning {
+    /// The magic database file that the warning relates to, if libmagic reported one.
+    pub path: Option<PathBuf>,
+
+    /// The line within [`CheckWarning::path`] that the warning relates to, if libmagic reported
+    /// one.
+    pub line: Option<usize>,
+
+    /// The warning itself.
+    pub message: String,
+}
+
+impl CheckWarning {
+    fn parse(line: &str) -> Option<Self> {
+        let (prefix, message) = line.split_once("Warning: ")?;
+
+        // libmagic always emits this when loading a source file, rather than a compiled one, which
+        // isn't very useful when the whole point is to check source files.
+        if message.starts_with("using regular magic file") {
+            return None;
+        }
+
+        let (path, line) = match prefix
+            .trim_end_matches(": ")
+            .rsplit_once(", ")
+            .and_then(|(path, line)| Some((path, line.parse().ok()?)))
+        {
+            Some((path, line)) => (Some(path.into()), Some(line)),
+            None => (None, None),
+        };
+
+        Some(Self {
+            path,
+            line,
+            message: message.to_string(),
+        })
+    }
+}
+
+/// Formats the warning in the same way libmagic does.
+impl Display for CheckWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.path, self.line) {
+            (Some(path), Some(line)) => write!(f, "{}, {line}: {}", path.display(), self.message),
+            (Some(path), None) => write!(f, "{}: {}", path.display(), self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for CheckWarning {}
+
+/// Runs `magic_check` on the given cookie, returning any warnings that libmagic emitted.
+///
+/// If `path` is `None`, the default database is checked.
+pub(crate) fn check(cookie: &mut Cookie, path: Option<&CStr>) -> Result<Vec<CheckWarning>, Error> {
+    let path = path.map_or(std::ptr::null(), CStr::as_ptr);
+    let (result, output) =
+        capture_stderr(|| cookie.raw(|cookie| unsafe { magic_check(cookie, path) }))?;
+
+    let warnings = String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(CheckWarning::parse)
+        .collect();
+
+    match result {
+        Ok(_) => Ok(warnings),
+        Err(source) => Err(Error::Check {
+            source: Box::new(source),
+            warnings,
+        }),
+    }
+}
+
+// libmagic writes its warnings directly to stderr, so the only way to get at them is to point the
+// stderr file descriptor at a pipe while it runs. Since that affects the whole process, we at
+// least need to make sure that we're not doing it on multiple threads at once.
+static STDERR: Mutex<()> = Mutex::new(());
+
+const STDERR_FILENO: c_int = 2;
+
+unsafe extern "C" {
+    fn dup2(oldfd: c_int, newfd: c_int) -> c_int;
+}
+
+/// Invokes `f` while capturing anything written to stderr.
+fn capture_stderr<F, R>(f: F) -> Result<(R, Vec<u8>), Error>
+where
+    F: FnOnce() -> R,
+{
+    let _guard = STDERR.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let (mut reader, writer) = std::io::pipe().map_err(Error::StderrCapture)?;
+    let saved = std::io::stderr()
+        .as_fd()
+        .try_clone_to_owned()
+        .map_err(Error::StderrCapture)?;
+
+    // As with Handle::read, we need to drain the pipe from another thread, otherwise libmagic will
+    // block once the pipe buffer is full.
+    let drain = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).map(|_| output)
+    });
+
+    let _ = std::io::stderr().flush();
+    if unsafe { dup2(writer.as_raw_fd(), STDERR_FILENO) } == -1 {
+        return Err(Error::StderrCapture(std::io::Error::last_os_error()));
+    }
+    drop(writer);
+
+    let result = f();
+
+    // Restoring stderr also closes the last write end of the pipe, which will terminate the drain
+    // thread.
+    if unsafe { dup2(saved.as_raw_fd(), STDERR_FILENO) } == -1 {
+        return Err(Error::StderrCapture(std::io::Error::last_os_error()));
+    }
+
+    let output = drain
+        .join()
+        .map_err(|_| Error::PipeJoin)?
+        .map_err(Error::StderrCapture)?;
+
+    Ok((result, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            CheckWarning::parse("/tmp/bad.magic, 3: Warning: type `strnig' invalid"),
+            Some(CheckWarning {
+                path: Some("/tmp/bad.magic".into()),
+                line: Some(3),
+                message: "type `strnig' invalid".into(),
+            })
+        );
+        assert_eq!(
+            CheckWarning::parse("Warning: something odd"),
+            Some(CheckWarning {
+                path: None,
+                line: None,
+                message: "something odd".into(),
+            })
+        );
+        assert_eq!(
+            CheckWarning::parse("Warning: using regular magic file `/tmp/bad.magic'"),
+            None
+        );
+        assert_eq!(CheckWarning::parse("2: > 0 string,=FOO,\"foo\"]"), None);
+    }
+}
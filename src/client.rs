@@ -0,0 +1,111 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    os::{
+        fd::{AsFd, AsRawFd},
+        unix::net::UnixStream,
+    },
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    BYTES_MAX, Backend, Detection, Error,
+    daemon::{
+        REQUEST_BYTES, REQUEST_FD, RESPONSE_ERROR, RESPONSE_MAX, RESPONSE_OK, malformed, read_len,
+        send_fd, write_frame,
+    },
+};
+
+/// A client for a [`Daemon`][crate::Daemon] listening on a Unix socket.
+///
+/// A new connection is made for each request, so clients are cheap to create and can be shared
+/// freely between threads. Files are opened by the client and passed to the daemon as file
+/// descriptors, so the daemon doesn't need access to them.
+///
+/// Errors communicating with the daemon are returned as [`Error::DaemonConnection`], and errors
+/// reported by the daemon as [`Error::Daemon`].
+///
+/// ```no_run
+/// use mojique::{Backend, Client};
+///
+/// let client = Client::new("/run/mojique.sock");
+/// println!("{}", client.buffer(b"#!/bin/sh\n")?);
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client {
+    path: PathBuf,
+}
+
+impl Client {
+    /// Creates a new client for the daemon listening on the given socket.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Performs detection on an open file descriptor.
+    ///
+    /// As with [`Handle::raw_fd`][crate::Handle::raw_fd], detection starts from the current
+    /// offset of the descriptor.
+    pub fn fd(&self, fd: impl AsFd) -> Result<Detection, Error> {
+        let mut stream = self.connect()?;
+        send_fd(&stream, REQUEST_FD, fd.as_fd().as_raw_fd()).map_err(Error::DaemonConnection)?;
+
+        read_response(&mut stream)
+    }
+
+    fn connect(&self) -> Result<UnixStream, Error> {
+        UnixStream::connect(&self.path).map_err(Error::DaemonConnection)
+    }
+}
+
+impl Backend for Client {
+    fn name(&self) -> &'static str {
+        "daemon"
+    }
+
+    /// Performs detection on the given buffer.
+    ///
+    /// Only the first [`BYTES_MAX`] bytes are sent to the daemon.
+    fn buffer(&self, buf: &[u8]) -> Result<Detection, Error> {
+        let mut stream = self.connect()?;
+        write_frame(&mut stream, REQUEST_BYTES, &buf[..buf.len().min(BYTES_MAX)])
+            .map_err(Error::DaemonConnection)?;
+
+        read_response(&mut stream)
+    }
+
+    fn file(&self, path: &Path) -> Result<Detection, Error> {
+        File::open(path)
+            .map_err(Error::ReadInput)
+            .and_then(|file| self.fd(file))
+            .map_err(|source| Error::Detect {
+                path: path.to_path_buf(),
+                source: Box::new(source),
+            })
+    }
+}
+
+fn read_response(stream: &mut UnixStream) -> Result<Detection, Error> {
+    let read = |stream: &mut UnixStream| -> io::Result<(u8, String)> {
+        let mut status = [0];
+        stream.read_exact(&mut status)?;
+        let len = read_len(stream)?;
+        if len > RESPONSE_MAX {
+            return Err(malformed("response is too long"));
+        }
+        let mut message = vec![0; len];
+        stream.read_exact(&mut message)?;
+
+        let message = String::from_utf8(message).map_err(|_| malformed("message is not UTF-8"))?;
+        Ok((status[0], message))
+    };
+
+    match read(stream).map_err(Error::DaemonConnection)? {
+        (RESPONSE_OK, description) => Ok(Detection::new(description)),
+        (RESPONSE_ERROR, message) => Err(Error::Daemon(message)),
+        _ => Err(Error::DaemonConnection(malformed(
+            "unexpected response status",
+        ))),
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    ffi::{CString, c_int},
+    io::{ErrorKind, Read},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{Error, Flag, config::compiled_path, handle::Cookie, sys::magic_compile};
+
+// libmagic always writes compiled databases into the current working directory, so we have to
+// change it while compiling. That's process wide, so let's at least not race ourselves.
+static WORKING_DIRECTORY: Mutex<()> = Mutex::new(());
+
+/// Compiles magic database source files into `.mgc` files within `output_dir`, returning the paths
+/// of the compiled files in the same order as `paths`.
+///
+/// As with `file -C`, each compiled file is named after its source file, with `.mgc` appended. If a
+/// path is a directory, every file within it is compiled into a single database named after the
+/// directory.
+///
+/// libmagic writes compiled databases into the current working directory, so the working directory
+/// of the whole process is changed to `output_dir` while compiling, and then restored. Any relative
+/// paths used by other threads in the meantime will be resolved against `output_dir`.
+pub fn compile<P>(
+    paths: impl IntoIterator<Item = P>,
+    output_dir: impl AsRef<Path>,
+) -> Result<Vec<PathBuf>, Error>
+where
+    P: AsRef<Path>,
+{
+    // Since we're about to change the working directory, any relative paths need to be resolved
+    // first.
+    let output_dir = std::path::absolute(output_dir).map_err(Error::WorkingDirectory)?;
+    let paths = paths
+        .into_iter()
+        .map(std::path::absolute)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::WorkingDirectory)?;
+
+    let mut cookie = Cookie::open(Flag::Error as c_int)?;
+
+    let _guard = WORKING_DIRECTORY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let previous = std::env::current_dir().map_err(Error::WorkingDirectory)?;
+    std::env::set_current_dir(&output_dir).map_err(Error::WorkingDirectory)?;
+
+    let result = paths
+        .into_iter()
+        .map(|path| compile_one(&mut cookie, &output_dir, path))
+        .collect();
+
+    std::env::set_current_dir(previous).map_err(Error::WorkingDirectory)?;
+    result
+}
+
+fn compile_one(cookie: &mut Cookie, output_dir: &Path, path: PathBuf) -> Result<PathBuf, Error> {
+    let Some(name) = path.file_name() else {
+        return Err(Error::InvalidDatabasePath(path));
+    };
+
+    // libmagic's error for this case is an unhelpful complaint about the first line, so let's
+    // catch it here.
+    if path.is_file() && is_compiled_file(&path)? {
+        return Err(Error::AlreadyCompiled(path));
+    }
+
+    let mut output = name.to_owned();
+    output.push(".mgc");
+    let output = output_dir.join(output);
+
+    let filename =
+        CString::new(path.as_os_str().as_encoded_bytes()).map_err(|_| Error::EmbeddedNuls)?;
+
+    match cookie.raw(|cookie| unsafe { magic_compile(cookie, filename.as_ptr()) }) {
+        Ok(_) => Ok(output),
+        Err(e) => Err(Error::Compile {
+            path,
+            source: Box::new(e),
+        }),
+    }
+}
+
+/// Where, if anywhere, compiled forms of text magic databases should be cached.
+#[derive(Debug, Clone, Default)]
+pub(crate) enum CompileCache {
+    #[default]
+    Disabled,
+    Default,
+    Directory(PathBuf),
+}
+
+impl CompileCache {
+    /// Replaces any text magic database files within `paths` with cached compiled forms,
+    /// compiling them first if required.
+    pub(crate) fn resolve(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error> {
+        let dir = match self {
+            CompileCache::Disabled => return Ok(paths.to_vec()),
+            CompileCache::Default => default_cache_dir().ok_or(Error::NoCacheDirectory)?,
+            CompileCache::Directory(dir) => dir.clone(),
+        };
+
+        paths
+            .iter()
+            .map(|path| {
+                if is_text_database(path)? {
+                    cached(path, &dir)
+                } else {
+                    Ok(path.clone())
+                }
+            })
+            .collect()
+    }
+}
+
+/// The magic number at the start of every compiled database, in either byte order.
+const COMPILED_MAGIC: [[u8; 4]; 2] = [[0x1c, 0x04, 0x1e, 0xf1], [0xf1, 0x1e, 0x04, 0x1c]];
+
+/// Returns `true` if the buffer starts with the magic number of a compiled database.
+pub(crate) fn is_compiled_database(buffer: &[u8]) -> bool {
+    buffer
+        .first_chunk::<4>()
+        .is_some_and(|header| COMPILED_MAGIC.contains(header))
+}
+
+/// Returns `true` if the path is a regular file containing a text magic database that libmagic
+/// wouldn't otherwise load a compiled form of.
+pub(crate) fn is_text_database(path: &Path) -> Result<bool, Error> {
+    if !path.is_file() || compiled_path(path).exists() {
+        return Ok(false);
+    }
+
+    Ok(!is_compiled_file(path)?)
+}
+
+/// Returns `true` if the path is a file containing a compiled database.
+fn is_compiled_file(path: &Path) -> Result<bool, Error> {
+    let mut header = [0u8; 4];
+    let read = std::fs::File::open(path)
+        .and_then(|mut file| file.read(&mut header))
+        .map_err(|source| Error::ReadDatabase {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+    Ok(is_compiled_database(&header[..read]))
+}
+
+/// Returns the path to a compiled form of `path` within the cache directory, compiling it if it
+/// isn't already cached.
+fn cached(path: &Path, dir: &Path) -> Result<PathBuf, Error> {
+    let contents = std::fs::read(path).map_err(|source| Error::ReadDatabase {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let Some(name) = path.file_name() else {
+        return Err(Error::InvalidDatabasePath(path.to_path_buf()));
+    };
+
+    // The compiled format depends on the libmagic version, so that needs to be part of the key,
+    // as does the file name, since the compiled database is named after it. The name's length is
+    // included so that it can't run into the contents.
+    let name_bytes = name.as_bytes();
+    let key = fnv1a(
+        crate::version()
+            .to_le_bytes()
+            .iter()
+            .chain(name_bytes.len().to_le_bytes().iter())
+            .chain(name_bytes)
+            .chain(contents.iter()),
+    );
+    let entry = dir.join(format!("{key:016x}"));
+
+    let mut compiled_name = name.to_owned();
+    compiled_name.push(".mgc");
+
+    let compiled = entry.join(&compiled_name);
+    if compiled.is_file() {
+        return Ok(compiled);
+    }
+
+    // Compile into a temporary directory and then rename it into place, so that other processes
+    // never see a partially written database.
+    let cache_error = |source| Error::CompileCache {
+        path: dir.to_path_buf(),
+        source,
+    };
+    let temporary = dir.join(format!("{key:016x}.{}.tmp", std::process::id()));
+    std::fs::create_dir_all(&temporary).map_err(cache_error)?;
+    compile([path], &temporary)?;
+
+    if std::fs::rename(&temporary, &entry).is_err() {
+        // Most likely, another process won the race, in which case we'll use its copy.
+        let _ = std::fs::remove_dir_all(&temporary);
+        if !compiled.is_file() {
+            return Err(cache_error(std::io::Error::from(ErrorKind::NotFound)));
+        }
+    }
+
+    Ok(compiled)
+}
+
+/// Returns the default compile cache directory, following the XDG base directory specification.
+fn default_cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".cache")))
+        .map(|dir| dir.join("mojique"))
+}
+
+/// A 64-bit FNV-1a hash, which is stable across Rust versions, unlike the standard library's
+/// default hasher.
+fn fnv1a<'a>(bytes: impl Iterator<Item = &'a u8>) -> u64 {
+    bytes.fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -1,30 +1,80 @@
 #![allow(private_interfaces)]
 
 use std::{
-    ffi::{CString, c_int},
-    path::PathBuf,
+    ffi::{CStr, CString, OsStr, OsString, c_int},
+    io::{ErrorKind, Read},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use crate::{
-    Error, Handle,
+    CheckWarning, Database, Error, Handle, Scanner,
+    compile::{CompileCache, is_compiled_database},
     config::private::ConfigPrivateExt,
-    ffi::Flag,
-    pool::{Pool, Source},
+    ffi::{Check, Flag, FlagSet},
+    pool::{Pool, PoolOptions, SharedBuffer, SharedFiles, Source},
+    sys,
 };
 
 /// A configuration that sets libmagic flags on any created [`Handle`] instances.
 ///
 /// By default, the only flag that is set is [`Flag::Error`].
+///
+/// Building handles and pools doesn't consume the configuration, so a single configuration can be
+/// used to build as many independent handles and pools as required. Any buffers within the
+/// configuration are shared, rather than copied.
 pub trait Config: ConfigPrivateExt + Sized {
     /// Builds a single [`Handle`] from the configuration.
-    fn build_handle(self) -> Result<Handle, Error> {
-        let flags = self.flags();
-        self.into_source()?.create_handle(flags, None)
+    ///
+    /// Returns [`Error::UnsupportedFlag`] if a flag has been set that the libmagic in use doesn't
+    /// support.
+    fn build_handle(&self) -> Result<Handle, Error> {
+        validate_flags(self.flags())?;
+        self.source()?.create_handle(self.flags())
     }
 
     /// Builds a [`Pool`] of handles from the configuration.
-    fn build_pool(self) -> Result<Pool, Error> {
-        Pool::new(self.flags(), self.into_source()?)
+    fn build_pool(&self) -> Result<Pool, Error> {
+        self.build_pool_with_options(PoolOptions::default())
+    }
+
+    /// Builds a [`Pool`] of handles from the configuration, using the given [`PoolOptions`].
+    ///
+    /// As with [`Config::build_handle`], unsupported flags result in [`Error::UnsupportedFlag`].
+    fn build_pool_with_options(&self, options: PoolOptions) -> Result<Pool, Error> {
+        validate_flags(self.flags())?;
+        Pool::new(self.flags(), self.source()?, options)
+    }
+
+    /// Builds a [`Scanner`] that performs detection with a [`Pool`] built from the configuration.
+    fn build_scanner(&self) -> Result<Scanner, Error> {
+        Ok(Scanner::new(self.build_pool()?))
+    }
+
+    /// Checks that the configuration is likely to be usable, without building a handle.
+    ///
+    /// This verifies that any database paths exist and are readable, that any buffers are
+    /// non-empty, and that every flag that has been set is supported by the linked libmagic. The
+    /// databases themselves aren't parsed, so a successful validation doesn't guarantee that
+    /// building a handle will succeed.
+    fn validate(&self) -> Result<(), Error> {
+        validate_flags(self.flags())?;
+        self.validate_source()
+    }
+
+    /// Checks the magic database(s) within the configuration for consistency, returning any
+    /// warnings that libmagic emits.
+    ///
+    /// Only databases on the filesystem can be checked, so this returns [`Error::CheckBuffers`]
+    /// for configurations that load buffers. If the check fails, the returned [`Error::Check`]
+    /// includes any warnings that explain why.
+    ///
+    /// libmagic writes its warnings to stderr, so while the check runs, stderr is redirected for
+    /// the whole process. Anything written to stderr by other threads in the meantime will be
+    /// discarded.
+    fn check(&self) -> Result<Vec<CheckWarning>, Error> {
+        self.source()?.check(self.flags())
     }
 
     /// Removes a flag from the configuration.
@@ -32,6 +82,106 @@ pub trait Config: ConfigPrivateExt + Sized {
 
     /// Sets a flag on the configuration.
     fn set_flag(self, flag: Flag) -> Self;
+
+    /// Sets a flag on the configuration, returning [`Error::UnsupportedFlag`] if the libmagic in use
+    /// doesn't support it.
+    fn try_set_flag(self, flag: Flag) -> Result<Self, Error> {
+        validate_flags(flag as c_int)?;
+        Ok(self.set_flag(flag))
+    }
+
+    /// Sets every flag in `flags` on the configuration.
+    ///
+    /// `flags` can be a single [`Flag`], a [`FlagSet`], or an array or [`Vec`] of flags. Any other
+    /// iterator of flags can be collected into a [`FlagSet`] first.
+    fn set_flags(self, flags: impl Into<FlagSet>) -> Self {
+        flags.into().iter().fold(self, Self::set_flag)
+    }
+
+    /// Returns the flags that are set on the configuration.
+    fn flag_set(&self) -> FlagSet {
+        FlagSet::from_bits(self.flags())
+    }
+
+    /// Sets every flag that is set on `other` on this configuration, in addition to the flags that
+    /// are already set. Only flags are merged: the magic database(s) of `other` are ignored.
+    fn merge_flags(self, other: &impl Config) -> Self {
+        self.set_flags(other.flag_set())
+    }
+
+    /// Disables one of libmagic's built in checks.
+    ///
+    /// This is equivalent to setting the corresponding `NoCheck*` flag, such as
+    /// [`Flag::NoCheckTar`] for [`Check::Tar`].
+    fn disable_check(self, check: Check) -> Self {
+        self.set_flag(check.flag())
+    }
+
+    /// Disables every built in check, other than the given checks, which are enabled if they had
+    /// previously been disabled.
+    ///
+    /// For example, `disable_all_checks_except([Check::Soft])` results in libmagic only consulting
+    /// the magic database.
+    fn disable_all_checks_except(self, checks: impl IntoIterator<Item = Check>) -> Self {
+        let enabled: Vec<Check> = checks.into_iter().collect();
+        Check::ALL.iter().fold(self, |config, check| {
+            if enabled.contains(check) {
+                config.remove_flag(check.flag())
+            } else {
+                config.disable_check(*check)
+            }
+        })
+    }
+
+    /// Returns MIME types and encodings, rather than textual descriptions.
+    ///
+    /// This is equivalent to setting [`Flag::Mime`].
+    fn mime(self) -> Self {
+        self.set_flag(Flag::Mime)
+    }
+
+    /// Returns MIME types, rather than textual descriptions.
+    ///
+    /// This is equivalent to setting [`Flag::MimeType`].
+    fn mime_type(self) -> Self {
+        self.set_flag(Flag::MimeType)
+    }
+
+    /// Returns MIME encodings, rather than textual descriptions.
+    ///
+    /// This is equivalent to setting [`Flag::MimeEncoding`].
+    fn mime_encoding(self) -> Self {
+        self.set_flag(Flag::MimeEncoding)
+    }
+
+    /// Returns a slash-separated list of file extensions, rather than textual descriptions.
+    ///
+    /// This is equivalent to setting [`Flag::Extension`].
+    #[cfg(feature = "v5-23")]
+    fn extension(self) -> Self {
+        self.set_flag(Flag::Extension)
+    }
+
+    /// Follows symlinks when detecting files.
+    ///
+    /// This is equivalent to setting [`Flag::Symlink`].
+    fn follow_symlinks(self) -> Self {
+        self.set_flag(Flag::Symlink)
+    }
+
+    /// Looks inside compressed files.
+    ///
+    /// This is equivalent to setting [`Flag::Compress`].
+    fn decompress(self) -> Self {
+        self.set_flag(Flag::Compress)
+    }
+
+    /// Attempts to preserve the access time of files that are detected.
+    ///
+    /// This is equivalent to setting [`Flag::PreserveAccessTime`].
+    fn preserve_atime(self) -> Self {
+        self.set_flag(Flag::PreserveAccessTime)
+    }
 }
 
 pub(crate) mod private {
@@ -39,7 +189,8 @@ pub(crate) mod private {
 
     pub trait ConfigPrivateExt {
         fn flags(&self) -> c_int;
-        fn into_source(self) -> Result<Source, Error>;
+        fn source(&self) -> Result<Source, Error>;
+        fn validate_source(&self) -> Result<(), Error>;
     }
 }
 
@@ -76,9 +227,13 @@ impl ConfigPrivateExt for DefaultConfig {
         self.flags
     }
 
-    fn into_source(self) -> Result<Source, Error> {
+    fn source(&self) -> Result<Source, Error> {
         Ok(Source::Default)
     }
+
+    fn validate_source(&self) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 impl Default for DefaultConfig {
@@ -93,14 +248,86 @@ impl Default for DefaultConfig {
 #[derive(Debug, Clone, Default)]
 pub struct BufferConfig {
     config: DefaultConfig,
-    buffers: Vec<Vec<u8>>,
+    buffers: Vec<SharedBuffer>,
 }
 
 impl BufferConfig {
-    pub fn with_buffer(mut self, buffer: &[u8]) -> Self {
-        self.buffers.push(buffer.to_vec());
+    /// Adds a buffer.
+    ///
+    /// A `Vec<u8>` is moved into the configuration rather than being copied, whereas slices are
+    /// copied.
+    pub fn with_buffer(mut self, buffer: impl Into<Vec<u8>>) -> Self {
+        self.buffers.push(SharedBuffer::new(buffer.into()));
+        self
+    }
+
+    /// Adds a buffer that is already shared, without copying it.
+    pub fn with_buffer_arc(mut self, buffer: Arc<[u8]>) -> Self {
+        self.buffers.push(SharedBuffer::new(buffer));
         self
     }
+
+    /// Adds a buffer without copying it.
+    ///
+    /// This accepts any owned buffer type, such as `Vec<u8>`, `Arc<[u8]>`, `Cow<'static, [u8]>`,
+    /// or a `&'static [u8]` from [`include_bytes!`], and shares it with every handle and pool built
+    /// from this configuration.
+    pub fn with_owned_buffer<B>(mut self, buffer: B) -> Self
+    where
+        B: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        self.buffers.push(SharedBuffer::new(buffer));
+        self
+    }
+
+    /// Adds every buffer within a [`Database`], without copying them.
+    pub fn with_database(mut self, database: &Database) -> Self {
+        self.buffers.extend_from_slice(database.buffers());
+        self
+    }
+
+    /// Adds a buffer without copying it, after checking that it is a compiled magic database.
+    ///
+    /// libmagic can only load compiled databases from buffers, but only reports a text database
+    /// (or anything else) as a generic error when a handle is created. This returns
+    /// [`Error::UncompiledBuffer`] immediately instead.
+    pub fn with_compiled_buffer<B>(self, buffer: B) -> Result<Self, Error>
+    where
+        B: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        if is_compiled_database(buffer.as_ref()) {
+            Ok(self.with_owned_buffer(buffer))
+        } else {
+            Err(Error::UncompiledBuffer)
+        }
+    }
+
+    pub fn with_buffers<B>(mut self, buffers: impl IntoIterator<Item = B>) -> Self
+    where
+        B: AsRef<[u8]>,
+    {
+        self.buffers.extend(
+            buffers
+                .into_iter()
+                .map(|buffer| SharedBuffer::new(buffer.as_ref().to_vec())),
+        );
+        self
+    }
+}
+
+impl<B> FromIterator<B> for BufferConfig
+where
+    B: AsRef<[u8]>,
+{
+    fn from_iter<T: IntoIterator<Item = B>>(iter: T) -> Self {
+        Self::default().with_buffers(iter)
+    }
+}
+
+impl From<Database> for BufferConfig {
+    fn from(database: Database) -> Self {
+        Self::default().with_database(&database)
+    }
 }
 
 impl Config for BufferConfig {
@@ -120,17 +347,82 @@ impl ConfigPrivateExt for BufferConfig {
         self.config.flags
     }
 
-    fn into_source(self) -> Result<Source, Error> {
-        Ok(Source::Buffers(self.buffers.into()))
+    fn source(&self) -> Result<Source, Error> {
+        Ok(Source::Buffers(self.buffers.clone().into()))
+    }
+
+    fn validate_source(&self) -> Result<(), Error> {
+        if self.buffers.is_empty() {
+            return Err(Error::NoBuffers);
+        }
+        validate_buffers(&self.buffers)
+    }
+}
+
+/// A configuration using one or more magic databases read from [`Read`] implementations.
+///
+/// Each reader is read to completion when it is added, and the resulting bytes are loaded as
+/// buffers in the same way as [`BufferConfig`]. This means that the databases must be compiled.
+#[derive(Debug, Clone, Default)]
+pub struct ReaderConfig {
+    config: DefaultConfig,
+    buffers: Vec<SharedBuffer>,
+}
+
+impl ReaderConfig {
+    pub fn with_reader(mut self, mut reader: impl Read) -> Result<Self, Error> {
+        let mut buffer = Vec::new();
+        reader
+            .read_to_end(&mut buffer)
+            .map_err(Error::ReadDatabaseReader)?;
+
+        self.buffers.push(SharedBuffer::new(buffer));
+        Ok(self)
+    }
+}
+
+impl Config for ReaderConfig {
+    fn remove_flag(mut self, flag: Flag) -> Self {
+        self.config._remove_flag(flag);
+        self
+    }
+
+    fn set_flag(mut self, flag: Flag) -> Self {
+        self.config._set_flag(flag);
+        self
+    }
+}
+
+impl ConfigPrivateExt for ReaderConfig {
+    fn flags(&self) -> c_int {
+        self.config.flags
+    }
+
+    fn source(&self) -> Result<Source, Error> {
+        Ok(Source::Buffers(self.buffers.clone().into()))
+    }
+
+    fn validate_source(&self) -> Result<(), Error> {
+        if self.buffers.is_empty() {
+            return Err(Error::NoBuffers);
+        }
+        validate_buffers(&self.buffers)
     }
 }
 
-/// A configuration using one or more magic databases on the filesystem, but not including the
-/// default database.
+/// A configuration using one or more magic databases on the filesystem.
+///
+/// By default, this doesn't include the default database, but it can be added with
+/// [`FileConfig::with_default_database`].
 #[derive(Debug, Clone, Default)]
 pub struct FileConfig {
     config: DefaultConfig,
+    default_database: bool,
     paths: Vec<PathBuf>,
+    compile_cache: CompileCache,
+    buffered: Option<bool>,
+    expand_paths: bool,
+    shared_database: bool,
 }
 
 impl FileConfig {
@@ -138,6 +430,124 @@ impl FileConfig {
         self.paths.push(path.into());
         self
     }
+
+    /// Adds a path, checking it immediately, rather than when a handle or pool is built.
+    ///
+    /// This returns [`Error::EmbeddedNuls`] or [`Error::EmbeddedColons`] if the path can't be
+    /// passed to libmagic, or [`Error::ReadDatabase`] if neither the path nor its compiled `.mgc`
+    /// form can be read. Buffered loading and path expansion are taken into account if they have
+    /// already been enabled.
+    pub fn try_with_file(self, path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        if self.expand_paths {
+            validate_path(&expand_path(&path)?, self.buffered())?;
+        } else {
+            validate_path(&path, self.buffered())?;
+        }
+
+        Ok(self.with_file(path))
+    }
+
+    pub fn with_files<P>(mut self, paths: impl IntoIterator<Item = P>) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets whether the default magic database is loaded in addition to the configured files,
+    /// which allows custom rules to augment the default database, rather than replacing it. This
+    /// defaults to `false`.
+    pub fn with_default_database(mut self, default_database: bool) -> Self {
+        self.default_database = default_database;
+        self
+    }
+
+    /// Sets whether text magic database files are compiled and cached, rather than being parsed
+    /// each time a handle is created. This defaults to `false`.
+    ///
+    /// When enabled, each configured file that is a text magic database without a compiled
+    /// `.mgc` alongside it is compiled once into a cache directory, keyed by its contents and the
+    /// libmagic version, and the compiled form is loaded instead. The cache directory is
+    /// `$XDG_CACHE_HOME/mojique`, or `~/.cache/mojique` if that isn't set; use
+    /// [`FileConfig::with_compile_cache_dir`] to choose another directory.
+    pub fn with_compile_cache(mut self, compile_cache: bool) -> Self {
+        self.compile_cache = if compile_cache {
+            CompileCache::Default
+        } else {
+            CompileCache::Disabled
+        };
+        self
+    }
+
+    /// Enables the compile cache described in [`FileConfig::with_compile_cache`], using the given
+    /// cache directory.
+    pub fn with_compile_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.compile_cache = CompileCache::Directory(dir.into());
+        self
+    }
+
+    /// Sets whether the configured files are read into memory and loaded as buffers, rather than
+    /// libmagic loading them by path. This defaults to `true` on Windows, and `false` elsewhere.
+    ///
+    /// libmagic only accepts multiple paths as a single colon separated string, so paths that
+    /// contain colons (such as `C:\magic`) can't otherwise be loaded, and result in
+    /// [`Error::EmbeddedColons`]. Buffered loading doesn't have that restriction, but each file is
+    /// loaded as a [`Database`] would be: compiled `.mgc` files alongside the paths are preferred,
+    /// and text magic databases are compiled first. libmagic's compiler has the same restriction on
+    /// colons, so text magic databases with colons in their paths need to be compiled in advance.
+    pub fn with_buffered_loading(mut self, buffered: bool) -> Self {
+        self.buffered = Some(buffered);
+        self
+    }
+
+    /// Sets whether pools load the configured files into memory once, and share them between
+    /// every handle they create, rather than each handle loading them from disk. This defaults to
+    /// `false`.
+    ///
+    /// Text magic databases are then compiled once, rather than being parsed by every new handle,
+    /// and further handles load the compiled databases from memory, which cuts the time and memory
+    /// taken to grow a pool under load. Files are loaded as per
+    /// [`FileConfig::with_buffered_loading`], but only once the first handle is needed, and
+    /// [`Pool::reload`] reads them from disk again, so the pool can still be watched for changes.
+    pub fn with_shared_database(mut self, shared_database: bool) -> Self {
+        self.shared_database = shared_database;
+        self
+    }
+
+    /// Sets whether the configured paths are expanded before they are loaded. This defaults to
+    /// `false`.
+    ///
+    /// When enabled, a leading `~` is replaced with the home directory, `$VAR` and `${VAR}` are
+    /// replaced with the value of the environment variable, and the path is then canonicalised if
+    /// it exists. An undefined variable results in [`Error::UndefinedVariable`]. Expansion happens
+    /// each time a handle or pool is built, rather than when the path is added.
+    pub fn with_path_expansion(mut self, expand_paths: bool) -> Self {
+        self.expand_paths = expand_paths;
+        self
+    }
+
+    fn buffered(&self) -> bool {
+        self.shared_database || self.buffered.unwrap_or(cfg!(windows))
+    }
+
+    fn paths(&self) -> Result<Vec<PathBuf>, Error> {
+        if self.expand_paths {
+            self.paths.iter().map(|path| expand_path(path)).collect()
+        } else {
+            Ok(self.paths.clone())
+        }
+    }
+}
+
+impl<P> FromIterator<P> for FileConfig
+where
+    P: Into<PathBuf>,
+{
+    fn from_iter<T: IntoIterator<Item = P>>(iter: T) -> Self {
+        Self::default().with_files(iter)
+    }
 }
 
 impl Config for FileConfig {
@@ -157,30 +567,558 @@ impl ConfigPrivateExt for FileConfig {
         self.config.flags
     }
 
-    fn into_source(self) -> Result<Source, Error> {
-        // libmagic only accepts a colon-separated set of paths, so we have to take our Rust
-        // PathBufs and turn them into that. An obvious corollary here is that no path can include
-        // a colon, which will probably make Windows support spicy.
-        self.paths
-            .into_iter()
-            .try_fold(Vec::new(), |mut acc, path| {
-                if !acc.is_empty() {
-                    acc.push(b':');
-                }
-
-                let bytes = path.into_os_string().into_encoded_bytes();
-                if bytes.contains(&b':') {
-                    Err(Error::EmbeddedColons)
-                } else {
-                    acc.extend(bytes);
-                    Ok(acc)
-                }
+    fn source(&self) -> Result<Source, Error> {
+        let paths = self.compile_cache.resolve(&self.paths()?)?;
+        if self.shared_database {
+            return Ok(Source::Shared(SharedFiles::new(
+                self.default_database,
+                paths,
+            )));
+        } else if !self.buffered() {
+            return join_paths(default_database_base(self.default_database)?, paths)
+                .map(Source::Files);
+        }
+
+        let database = Database::from_files(self.default_database, &paths)?;
+        Ok(Source::Buffers(database.buffers().to_vec().into()))
+    }
+
+    fn validate_source(&self) -> Result<(), Error> {
+        let paths = self.paths()?;
+        if self.buffered() {
+            if self.default_database {
+                raw_default_database_path().ok_or(Error::NoDefaultDatabase)?;
+            }
+        } else {
+            join_paths(default_database_base(self.default_database)?, &paths)?;
+        }
+
+        validate_paths(&paths)
+    }
+}
+
+/// A configuration using every magic database within a directory, such as `/etc/magic.d`.
+///
+/// The directory is scanned each time a handle or pool is built, and the regular files within it
+/// are loaded in sorted order. Where both a source file and its compiled `.mgc` form are present,
+/// libmagic will load the compiled form in place of the source file.
+#[derive(Debug, Clone)]
+pub struct DirectoryConfig {
+    config: DefaultConfig,
+    path: PathBuf,
+    extensions: Vec<OsString>,
+    excluded: Vec<OsString>,
+}
+
+impl DirectoryConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            config: DefaultConfig::default(),
+            path: path.into(),
+            extensions: Vec::new(),
+            excluded: Vec::new(),
+        }
+    }
+
+    /// Only loads files with the given extension.
+    ///
+    /// This may be called multiple times to include multiple extensions. If it is never called,
+    /// all files are included.
+    pub fn with_extension(mut self, extension: impl Into<OsString>) -> Self {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    /// Excludes the file with the given name from being loaded.
+    pub fn without_file(mut self, name: impl Into<OsString>) -> Self {
+        self.excluded.push(name.into());
+        self
+    }
+
+    fn is_included(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name() else {
+            return false;
+        };
+
+        if self.excluded.iter().any(|excluded| excluded == name) {
+            return false;
+        }
+
+        self.extensions.is_empty()
+            || path
+                .extension()
+                .is_some_and(|ext| self.extensions.iter().any(|included| included == ext))
+    }
+}
+
+impl Config for DirectoryConfig {
+    fn remove_flag(mut self, flag: Flag) -> Self {
+        self.config._remove_flag(flag);
+        self
+    }
+
+    fn set_flag(mut self, flag: Flag) -> Self {
+        self.config._set_flag(flag);
+        self
+    }
+}
+
+impl ConfigPrivateExt for DirectoryConfig {
+    fn flags(&self) -> c_int {
+        self.config.flags
+    }
+
+    fn source(&self) -> Result<Source, Error> {
+        join_paths(Vec::new(), &self.database_paths()?).map(Source::Files)
+    }
+
+    fn validate_source(&self) -> Result<(), Error> {
+        validate_paths(&self.database_paths()?)
+    }
+}
+
+impl DirectoryConfig {
+    fn database_paths(&self) -> Result<Vec<PathBuf>, Error> {
+        let read_error = |source| Error::ReadDatabase {
+            path: self.path.clone(),
+            source,
+        };
+
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(&self.path).map_err(read_error)? {
+            let path = entry.map_err(read_error)?.path();
+            if path.is_file() && self.is_included(&path) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        // Since libmagic will already load the compiled form of any source file, we don't want to
+        // load it a second time.
+        let duplicates: Vec<PathBuf> = paths
+            .iter()
+            .filter(|path| {
+                path.extension().is_some_and(|ext| ext == "mgc")
+                    && paths.contains(&path.with_extension(""))
+            })
+            .cloned()
+            .collect();
+        paths.retain(|path| !duplicates.contains(path));
+
+        if paths.is_empty() {
+            return Err(Error::NoDatabases(self.path.clone()));
+        }
+
+        Ok(paths)
+    }
+}
+
+/// A configuration using the magic database(s) named by the `MAGIC` environment variable.
+///
+/// libmagic already consults `MAGIC` when loading the default database, but silently falls back
+/// to the system database if the files it names can't be found. This configuration resolves the
+/// variable each time a handle or pool is built, and returns [`Error::EnvDatabaseMissing`] if any
+/// of the colon separated paths within it don't exist, either as a source file or in compiled
+/// form.
+///
+/// If `MAGIC` is unset or empty, the default database installed on the system is used.
+#[derive(Debug, Clone, Default)]
+pub struct EnvConfig {
+    config: DefaultConfig,
+}
+
+impl Config for EnvConfig {
+    fn remove_flag(mut self, flag: Flag) -> Self {
+        self.config._remove_flag(flag);
+        self
+    }
+
+    fn set_flag(mut self, flag: Flag) -> Self {
+        self.config._set_flag(flag);
+        self
+    }
+}
+
+impl ConfigPrivateExt for EnvConfig {
+    fn flags(&self) -> c_int {
+        self.config.flags
+    }
+
+    fn source(&self) -> Result<Source, Error> {
+        let Some(value) = std::env::var_os("MAGIC").filter(|value| !value.is_empty()) else {
+            return Ok(Source::Default);
+        };
+
+        for path in std::env::split_paths(&value) {
+            if !path.exists() && !compiled_path(&path).exists() {
+                return Err(Error::EnvDatabaseMissing(path));
+            }
+        }
+
+        CString::new(value.into_encoded_bytes())
+            .map(Source::Files)
+            .map_err(|_| Error::EmbeddedNuls)
+    }
+
+    fn validate_source(&self) -> Result<(), Error> {
+        self.source()?;
+        match std::env::var_os("MAGIC").filter(|value| !value.is_empty()) {
+            Some(value) => validate_paths(&std::env::split_paths(&value).collect::<Vec<_>>()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A configuration that combines the default magic database installed on the system with any
+/// number of additional magic databases from the filesystem and `[u8]` buffers.
+///
+/// libmagic can only load databases from either the filesystem or from buffers, but not both at
+/// once. If no buffers are added, all databases are loaded from the filesystem as normal.
+/// Otherwise, mojique reads the default database and any files into memory and loads everything
+/// as buffers, which requires that all databases are compiled: for each path, a `.mgc` file
+/// alongside the path is used in preference to the path itself, as libmagic would do.
+#[derive(Debug, Clone)]
+pub struct CombinedConfig {
+    config: DefaultConfig,
+    default_database: bool,
+    paths: Vec<PathBuf>,
+    buffers: Vec<SharedBuffer>,
+}
+
+impl CombinedConfig {
+    /// Adds a buffer.
+    ///
+    /// A `Vec<u8>` is moved into the configuration rather than being copied, whereas slices are
+    /// copied.
+    pub fn with_buffer(mut self, buffer: impl Into<Vec<u8>>) -> Self {
+        self.buffers.push(SharedBuffer::new(buffer.into()));
+        self
+    }
+
+    /// Adds a buffer that is already shared, without copying it.
+    pub fn with_buffer_arc(mut self, buffer: Arc<[u8]>) -> Self {
+        self.buffers.push(SharedBuffer::new(buffer));
+        self
+    }
+
+    /// Adds a buffer without copying it.
+    ///
+    /// This accepts any owned buffer type, such as `Vec<u8>`, `Arc<[u8]>`, `Cow<'static, [u8]>`,
+    /// or a `&'static [u8]` from [`include_bytes!`], and shares it with every handle and pool built
+    /// from this configuration.
+    pub fn with_owned_buffer<B>(mut self, buffer: B) -> Self
+    where
+        B: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        self.buffers.push(SharedBuffer::new(buffer));
+        self
+    }
+
+    /// Adds every buffer within a [`Database`], without copying them.
+    pub fn with_database(mut self, database: &Database) -> Self {
+        self.buffers.extend_from_slice(database.buffers());
+        self
+    }
+
+    pub fn with_buffers<B>(mut self, buffers: impl IntoIterator<Item = B>) -> Self
+    where
+        B: AsRef<[u8]>,
+    {
+        self.buffers.extend(
+            buffers
+                .into_iter()
+                .map(|buffer| SharedBuffer::new(buffer.as_ref().to_vec())),
+        );
+        self
+    }
+
+    pub fn with_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Adds a path, checking it immediately, rather than when a handle or pool is built.
+    ///
+    /// As with [`FileConfig::try_with_file`], this returns [`Error::EmbeddedNuls`],
+    /// [`Error::EmbeddedColons`], or [`Error::ReadDatabase`] if the path can't be used.
+    pub fn try_with_file(self, path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        validate_path(&path, false)?;
+        Ok(self.with_file(path))
+    }
+
+    pub fn with_files<P>(mut self, paths: impl IntoIterator<Item = P>) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets whether the default magic database is loaded. This defaults to `true`.
+    pub fn with_default_database(mut self, default_database: bool) -> Self {
+        self.default_database = default_database;
+        self
+    }
+}
+
+impl Default for CombinedConfig {
+    fn default() -> Self {
+        Self {
+            config: DefaultConfig::default(),
+            default_database: true,
+            paths: Vec::new(),
+            buffers: Vec::new(),
+        }
+    }
+}
+
+impl Config for CombinedConfig {
+    fn remove_flag(mut self, flag: Flag) -> Self {
+        self.config._remove_flag(flag);
+        self
+    }
+
+    fn set_flag(mut self, flag: Flag) -> Self {
+        self.config._set_flag(flag);
+        self
+    }
+}
+
+impl ConfigPrivateExt for CombinedConfig {
+    fn flags(&self) -> c_int {
+        self.config.flags
+    }
+
+    fn source(&self) -> Result<Source, Error> {
+        if self.buffers.is_empty() {
+            return join_paths(default_database_base(self.default_database)?, &self.paths)
+                .map(Source::Files);
+        }
+
+        let mut buffers = Vec::new();
+        if self.default_database {
+            buffers.extend_from_slice(Database::default_database()?.buffers());
+        }
+
+        for path in &self.paths {
+            let bytes = match read_database(&compiled_path(path))? {
+                Some(bytes) => bytes,
+                None => std::fs::read(path).map_err(|source| Error::ReadDatabase {
+                    path: path.clone(),
+                    source,
+                })?,
+            };
+            buffers.push(SharedBuffer::new(bytes));
+        }
+
+        buffers.extend(self.buffers.iter().cloned());
+        Ok(Source::Buffers(buffers.into()))
+    }
+
+    fn validate_source(&self) -> Result<(), Error> {
+        if self.buffers.is_empty() {
+            self.source()?;
+        } else if self.default_database {
+            raw_default_database_path().ok_or(Error::NoDefaultDatabase)?;
+        }
+
+        validate_paths(&self.paths)?;
+        validate_buffers(&self.buffers)
+    }
+}
+
+/// Checks that every flag is supported by the libmagic in use.
+fn validate_flags(flags: c_int) -> Result<(), Error> {
+    match FlagSet::from_bits(flags)
+        .iter()
+        .find(|flag| !flag.is_supported())
+    {
+        Some(flag) => {
+            // If libmagic couldn't be loaded, every flag looks unsupported, so report that
+            // instead. Otherwise, pools can still be built lazily without libmagic.
+            sys::load()?;
+            Err(Error::UnsupportedFlag {
+                flag,
+                version: crate::version(),
             })
-            .map(|bytes| {
-                CString::new(bytes)
-                    .map(Source::Files)
-                    .map_err(|_| Error::EmbeddedNuls)
-            })?
+        }
+        None => Ok(()),
+    }
+}
+
+/// Returns the default magic database path(s), as understood by libmagic.
+pub(crate) fn raw_default_database_path() -> Option<CString> {
+    let path = unsafe { sys::magic_getpath(std::ptr::null(), 0) };
+    if path.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(path) }.to_owned())
+    }
+}
+
+/// Returns the base that other paths should be joined onto, which is either the default database
+/// path or nothing at all.
+pub(crate) fn default_database_base(default_database: bool) -> Result<Vec<u8>, Error> {
+    if default_database {
+        Ok(raw_default_database_path()
+            .ok_or(Error::NoDefaultDatabase)?
+            .into_bytes())
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Joins paths onto `base` in the colon separated form that libmagic expects.
+pub(crate) fn join_paths<P>(
+    base: Vec<u8>,
+    paths: impl IntoIterator<Item = P>,
+) -> Result<CString, Error>
+where
+    P: AsRef<Path>,
+{
+    // libmagic only accepts a colon-separated set of paths, so we have to take our Rust PathBufs
+    // and turn them into that. An obvious corollary here is that no path can include a colon;
+    // FileConfig's buffered loading exists to get around that where necessary.
+    paths
+        .into_iter()
+        .try_fold(base, |mut acc, path| {
+            if !acc.is_empty() {
+                acc.push(b':');
+            }
+
+            let bytes = path.as_ref().as_os_str().as_encoded_bytes();
+            if bytes.contains(&b':') {
+                Err(Error::EmbeddedColons)
+            } else {
+                acc.extend(bytes);
+                Ok(acc)
+            }
+        })
+        .map(|bytes| CString::new(bytes).map_err(|_| Error::EmbeddedNuls))?
+}
+
+/// Returns the path to the compiled form of a magic database, following libmagic's convention of
+/// appending `.mgc` to the path of the source.
+pub(crate) fn compiled_path(path: &Path) -> PathBuf {
+    if path.extension().is_some_and(|ext| ext == "mgc") {
+        path.to_path_buf()
+    } else {
+        let mut compiled = path.as_os_str().to_owned();
+        compiled.push(".mgc");
+        compiled.into()
+    }
+}
+
+/// Checks that each path, or its compiled form, can be opened for reading.
+fn validate_paths(paths: &[PathBuf]) -> Result<(), Error> {
+    for path in paths {
+        if let Err(source) = std::fs::File::open(path)
+            && (source.kind() != ErrorKind::NotFound || !compiled_path(path).is_file())
+        {
+            return Err(Error::ReadDatabase {
+                path: path.clone(),
+                source,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks a single path as it's added to a configuration. Colons are only acceptable if the path
+/// won't be joined with others.
+fn validate_path(path: &Path, allow_colons: bool) -> Result<(), Error> {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    if bytes.contains(&0) {
+        Err(Error::EmbeddedNuls)
+    } else if !allow_colons && bytes.contains(&b':') {
+        Err(Error::EmbeddedColons)
+    } else {
+        validate_paths(&[path.to_path_buf()])
+    }
+}
+
+/// Checks that no buffer is empty.
+fn validate_buffers(buffers: &[SharedBuffer]) -> Result<(), Error> {
+    match buffers
+        .iter()
+        .position(|buffer| buffer.as_slice().is_empty())
+    {
+        Some(index) => Err(Error::EmptyBuffer(index)),
+        None => Ok(()),
+    }
+}
+
+/// Reads a magic database into memory, returning `None` if it doesn't exist.
+pub(crate) fn read_database(path: &Path) -> Result<Option<Vec<u8>>, Error> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(Error::ReadDatabase {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// Expands a leading `~` and any environment variables within a path, and then canonicalises it
+/// if it exists.
+fn expand_path(path: &Path) -> Result<PathBuf, Error> {
+    let undefined = |variable: &[u8]| Error::UndefinedVariable {
+        path: path.to_path_buf(),
+        variable: String::from_utf8_lossy(variable).into_owned(),
+    };
+
+    let mut rest = path.as_os_str().as_bytes();
+    let mut expanded = Vec::with_capacity(rest.len());
+
+    if rest == b"~" || rest.starts_with(b"~/") {
+        let home = std::env::home_dir().ok_or_else(|| undefined(b"HOME"))?;
+        expanded.extend_from_slice(home.as_os_str().as_bytes());
+        rest = &rest[1..];
+    }
+
+    while let Some(dollar) = rest.iter().position(|b| *b == b'$') {
+        expanded.extend_from_slice(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+
+        let (name, remainder) = match rest.strip_prefix(b"{") {
+            Some(braced) => match braced.iter().position(|b| *b == b'}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => (&braced[..0], rest),
+            },
+            None => {
+                let end = rest
+                    .iter()
+                    .position(|b| !(b.is_ascii_alphanumeric() || *b == b'_'))
+                    .unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+
+        // A `$` that isn't followed by a variable name is left alone.
+        if name.is_empty() {
+            expanded.push(b'$');
+            continue;
+        }
+
+        let value = std::env::var_os(OsStr::from_bytes(name)).ok_or_else(|| undefined(name))?;
+        expanded.extend_from_slice(value.as_bytes());
+        rest = remainder;
+    }
+    expanded.extend_from_slice(rest);
+
+    let expanded = PathBuf::from(OsString::from_vec(expanded));
+    match std::fs::canonicalize(&expanded) {
+        Ok(canonical) => Ok(canonical),
+        // The path may only exist in its compiled form, or not at all, in which case we'll let
+        // validation or libmagic report it in the usual way.
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(expanded),
+        Err(source) => Err(Error::ReadDatabase {
+            path: expanded,
+            source,
+        }),
     }
 }
 
@@ -208,4 +1146,83 @@ mod tests {
             (Flag::Apple as c_int) | (Flag::Check as c_int)
         );
     }
+
+    #[test]
+    fn from_iter() {
+        let config: FileConfig = ["a", "b"].into_iter().collect();
+        assert_eq!(config.paths, vec![PathBuf::from("a"), PathBuf::from("b")]);
+
+        let config = config.with_files(vec![PathBuf::from("c")]);
+        assert_eq!(config.paths.len(), 3);
+
+        let config: BufferConfig = [b"foo".as_slice(), b"bar"].into_iter().collect();
+        assert_eq!(buffers(&config.buffers), vec![b"foo", b"bar"]);
+
+        let config = CombinedConfig::default()
+            .with_files(["a"])
+            .with_buffers([vec![0u8]]);
+        assert_eq!(config.paths, vec![PathBuf::from("a")]);
+        assert_eq!(buffers(&config.buffers), vec![[0u8]]);
+    }
+
+    #[test]
+    fn owned_buffer() {
+        static DATA: &[u8] = b"foo";
+
+        let config = BufferConfig::default().with_owned_buffer(DATA);
+        assert_eq!(config.buffers[0].as_slice().as_ptr(), DATA.as_ptr());
+
+        // Cloning the configuration shouldn't copy the buffer, either.
+        let cloned = config.clone();
+        assert_eq!(cloned.buffers[0].as_slice().as_ptr(), DATA.as_ptr());
+    }
+
+    #[test]
+    fn by_value_buffer() {
+        let buffer = b"foo".to_vec();
+        let ptr = buffer.as_ptr();
+        let config = BufferConfig::default().with_buffer(buffer);
+        assert_eq!(config.buffers[0].as_slice().as_ptr(), ptr);
+
+        let buffer: Arc<[u8]> = Arc::from(b"bar".as_slice());
+        let config = CombinedConfig::default()
+            .with_buffer(b"foo")
+            .with_buffer_arc(buffer.clone());
+        assert_eq!(buffers(&config.buffers), vec![b"foo", b"bar"]);
+        assert_eq!(config.buffers[1].as_slice().as_ptr(), buffer.as_ptr());
+    }
+
+    #[test]
+    fn expand_path() {
+        let home = std::env::home_dir().expect("home directory");
+        let expand = |path: &str| super::expand_path(Path::new(path));
+
+        assert_eq!(
+            expand("~/mojique-missing").unwrap(),
+            home.join("mojique-missing")
+        );
+        assert_eq!(
+            expand("$HOME/mojique-missing").unwrap(),
+            home.join("mojique-missing")
+        );
+        assert_eq!(
+            expand("${HOME}/mojique-$/missing").unwrap(),
+            home.join("mojique-$/missing")
+        );
+        assert_eq!(expand("/mojique/~").unwrap(), PathBuf::from("/mojique/~"));
+        assert!(matches!(
+            expand("$MOJIQUE_UNDEFINED_VARIABLE/magic"),
+            Err(Error::UndefinedVariable { variable, .. }) if variable == "MOJIQUE_UNDEFINED_VARIABLE"
+        ));
+
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(
+            expand(&format!("{}/tests/../tests/data", manifest_dir.display())).unwrap(),
+            manifest_dir.canonicalize().unwrap().join("tests/data")
+        );
+    }
+
+    fn buffers(buffers: &[SharedBuffer]) -> Vec<&[u8]> {
+        buffers.iter().map(SharedBuffer::as_slice).collect()
+    }
 }
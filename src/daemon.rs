@@ -0,0 +1,295 @@
+//! A daemon that performs detection on behalf of other processes over a Unix socket, along with
+//! the protocol that it speaks.
+//!
+//! Each connection carries any number of requests, each of which receives a single response:
+//!
+//! * A request starts with a single kind byte. `B` is followed by a 32 bit big endian length and
+//!   then that many bytes of content, which may not exceed [`BYTES_MAX`]. `F` has exactly one file
+//!   descriptor attached to the kind byte as `SCM_RIGHTS` ancillary data, and nothing else.
+//! * A response is a status byte (`0` for success, `1` for an error), followed by a 32 bit big
+//!   endian length and then that many bytes of UTF-8: either the description, or the error
+//!   message. Clients reject responses longer than 4 MiB.
+//!
+//! Malformed requests cause the daemon to close the connection.
+
+use std::{
+    io::{self, Read, Write},
+    mem::{size_of, zeroed},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+};
+
+use crate::{BYTES_MAX, Pool, instrument};
+
+pub(crate) const REQUEST_BYTES: u8 = b'B';
+pub(crate) const REQUEST_FD: u8 = b'F';
+pub(crate) const RESPONSE_OK: u8 = 0;
+pub(crate) const RESPONSE_ERROR: u8 = 1;
+
+/// The longest response that a client will accept, which is far longer than any description or
+/// error message, but stops a misbehaving daemon from making the client allocate up to 4 GiB.
+pub(crate) const RESPONSE_MAX: usize = 4 * 1024 * 1024;
+
+/// A daemon that serves detection requests over a Unix socket, backed by a [`Pool`].
+///
+/// Requests may contain either the content to be detected, or a file descriptor opened by the
+/// client. Since the daemon never opens files itself, it can run sandboxed as an unprivileged
+/// user, isolating libmagic's parsers from the clients that use it. [`Client`][crate::Client]
+/// implements the client side of the protocol, and the `mojique-daemon` binary runs a daemon as
+/// a standalone process.
+///
+/// ```no_run
+/// use std::os::unix::net::UnixListener;
+///
+/// use mojique::{Config, Daemon, DefaultConfig, Flag};
+///
+/// let pool = DefaultConfig::default().set_flag(Flag::MimeType).build_pool()?;
+/// Daemon::new(pool).serve(UnixListener::bind("/run/mojique.sock")?)?;
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Daemon {
+    pool: Pool,
+}
+
+impl Daemon {
+    /// Creates a new daemon that performs detection using the given pool.
+    pub fn new(pool: Pool) -> Self {
+        Self { pool }
+    }
+
+    /// Accepts connections on the listener until accepting fails, serving each connection on
+    /// its own thread.
+    ///
+    /// There's no limit on the number of connections served at once, so the number of threads is
+    /// only bounded by the clients, although detection itself is still limited by the pool's
+    /// [`max_size`][crate::PoolOptions::max_size], if any. Errors serving a connection, such as a
+    /// malformed request, close that connection and are logged if the `log` feature is enabled;
+    /// callers that need to handle them can use [`Daemon::serve_connection`] directly.
+    pub fn serve(&self, listener: UnixListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let daemon = self.clone();
+            std::thread::Builder::new()
+                .name("mojique-daemon".into())
+                .spawn(move || {
+                    if let Err(e) = daemon.serve_connection(stream) {
+                        instrument::connection_failed(&e);
+                    }
+                })?;
+        }
+    }
+
+    /// Serves requests on a single connection until the client disconnects, returning an error
+    /// if the client sends a malformed request.
+    pub fn serve_connection(&self, mut stream: UnixStream) -> io::Result<()> {
+        while let Some((kind, fd)) = recv_kind(&stream)? {
+            let result = match (kind, fd) {
+                (REQUEST_BYTES, None) => {
+                    let len = read_len(&mut stream)?;
+                    if len > BYTES_MAX {
+                        return Err(malformed("content is larger than BYTES_MAX"));
+                    }
+                    let mut buf = vec![0; len];
+                    stream.read_exact(&mut buf)?;
+
+                    self.pool.buffer(&buf)
+                }
+                (REQUEST_FD, Some(fd)) => {
+                    self.pool.handle().and_then(|mut handle| handle.raw_fd(fd))
+                }
+                _ => return Err(malformed("unexpected request kind")),
+            };
+
+            match result {
+                Ok(description) => write_frame(&mut stream, RESPONSE_OK, description.as_bytes())?,
+                Err(e) => write_frame(&mut stream, RESPONSE_ERROR, e.to_string().as_bytes())?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn malformed(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn read_len(stream: &mut impl Read) -> io::Result<usize> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    Ok(u32::from_be_bytes(len) as usize)
+}
+
+/// Writes a kind or status byte, followed by the length of the data and then the data itself.
+pub(crate) fn write_frame(stream: &mut impl Write, kind: u8, data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len()).map_err(|_| malformed("frame is too long"))?;
+    let mut buf = Vec::with_capacity(data.len() + 5);
+    buf.push(kind);
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(data);
+    stream.write_all(&buf)
+}
+
+/// Sends a single kind byte with a file descriptor attached.
+pub(crate) fn send_fd(stream: &UnixStream, kind: u8, fd: RawFd) -> io::Result<()> {
+    let mut data = [kind];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: data.len(),
+    };
+    let mut control = ControlBuffer::new();
+
+    // SAFETY: the message only refers to buffers that outlive the call to sendmsg, and the
+    // control buffer is large enough and suitably aligned for a single cmsghdr with one fd.
+    let sent = unsafe {
+        let mut msg: libc::msghdr = zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr();
+        msg.msg_controllen = control.len() as _;
+
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(size_of::<RawFd>() as u32) as _;
+        libc::CMSG_DATA(cmsg).cast::<RawFd>().write_unaligned(fd);
+
+        libc::sendmsg(stream.as_raw_fd(), &msg, 0)
+    };
+
+    match sent {
+        sent if sent < 0 => Err(io::Error::last_os_error()),
+        0 => Err(io::ErrorKind::WriteZero.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Receives a single kind byte, along with the file descriptor attached to it, if any.
+///
+/// Returns `None` if the peer has closed the connection.
+fn recv_kind(stream: &UnixStream) -> io::Result<Option<(u8, Option<OwnedFd>)>> {
+    let mut data = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: data.len(),
+    };
+    let mut control = ControlBuffer::new();
+
+    // SAFETY: as above, and any file descriptors received are immediately wrapped in OwnedFds so
+    // that they're closed if they aren't used.
+    unsafe {
+        let mut msg: libc::msghdr = zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr();
+        msg.msg_controllen = control.len() as _;
+
+        let received = libc::recvmsg(stream.as_raw_fd(), &mut msg, RECV_FLAGS);
+        if received < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut fds = Vec::new();
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg).cast::<RawFd>();
+                let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                for i in 0..len / size_of::<RawFd>() {
+                    let fd = OwnedFd::from_raw_fd(data.add(i).read_unaligned());
+                    #[cfg(not(any(
+                        target_os = "android",
+                        target_os = "freebsd",
+                        target_os = "linux",
+                        target_os = "netbsd",
+                        target_os = "openbsd"
+                    )))]
+                    if libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    fds.push(fd);
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+
+        if received == 0 {
+            return Ok(None);
+        }
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 || fds.len() > 1 {
+            return Err(malformed("too many file descriptors"));
+        }
+
+        Ok(Some((data[0], fds.pop())))
+    }
+}
+
+// Received descriptors mustn't be inherited by the decompressors that libmagic forks for
+// Flag::Compress, since they could then access the files of other connections. Where it's
+// supported, the flag is set atomically on receipt; otherwise it's set immediately afterwards,
+// which still leaves a window in which another thread could fork.
+#[cfg(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+const RECV_FLAGS: libc::c_int = libc::MSG_CMSG_CLOEXEC;
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+const RECV_FLAGS: libc::c_int = 0;
+
+/// A control message buffer with enough space for a single file descriptor, aligned for
+/// `cmsghdr`.
+struct ControlBuffer([u64; 4]);
+
+impl ControlBuffer {
+    fn new() -> Self {
+        Self([0; 4])
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut libc::c_void {
+        self.0.as_mut_ptr().cast()
+    }
+
+    fn len(&self) -> usize {
+        // SAFETY: CMSG_SPACE is a pure calculation.
+        let len = unsafe { libc::CMSG_SPACE(size_of::<RawFd>() as u32) } as usize;
+        debug_assert!(len <= size_of::<[u64; 4]>());
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    #[test]
+    fn received_fds_are_cloexec() -> anyhow::Result<()> {
+        let (client, server) = UnixStream::pair()?;
+        let file = File::open(env!("CARGO_MANIFEST_DIR"))?;
+        send_fd(&client, REQUEST_FD, file.as_raw_fd())?;
+
+        let (kind, fd) = recv_kind(&server)?.expect("request");
+        assert_eq!(kind, REQUEST_FD);
+        let fd = fd.expect("file descriptor");
+
+        // SAFETY: fd is open for the duration of the call.
+        let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFD) };
+        assert!(flags >= 0);
+        assert_ne!(flags & libc::FD_CLOEXEC, 0);
+
+        Ok(())
+    }
+}
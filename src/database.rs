@@ -0,0 +1,138 @@
+use std::{
+    ffi::OsStr,
+    io::Read,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    Error,
+    compile::{compile, is_text_database},
+    config::{compiled_path, raw_default_database_path, read_database},
+    pool::SharedBuffer,
+};
+
+/// One or more compiled magic databases, held in memory.
+///
+/// A `Database` is loaded (and, if necessary, compiled) once, and can then be cheaply cloned into
+/// as many [`BufferConfig`][crate::BufferConfig]s and [`CombinedConfig`][crate::CombinedConfig]s as
+/// required, without the underlying buffers being copied or reparsed from disk.
+#[derive(Debug, Clone, Default)]
+pub struct Database {
+    buffers: Vec<SharedBuffer>,
+}
+
+impl Database {
+    /// Creates a database from a compiled magic database buffer, without copying it.
+    pub fn from_buffer<B>(buffer: B) -> Self
+    where
+        B: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        Self {
+            buffers: vec![SharedBuffer::new(buffer)],
+        }
+    }
+
+    /// Creates a database by reading a compiled magic database from a [`Read`].
+    pub fn from_reader(mut reader: impl Read) -> Result<Self, Error> {
+        let mut buffer = Vec::new();
+        reader
+            .read_to_end(&mut buffer)
+            .map_err(Error::ReadDatabaseReader)?;
+
+        Ok(Self::from_buffer(buffer))
+    }
+
+    /// Creates a database from a magic database file.
+    ///
+    /// As libmagic would, a compiled `.mgc` file alongside the path is used in preference to the
+    /// path itself. Otherwise, if the file is a text magic database, it is compiled first.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        if let Some(bytes) = read_database(&compiled_path(path))? {
+            return Ok(Self::from_buffer(bytes));
+        }
+
+        if is_text_database(path)? {
+            return compile_to_buffer(path).map(Self::from_buffer);
+        }
+
+        match read_database(path)? {
+            Some(bytes) => Ok(Self::from_buffer(bytes)),
+            None => Err(Error::ReadDatabase {
+                path: path.to_path_buf(),
+                source: std::io::ErrorKind::NotFound.into(),
+            }),
+        }
+    }
+
+    /// Creates a database from the default magic database installed on the system.
+    ///
+    /// The default path may include multiple colon separated paths, and they may not all exist,
+    /// so this loads the compiled forms of whichever ones it can find.
+    pub fn default_database() -> Result<Self, Error> {
+        let default = raw_default_database_path().ok_or(Error::NoDefaultDatabase)?;
+
+        let mut buffers = Vec::new();
+        for component in default.as_bytes().split(|b| *b == b':') {
+            let path = Path::new(OsStr::from_bytes(component));
+            if let Some(bytes) = read_database(&compiled_path(path))? {
+                buffers.push(SharedBuffer::new(bytes));
+            }
+        }
+
+        if buffers.is_empty() {
+            Err(Error::NoDefaultDatabase)
+        } else {
+            Ok(Self { buffers })
+        }
+    }
+
+    /// Appends the buffers of another database to this one, so that both are loaded together.
+    pub fn with_database(mut self, database: &Database) -> Self {
+        self.buffers.extend(database.buffers.iter().cloned());
+        self
+    }
+
+    /// Loads magic database files as per [`Database::from_file`], optionally after the default
+    /// database.
+    pub(crate) fn from_files(default_database: bool, paths: &[PathBuf]) -> Result<Self, Error> {
+        let database = if default_database {
+            Self::default_database()?
+        } else {
+            Self::default()
+        };
+
+        paths.iter().try_fold(database, |database, path| {
+            Ok(database.with_database(&Self::from_file(path)?))
+        })
+    }
+
+    pub(crate) fn buffers(&self) -> &[SharedBuffer] {
+        &self.buffers
+    }
+}
+
+/// Compiles a text magic database into a temporary directory, and then reads it back.
+fn compile_to_buffer(path: &Path) -> Result<Vec<u8>, Error> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = std::env::temp_dir().join(format!(
+        "mojique-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).map_err(Error::TemporaryDirectory)?;
+
+    let result = compile([path], &dir).and_then(|compiled| {
+        std::fs::read(&compiled[0]).map_err(|source| Error::ReadDatabase {
+            path: compiled[0].clone(),
+            source,
+        })
+    });
+
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
@@ -0,0 +1,178 @@
+// Deduplication is only used by batch operations, which are all feature gated, whereas hashing is
+// also used by the scanner.
+#![cfg_attr(not(feature = "uring"), allow(dead_code))]
+
+use std::{collections::HashMap, fmt::Display};
+
+#[cfg(feature = "sha2")]
+use sha2::Digest as _;
+
+use crate::{Detection, Error, Handle};
+
+/// The hash algorithms that can be used to deduplicate inputs in batch operations, and to hash
+/// files while scanning them.
+///
+/// Each variant is only available if the feature of the same name is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HashAlgorithm {
+    /// The BLAKE3 cryptographic hash, from the [`blake3`][blake3] crate.
+    ///
+    /// This is slower than XXH3, but collisions are infeasible even for inputs that have been
+    /// crafted to collide, which makes it the better choice for untrusted content.
+    ///
+    /// [blake3]: https://crates.io/crates/blake3
+    #[cfg(feature = "blake3")]
+    Blake3,
+
+    /// The SHA-256 cryptographic hash, from the [`sha2`][sha2] crate.
+    ///
+    /// This is slower than BLAKE3, but is the most widely supported by other tools, which makes
+    /// it the better choice for hashes that will be compared against existing inventories.
+    ///
+    /// [sha2]: https://crates.io/crates/sha2
+    #[cfg(feature = "sha2")]
+    Sha256,
+
+    /// The 128 bit XXH3 hash, from the [`xxhash-rust`][xxhash-rust] crate.
+    ///
+    /// This is extremely fast, and accidental collisions are vanishingly unlikely, but it isn't
+    /// resistant to inputs that have been crafted to collide.
+    ///
+    /// [xxhash-rust]: https://crates.io/crates/xxhash-rust
+    #[cfg(feature = "xxhash")]
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    fn digest(self, buf: &[u8]) -> Digest {
+        let mut hasher = self.hasher();
+        hasher.update(buf);
+        hasher.finish()
+    }
+
+    pub(crate) fn hasher(self) -> Hasher {
+        match self {
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => Hasher::Blake3(Box::default()),
+            #[cfg(feature = "sha2")]
+            Self::Sha256 => Hasher::Sha256(Box::default()),
+            #[cfg(feature = "xxhash")]
+            Self::Xxh3 => Hasher::Xxh3(Box::default()),
+        }
+    }
+
+    /// Returns the length of the digests produced by the algorithm, in bytes.
+    fn len(self) -> usize {
+        match self {
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => 32,
+            #[cfg(feature = "sha2")]
+            Self::Sha256 => 32,
+            #[cfg(feature = "xxhash")]
+            Self::Xxh3 => 16,
+        }
+    }
+}
+
+/// Incrementally hashes an input that is read in chunks.
+pub(crate) enum Hasher {
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+    #[cfg(feature = "sha2")]
+    Sha256(Box<sha2::Sha256>),
+    #[cfg(feature = "xxhash")]
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+    pub(crate) fn update(&mut self, buf: &[u8]) {
+        match self {
+            #[cfg(feature = "blake3")]
+            Self::Blake3(hasher) => {
+                hasher.update(buf);
+            }
+            #[cfg(feature = "sha2")]
+            Self::Sha256(hasher) => hasher.update(buf),
+            #[cfg(feature = "xxhash")]
+            Self::Xxh3(hasher) => hasher.update(buf),
+        }
+    }
+
+    pub(crate) fn finish(self) -> Digest {
+        let (algorithm, bytes) = match self {
+            #[cfg(feature = "blake3")]
+            Self::Blake3(hasher) => (HashAlgorithm::Blake3, *hasher.finalize().as_bytes()),
+            #[cfg(feature = "sha2")]
+            Self::Sha256(hasher) => (HashAlgorithm::Sha256, hasher.finalize().into()),
+            #[cfg(feature = "xxhash")]
+            Self::Xxh3(hasher) => {
+                let mut bytes = [0; 32];
+                bytes[..16].copy_from_slice(&hasher.digest128().to_be_bytes());
+                (HashAlgorithm::Xxh3, bytes)
+            }
+        };
+
+        Digest { algorithm, bytes }
+    }
+}
+
+/// The hash of an input, as computed by a [`HashAlgorithm`].
+///
+/// This is formatted as lowercase hexadecimal, as by tools such as `sha256sum` and `b3sum`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Digest {
+    algorithm: HashAlgorithm,
+    bytes: [u8; 32],
+}
+
+impl Digest {
+    /// Returns the algorithm that produced the digest.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns the bytes of the digest, the length of which depends on the algorithm.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.algorithm.len()]
+    }
+}
+
+impl Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_bytes()
+            .iter()
+            .try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// Performs detection on each unique input once, returning the same detection for duplicates.
+///
+/// Errors aren't remembered, so detection is performed again on duplicates of an input that
+/// failed.
+#[derive(Debug)]
+pub(crate) struct Dedup {
+    algorithm: HashAlgorithm,
+    seen: HashMap<Digest, Detection>,
+}
+
+impl Dedup {
+    pub(crate) fn new(algorithm: HashAlgorithm) -> Self {
+        Self {
+            algorithm,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn buffer(&mut self, handle: &mut Handle, buf: &[u8]) -> Result<Detection, Error> {
+        let digest = self.algorithm.digest(buf);
+        if let Some(detection) = self.seen.get(&digest) {
+            return Ok(detection.clone());
+        }
+
+        let detection = Detection::from(handle.buffer(buf)?);
+        self.seen.insert(digest, detection.clone());
+
+        Ok(detection)
+    }
+}
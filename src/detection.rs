@@ -0,0 +1,175 @@
+use std::fmt::Display;
+
+/// The result of a detection, wrapping the textual description returned by libmagic.
+///
+/// The format of the description depends on the flags that the handle was configured with. If
+/// [`Flag::MimeType`][crate::Flag::MimeType] or [`Flag::Mime`][crate::Flag::Mime] were set, the
+/// MIME type can be retrieved with [`Detection::mime_type`] and matched against patterns such as
+/// `image/*` with [`Detection::matches`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Detection {
+    description: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    prefix: bool,
+}
+
+impl Detection {
+    /// Creates a detection from a textual description.
+    pub fn new(description: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            prefix: false,
+        }
+    }
+
+    /// Sets whether the detection was only performed on a prefix of the input.
+    pub fn with_prefix(mut self, prefix: bool) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Returns the textual description.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns `true` if the detection was only performed on a prefix of the input, such as for
+    /// large files scanned with [`LargeFilePolicy::Prefix`][crate::LargeFilePolicy::Prefix], and
+    /// may therefore be less accurate than usual.
+    pub fn is_prefix(&self) -> bool {
+        self.prefix
+    }
+
+    /// Returns the textual description, consuming the detection.
+    pub fn into_description(self) -> String {
+        self.description
+    }
+
+    /// Returns the MIME type, if the description is a MIME type.
+    ///
+    /// Any parameters, such as the `charset` included by [`Flag::Mime`][crate::Flag::Mime], are
+    /// omitted.
+    pub fn mime_type(&self) -> Option<&str> {
+        mime_type(&self.description)
+    }
+
+    /// Returns the MIME encoding, if the description is a MIME type with a `charset` parameter.
+    pub fn mime_encoding(&self) -> Option<&str> {
+        self.mime_type()?;
+        self.description
+            .split(';')
+            .skip(1)
+            .filter_map(|param| param.trim().split_once('='))
+            .find(|(name, _)| name.eq_ignore_ascii_case("charset"))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns `true` if the MIME type matches the given pattern.
+    ///
+    /// Patterns may be a full MIME type such as `application/pdf`, a wildcard subtype such as
+    /// `image/*`, or `*/*` (or `*`) to match any MIME type. Matching is case insensitive. If the
+    /// description isn't a MIME type, this always returns `false`.
+    pub fn matches(&self, pattern: &str) -> bool {
+        let Some(mime_type) = self.mime_type() else {
+            return false;
+        };
+
+        match pattern.trim() {
+            "*" | "*/*" => true,
+            pattern => match pattern.strip_suffix("/*") {
+                Some(type_) => mime_type
+                    .split_once('/')
+                    .is_some_and(|(actual, _)| actual.eq_ignore_ascii_case(type_)),
+                None => mime_type.eq_ignore_ascii_case(pattern),
+            },
+        }
+    }
+}
+
+impl Display for Detection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.description)
+    }
+}
+
+impl From<String> for Detection {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<Detection> for String {
+    fn from(value: Detection) -> Self {
+        value.description
+    }
+}
+
+/// The source of a [`Detection`] returned by
+/// [`Handle::detect_with_fallback`][crate::Handle::detect_with_fallback].
+#[cfg(feature = "mime_guess")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DetectionSource {
+    /// The detection was made by libmagic, based on the content.
+    Libmagic,
+
+    /// libmagic couldn't identify the content, so the MIME type was guessed from the file name
+    /// extension.
+    Extension,
+}
+
+/// Returns the MIME type within a description, if the description is a MIME type.
+pub(crate) fn mime_type(description: &str) -> Option<&str> {
+    let mime_type = description.split(';').next()?.trim();
+    match mime_type.split_once('/') {
+        Some((type_, subtype))
+            if is_token(type_) && is_token(subtype) && !subtype.contains('/') =>
+        {
+            Some(mime_type)
+        }
+        _ => None,
+    }
+}
+
+/// Returns `true` if the string is a valid MIME type or subtype token.
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$&^_.+-".contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mime() {
+        let detection = Detection::new("text/plain; charset=us-ascii");
+        assert_eq!(detection.mime_type(), Some("text/plain"));
+        assert_eq!(detection.mime_encoding(), Some("us-ascii"));
+        assert!(detection.matches("text/plain"));
+        assert!(detection.matches("TEXT/*"));
+        assert!(detection.matches("*/*"));
+        assert!(!detection.matches("text/html"));
+        assert!(!detection.matches("image/*"));
+
+        let detection = Detection::new("application/vnd.ms-excel");
+        assert_eq!(detection.mime_type(), Some("application/vnd.ms-excel"));
+        assert_eq!(detection.mime_encoding(), None);
+
+        // Descriptions that aren't MIME types shouldn't match anything.
+        let detection = Detection::new("ASCII text");
+        assert_eq!(detection.mime_type(), None);
+        assert!(!detection.matches("*"));
+        let detection = Detection::new("Zip archive data, at least v2.0 to extract/compress");
+        assert_eq!(detection.mime_type(), None);
+    }
+}
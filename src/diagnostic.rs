@@ -0,0 +1,95 @@
+use std::fmt::Display;
+
+use miette::{Diagnostic, Severity};
+
+use crate::{CheckWarning, Error};
+
+const INSTALL_DATABASE: &str = "install the magic database for libmagic (usually packaged as \
+                                `libmagic-mgc`, `file-libs`, or `file`), or configure a magic \
+                                database explicitly";
+
+impl Diagnostic for Error {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(format!("mojique::{}", self.name())))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let help: Box<dyn Display + 'a> = match self {
+            Error::AlreadyCompiled(path) => Box::new(format!(
+                "load {} directly, rather than compiling it again",
+                path.display()
+            )),
+            Error::Check { warnings, .. } if !warnings.is_empty() => {
+                Box::new("the warnings from libmagic explain why the check failed")
+            }
+            Error::CheckBuffers => {
+                Box::new("check the database files before they are loaded into buffers")
+            }
+            Error::DaemonConnection(_) => {
+                Box::new("check that mojique-daemon is running and listening on the socket")
+            }
+            Error::DatabaseLoad { .. }
+            | Error::EnvDatabaseMissing(_)
+            | Error::Load { .. }
+            | Error::NoDatabases(_)
+            | Error::NoDefaultDatabase => Box::new(INSTALL_DATABASE),
+            Error::EmbeddedColons => {
+                Box::new("use FileConfig::with_buffered_loading to load paths containing colons")
+            }
+            Error::LibraryLoad(_) => Box::new(
+                "install libmagic, or set MOJIQUE_LIBMAGIC to the name or path of the library",
+            ),
+            Error::NoCacheDirectory => {
+                Box::new("set XDG_CACHE_HOME, or use FileConfig::with_compile_cache_dir")
+            }
+            Error::UncompiledBuffer => {
+                Box::new("compile text magic databases with mojique::compile first")
+            }
+            Error::UndefinedVariable { variable, .. } => Box::new(format!(
+                "set {variable}, or disable path expansion with FileConfig::with_path_expansion"
+            )),
+            Error::UnsupportedFlag { .. } => Box::new(
+                "use mojique::capabilities or Flag::is_supported to check for support first, or \
+                 upgrade libmagic",
+            ),
+            Error::UnsupportedFunction(_) => Box::new(
+                "upgrade libmagic, or set MOJIQUE_LIBMAGIC to the name or path of a newer library",
+            ),
+            Error::WatchBuffers => Box::new(
+                "load the databases from the filesystem with FileConfig, so they can be watched",
+            ),
+            _ => return None,
+        };
+
+        Some(help)
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        match self {
+            Error::Check { warnings, .. } if !warnings.is_empty() => Some(Box::new(
+                warnings.iter().map(|warning| warning as &dyn Diagnostic),
+            )),
+            _ => None,
+        }
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        match self {
+            Error::Check { source, .. }
+            | Error::Compile { source, .. }
+            | Error::Detect { source, .. }
+            | Error::Load { source } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Diagnostic for CheckWarning {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new("mojique::check_warning"))
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Warning)
+    }
+}
@@ -0,0 +1,515 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{Detection, Error, Pool};
+
+/// The first line of every cache file, which identifies the format.
+const HEADER: &str = "mojique-cache 1";
+
+/// The default maximum number of files held by a [`DiskCache`].
+const DEFAULT_MAX_ENTRIES: usize = 1_000_000;
+
+/// The number of superseded records tolerated in a cache file before it's compacted, beyond one
+/// for each live entry.
+const COMPACT_SLACK: usize = 1024;
+
+/// A cache of file detections that persists on disk between runs.
+///
+/// Files are keyed by their device, inode, size, and modification time, so repeated scans of a
+/// mostly unchanged tree, such as when verifying backups or indexing a desktop, only perform
+/// detection on the files that have changed since the last scan. Since the key doesn't include the
+/// path, files that are renamed or hard linked are also found in the cache. Symlinks and special
+/// files, such as FIFOs and devices, are never cached.
+///
+/// The cache is stored as an append-only log, which is compacted whenever it grows to more than
+/// twice the size of its live entries. Results are buffered, so
+/// [`DiskCache::flush`] should be called to ensure that they have been written, although this also
+/// happens when the cache is dropped. A partially written record, such as after a crash, is
+/// discarded when the cache is next opened.
+///
+/// The cache doesn't record how the pool was configured, so the same cache file should only be
+/// used with pools that use the same magic databases and flags, and [`DiskCache::clear`] should
+/// be called when either changes. A cache file also shouldn't be opened by more than one process
+/// at a time.
+pub struct DiskCache {
+    pool: Pool,
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+impl DiskCache {
+    /// Opens the cache at the given path, creating it if it doesn't exist, and performs detection
+    /// with the given pool on files that aren't in the cache.
+    ///
+    /// A file that isn't a cache, or was written by an incompatible version of mojique, is
+    /// replaced with an empty cache.
+    pub fn open(pool: Pool, path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let error = |source| Error::DiskCache {
+            path: path.clone(),
+            source,
+        };
+
+        let mut state = match File::open(&path) {
+            Ok(file) => State::load(BufReader::new(file)).map_err(error)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(e) => return Err(error(e)),
+        };
+        state.compact_if_needed(&path).map_err(error)?;
+
+        Ok(Self {
+            pool,
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Sets the maximum number of files held by the cache.
+    ///
+    /// Once the cache is full, the files that were added to the cache the longest time ago are
+    /// evicted first. By default, up to one million files are held, and a maximum of zero disables
+    /// caching.
+    pub fn with_max_entries(self, max_entries: usize) -> Self {
+        {
+            let mut state = self.lock();
+            state.max_entries = max_entries;
+            state.evict();
+        }
+        self
+    }
+
+    /// Returns the path to the cache file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the underlying pool.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    /// Performs detection on the given file, returning the cached detection if the file hasn't
+    /// changed since it was last seen.
+    ///
+    /// Errors from detection are returned as they would be from [`Pool::file`], and aren't cached.
+    /// Errors writing to the cache are returned as [`Error::DiskCache`].
+    pub fn file(&self, path: impl AsRef<Path>) -> Result<Detection, Error> {
+        let path = path.as_ref();
+
+        // As with CachedPool, anything that can't be cached is left to libmagic.
+        let key = match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_file() => Key::from(&metadata),
+            _ => return self.pool.file(path).map(Detection::from),
+        };
+
+        if let Some(detection) = self.lock().get(&key) {
+            return Ok(detection);
+        }
+
+        let detection = Detection::from(self.pool.file(path)?);
+        self.lock()
+            .insert(&self.path, key, detection.clone())
+            .map_err(|source| self.error(source))?;
+
+        Ok(detection)
+    }
+
+    /// Removes the given file from the cache, returning `true` if it was cached.
+    pub fn invalidate(&self, path: impl AsRef<Path>) -> Result<bool, Error> {
+        let metadata = match std::fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(Error::ReadInput(e)),
+        };
+
+        self.lock()
+            .remove(metadata.dev(), metadata.ino())
+            .map_err(|source| self.error(source))
+    }
+
+    /// Removes every file from the cache.
+    pub fn clear(&self) -> Result<(), Error> {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.order.clear();
+        state
+            .compact(&self.path)
+            .map_err(|source| self.error(source))
+    }
+
+    /// Rewrites the cache file to only contain the files currently in the cache.
+    ///
+    /// This happens automatically as the cache grows, so it's rarely necessary to call this.
+    pub fn compact(&self) -> Result<(), Error> {
+        self.lock()
+            .compact(&self.path)
+            .map_err(|source| self.error(source))
+    }
+
+    /// Writes any buffered results to the cache file.
+    pub fn flush(&self) -> Result<(), Error> {
+        match &mut self.lock().log {
+            Some(log) => log.flush().map_err(|source| self.error(source)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the number of files in the cache.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Returns `true` if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn error(&self, source: std::io::Error) -> Error {
+        Error::DiskCache {
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    // If a write to the cache file panics, the worst case is a partially written record, which is
+    // discarded on load, so a poisoned lock is safe to recover.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Debug for DiskCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiskCache")
+            .field("pool", &self.pool)
+            .field("path", &self.path)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Key {
+    dev: u64,
+    ino: u64,
+    size: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+}
+
+impl From<&std::fs::Metadata> for Key {
+    fn from(metadata: &std::fs::Metadata) -> Self {
+        Self {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            size: metadata.size(),
+            mtime: metadata.mtime(),
+            mtime_nsec: metadata.mtime_nsec(),
+        }
+    }
+}
+
+struct Entry {
+    key: Key,
+    detection: Detection,
+    seq: u64,
+}
+
+struct State {
+    // Entries are indexed by device and inode, so that a file that has changed replaces its
+    // previous entry.
+    entries: HashMap<(u64, u64), Entry>,
+
+    // Maps the sequence number of each entry back to its device and inode, so that the oldest
+    // entry is always the first.
+    order: BTreeMap<u64, (u64, u64)>,
+
+    seq: u64,
+    max_entries: usize,
+
+    // The number of records in the cache file, including those that have been superseded.
+    records: usize,
+
+    // Whether the cache file needs to be rewritten, because it's missing, isn't a cache, or
+    // contains a partially written record.
+    rewrite: bool,
+
+    // The cache file, which is only opened for appending once it has been compacted.
+    log: Option<BufWriter<File>>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            seq: 0,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            records: 0,
+            rewrite: true,
+            log: None,
+        }
+    }
+}
+
+impl State {
+    fn load(mut reader: impl BufRead) -> std::io::Result<Self> {
+        let mut state = Self::default();
+
+        let mut line = Vec::new();
+        reader.read_until(b'\n', &mut line)?;
+        if line.strip_suffix(b"\n") != Some(HEADER.as_bytes()) {
+            return Ok(state);
+        }
+        state.rewrite = false;
+
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+
+            state.records += 1;
+            match line
+                .strip_suffix(b"\n")
+                .and_then(|line| std::str::from_utf8(line).ok())
+                .and_then(Record::parse)
+            {
+                Some(Record::Insert(key, detection)) => state.put(key, detection),
+                Some(Record::Remove(dev, ino)) => {
+                    state.take(dev, ino);
+                }
+                None => state.rewrite = true,
+            }
+        }
+
+        Ok(state)
+    }
+
+    fn get(&self, key: &Key) -> Option<Detection> {
+        self.entries
+            .get(&(key.dev, key.ino))
+            .filter(|entry| entry.key == *key)
+            .map(|entry| entry.detection.clone())
+    }
+
+    fn insert(&mut self, path: &Path, key: Key, detection: Detection) -> std::io::Result<()> {
+        if self.max_entries == 0 {
+            return Ok(());
+        }
+
+        self.append(&Record::Insert(key, detection.clone()))?;
+        self.put(key, detection);
+        self.compact_if_needed(path)
+    }
+
+    fn remove(&mut self, dev: u64, ino: u64) -> std::io::Result<bool> {
+        if !self.take(dev, ino) {
+            return Ok(false);
+        }
+
+        self.append(&Record::Remove(dev, ino))?;
+        Ok(true)
+    }
+
+    fn put(&mut self, key: Key, detection: Detection) {
+        self.take(key.dev, key.ino);
+
+        self.seq += 1;
+        self.order.insert(self.seq, (key.dev, key.ino));
+        self.entries.insert(
+            (key.dev, key.ino),
+            Entry {
+                key,
+                detection,
+                seq: self.seq,
+            },
+        );
+        self.evict();
+    }
+
+    fn take(&mut self, dev: u64, ino: u64) -> bool {
+        match self.entries.remove(&(dev, ino)) {
+            Some(entry) => {
+                self.order.remove(&entry.seq);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn append(&mut self, record: &Record) -> std::io::Result<()> {
+        if let Some(log) = &mut self.log {
+            writeln!(log, "{record}")?;
+            self.records += 1;
+        }
+
+        Ok(())
+    }
+
+    fn compact_if_needed(&mut self, path: &Path) -> std::io::Result<()> {
+        if self.rewrite || self.records > self.entries.len() * 2 + COMPACT_SLACK {
+            return self.compact(path);
+        }
+
+        if self.log.is_none() {
+            self.log = Some(open_log(path)?);
+        }
+        Ok(())
+    }
+
+    fn compact(&mut self, path: &Path) -> std::io::Result<()> {
+        // Write the new cache file alongside the old one, and then replace it, so that the cache
+        // is never left partially written.
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        writeln!(writer, "{HEADER}")?;
+        for (dev, ino) in self.order.values() {
+            let entry = &self.entries[&(*dev, *ino)];
+            writeln!(
+                writer,
+                "{}",
+                Record::Insert(entry.key, entry.detection.clone())
+            )?;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        std::fs::rename(&tmp, path)?;
+
+        self.records = self.entries.len();
+        self.rewrite = false;
+        self.log = Some(open_log(path)?);
+
+        Ok(())
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.max_entries {
+            let Some((_, (dev, ino))) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&(dev, ino));
+        }
+    }
+}
+
+fn open_log(path: &Path) -> std::io::Result<BufWriter<File>> {
+    Ok(BufWriter::new(OpenOptions::new().append(true).open(path)?))
+}
+
+/// A line in the cache file.
+///
+/// Insertions are written as `+ dev ino size mtime mtime_nsec description`, with backslashes and
+/// newlines in the description escaped, and removals as `- dev ino`.
+enum Record {
+    Insert(Key, Detection),
+    Remove(u64, u64),
+}
+
+impl Record {
+    fn parse(line: &str) -> Option<Self> {
+        let (op, rest) = line.split_once(' ')?;
+        match op {
+            "+" => {
+                let mut fields = rest.splitn(6, ' ');
+                let mut next = || fields.next();
+                let key = Key {
+                    dev: next()?.parse().ok()?,
+                    ino: next()?.parse().ok()?,
+                    size: next()?.parse().ok()?,
+                    mtime: next()?.parse().ok()?,
+                    mtime_nsec: next()?.parse().ok()?,
+                };
+                let description = unescape(next()?)?;
+
+                Some(Self::Insert(key, Detection::from(description)))
+            }
+            "-" => {
+                let (dev, ino) = rest.split_once(' ')?;
+                Some(Self::Remove(dev.parse().ok()?, ino.parse().ok()?))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Insert(key, detection) => {
+                write!(
+                    f,
+                    "+ {} {} {} {} {} ",
+                    key.dev, key.ino, key.size, key.mtime, key.mtime_nsec
+                )?;
+                for c in detection.description().chars() {
+                    match c {
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                Ok(())
+            }
+            Self::Remove(dev, ino) => write!(f, "- {dev} {ino}"),
+        }
+    }
+}
+
+fn unescape(s: &str) -> Option<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                '\\' => out.push('\\'),
+                'n' => out.push('\n'),
+                _ => return None,
+            }
+        } else {
+            out.push(c);
+        }
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_round_trip() {
+        let key = Key {
+            dev: 1,
+            ino: 2,
+            size: 3,
+            mtime: -4,
+            mtime_nsec: 5,
+        };
+        let detection = Detection::from("a \\ b\nc".to_string());
+
+        let line = Record::Insert(key, detection.clone()).to_string();
+        assert_eq!(line, "+ 1 2 3 -4 5 a \\\\ b\\nc");
+        assert!(matches!(
+            Record::parse(&line),
+            Some(Record::Insert(k, d)) if k == key && d == detection
+        ));
+
+        assert!(matches!(Record::parse("- 1 2"), Some(Record::Remove(1, 2))));
+        assert!(Record::parse("+ 1 2 3").is_none());
+        assert!(Record::parse("+ 1 2 3 4 5 bad \\escape").is_none());
+    }
+}
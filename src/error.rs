@@ -1,24 +1,84 @@
 use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    borrow::Cow,
     ffi::{CStr, CString, c_int},
     fmt::{Debug, Display},
+    path::{Path, PathBuf},
     sync::{MutexGuard, PoisonError},
 };
 
 use thiserror::Error;
 
-use crate::pool::Reservoir;
+use crate::{CheckWarning, Flag, pool::Reservoir};
 
 /// Errors that can be returned from mojique.
 #[derive(Debug, Error)]
 pub enum Error {
+    #[error("{} is already a compiled magic database", .0.display())]
+    AlreadyCompiled(PathBuf),
+
+    #[error("reading archive: {0}")]
+    Archive(#[source] std::io::Error),
+
+    #[error("checking magic database: {source}")]
+    Check {
+        #[source]
+        source: Box<Error>,
+        warnings: Vec<CheckWarning>,
+    },
+
+    #[error("only magic databases on the filesystem can be checked")]
+    CheckBuffers,
+
+    #[error("compiling magic database {}: {source}", path.display())]
+    Compile {
+        path: PathBuf,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[error("populating magic database compile cache {}: {source}", path.display())]
+    CompileCache {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
     #[error("cookie was previously dropped")]
     CookieNommed,
 
-    #[error("creating magic cookie (): {0}")]
-    Create(#[source] std::io::Error),
+    #[error("creating magic cookie (): {source}")]
+    Create {
+        #[source]
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
+
+    #[error("detection daemon returned an error: {0}")]
+    Daemon(String),
+
+    #[error("communicating with detection daemon: {0}")]
+    DaemonConnection(#[source] std::io::Error),
+
+    #[error("loading magic databases: {}", DisplayFailures(.failures))]
+    DatabaseLoad { failures: Vec<(PathBuf, Error)> },
+
+    #[error("detecting {}: {source}", path.display())]
+    Detect {
+        path: PathBuf,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[error("description was not valid UTF-8: {0}")]
+    DescriptionNotUtf8(Description),
 
-    #[error("description was not valid UTF-8: {0:?}")]
-    DescriptionNotUtf8(Vec<u8>),
+    #[error("accessing results cache {}: {source}", path.display())]
+    DiskCache {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
 
     #[error("one or more embedded colons in database path")]
     EmbeddedColons,
@@ -26,28 +86,621 @@ pub enum Error {
     #[error("one or more embedded NUL bytes in database path")]
     EmbeddedNuls,
 
+    #[error("magic database buffer {0} is empty")]
+    EmptyBuffer(usize),
+
+    #[error("MAGIC environment variable refers to a missing magic database: {}", .0.display())]
+    EnvDatabaseMissing(PathBuf),
+
+    #[error("invalid magic database path: {}", .0.display())]
+    InvalidDatabasePath(PathBuf),
+
+    #[error("invalid glob pattern: {0}")]
+    InvalidGlob(String),
+
+    #[error("{0} must be greater than zero")]
+    InvalidLimit(&'static str),
+
+    #[error("loading libmagic: {0}")]
+    LibraryLoad(String),
+
+    #[error("loading magic database: {source}")]
+    Load {
+        #[source]
+        source: Box<Error>,
+    },
+
     #[error("[{errno}] {message}")]
-    Magic { errno: c_int, message: Message },
+    Magic {
+        errno: c_int,
+        message: Message,
+        backtrace: Backtrace,
+    },
 
     #[error("libmagic call errored with code {0}; then trying to get error message also errored")]
     Nested(c_int),
 
+    #[error("no magic database buffers were provided")]
+    NoBuffers,
+
+    #[error("unable to determine a compile cache directory")]
+    NoCacheDirectory,
+
+    #[error("no magic databases found in {}", .0.display())]
+    NoDatabases(PathBuf),
+
+    #[error("unable to locate the default magic database")]
+    NoDefaultDatabase,
+
     #[error("creating an anonymous pipe")]
-    PipeCreate(#[source] std::io::Error),
+    PipeCreate {
+        #[source]
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
 
-    #[error("copying data into an anonymous pipe: {0}")]
-    PipeCopy(#[source] std::io::Error),
+    #[error("copying data into an anonymous pipe: {source}")]
+    PipeCopy {
+        #[source]
+        source: std::io::Error,
+        backtrace: Backtrace,
+    },
 
     #[error("waiting for pipe thread")]
     PipeJoin,
 
+    #[error("pool has been closed")]
+    PoolClosed,
+
     #[error("environment pool lock poisoned")]
     PoolPoisoned,
+
+    #[error("reading magic database {}: {source}", path.display())]
+    ReadDatabase {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("reading magic database from reader: {0}")]
+    ReadDatabaseReader(#[source] std::io::Error),
+
+    #[error("reading input: {0}")]
+    ReadInput(#[source] std::io::Error),
+
+    #[error("spawning idle handle reaper thread: {0}")]
+    ReaperSpawn(#[source] std::io::Error),
+
+    #[error("writing scan results: {0}")]
+    ScanOutput(#[source] std::io::Error),
+
+    #[error("spawning scanner thread: {0}")]
+    ScannerSpawn(#[source] std::io::Error),
+
+    #[error("config spec has a {actual} source, but a {expected} source is required")]
+    SpecSourceMismatch {
+        expected: &'static str,
+        actual: &'static str,
+    },
+
+    #[error("capturing libmagic warnings from stderr: {0}")]
+    StderrCapture(#[source] std::io::Error),
+
+    #[error("waiting for blocking detection task")]
+    TaskJoin,
+
+    #[error("creating a temporary directory: {0}")]
+    TemporaryDirectory(#[source] std::io::Error),
+
+    #[error("buffer is not a compiled magic database")]
+    UncompiledBuffer,
+
+    #[error("{variable} is not set, so {} cannot be expanded", .path.display())]
+    UndefinedVariable { path: PathBuf, variable: String },
+
+    #[error("unknown flag: {0}")]
+    UnknownFlag(String),
+
+    #[error("flag {flag} is not supported by libmagic version {version}")]
+    UnsupportedFlag { flag: Flag, version: c_int },
+
+    #[error("{0} is not available in the loaded libmagic")]
+    UnsupportedFunction(&'static str),
+
+    #[error("setting up io_uring: {0}")]
+    Uring(#[source] std::io::Error),
+
+    #[error("watching magic databases: {0}")]
+    Watch(#[source] std::io::Error),
+
+    #[error("only magic databases on the filesystem can be watched")]
+    WatchBuffers,
+
+    #[error("changing working directory for libmagic: {0}")]
+    WorkingDirectory(#[source] std::io::Error),
 }
 
 impl Error {
     pub(crate) fn create() -> Self {
-        Self::Create(std::io::Error::last_os_error())
+        Self::Create {
+            source: std::io::Error::last_os_error(),
+            backtrace: capture(),
+        }
+    }
+
+    pub(crate) fn magic(errno: c_int, message: impl Into<Message>) -> Self {
+        Self::Magic {
+            errno,
+            message: message.into(),
+            backtrace: capture(),
+        }
+    }
+
+    pub(crate) fn pipe_copy(source: std::io::Error) -> Self {
+        Self::PipeCopy {
+            source,
+            backtrace: capture(),
+        }
+    }
+
+    pub(crate) fn pipe_create(source: std::io::Error) -> Self {
+        Self::PipeCreate {
+            source,
+            backtrace: capture(),
+        }
+    }
+
+    /// Returns the backtrace captured when the error was created, if any.
+    ///
+    /// Backtraces are only captured if the `backtrace` feature is enabled, and only for errors
+    /// that originate within libmagic or the operating system. For errors that wrap another
+    /// error, such as [`Error::Detect`], the backtrace of the wrapped error is returned.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        match self {
+            Error::Create { backtrace, .. }
+            | Error::Magic { backtrace, .. }
+            | Error::PipeCopy { backtrace, .. }
+            | Error::PipeCreate { backtrace, .. } => {
+                (backtrace.status() == BacktraceStatus::Captured).then_some(backtrace)
+            }
+
+            Error::Check { source, .. }
+            | Error::Compile { source, .. }
+            | Error::Detect { source, .. }
+            | Error::Load { source } => source.backtrace(),
+
+            _ => None,
+        }
+    }
+
+    /// Returns the path that the error relates to, if any.
+    ///
+    /// This is most useful to attribute errors from [`Handle::file`][crate::Handle::file] to the
+    /// file being detected, but also covers errors relating to specific magic databases.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::AlreadyCompiled(path)
+            | Error::Compile { path, .. }
+            | Error::CompileCache { path, .. }
+            | Error::Detect { path, .. }
+            | Error::DiskCache { path, .. }
+            | Error::EnvDatabaseMissing(path)
+            | Error::InvalidDatabasePath(path)
+            | Error::NoDatabases(path)
+            | Error::ReadDatabase { path, .. }
+            | Error::UndefinedVariable { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the `errno` reported by libmagic as an [`std::io::Error`], if there is one.
+    ///
+    /// This allows the usual handling of OS errors to be applied to failures within libmagic,
+    /// such as checking [`std::io::Error::kind`]. Errors that wrap a libmagic error, such as
+    /// [`Error::Detect`], return the `errno` of the wrapped error.
+    pub fn os_error(&self) -> Option<std::io::Error> {
+        match self {
+            // libmagic doesn't always set errno, so 0 means that there isn't one.
+            Error::Magic { errno: 0, .. } | Error::Nested(0) => None,
+            Error::Magic { errno, .. } | Error::Nested(errno) => {
+                Some(std::io::Error::from_raw_os_error(*errno))
+            }
+
+            Error::Check { source, .. }
+            | Error::Compile { source, .. }
+            | Error::Detect { source, .. }
+            | Error::Load { source } => source.os_error(),
+
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the variant, in snake case.
+    #[cfg(any(feature = "miette", feature = "serde"))]
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Error::AlreadyCompiled(_) => "already_compiled",
+            Error::Archive(_) => "archive",
+            Error::Check { .. } => "check",
+            Error::CheckBuffers => "check_buffers",
+            Error::Compile { .. } => "compile",
+            Error::CompileCache { .. } => "compile_cache",
+            Error::CookieNommed => "cookie_nommed",
+            Error::Create { .. } => "create",
+            Error::Daemon(_) => "daemon",
+            Error::DaemonConnection(_) => "daemon_connection",
+            Error::DatabaseLoad { .. } => "database_load",
+            Error::Detect { .. } => "detect",
+            Error::DescriptionNotUtf8(_) => "description_not_utf8",
+            Error::DiskCache { .. } => "disk_cache",
+            Error::EmbeddedColons => "embedded_colons",
+            Error::EmbeddedNuls => "embedded_nuls",
+            Error::EmptyBuffer(_) => "empty_buffer",
+            Error::EnvDatabaseMissing(_) => "env_database_missing",
+            Error::InvalidDatabasePath(_) => "invalid_database_path",
+            Error::InvalidGlob(_) => "invalid_glob",
+            Error::InvalidLimit(_) => "invalid_limit",
+            Error::LibraryLoad(_) => "library_load",
+            Error::Load { .. } => "load",
+            Error::Magic { .. } => "magic",
+            Error::Nested(_) => "nested",
+            Error::NoBuffers => "no_buffers",
+            Error::NoCacheDirectory => "no_cache_directory",
+            Error::NoDatabases(_) => "no_databases",
+            Error::NoDefaultDatabase => "no_default_database",
+            Error::PipeCreate { .. } => "pipe_create",
+            Error::PipeCopy { .. } => "pipe_copy",
+            Error::PipeJoin => "pipe_join",
+            Error::PoolClosed => "pool_closed",
+            Error::PoolPoisoned => "pool_poisoned",
+            Error::ReadDatabase { .. } => "read_database",
+            Error::ReadDatabaseReader(_) => "read_database_reader",
+            Error::ReadInput(_) => "read_input",
+            Error::ReaperSpawn(_) => "reaper_spawn",
+            Error::ScanOutput(_) => "scan_output",
+            Error::ScannerSpawn(_) => "scanner_spawn",
+            Error::SpecSourceMismatch { .. } => "spec_source_mismatch",
+            Error::StderrCapture(_) => "stderr_capture",
+            Error::TaskJoin => "task_join",
+            Error::TemporaryDirectory(_) => "temporary_directory",
+            Error::UncompiledBuffer => "uncompiled_buffer",
+            Error::UndefinedVariable { .. } => "undefined_variable",
+            Error::UnknownFlag(_) => "unknown_flag",
+            Error::UnsupportedFlag { .. } => "unsupported_flag",
+            Error::UnsupportedFunction(_) => "unsupported_function",
+            Error::Uring(_) => "uring",
+            Error::Watch(_) => "watch",
+            Error::WatchBuffers => "watch_buffers",
+            Error::WorkingDirectory(_) => "working_directory",
+        }
+    }
+
+    /// Returns the `errno` associated with the error, if any.
+    #[cfg(feature = "serde")]
+    fn errno(&self) -> Option<c_int> {
+        match self {
+            // libmagic doesn't always set errno, so 0 means that there isn't one.
+            Error::Magic { errno: 0, .. } | Error::Nested(0) => None,
+            Error::Magic { errno, .. } | Error::Nested(errno) => Some(*errno),
+
+            Error::Check { source, .. }
+            | Error::Compile { source, .. }
+            | Error::Detect { source, .. }
+            | Error::Load { source } => source.errno(),
+
+            Error::DatabaseLoad { failures } => {
+                failures.first().and_then(|(_, error)| error.errno())
+            }
+
+            Error::Archive(source)
+            | Error::CompileCache { source, .. }
+            | Error::Create { source, .. }
+            | Error::DaemonConnection(source)
+            | Error::DiskCache { source, .. }
+            | Error::PipeCopy { source, .. }
+            | Error::PipeCreate { source, .. }
+            | Error::ReadDatabase { source, .. }
+            | Error::ReadDatabaseReader(source)
+            | Error::ReadInput(source)
+            | Error::ReaperSpawn(source)
+            | Error::ScanOutput(source)
+            | Error::ScannerSpawn(source)
+            | Error::StderrCapture(source)
+            | Error::TemporaryDirectory(source)
+            | Error::Uring(source)
+            | Error::Watch(source)
+            | Error::WorkingDirectory(source) => source.raw_os_error(),
+
+            _ => None,
+        }
+    }
+
+    /// Returns the broad category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Magic { errno: 0, .. } => ErrorKind::Internal,
+            Error::Magic { errno, .. } => classify(&std::io::Error::from_raw_os_error(*errno)),
+
+            // This is used for errors reading files and streams that are being detected, so a
+            // missing or unreadable file is the caller's problem rather than ours.
+            Error::ReadInput(source) => classify(source),
+
+            Error::Detect { source, .. } => source.kind(),
+
+            Error::AlreadyCompiled(_)
+            | Error::Check { .. }
+            | Error::DatabaseLoad { .. }
+            | Error::Compile { .. }
+            | Error::CompileCache { .. }
+            | Error::EmptyBuffer(_)
+            | Error::EnvDatabaseMissing(_)
+            | Error::InvalidDatabasePath(_)
+            | Error::Load { .. }
+            | Error::NoBuffers
+            | Error::NoDatabases(_)
+            | Error::NoDefaultDatabase
+            | Error::ReadDatabase { .. }
+            | Error::ReadDatabaseReader(_)
+            | Error::UncompiledBuffer => ErrorKind::DatabaseLoad,
+
+            Error::Archive(_)
+            | Error::CheckBuffers
+            | Error::EmbeddedColons
+            | Error::EmbeddedNuls
+            | Error::InvalidGlob(_)
+            | Error::InvalidLimit(_)
+            | Error::SpecSourceMismatch { .. }
+            | Error::UndefinedVariable { .. }
+            | Error::UnknownFlag(_)
+            | Error::UnsupportedFlag { .. }
+            | Error::WatchBuffers => ErrorKind::InvalidInput,
+
+            Error::CookieNommed
+            | Error::Create { .. }
+            | Error::Daemon(_)
+            | Error::DaemonConnection(_)
+            | Error::DescriptionNotUtf8(_)
+            | Error::DiskCache { .. }
+            | Error::LibraryLoad(_)
+            | Error::Nested(_)
+            | Error::NoCacheDirectory
+            | Error::PipeCopy { .. }
+            | Error::PipeCreate { .. }
+            | Error::PipeJoin
+            | Error::PoolClosed
+            | Error::PoolPoisoned
+            | Error::ReaperSpawn(_)
+            | Error::ScanOutput(_)
+            | Error::ScannerSpawn(_)
+            | Error::StderrCapture(_)
+            | Error::TaskJoin
+            | Error::TemporaryDirectory(_)
+            | Error::UnsupportedFunction(_)
+            | Error::Uring(_)
+            | Error::Watch(_)
+            | Error::WorkingDirectory(_) => ErrorKind::Internal,
+        }
+    }
+
+    /// Returns true if the error is likely to be transient, such as the process temporarily
+    /// running out of file descriptors or memory, and the operation may succeed if retried.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Magic { errno, .. } => is_transient(&std::io::Error::from_raw_os_error(*errno)),
+
+            Error::Check { source, .. }
+            | Error::Compile { source, .. }
+            | Error::Detect { source, .. }
+            | Error::Load { source } => source.is_retryable(),
+
+            Error::Archive(source)
+            | Error::CompileCache { source, .. }
+            | Error::Create { source, .. }
+            | Error::DaemonConnection(source)
+            | Error::DiskCache { source, .. }
+            | Error::PipeCopy { source, .. }
+            | Error::PipeCreate { source, .. }
+            | Error::ReadDatabase { source, .. }
+            | Error::ReadDatabaseReader(source)
+            | Error::ReadInput(source)
+            | Error::ReaperSpawn(source)
+            | Error::ScanOutput(source)
+            | Error::ScannerSpawn(source)
+            | Error::StderrCapture(source)
+            | Error::TemporaryDirectory(source)
+            | Error::Uring(source)
+            | Error::Watch(source)
+            | Error::WorkingDirectory(source) => is_transient(source),
+
+            _ => false,
+        }
+    }
+
+    /// Returns the closest [`std::io::ErrorKind`] to the error.
+    pub(crate) fn io_error_kind(&self) -> std::io::ErrorKind {
+        use std::io::ErrorKind;
+
+        match self {
+            // libmagic doesn't always set errno, in which case there's nothing to go on.
+            Error::Magic { errno: 0, .. } => ErrorKind::Other,
+            Error::Magic { errno, .. } => std::io::Error::from_raw_os_error(*errno).kind(),
+
+            Error::Check { source, .. }
+            | Error::Compile { source, .. }
+            | Error::Detect { source, .. }
+            | Error::Load { source } => source.io_error_kind(),
+
+            Error::DatabaseLoad { failures } => failures
+                .first()
+                .map_or(ErrorKind::Other, |(_, error)| error.io_error_kind()),
+
+            Error::Archive(source)
+            | Error::CompileCache { source, .. }
+            | Error::Create { source, .. }
+            | Error::DaemonConnection(source)
+            | Error::DiskCache { source, .. }
+            | Error::PipeCopy { source, .. }
+            | Error::PipeCreate { source, .. }
+            | Error::ReadDatabase { source, .. }
+            | Error::ReadDatabaseReader(source)
+            | Error::ReadInput(source)
+            | Error::ReaperSpawn(source)
+            | Error::ScanOutput(source)
+            | Error::ScannerSpawn(source)
+            | Error::StderrCapture(source)
+            | Error::TemporaryDirectory(source)
+            | Error::Uring(source)
+            | Error::Watch(source)
+            | Error::WorkingDirectory(source) => source.kind(),
+
+            Error::EnvDatabaseMissing(_)
+            | Error::LibraryLoad(_)
+            | Error::NoDatabases(_)
+            | Error::NoDefaultDatabase => ErrorKind::NotFound,
+
+            Error::AlreadyCompiled(_)
+            | Error::EmbeddedColons
+            | Error::EmbeddedNuls
+            | Error::EmptyBuffer(_)
+            | Error::InvalidDatabasePath(_)
+            | Error::InvalidGlob(_)
+            | Error::InvalidLimit(_)
+            | Error::NoBuffers
+            | Error::SpecSourceMismatch { .. }
+            | Error::UncompiledBuffer
+            | Error::UndefinedVariable { .. }
+            | Error::UnknownFlag(_) => ErrorKind::InvalidInput,
+
+            Error::CheckBuffers
+            | Error::UnsupportedFlag { .. }
+            | Error::UnsupportedFunction(_)
+            | Error::WatchBuffers => ErrorKind::Unsupported,
+
+            Error::DescriptionNotUtf8(_) => ErrorKind::InvalidData,
+
+            Error::CookieNommed
+            | Error::Daemon(_)
+            | Error::Nested(_)
+            | Error::NoCacheDirectory
+            | Error::PipeJoin
+            | Error::PoolClosed
+            | Error::PoolPoisoned
+            | Error::TaskJoin => ErrorKind::Other,
+        }
+    }
+}
+
+/// Captures a backtrace if the `backtrace` feature is enabled.
+fn capture() -> Backtrace {
+    if cfg!(feature = "backtrace") {
+        Backtrace::force_capture()
+    } else {
+        Backtrace::disabled()
+    }
+}
+
+/// Serialises the error as a structure containing the name of the variant, the `errno` if there
+/// is one, the full error message, and the path that the error relates to, if any.
+///
+/// For example, failing to detect a file that doesn't exist serialises to JSON as:
+///
+/// ```json
+/// {
+///   "variant": "detect",
+///   "errno": 2,
+///   "message": "detecting missing: [2] cannot stat `missing' (No such file or directory)",
+///   "path": "missing"
+/// }
+/// ```
+#[cfg(feature = "serde")]
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Error", 4)?;
+        state.serialize_field("variant", self.name())?;
+        state.serialize_field("errno", &self.errno())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("path", &self.path().map(Path::to_string_lossy))?;
+        state.end()
+    }
+}
+
+/// Formats each failure of an [`Error::DatabaseLoad`] with its path.
+struct DisplayFailures<'a>(&'a [(PathBuf, Error)]);
+
+impl Display for DisplayFailures<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (path, error)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}: {error}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// The broad categories of [`Error`], as returned by [`Error::kind`].
+///
+/// These are intended to allow services to decide how to respond to an error (for example,
+/// whether it was the fault of the client or the server) without matching on every variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The file being detected doesn't exist.
+    NotFound,
+
+    /// The file being detected couldn't be accessed.
+    Permission,
+
+    /// A magic database couldn't be found, read, compiled, or loaded.
+    DatabaseLoad,
+
+    /// The configuration or input was invalid.
+    InvalidInput,
+
+    /// Anything else, including failures within libmagic or the operating system.
+    Internal,
+}
+
+// EMFILE and ENFILE don't have stable io::ErrorKinds, but have the same values on Linux, macOS,
+// and the BSDs.
+const EMFILE: i32 = 24;
+const ENFILE: i32 = 23;
+
+/// Returns the [`ErrorKind`] of an I/O error encountered while detecting an input.
+fn classify(error: &std::io::Error) -> ErrorKind {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+        std::io::ErrorKind::PermissionDenied => ErrorKind::Permission,
+        std::io::ErrorKind::InvalidInput | std::io::ErrorKind::InvalidData => {
+            ErrorKind::InvalidInput
+        }
+        _ => ErrorKind::Internal,
+    }
+}
+
+/// Returns true if the I/O error is likely to be transient.
+fn is_transient(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+
+    matches!(
+        error.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::OutOfMemory
+            | ErrorKind::ResourceBusy
+            | ErrorKind::TimedOut
+            | ErrorKind::WouldBlock
+    ) || matches!(error.raw_os_error(), Some(EMFILE | ENFILE))
+}
+
+/// Converts the error into an [`std::io::Error`], with the closest matching
+/// [`std::io::ErrorKind`]. Errors reported by libmagic are mapped using their `errno`, and the
+/// original error is retained as the inner error.
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        std::io::Error::new(error.io_error_kind(), error)
     }
 }
 
@@ -57,6 +710,40 @@ impl From<PoisonError<MutexGuard<'_, Reservoir>>> for Error {
     }
 }
 
+/// A description returned by libmagic that isn't valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Description(Vec<u8>);
+
+impl Description {
+    /// Returns the raw bytes of the description.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the raw bytes of the description, consuming it.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Returns the description with any invalid UTF-8 sequences replaced with
+    /// [`U+FFFD REPLACEMENT CHARACTER`][std::char::REPLACEMENT_CHARACTER].
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.0)
+    }
+}
+
+impl Display for Description {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_string_lossy())
+    }
+}
+
+impl From<&[u8]> for Description {
+    fn from(value: &[u8]) -> Self {
+        Self(value.to_vec())
+    }
+}
+
 #[derive(Clone)]
 pub struct Message(CString);
 
@@ -98,3 +785,83 @@ impl From<CString> for Message {
         Self(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_input_kind() {
+        let kind = |kind| Error::ReadInput(std::io::Error::from(kind)).kind();
+
+        assert_eq!(kind(std::io::ErrorKind::NotFound), ErrorKind::NotFound);
+        assert_eq!(
+            kind(std::io::ErrorKind::PermissionDenied),
+            ErrorKind::Permission
+        );
+        assert_eq!(
+            kind(std::io::ErrorKind::InvalidInput),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            kind(std::io::ErrorKind::InvalidData),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(kind(std::io::ErrorKind::BrokenPipe), ErrorKind::Internal);
+
+        // The kind should also carry through detection errors.
+        let error = Error::Detect {
+            path: "foo".into(),
+            source: Box::new(Error::ReadInput(std::io::Error::from_raw_os_error(2))),
+        };
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn load_kind() {
+        // However libmagic fails to load a database, it's a database error.
+        for errno in [0, 2, 13] {
+            let error = Error::Load {
+                source: Box::new(Error::magic(errno, c"cannot load")),
+            };
+            assert_eq!(error.kind(), ErrorKind::DatabaseLoad);
+        }
+    }
+
+    #[test]
+    fn database_io_retryable() {
+        let interrupted = || std::io::Error::from(std::io::ErrorKind::Interrupted);
+
+        assert!(
+            Error::ReadDatabase {
+                path: "foo".into(),
+                source: interrupted(),
+            }
+            .is_retryable()
+        );
+        assert!(Error::ReadDatabaseReader(interrupted()).is_retryable());
+        assert!(
+            Error::CompileCache {
+                path: "foo".into(),
+                source: std::io::Error::from(std::io::ErrorKind::WouldBlock),
+            }
+            .is_retryable()
+        );
+        assert!(Error::WorkingDirectory(interrupted()).is_retryable());
+        assert!(!Error::ReadDatabaseReader(std::io::Error::other("oh no")).is_retryable());
+    }
+
+    #[test]
+    fn unsupported_function() {
+        let error = Error::UnsupportedFunction("magic_load_buffers");
+        assert_eq!(error.kind(), ErrorKind::Internal);
+        assert_eq!(error.io_error_kind(), std::io::ErrorKind::Unsupported);
+        assert!(!error.is_retryable());
+
+        // Missing functions needed to load a database are still database errors.
+        let error = Error::Load {
+            source: Box::new(error),
+        };
+        assert_eq!(error.kind(), ErrorKind::DatabaseLoad);
+    }
+}
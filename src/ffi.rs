@@ -1,8 +1,15 @@
-use std::ffi::c_int;
+use std::{
+    ffi::c_int,
+    fmt::{Debug, Display},
+    ops::{BitAnd, BitOr, BitOrAssign, Not, Sub},
+    str::FromStr,
+};
 
 use magic_sys::*;
 use static_assertions::assert_eq_size;
 
+use crate::Error;
+
 // XXX: this will break if sizeof(int) != 4, since we can't repr(c_int)
 //
 // Refreshing my memory with Wikipedia, it seems that the only non-embedded platforms with
@@ -15,11 +22,29 @@ use static_assertions::assert_eq_size;
 // true.
 assert_eq_size!(c_int, i32);
 
+// magic-sys only knows about flags up to libmagic 5.40, so anything newer has to be defined here.
+#[cfg(feature = "v5-44")]
+const MAGIC_NO_COMPRESS_FORK: c_int = 0x4000000;
+
+// This is the definition of MAGIC_NO_CHECK_BUILTIN from libmagic 5.38 onwards, at least until
+// further checks are added.
+#[cfg(feature = "v5-38")]
+const NO_CHECK_BUILTIN: c_int = MAGIC_NO_CHECK_COMPRESS
+    | MAGIC_NO_CHECK_TAR
+    | MAGIC_NO_CHECK_APPTYPE
+    | MAGIC_NO_CHECK_ELF
+    | MAGIC_NO_CHECK_TEXT
+    | MAGIC_NO_CHECK_CSV
+    | MAGIC_NO_CHECK_CDF
+    | MAGIC_NO_CHECK_TOKENS
+    | MAGIC_NO_CHECK_ENCODING
+    | MAGIC_NO_CHECK_JSON;
+
 /// libmagic flags.
 ///
 /// The flag descriptions below are reproduced directly from the `libmagic(3)` man page, which is
 /// the authoritative source of any behavioural information.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[repr(i32)]
 pub enum Flag {
     /// Print debugging messages to stderr.
@@ -72,6 +97,10 @@ pub enum Flag {
     #[cfg(feature = "v5-23")]
     CompressTransparent = MAGIC_COMPRESS_TRANSP,
 
+    /// Don't allow decompression that needs to fork.
+    #[cfg(feature = "v5-44")]
+    NoCompressFork = MAGIC_NO_COMPRESS_FORK,
+
     /// Don't check for `EMX` application type (only on EMX).
     NoCheckAppType = MAGIC_NO_CHECK_APPTYPE,
 
@@ -106,4 +135,521 @@ pub enum Flag {
     /// Don't examine CSV files.
     #[cfg(feature = "v5-38")]
     NoCheckCSV = MAGIC_NO_CHECK_CSV,
+
+    /// A shorthand for every `NoCheck*` flag other than [`Flag::NoCheckSoft`], which results in
+    /// only the magic database being consulted.
+    #[cfg(feature = "v5-38")]
+    NoCheckBuiltin = NO_CHECK_BUILTIN,
+}
+
+impl Flag {
+    /// Every flag, including composite flags.
+    const ALL: &[Flag] = &[
+        Flag::Debug,
+        Flag::Symlink,
+        Flag::Compress,
+        Flag::Devices,
+        Flag::MimeType,
+        Flag::MimeEncoding,
+        Flag::Mime,
+        Flag::Continue,
+        Flag::Check,
+        Flag::PreserveAccessTime,
+        Flag::Raw,
+        Flag::Error,
+        Flag::Apple,
+        #[cfg(feature = "v5-23")]
+        Flag::Extension,
+        #[cfg(feature = "v5-23")]
+        Flag::CompressTransparent,
+        #[cfg(feature = "v5-44")]
+        Flag::NoCompressFork,
+        Flag::NoCheckAppType,
+        Flag::NoCheckCDF,
+        Flag::NoCheckCompress,
+        Flag::NoCheckELF,
+        Flag::NoCheckEncoding,
+        Flag::NoCheckSoft,
+        Flag::NoCheckTar,
+        Flag::NoCheckText,
+        Flag::NoCheckTokens,
+        #[cfg(feature = "v5-35")]
+        Flag::NoCheckJSON,
+        #[cfg(feature = "v5-38")]
+        Flag::NoCheckCSV,
+        #[cfg(feature = "v5-38")]
+        Flag::NoCheckBuiltin,
+    ];
+
+    /// Returns the human readable name of the flag, as used by its [`Display`] and [`FromStr`]
+    /// implementations.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Flag::Debug => "debug",
+            Flag::Symlink => "symlink",
+            Flag::Compress => "compress",
+            Flag::Devices => "devices",
+            Flag::MimeType => "mime-type",
+            Flag::MimeEncoding => "mime-encoding",
+            Flag::Mime => "mime",
+            Flag::Continue => "continue",
+            Flag::Check => "check",
+            Flag::PreserveAccessTime => "preserve-atime",
+            Flag::Raw => "raw",
+            Flag::Error => "error",
+            Flag::Apple => "apple",
+            #[cfg(feature = "v5-23")]
+            Flag::Extension => "extension",
+            #[cfg(feature = "v5-23")]
+            Flag::CompressTransparent => "compress-transparent",
+            #[cfg(feature = "v5-44")]
+            Flag::NoCompressFork => "no-compress-fork",
+            Flag::NoCheckAppType => "no-check-apptype",
+            Flag::NoCheckCDF => "no-check-cdf",
+            Flag::NoCheckCompress => "no-check-compress",
+            Flag::NoCheckELF => "no-check-elf",
+            Flag::NoCheckEncoding => "no-check-encoding",
+            Flag::NoCheckSoft => "no-check-soft",
+            Flag::NoCheckTar => "no-check-tar",
+            Flag::NoCheckText => "no-check-text",
+            Flag::NoCheckTokens => "no-check-tokens",
+            #[cfg(feature = "v5-35")]
+            Flag::NoCheckJSON => "no-check-json",
+            #[cfg(feature = "v5-38")]
+            Flag::NoCheckCSV => "no-check-csv",
+            #[cfg(feature = "v5-38")]
+            Flag::NoCheckBuiltin => "no-check-builtin",
+        }
+    }
+
+    /// Returns true if the libmagic in use at runtime supports the flag.
+    ///
+    /// [`Config::try_set_flag`][crate::Config::try_set_flag] can be used to set a flag only if it
+    /// is supported, and [`crate::capabilities`] returns every supported flag.
+    pub fn is_supported(&self) -> bool {
+        self.min_version() <= crate::version()
+    }
+
+    /// Returns the earliest libmagic version that supports the flag, in the same form as
+    /// [`crate::version`].
+    pub(crate) fn min_version(&self) -> c_int {
+        match self {
+            #[cfg(feature = "v5-23")]
+            Flag::Extension | Flag::CompressTransparent => 523,
+            #[cfg(feature = "v5-35")]
+            Flag::NoCheckJSON => 535,
+            #[cfg(feature = "v5-38")]
+            Flag::NoCheckCSV | Flag::NoCheckBuiltin => 538,
+            #[cfg(feature = "v5-44")]
+            Flag::NoCompressFork => 544,
+            _ => 0,
+        }
+    }
+
+    /// Every flag that represents a single bit, in bit order. [`Flag::Mime`] is omitted, since it
+    /// is a combination of two other flags.
+    pub(crate) const SINGLE: &[Flag] = &[
+        Flag::Debug,
+        Flag::Symlink,
+        Flag::Compress,
+        Flag::Devices,
+        Flag::MimeType,
+        Flag::Continue,
+        Flag::Check,
+        Flag::PreserveAccessTime,
+        Flag::Raw,
+        Flag::Error,
+        Flag::MimeEncoding,
+        Flag::Apple,
+        Flag::NoCheckCompress,
+        Flag::NoCheckTar,
+        Flag::NoCheckSoft,
+        Flag::NoCheckAppType,
+        Flag::NoCheckELF,
+        Flag::NoCheckText,
+        Flag::NoCheckCDF,
+        #[cfg(feature = "v5-38")]
+        Flag::NoCheckCSV,
+        Flag::NoCheckTokens,
+        Flag::NoCheckEncoding,
+        #[cfg(feature = "v5-35")]
+        Flag::NoCheckJSON,
+        #[cfg(feature = "v5-23")]
+        Flag::Extension,
+        #[cfg(feature = "v5-23")]
+        Flag::CompressTransparent,
+        #[cfg(feature = "v5-44")]
+        Flag::NoCompressFork,
+    ];
+}
+
+impl Display for Flag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Parses a flag from its [`Flag::name`]. Parsing is case insensitive, and underscores may be used
+/// in place of hyphens.
+impl FromStr for Flag {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase().replace('_', "-");
+        Flag::ALL
+            .iter()
+            .find(|flag| flag.name() == name)
+            .copied()
+            .ok_or_else(|| Error::UnknownFlag(s.to_string()))
+    }
+}
+
+/// Deserialises a flag from a string, using the same names as [`FromStr`].
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Flag {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "clap")]
+impl clap::ValueEnum for Flag {
+    fn value_variants<'a>() -> &'a [Self] {
+        Flag::ALL
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(self.name()))
+    }
+}
+
+impl BitOr for Flag {
+    type Output = FlagSet;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        FlagSet::from(self) | rhs
+    }
+}
+
+impl BitOr<FlagSet> for Flag {
+    type Output = FlagSet;
+
+    fn bitor(self, rhs: FlagSet) -> Self::Output {
+        FlagSet::from(self) | rhs
+    }
+}
+
+/// The built in checks that libmagic performs in addition to consulting the magic database, each of
+/// which can be disabled with one of the `NoCheck*` [`Flag`]s.
+///
+/// See [`Config::disable_check`][crate::Config::disable_check] and
+/// [`Config::disable_all_checks_except`][crate::Config::disable_all_checks_except].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Check {
+    /// Looking inside compressed files, disabled by [`Flag::NoCheckCompress`].
+    Compress,
+
+    /// Examining tar files, disabled by [`Flag::NoCheckTar`].
+    Tar,
+
+    /// Printing ELF details, disabled by [`Flag::NoCheckELF`].
+    Elf,
+
+    /// Examining JSON files, disabled by [`Flag::NoCheckJSON`].
+    #[cfg(feature = "v5-35")]
+    Json,
+
+    /// Examining CSV files, disabled by [`Flag::NoCheckCSV`].
+    #[cfg(feature = "v5-38")]
+    Csv,
+
+    /// Checking for various types of text files, disabled by [`Flag::NoCheckText`].
+    Text,
+
+    /// Looking for known tokens inside ASCII files, disabled by [`Flag::NoCheckTokens`].
+    Tokens,
+
+    /// Checking text encodings, disabled by [`Flag::NoCheckEncoding`].
+    Encoding,
+
+    /// Checking for `EMX` application types, disabled by [`Flag::NoCheckAppType`].
+    AppType,
+
+    /// Getting extra information on MS Composite Document Files, disabled by
+    /// [`Flag::NoCheckCDF`].
+    Cdf,
+
+    /// Consulting the magic database, disabled by [`Flag::NoCheckSoft`].
+    Soft,
+}
+
+impl Check {
+    /// Every check.
+    pub const ALL: &[Check] = &[
+        Check::Compress,
+        Check::Tar,
+        Check::Elf,
+        #[cfg(feature = "v5-35")]
+        Check::Json,
+        #[cfg(feature = "v5-38")]
+        Check::Csv,
+        Check::Text,
+        Check::Tokens,
+        Check::Encoding,
+        Check::AppType,
+        Check::Cdf,
+        Check::Soft,
+    ];
+
+    /// Returns the flag that disables the check.
+    pub fn flag(&self) -> Flag {
+        match self {
+            Check::Compress => Flag::NoCheckCompress,
+            Check::Tar => Flag::NoCheckTar,
+            Check::Elf => Flag::NoCheckELF,
+            #[cfg(feature = "v5-35")]
+            Check::Json => Flag::NoCheckJSON,
+            #[cfg(feature = "v5-38")]
+            Check::Csv => Flag::NoCheckCSV,
+            Check::Text => Flag::NoCheckText,
+            Check::Tokens => Flag::NoCheckTokens,
+            Check::Encoding => Flag::NoCheckEncoding,
+            Check::AppType => Flag::NoCheckAppType,
+            Check::Cdf => Flag::NoCheckCDF,
+            Check::Soft => Flag::NoCheckSoft,
+        }
+    }
+}
+
+impl From<Check> for Flag {
+    fn from(check: Check) -> Self {
+        check.flag()
+    }
+}
+
+/// A set of libmagic [`Flag`]s.
+///
+/// Sets are most easily built by combining flags with `|`:
+///
+/// ```
+/// use mojique::{Flag, FlagSet};
+///
+/// let flags = Flag::MimeType | Flag::Compress;
+/// assert!(flags.contains(Flag::Compress));
+/// assert!(!flags.contains(Flag::Mime));
+/// ```
+///
+/// Sets returned from libmagic may include bits that don't correspond to any [`Flag`] known to
+/// this crate. These are retained, but are not returned when iterating.
+///
+/// Sets are displayed as a comma separated list of [flag names](Flag::name), and can be parsed
+/// from the same format.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FlagSet(c_int);
+
+impl FlagSet {
+    /// Returns an empty set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Creates a set from raw libmagic flag bits.
+    pub const fn from_bits(bits: c_int) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw libmagic flag bits.
+    pub const fn bits(&self) -> c_int {
+        self.0
+    }
+
+    /// Returns `true` if the set has no flags.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if every flag in `flags` is within the set.
+    pub fn contains(&self, flags: impl Into<FlagSet>) -> bool {
+        let flags = flags.into();
+        self.0 & flags.0 == flags.0
+    }
+
+    /// Adds every flag in `flags` to the set.
+    pub fn insert(&mut self, flags: impl Into<FlagSet>) {
+        self.0 |= flags.into().0;
+    }
+
+    /// Removes every flag in `flags` from the set.
+    pub fn remove(&mut self, flags: impl Into<FlagSet>) {
+        self.0 &= !flags.into().0;
+    }
+
+    /// Iterates over the individual flags within the set.
+    ///
+    /// Composite flags such as [`Flag::Mime`] are returned as their component flags.
+    pub fn iter(&self) -> impl Iterator<Item = Flag> + use<> {
+        let bits = self.0;
+        Flag::SINGLE
+            .iter()
+            .copied()
+            .filter(move |flag| bits & (*flag as c_int) != 0)
+    }
+}
+
+impl Debug for FlagSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl Display for FlagSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, flag) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{flag}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for FlagSet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|name| !name.trim().is_empty())
+            .map(Flag::from_str)
+            .collect()
+    }
+}
+
+impl From<Flag> for FlagSet {
+    fn from(flag: Flag) -> Self {
+        Self(flag as c_int)
+    }
+}
+
+impl FromIterator<Flag> for FlagSet {
+    fn from_iter<T: IntoIterator<Item = Flag>>(iter: T) -> Self {
+        iter.into_iter().fold(Self::empty(), |set, flag| set | flag)
+    }
+}
+
+impl Extend<Flag> for FlagSet {
+    fn extend<T: IntoIterator<Item = Flag>>(&mut self, iter: T) {
+        iter.into_iter().for_each(|flag| self.insert(flag));
+    }
+}
+
+impl<const N: usize> From<[Flag; N]> for FlagSet {
+    fn from(flags: [Flag; N]) -> Self {
+        flags.into_iter().collect()
+    }
+}
+
+impl From<Vec<Flag>> for FlagSet {
+    fn from(flags: Vec<Flag>) -> Self {
+        flags.into_iter().collect()
+    }
+}
+
+impl<F: Into<FlagSet>> BitOr<F> for FlagSet {
+    type Output = FlagSet;
+
+    fn bitor(self, rhs: F) -> Self::Output {
+        Self(self.0 | rhs.into().0)
+    }
+}
+
+impl<F: Into<FlagSet>> BitOrAssign<F> for FlagSet {
+    fn bitor_assign(&mut self, rhs: F) {
+        self.insert(rhs);
+    }
+}
+
+impl<F: Into<FlagSet>> BitAnd<F> for FlagSet {
+    type Output = FlagSet;
+
+    fn bitand(self, rhs: F) -> Self::Output {
+        Self(self.0 & rhs.into().0)
+    }
+}
+
+impl<F: Into<FlagSet>> Sub<F> for FlagSet {
+    type Output = FlagSet;
+
+    fn sub(self, rhs: F) -> Self::Output {
+        Self(self.0 & !rhs.into().0)
+    }
+}
+
+impl Not for FlagSet {
+    type Output = FlagSet;
+
+    fn not(self) -> Self::Output {
+        Self(!self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_set() {
+        let mut flags = Flag::Mime | Flag::Compress;
+        assert!(flags.contains(Flag::MimeType));
+        assert!(flags.contains(Flag::Mime));
+        assert!(!flags.contains(Flag::Compress | Flag::Raw));
+        assert_eq!(
+            flags.iter().collect::<Vec<_>>(),
+            vec![Flag::Compress, Flag::MimeType, Flag::MimeEncoding]
+        );
+        assert_eq!(flags.to_string(), "compress,mime-type,mime-encoding");
+
+        flags.remove(Flag::MimeEncoding);
+        flags |= Flag::Raw;
+        assert_eq!(
+            flags,
+            [Flag::Compress, Flag::MimeType, Flag::Raw]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(flags - Flag::Raw, Flag::Compress | Flag::MimeType);
+
+        // Unknown bits should be retained, but not iterated.
+        let flags = FlagSet::from_bits(0x40000000 | Flag::Debug as c_int);
+        assert_eq!(flags.iter().collect::<Vec<_>>(), vec![Flag::Debug]);
+        assert_eq!(flags.bits(), 0x40000001);
+
+        // As with Mime, NoCheckBuiltin should be treated as the flags it's made up of.
+        #[cfg(feature = "v5-38")]
+        {
+            let flags = FlagSet::from(Flag::NoCheckBuiltin);
+            assert!(flags.contains(Flag::NoCheckJSON | Flag::NoCheckCSV | Flag::NoCheckText));
+            assert!(!flags.contains(Flag::NoCheckSoft));
+        }
+    }
+
+    #[test]
+    fn names() {
+        for flag in Flag::ALL {
+            assert_eq!(flag.to_string().parse::<Flag>().unwrap(), *flag);
+        }
+
+        assert_eq!("MIME_TYPE".parse::<Flag>().unwrap(), Flag::MimeType);
+        assert!(matches!(
+            "mimetype".parse::<Flag>(),
+            Err(Error::UnknownFlag(name)) if name == "mimetype"
+        ));
+
+        let flags: FlagSet = "mime, symlink,".parse().unwrap();
+        assert_eq!(flags, Flag::Mime | Flag::Symlink);
+        assert_eq!(flags.to_string().parse::<FlagSet>().unwrap(), flags);
+        assert_eq!("".parse::<FlagSet>().unwrap(), FlagSet::empty());
+    }
 }
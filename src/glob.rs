@@ -0,0 +1,217 @@
+use std::path::{Component, Path};
+
+use crate::Error;
+
+/// A compiled glob pattern, as used by [`Scanner`][crate::Scanner] to filter paths.
+///
+/// Patterns are matched against paths relative to the root being scanned, one component at a
+/// time:
+///
+/// * `*` matches any sequence of characters within a component, and `?` matches any single
+///   character.
+/// * `[abc]` and `[a-z]` match any character in the set, and `[!abc]` matches any character that
+///   isn't.
+/// * `**` as an entire component matches zero or more components.
+///
+/// A pattern that doesn't contain a `/` matches the final component in any directory, so `*.bin`
+/// is equivalent to `**/*.bin`. A leading `/` anchors the pattern to the root, and is otherwise
+/// ignored.
+#[derive(Debug, Clone)]
+pub(crate) struct Glob {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    AnyDepth,
+    Name(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Any,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    Literal(char),
+    Star,
+}
+
+impl Glob {
+    pub(crate) fn new(pattern: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidGlob(pattern.to_string());
+
+        let anchored = pattern.strip_prefix('/');
+        let mut segments = Vec::new();
+        if anchored.is_none() && !pattern.contains('/') {
+            segments.push(Segment::AnyDepth);
+        }
+
+        for component in anchored.unwrap_or(pattern).split('/') {
+            match component {
+                "" => return Err(invalid()),
+                "**" => segments.push(Segment::AnyDepth),
+                component => segments.push(Segment::Name(tokenise(component).ok_or_else(invalid)?)),
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Returns `true` if the pattern matches the given relative path.
+    pub(crate) fn is_match(&self, path: &Path) -> bool {
+        let components: Vec<_> = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .collect();
+        let components: Vec<&str> = components.iter().map(AsRef::as_ref).collect();
+
+        match_segments(&self.segments, &components)
+    }
+}
+
+fn tokenise(component: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '*' => Token::Star,
+            '?' => Token::Any,
+            '[' => {
+                let mut negated = false;
+                let mut ranges = Vec::new();
+                let mut first = true;
+                loop {
+                    let c = chars.next()?;
+                    match c {
+                        '!' if first => negated = true,
+                        ']' if !ranges.is_empty() => break,
+                        c => {
+                            // A `-` at the start or end of a class is a literal.
+                            let rest = chars.as_str();
+                            if let Some(end) = rest.strip_prefix('-').and_then(|s| s.chars().next())
+                                && end != ']'
+                            {
+                                chars.nth(1);
+                                ranges.push((c, end));
+                            } else {
+                                ranges.push((c, c));
+                            }
+                        }
+                    }
+                    first = false;
+                }
+
+                Token::Class { negated, ranges }
+            }
+            c => Token::Literal(c),
+        });
+    }
+
+    Some(tokens)
+}
+
+fn match_segments(segments: &[Segment], components: &[&str]) -> bool {
+    match segments.split_first() {
+        None => components.is_empty(),
+        Some((Segment::AnyDepth, rest)) => {
+            (0..=components.len()).any(|skip| match_segments(rest, &components[skip..]))
+        }
+        Some((Segment::Name(tokens), rest)) => {
+            components
+                .split_first()
+                .is_some_and(|(component, components)| {
+                    match_tokens(tokens, component) && match_segments(rest, components)
+                })
+        }
+    }
+}
+
+fn match_tokens(tokens: &[Token], component: &str) -> bool {
+    let chars: Vec<char> = component.chars().collect();
+
+    // The position to backtrack to after the most recent star, if a later token fails to match.
+    let mut backtrack = None;
+    let (mut t, mut c) = (0, 0);
+    while c < chars.len() {
+        match tokens.get(t) {
+            Some(Token::Star) => {
+                backtrack = Some((t, c));
+                t += 1;
+            }
+            Some(token) if token_matches(token, chars[c]) => {
+                t += 1;
+                c += 1;
+            }
+            _ => match backtrack {
+                Some((star, start)) => {
+                    backtrack = Some((star, start + 1));
+                    t = star + 1;
+                    c = start + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    tokens[t..].iter().all(|token| *token == Token::Star)
+}
+
+fn token_matches(token: &Token, c: char) -> bool {
+    match token {
+        Token::Any => true,
+        Token::Class { negated, ranges } => {
+            ranges
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&c))
+                != *negated
+        }
+        Token::Literal(literal) => *literal == c,
+        Token::Star => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_match() {
+        let matches =
+            |pattern: &str, path: &str| Glob::new(pattern).unwrap().is_match(Path::new(path));
+
+        assert!(matches("*.bin", "a.bin"));
+        assert!(matches("*.bin", "nested/deeper/a.bin"));
+        assert!(!matches("*.bin", "a.bin.gz"));
+        assert!(matches("**/*.bin", "a.bin"));
+        assert!(matches("**/*.bin", "nested/a.bin"));
+        assert!(matches("node_modules", "web/node_modules"));
+        assert!(!matches("node_modules", "web/node_modules_old"));
+        assert!(matches("/build", "build"));
+        assert!(!matches("/build", "nested/build"));
+        assert!(matches("src/**", "src/a/b.rs"));
+        assert!(matches("src/*.rs", "src/lib.rs"));
+        assert!(!matches("src/*.rs", "src/nested/lib.rs"));
+        assert!(matches("a/**/z", "a/z"));
+        assert!(matches("a/**/z", "a/b/c/z"));
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(!matches("file?.txt", "file10.txt"));
+        assert!(matches("*a*b*", "xxaxxbxx"));
+        assert!(!matches("*a*b*", "xxbxxaxx"));
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[!a-c]x", "bx"));
+        assert!(matches("[-a]", "-"));
+        assert!(matches("[]]", "]"));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Glob::new("[abc").is_err());
+        assert!(Glob::new("a//b").is_err());
+        assert!(Glob::new("a/").is_err());
+    }
+}
@@ -1,15 +1,18 @@
 use std::{
     ffi::{CStr, CString, c_char, c_int},
     fmt::Debug,
-    io::{BufReader, ErrorKind, Read, Write},
+    io::{ErrorKind, Read, Write},
     os::{fd::AsRawFd, unix::ffi::OsStrExt},
     path::Path,
-    sync::{Arc, Mutex},
+    time::Instant,
 };
 
-use magic_sys::*;
+#[cfg(all(feature = "bytes", feature = "tokio"))]
+use bytes::Bytes;
 
-use crate::{Error, pool::Reservoir};
+#[cfg(all(feature = "bytes", feature = "tokio"))]
+use crate::stream::Remainder;
+use crate::{Error, FlagSet, instrument, pool::SharedBuffer, sys::*};
 
 /// A handle to a single libmagic "cookie", which is better thought of as an instance of the
 /// libmagic database.
@@ -22,31 +25,229 @@ use crate::{Error, pool::Reservoir};
 /// [`crate::Config`]: most notably [`Extension`][`crate::Flag::Extension`],
 /// [`Mime`][`crate::Flag::Mime`], [`MimeEncoding`][`crate::Flag::MimeEncoding`],
 /// [`MimeType`][`crate::Flag::MimeType`], and [`Continue`][`crate::Flag::Continue`].
+///
+/// Handles acquired from a [`Pool`][crate::Pool] are wrapped in a
+/// [`PooledHandle`][crate::PooledHandle], which dereferences to a `Handle`.
 pub struct Handle {
     cookie: Option<Cookie>,
-    reservoir: Option<Arc<Mutex<Reservoir>>>,
+    prefix_read: bool,
+    read_buffer_size: usize,
 }
 
 impl Handle {
-    pub(crate) fn new(cookie: Cookie, reservoir: Option<Arc<Mutex<Reservoir>>>) -> Self {
+    pub(crate) fn new(cookie: Cookie) -> Self {
         Self {
             cookie: Some(cookie),
-            reservoir,
+            prefix_read: false,
+            read_buffer_size: READ_BUFFER_SIZE,
+        }
+    }
+
+    pub(crate) fn into_cookie(self) -> Option<Cookie> {
+        self.cookie
+    }
+
+    /// Returns `true` if the handle has permanently lost its cookie.
+    #[cfg(any(feature = "bb8", feature = "r2d2"))]
+    pub(crate) fn is_broken(&self) -> bool {
+        self.cookie.is_none()
+    }
+
+    /// Checks that the handle is still usable.
+    #[cfg(any(feature = "bb8", feature = "deadpool", feature = "r2d2"))]
+    pub(crate) fn probe(&mut self) -> Result<(), Error> {
+        match self.cookie.as_mut() {
+            Some(cookie) => cookie.probe(),
+            None => Err(Error::CookieNommed),
         }
     }
 
+    /// Returns the flags currently set on the handle.
+    #[cfg(feature = "v5-21")]
+    ///
+    /// If the `dlopen` feature is enabled and the loaded libmagic predates 5.21, this returns
+    /// [`Error::UnsupportedFunction`].
+    pub fn flags(&mut self) -> Result<FlagSet, Error> {
+        require("magic_getflags")?;
+        self.raw(|cookie| unsafe { magic_getflags(cookie) })
+            .map(FlagSet::from_bits)
+    }
+
     /// Returns a textual description of the given buffer.
     pub fn buffer(&mut self, buf: &[u8]) -> Result<String, Error> {
+        instrument::input_size(buf.len() as u64);
+        timed(|| {
+            description_to_str(
+                self.raw(|cookie| unsafe { magic_buffer(cookie, buf.as_ptr(), buf.len()) })?,
+            )
+        })
+    }
+
+    /// Returns a textual description of the given file.
+    ///
+    /// If [`Handle::set_prefix_read`] has been enabled, regular files are read as described
+    /// there, rather than by libmagic.
+    ///
+    /// Any error is wrapped in [`Error::Detect`], which includes the path.
+    pub fn file(&mut self, path: impl AsRef<Path>) -> Result<String, Error> {
+        let path = path.as_ref();
+        self.file_inner(path).map_err(|source| Error::Detect {
+            path: path.to_path_buf(),
+            source: Box::new(source),
+        })
+    }
+
+    /// Returns a description of the given file, falling back to guessing a MIME type from the
+    /// file name extension if libmagic can only describe the file as generic data.
+    ///
+    /// The fallback is used when libmagic returns `data`, `application/octet-stream`, or an empty
+    /// description, and [`mime_guess`] knows the extension. Since the guess is always a MIME type,
+    /// this is most useful with handles configured with [`Flag::MimeType`][crate::Flag::MimeType].
+    /// The returned [`DetectionSource`][crate::DetectionSource] indicates which produced the
+    /// result.
+    ///
+    /// Errors from libmagic are returned as per [`Handle::file`], and don't trigger the fallback.
+    #[cfg(feature = "mime_guess")]
+    pub fn detect_with_fallback(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<(crate::Detection, crate::DetectionSource), Error> {
+        let path = path.as_ref();
+        let detection = crate::Detection::from(self.file(path)?);
+
+        let description = detection.description().trim();
+        if (description.is_empty()
+            || description == "data"
+            || detection.mime_type() == Some("application/octet-stream"))
+            && let Some(guess) = mime_guess::from_path(path).first_raw()
+        {
+            return Ok((
+                crate::Detection::new(guess),
+                crate::DetectionSource::Extension,
+            ));
+        }
+
+        Ok((detection, crate::DetectionSource::Libmagic))
+    }
+
+    /// Returns `true` if [`Handle::file`] reads the start of regular files itself.
+    pub fn prefix_read(&self) -> bool {
+        self.prefix_read
+    }
+
+    /// Sets whether [`Handle::file`] reads the start of regular files itself, rather than leaving
+    /// libmagic to access the file.
+    ///
+    /// When enabled, up to [`BYTES_MAX`] bytes are read with a single sequential pass and then
+    /// described as per [`Handle::buffer`]. libmagic otherwise stats and reads files with access
+    /// patterns that can be slow and unpredictable on network and FUSE filesystems, whereas this
+    /// bounds the I/O for each file. Descriptions are the same for most files, but magic that
+    /// examines data beyond the limit, such as at the end of a file, won't match.
+    ///
+    /// Anything other than a regular file, including a symbolic link, is still passed to libmagic,
+    /// so that it's described according to the handle's flags, as is every file if the handle was
+    /// configured with [`Flag::PreserveAccessTime`][crate::Flag::PreserveAccessTime]. Errors
+    /// opening or reading the file are returned as [`Error::ReadInput`], which retains the kind of
+    /// the underlying I/O error.
+    ///
+    /// This only applies until a pooled handle is returned to its pool; use
+    /// [`PoolOptions::prefix_read`][crate::PoolOptions::prefix_read] to enable it for every handle
+    /// acquired from a pool.
+    pub fn set_prefix_read(&mut self, prefix_read: bool) {
+        self.prefix_read = prefix_read;
+    }
+
+    fn file_inner(&mut self, path: &Path) -> Result<String, Error> {
+        // libmagic restores the access time of files that it reads itself when asked to, whereas
+        // we don't, so that's left to libmagic.
+        if self.prefix_read
+            && !self
+                .cookie
+                .as_ref()
+                .is_some_and(|cookie| cookie.has_flag(crate::Flag::PreserveAccessTime))
+            && let Ok(metadata) = std::fs::symlink_metadata(path)
+            && metadata.is_file()
+        {
+            return timed(|| self.file_prefix(path, metadata.len()));
+        }
+
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| Error::EmbeddedNuls)?;
+        timed(|| {
+            description_to_str(self.raw(|cookie| unsafe { magic_file(cookie, path.as_ptr()) })?)
+        })
+    }
+
+    fn file_prefix(&mut self, path: &Path, len: u64) -> Result<String, Error> {
+        let mut buf = Vec::with_capacity(len.min(BYTES_MAX as u64) as usize);
+        std::fs::File::open(path)
+            .and_then(|file| file.take(BYTES_MAX as u64).read_to_end(&mut buf))
+            .map_err(Error::ReadInput)?;
+
+        instrument::input_size(buf.len() as u64);
         description_to_str(
             self.raw(|cookie| unsafe { magic_buffer(cookie, buf.as_ptr(), buf.len()) })?,
         )
     }
 
-    /// Returns a textual description of the given file.
-    pub fn file(&mut self, path: impl AsRef<Path>) -> Result<String, Error> {
-        let path =
-            CString::new(path.as_ref().as_os_str().as_bytes()).map_err(|_| Error::EmbeddedNuls)?;
-        description_to_str(self.raw(|cookie| unsafe { magic_file(cookie, path.as_ptr()) })?)
+    /// Iterates over the members of a ZIP archive, performing detection on the start of each
+    /// member's decompressed content.
+    ///
+    /// libmagic only identifies the archive itself, so this is useful for content scanners that
+    /// need to see what an archive contains. Members are decompressed in memory, and only a prefix
+    /// of each is read, as per [`ZipEntries::with_prefix_len`][crate::ZipEntries::with_prefix_len].
+    ///
+    /// Returns [`Error::Archive`] if the archive can't be read. Errors for individual members are
+    /// returned by the iterator as [`Error::Detect`], with the member name as the path.
+    #[cfg(feature = "zip")]
+    pub fn zip_entries<R: Read + std::io::Seek>(
+        &mut self,
+        reader: R,
+    ) -> Result<crate::ZipEntries<'_, R>, Error> {
+        crate::ZipEntries::new(self, reader)
+    }
+
+    /// Performs detection on the start of each file within a tar archive, returning the results
+    /// in the order that the files appear in the archive.
+    ///
+    /// The archive is read as a stream, without extracting anything to disk, and only a prefix of
+    /// each file is passed to libmagic. Directories, links, and other special entries are
+    /// skipped. If the `flate2`, `xz2`, or `zstd` feature is enabled, archives compressed with
+    /// gzip, xz, or zstd respectively are decompressed transparently.
+    ///
+    /// Returns [`Error::Archive`] if the archive is compressed in a format that isn't enabled.
+    /// Errors for individual files are returned within the results as [`Error::Detect`], with the
+    /// file name as the path. Since the archive can only be read sequentially, an error reading
+    /// the archive itself is returned as the final result, as [`Error::Archive`].
+    #[cfg(feature = "tar")]
+    pub fn tar_entries(
+        &mut self,
+        reader: impl Read,
+    ) -> Result<Vec<crate::archive::tar::Entry>, Error> {
+        crate::archive::tar::entries(self, reader)
+    }
+
+    /// Performs detection on the given buffer and, if it's an archive, recursively on each file
+    /// within it, returning a tree of detections.
+    ///
+    /// Archives are identified by their content rather than by the detection, so this works
+    /// regardless of the flags set on the handle. ZIP archives are descended into if the `zip`
+    /// feature is enabled, and tar archives if the `tar` feature is enabled, including compressed
+    /// archives as per `Handle::tar_entries`. Each member is decompressed into memory in full, so
+    /// that archives within it can be read in turn, subject to the
+    /// [`DeepBudget`][crate::DeepBudget].
+    /// Once any limit of the budget is reached, the remaining members of every archive being
+    /// examined are skipped, and [`DeepDetection::truncated`][crate::DeepDetection::truncated] is
+    /// set on each of them.
+    ///
+    /// Errors performing detection on the buffer itself are returned directly. Errors within an
+    /// archive are returned as [`DeepDetection::members`][crate::DeepDetection::members].
+    #[cfg(any(feature = "tar", feature = "zip"))]
+    pub fn deep_detect(
+        &mut self,
+        buf: &[u8],
+        budget: crate::DeepBudget,
+    ) -> Result<crate::DeepDetection, Error> {
+        crate::archive::deep::detect(self, buf, budget)
     }
 
     /// Returns a textual description of the given [`Read`].
@@ -58,7 +259,33 @@ impl Handle {
     /// has been hit, regardless of what data is actually in the reader. That limit defaults to
     /// approximately 7 MiB; consider writing larger inputs out to a file and then using
     /// [`Handle::file`].
+    ///
+    /// Data is copied in chunks of up to [`Handle::read_buffer_size`] bytes, using a buffer that
+    /// is allocated on first use and then kept with the underlying libmagic cookie, so it's also
+    /// reused when the handle is returned to a [`Pool`][crate::Pool] and acquired again.
     pub fn read(&mut self, read: impl Read) -> Result<String, Error> {
+        timed(|| self.read_inner(read))
+    }
+
+    /// Returns the size of the chunks that [`Handle::read`] copies data in, which defaults to 64
+    /// KiB.
+    pub fn read_buffer_size(&self) -> usize {
+        self.read_buffer_size
+    }
+
+    /// Sets the size of the chunks that [`Handle::read`] copies data in.
+    ///
+    /// Larger chunks mean fewer system calls for each input, at the cost of the memory kept with
+    /// the handle. Sizes of zero are treated as one byte.
+    ///
+    /// This only applies until a pooled handle is returned to its pool; use
+    /// [`PoolOptions::read_buffer_size`][crate::PoolOptions::read_buffer_size] to set the size for
+    /// every handle acquired from a pool.
+    pub fn set_read_buffer_size(&mut self, size: usize) {
+        self.read_buffer_size = size.max(1);
+    }
+
+    fn read_inner(&mut self, mut read: impl Read) -> Result<String, Error> {
         // Our options to handle an arbitrary `Read` are basically either to buffer the entire input
         // or to feed it in via a file descriptor, which means an anonymous pipe. The latter is
         // definitely more efficient, but requires us to spawn a thread to drive the anonymous pipe.
@@ -66,9 +293,17 @@ impl Handle {
         // Since cookies are `Send`, we'll move the cookie within the handle into another thread,
         // and drive the pipe from this thread, thereby not requiring `read` to be `Send`.
 
-        let (reader, mut writer) = std::io::pipe().map_err(Error::PipeCreate)?;
+        let (reader, mut writer) = std::io::pipe().map_err(Error::pipe_create)?;
         let mut cookie = self.cookie.take().ok_or(Error::CookieNommed)?;
 
+        // The scratch buffer is reused between calls, since allocating it for each input shows up
+        // when detecting many small streams. It's kept with the cookie, rather than the handle, so
+        // that it survives the handle being returned to a pool.
+        let mut buf = std::mem::take(&mut cookie.scratch);
+        if buf.len() != self.read_buffer_size {
+            buf = vec![0u8; self.read_buffer_size];
+        }
+
         let cookie_handle = std::thread::spawn(move || {
             match cookie.raw(|cookie| unsafe { magic_descriptor(cookie, reader.as_raw_fd()) }) {
                 Ok(desc) => (description_to_str(desc), cookie),
@@ -79,35 +314,172 @@ impl Handle {
         // We can't use std::io::copy here because libmagic will unceremoniously drop the fd once
         // it has enough data or has hit its limit, so we need to handle a broken pipe as a
         // successful termination.
-        let mut read = BufReader::new(read);
-        let mut buf = vec![0u8; 8192];
+        let mut copied = 0;
+        let mut closed = false;
         loop {
-            let r = read.read(&mut buf).map_err(Error::PipeCopy)?;
+            let r = read.read(&mut buf).map_err(Error::pipe_copy)?;
             if r == 0 {
                 break;
             }
 
             match writer.write_all(&buf[0..r]) {
-                Ok(()) => {}
+                Ok(()) => copied += r as u64,
                 Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+                    closed = true;
                     break;
                 }
-                Err(e) => return Err(Error::PipeCopy(e)),
+                Err(e) => return Err(Error::pipe_copy(e)),
             }
         }
+        instrument::pipe_copied(copied, closed);
+        instrument::input_size(copied);
 
         // Drop the writer, just to ensure that our spawned thread terminates.
         drop(writer);
 
-        let (result, cookie) = cookie_handle.join().map_err(|_| Error::PipeJoin)?;
+        let (result, mut cookie) = cookie_handle.join().map_err(|_| Error::PipeJoin)?;
+        cookie.scratch = buf;
         self.cookie.replace(cookie);
 
         result
     }
 
+    /// Returns a textual description of the given [`AsyncRead`][tokio::io::AsyncRead], without
+    /// blocking the async runtime.
+    ///
+    /// Up to [`BYTES_MAX`] bytes are read into memory, which is libmagic's default file size
+    /// limit, and then detection is performed via [`tokio::task::spawn_blocking`]. This must
+    /// therefore be called within a Tokio runtime.
+    ///
+    /// If the returned future is dropped while detection is in progress, the handle will lose
+    /// its cookie, and any further use will return [`Error::CookieNommed`].
+    #[cfg(feature = "tokio")]
+    pub async fn read_async(
+        &mut self,
+        read: impl tokio::io::AsyncRead + Unpin,
+    ) -> Result<String, Error> {
+        use tokio::io::AsyncReadExt;
+
+        let mut buf = Vec::new();
+        read.take(BYTES_MAX as u64)
+            .read_to_end(&mut buf)
+            .await
+            .map_err(Error::ReadInput)?;
+
+        self.buffer_blocking(buf).await
+    }
+
+    /// Returns a textual description of the given [`AsyncRead`][futures_io::AsyncRead].
+    ///
+    /// This works with any async runtime. Up to [`BYTES_MAX`] bytes are read into memory, and then
+    /// detection is performed on the current task: since libmagic is examining a buffer at that
+    /// point, it won't block on I/O, but it may still take some time for large inputs. Tokio users
+    /// should prefer `Handle::read_async`, which performs detection on a blocking thread.
+    #[cfg(feature = "futures-io")]
+    pub async fn read_futures(
+        &mut self,
+        read: impl futures_io::AsyncRead + Unpin,
+    ) -> Result<String, Error> {
+        let buf = read_prefix(read).await?;
+        self.buffer(&buf)
+    }
+
+    /// Returns a textual description of the given [`AsyncRead`][futures_io::AsyncRead], without
+    /// blocking the async runtime.
+    ///
+    /// As with [`Handle::read_futures`], up to [`BYTES_MAX`] bytes are read into memory, but
+    /// detection is then performed on the thread pool used by [`blocking::unblock`]. This works
+    /// with any async runtime, and is the equivalent of `Handle::read_async` for applications using
+    /// `async-std` or `smol`.
+    ///
+    /// If the returned future is dropped while detection is in progress, the handle will lose
+    /// its cookie, and any further use will return [`Error::CookieNommed`].
+    #[cfg(feature = "smol")]
+    pub async fn read_unblock(
+        &mut self,
+        read: impl futures_io::AsyncRead + Unpin,
+    ) -> Result<String, Error> {
+        let buf = read_prefix(read).await?;
+
+        // As with run_blocking(), the cookie is moved into the blocking task and then restored
+        // afterwards.
+        let mut handle = Handle::new(self.cookie.take().ok_or(Error::CookieNommed)?);
+        let (result, handle) = blocking::unblock(move || (handle.buffer(&buf), handle)).await;
+        self.cookie = handle.cookie;
+
+        result
+    }
+
+    /// Returns a textual description of the data at the start of the given [`Stream`] of
+    /// [`Bytes`], along with the rest of the stream.
+    ///
+    /// Chunks are consumed until [`BYTES_MAX`] bytes have been read or the stream ends, and then
+    /// detection is performed via [`tokio::task::spawn_blocking`]. Any part of the final chunk
+    /// beyond the limit is yielded first by the returned [`Remainder`], followed by any chunks that
+    /// weren't consumed.
+    ///
+    /// This is intended for request bodies, such as those from `hyper` or `axum`.
+    ///
+    /// [`Stream`]: futures_core::Stream
+    #[cfg(all(feature = "bytes", feature = "tokio"))]
+    pub async fn stream<S, E>(&mut self, mut stream: S) -> Result<(String, Remainder<S>), Error>
+    where
+        S: futures_core::Stream<Item = Result<Bytes, E>> + Unpin,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let mut buf = Vec::new();
+        let mut pending = None;
+        while buf.len() < BYTES_MAX {
+            let next =
+                std::future::poll_fn(|cx| std::pin::Pin::new(&mut stream).poll_next(cx)).await;
+            let chunk = match next {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => return Err(Error::ReadInput(std::io::Error::other(e))),
+                None => break,
+            };
+
+            let len = chunk.len().min(BYTES_MAX - buf.len());
+            buf.extend_from_slice(&chunk[..len]);
+            if len < chunk.len() {
+                pending = Some(chunk.slice(len..));
+            }
+        }
+
+        let description = self.buffer_blocking(buf).await?;
+        Ok((description, Remainder::new(pending, stream)))
+    }
+
+    /// Returns a textual description of the given buffer, performing the detection via
+    /// [`tokio::task::spawn_blocking`].
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn buffer_blocking(&mut self, buf: Vec<u8>) -> Result<String, Error> {
+        self.run_blocking(move |handle| handle.buffer(&buf)).await
+    }
+
+    /// Invokes `f` with the handle via [`tokio::task::spawn_blocking`].
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn run_blocking<F>(&mut self, f: F) -> Result<String, Error>
+    where
+        F: FnOnce(&mut Handle) -> Result<String, Error> + Send + 'static,
+    {
+        // As with read(), the cookie is moved into the blocking task and then restored afterwards,
+        // since the task can't borrow the handle.
+        let mut handle = Handle::new(self.cookie.take().ok_or(Error::CookieNommed)?);
+        let (result, handle) = tokio::task::spawn_blocking(move || (f(&mut handle), handle))
+            .await
+            .map_err(|_| Error::TaskJoin)?;
+        self.cookie = handle.cookie;
+
+        result
+    }
+
     /// Returns a textual description of the given raw file descriptor.
     pub fn raw_fd(&mut self, fd: impl AsRawFd) -> Result<String, Error> {
-        description_to_str(self.raw(|cookie| unsafe { magic_descriptor(cookie, fd.as_raw_fd()) })?)
+        timed(|| {
+            description_to_str(
+                self.raw(|cookie| unsafe { magic_descriptor(cookie, fd.as_raw_fd()) })?,
+            )
+        })
     }
 
     /// Allows a raw libmagic function to be invoked on the [`magic_t`] cookie within the handle.
@@ -117,6 +489,9 @@ impl Handle {
     ///
     /// This crate re-exports [`magic_sys`][crate::magic_sys], so any required functions and
     /// constants are accessible that way.
+    ///
+    /// Note that if the `dlopen` feature is enabled, calling functions from `magic_sys` will make
+    /// libmagic a link time dependency of the binary again.
     pub fn raw<F, R>(&mut self, f: F) -> Result<R, Error>
     where
         F: FnOnce(magic_t) -> R,
@@ -137,40 +512,61 @@ impl Debug for Handle {
     }
 }
 
-impl Drop for Handle {
-    fn drop(&mut self) {
-        if let Some(cookie) = self.cookie.take()
-            && let Some(reservoir) = self.reservoir.take()
-        {
-            reservoir
-                .lock()
-                .expect("magic pool inner lock")
-                .unused
-                .push(cookie);
-        }
-    }
-}
+/// A raw libmagic cookie, along with any buffers that it has loaded.
+///
+/// libmagic doesn't copy buffers passed to `magic_load_buffers`, so they need to live at least as
+/// long as the cookie does, regardless of whether the cookie is in a pool or has been detached.
+pub(crate) struct Cookie {
+    magic: magic_t,
+    buffers: Vec<SharedBuffer>,
+    flags: c_int,
 
-#[derive(Debug)]
-pub(crate) struct Cookie(magic_t);
+    // The buffer used by Handle::read(), which lives here so that it's reused by every handle
+    // that the cookie is wrapped in.
+    scratch: Vec<u8>,
+}
 
 impl Cookie {
+    /// Opens a new cookie with the given flags, loading libmagic first if necessary.
+    pub(crate) fn open(flags: c_int) -> Result<Self, Error> {
+        crate::sys::load()?;
+        let mut cookie = Self::try_from(unsafe { magic_open(flags) })?;
+        cookie.flags = flags;
+
+        Ok(cookie)
+    }
+
+    /// Returns `true` if the cookie was opened with the given flag.
+    pub(crate) fn has_flag(&self, flag: crate::Flag) -> bool {
+        self.flags & flag as c_int != 0
+    }
+
+    /// Keeps the given buffers alive for as long as the cookie exists.
+    pub(crate) fn retain_buffers(&mut self, buffers: &[SharedBuffer]) {
+        self.buffers.extend_from_slice(buffers);
+    }
+
+    /// Checks that the cookie is still usable by identifying an empty buffer.
+    pub(crate) fn probe(&mut self) -> Result<(), Error> {
+        // An empty buffer is about the cheapest thing we can ask libmagic to identify, but it still
+        // resets any previous error state and exercises the loaded database.
+        self.raw(|cookie| unsafe { magic_buffer(cookie, b"".as_ptr(), 0) })
+            .map(|_| ())
+    }
+
     pub(crate) fn raw<F, R>(&mut self, f: F) -> Result<R, Error>
     where
         F: FnOnce(magic_t) -> R,
         R: ResultType,
     {
-        let result = f(self.0);
+        let result = f(self.magic);
         if result.is_error() {
-            let errno = unsafe { magic_errno(self.0) };
-            let error = unsafe { magic_error(self.0) };
+            let errno = unsafe { magic_errno(self.magic) };
+            let error = unsafe { magic_error(self.magic) };
             if error.is_null() {
                 Err(Error::Nested(errno))
             } else {
-                Err(Error::Magic {
-                    errno,
-                    message: unsafe { CStr::from_ptr(error) }.into(),
-                })
+                Err(Error::magic(errno, unsafe { CStr::from_ptr(error) }))
             }
         } else {
             Ok(result)
@@ -178,9 +574,19 @@ impl Cookie {
     }
 }
 
+impl Debug for Cookie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cookie")
+            .field("magic", &self.magic)
+            .field("buffers", &self.buffers)
+            .field("flags", &self.flags)
+            .finish()
+    }
+}
+
 impl Drop for Cookie {
     fn drop(&mut self) {
-        unsafe { magic_close(self.0) };
+        unsafe { magic_close(self.magic) };
     }
 }
 
@@ -191,13 +597,27 @@ impl TryFrom<magic_t> for Cookie {
         if cookie.is_null() {
             Err(Error::create())
         } else {
-            Ok(Self(cookie))
+            Ok(Self {
+                magic: cookie,
+                buffers: Vec::new(),
+                flags: 0,
+                scratch: Vec::new(),
+            })
         }
     }
 }
 
 unsafe impl Send for Cookie {}
 
+/// The number of bytes that libmagic examines by default, and therefore the most that will be
+/// read from asynchronous inputs before detection.
+///
+/// This is approximately 7 MiB, which has been libmagic's default limit since version 5.38.
+pub const BYTES_MAX: usize = 7 * 1024 * 1024;
+
+/// The default size of the chunks that [`Handle::read`] copies data in.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
 /// A raw result from the libmagic C API, which can be either a [`c_int`] or a [`*const
 /// c_char`][c_char].
 pub trait ResultType {
@@ -216,11 +636,67 @@ impl ResultType for *const c_char {
     }
 }
 
+/// Invokes a detection function, recording how long it took.
+fn timed<F>(f: F) -> Result<String, Error>
+where
+    F: FnOnce() -> Result<String, Error>,
+{
+    let start = Instant::now();
+    let result = f();
+    instrument::detection(start.elapsed(), &result);
+
+    result
+}
+
+/// Reads up to [`BYTES_MAX`] bytes from the given [`AsyncRead`][futures_io::AsyncRead].
+#[cfg(feature = "futures-io")]
+async fn read_prefix(mut read: impl futures_io::AsyncRead + Unpin) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    while buf.len() < BYTES_MAX {
+        let len = chunk.len().min(BYTES_MAX - buf.len());
+        let result = std::future::poll_fn(|cx| {
+            std::pin::Pin::new(&mut read).poll_read(cx, &mut chunk[..len])
+        })
+        .await;
+
+        match result {
+            Ok(0) => break,
+            Ok(r) => buf.extend_from_slice(&chunk[..r]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(Error::ReadInput(e)),
+        }
+    }
+
+    Ok(buf)
+}
+
 fn description_to_str(desc: *const c_char) -> Result<String, Error> {
     let cstr = unsafe { CStr::from_ptr(desc) };
 
     match cstr.to_str() {
         Ok(s) => Ok(s.to_string()),
-        Err(_) => Err(Error::DescriptionNotUtf8(cstr.to_bytes().to_vec())),
+        Err(_) => Err(Error::DescriptionNotUtf8(cstr.to_bytes().into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn description_not_utf8() {
+        let desc = b"ELF \xff\0";
+        let Err(Error::DescriptionNotUtf8(description)) = description_to_str(desc.as_ptr().cast())
+        else {
+            panic!("expected DescriptionNotUtf8");
+        };
+
+        assert_eq!(description.as_bytes(), b"ELF \xff");
+        assert_eq!(description.to_string_lossy(), "ELF \u{fffd}");
+        assert_eq!(
+            Error::DescriptionNotUtf8(description).to_string(),
+            "description was not valid UTF-8: ELF \u{fffd}"
+        );
     }
 }
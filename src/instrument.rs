@@ -0,0 +1,143 @@
+//! Internal instrumentation hooks, which compile down to nothing unless the relevant features are
+//! enabled.
+
+#![cfg_attr(
+    not(all(feature = "log", feature = "metrics")),
+    allow(unused_variables)
+)]
+
+use std::time::Duration;
+
+use crate::Error;
+
+/// Records that a magic database was loaded into a new cookie.
+pub(crate) fn database_loaded(source: &str, elapsed: Duration) {
+    #[cfg(feature = "log")]
+    ::log::debug!("loaded {source} magic database in {elapsed:?}");
+}
+
+/// Records that a pool had to create a new handle.
+pub(crate) fn handle_created(elapsed: Duration) {
+    #[cfg(feature = "log")]
+    ::log::debug!("pool created a new handle in {elapsed:?}");
+
+    #[cfg(feature = "metrics")]
+    {
+        ::metrics::counter!("mojique_pool_handles_created_total").increment(1);
+        ::metrics::histogram!("mojique_pool_handle_creation_seconds").record(elapsed.as_secs_f64());
+    }
+}
+
+/// Records that a pool discarded a handle instead of reusing it.
+pub(crate) fn handle_discarded(reason: &str) {
+    #[cfg(feature = "log")]
+    ::log::debug!("pool discarded a handle: {reason}");
+}
+
+/// Records that a pool was able to reuse an idle handle.
+pub(crate) fn handle_reused() {
+    #[cfg(feature = "log")]
+    ::log::trace!("pool reused an idle handle");
+
+    #[cfg(feature = "metrics")]
+    ::metrics::counter!("mojique_pool_handles_reused_total").increment(1);
+}
+
+/// Records that idle handles were evicted from a pool.
+pub(crate) fn handles_evicted(evicted: usize) {
+    #[cfg(feature = "log")]
+    if evicted > 0 {
+        ::log::debug!("pool evicted {evicted} idle handle(s)");
+    }
+}
+
+/// Records a change in the number of idle handles within a pool.
+///
+/// The gauge is shared by every pool with the same name, or by every unnamed pool, so it's
+/// adjusted by the difference between `before` and `after` rather than set outright.
+pub(crate) fn idle_handles(pool: Option<&str>, before: usize, after: usize) {
+    #[cfg(feature = "metrics")]
+    {
+        let gauge = match pool {
+            Some(name) => {
+                ::metrics::gauge!("mojique_pool_idle_handles", "pool" => name.to_string())
+            }
+            None => ::metrics::gauge!("mojique_pool_idle_handles"),
+        };
+        if after > before {
+            gauge.increment((after - before) as f64);
+        } else if before > after {
+            gauge.decrement((before - after) as f64);
+        }
+    }
+}
+
+/// Records that a daemon connection was closed because of an error.
+#[cfg(feature = "daemon")]
+pub(crate) fn connection_failed(error: &std::io::Error) {
+    #[cfg(feature = "log")]
+    ::log::warn!("daemon connection failed: {error}");
+}
+
+/// Records that a detection was performed, along with its outcome.
+pub(crate) fn detection(elapsed: Duration, result: &Result<String, Error>) {
+    #[cfg(feature = "metrics")]
+    {
+        ::metrics::histogram!("mojique_detection_seconds").record(elapsed.as_secs_f64());
+        ::metrics::counter!("mojique_detections_total", "outcome" => outcome(result)).increment(1);
+    }
+}
+
+/// Records the size of an input that detection was performed on.
+///
+/// This is only known for buffers and readers: libmagic reads files and descriptors itself.
+pub(crate) fn input_size(bytes: u64) {
+    #[cfg(feature = "metrics")]
+    ::metrics::histogram!("mojique_detection_input_bytes").record(bytes as f64);
+}
+
+/// Classifies the outcome of a detection for use as a metric label.
+///
+/// Successful detections are classified by their top level MIME type, such as `image`, or
+/// `unknown` if the description isn't a MIME type. Failed detections are classified as `timeout`
+/// if the input timed out, and `error` otherwise.
+#[cfg(feature = "metrics")]
+fn outcome(result: &Result<String, Error>) -> String {
+    match result {
+        Ok(description) => crate::detection::mime_type(description)
+            .and_then(|mime_type| mime_type.split_once('/'))
+            .map_or("unknown", |(type_, _)| type_)
+            .to_ascii_lowercase(),
+        Err(e) if e.io_error_kind() == std::io::ErrorKind::TimedOut => "timeout".to_string(),
+        Err(_) => "error".to_string(),
+    }
+}
+
+/// Records that input was copied into the pipe read by libmagic.
+///
+/// `closed` is `true` if libmagic closed the pipe before the input was exhausted.
+pub(crate) fn pipe_copied(bytes: u64, closed: bool) {
+    #[cfg(feature = "log")]
+    if closed {
+        ::log::trace!("copied {bytes} byte(s) to libmagic before it closed the pipe");
+    } else {
+        ::log::trace!("copied {bytes} byte(s) to libmagic");
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcomes() {
+        assert_eq!(outcome(&Ok("image/png".into())), "image");
+        assert_eq!(outcome(&Ok("text/plain; charset=us-ascii".into())), "text");
+        assert_eq!(outcome(&Ok("ASCII text".into())), "unknown");
+        assert_eq!(
+            outcome(&Err(Error::pipe_copy(std::io::ErrorKind::TimedOut.into()))),
+            "timeout"
+        );
+        assert_eq!(outcome(&Err(Error::magic(0, c"oops"))), "error");
+    }
+}
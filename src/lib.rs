@@ -8,9 +8,14 @@
 //!    1. [`DefaultConfig`]: uses the system magic database.
 //!    1. [`BufferConfig`]: uses magic database(s) provided from `&[u8]` buffers.
 //!    1. [`FileConfig`]: uses magic database(s) on the filesystem.
+//!    1. [`EnvConfig`]: uses the magic database(s) named by the `MAGIC` environment variable.
+//!    1. [`DirectoryConfig`]: uses every magic database within a directory.
+//!    1. [`ReaderConfig`]: uses magic database(s) read from any [`Read`](std::io::Read).
+//!    1. [`CombinedConfig`]: uses the system magic database, along with additional magic
+//!       database(s) from the filesystem and/or `&[u8]` buffers.
 //! 1. Build either a single [`Handle`] (which is [`Send`], but not [`Sync`]), or a [`Pool`] of
-//!    handles (that is both [`Send`] and [`Sync`]), which can then be used to acquire handles via
-//!    [`Pool::handle`].
+//!    handles (that is both [`Send`] and [`Sync`]), which can then be used to acquire
+//!    [`PooledHandle`]s via [`Pool::handle`].
 //! 1. Call methods on [`Handle`] to detect file types based on content.
 //!
 //! ## Simple example
@@ -41,28 +46,447 @@
 //! Once you have a pool, you can [`Clone`] it as much as needed and use [`Pool::handle`] to
 //! acquire handles to specific tasks or threads.
 //!
+//! If you would rather use an existing connection pool crate, `Manager` implements the relevant
+//! traits for [`bb8`][bb8], [`deadpool`][deadpool], and [`r2d2`][r2d2] when the feature of the
+//! same name is enabled.
+//!
+//! [`CachedPool`] wraps a pool with an in-memory cache of detections, keyed by a hash of the
+//! content for buffers, and by path, size, and modification time for files. This avoids calling
+//! into libmagic at all for content that has been seen before, which is common in deduplicated
+//! storage systems. If the `moka` feature is enabled, the cache is provided by [`moka`][moka]
+//! rather than a simple LRU.
+//!
+//! If the `disk-cache` feature is enabled, `DiskCache` keeps the detections of files in a cache
+//! file keyed by device, inode, size, and modification time, so that repeated scans of a mostly
+//! unchanged tree only perform detection on files that have changed since the last scan.
+//!
+//! ## Async
+//!
+//! If the `tokio` feature is enabled, `Handle::read_async` reads from a Tokio `AsyncRead` and
+//! performs detection on a blocking thread, so that async tasks don't have to bridge to
+//! [`Handle::read`] themselves. Similarly, `Pool::buffer_async` and `Pool::file_async` acquire a
+//! handle and perform detection on a blocking thread, returning the handle to the pool afterwards.
+//! `AsyncSniffReader` wraps an `AsyncRead`, performing detection on its start and then replaying
+//! it, which is useful when an input needs to be classified and then forwarded.
+//!
+//! If the `bytes` feature is also enabled, `Handle::stream` performs detection on the start of a
+//! stream of `Bytes`, such as an HTTP request body, and returns the rest of the stream.
+//!
+//! For other runtimes, the `futures-io` feature adds `Handle::read_futures`, which accepts the
+//! `AsyncRead` trait from the `futures` ecosystem.
+//!
+//! If the `smol` feature is enabled, `Pool::handle_unblock`, `Pool::buffer_unblock`,
+//! `Pool::file_unblock`, and `Handle::read_unblock` mirror the Tokio integration, but offload
+//! blocking work with [`blocking`][blocking] instead, which makes them suitable for applications
+//! using [`async-std`][async-std] or [`smol`][smol].
+//!
+//! If the `tower` feature is enabled, `DetectService` implements `tower::Service` on top of a
+//! [`Pool`], accepting buffers, paths, and streams, and only becoming ready when the pool has
+//! capacity. This allows timeouts, concurrency limits, and retries to be composed around
+//! detection with [`tower`][tower] middleware.
+//!
+//! If the `http` feature is enabled, `Pool::sniff_body` performs detection on the start of an
+//! [`http_body::Body`][http-body], such as a hyper request or response body, and returns a body
+//! that replays the entire input. This is useful in proxies that need to correct or fill in a
+//! missing `Content-Type`.
+//!
+//! If the `reqwest` feature is enabled, `Pool::sniff_response` performs detection on the start of
+//! a [`reqwest`][reqwest] response body, and returns a `SniffedResponse` that can compare the
+//! result with the `Content-Type` claimed by the server, along with a body that replays the entire
+//! input.
+//!
+//! ## Web frameworks
+//!
+//! If the [`axum`][axum] feature is enabled, the `Detected` extractor buffers a request body and
+//! performs detection on it using a [`Pool`] from the application state, and `AllowlistLayer`
+//! rejects requests whose bodies don't match a set of allowed MIME types.
+//!
+//! If the `actix` feature is enabled, the `Sniff` middleware performs detection on request bodies
+//! for [`actix-web`][actix-web] services, and the `Sniffed` extractor retrieves the result. The
+//! `SniffedFields` extractor instead performs detection on each field of a multipart body, and
+//! `Pool::sniff_stream` can be used to detect any other stream of `Bytes`.
+//!
+//! If the `rocket` feature is enabled, the `TypedData` data guard reads a request body and
+//! performs detection on it using a [`Pool`] managed by [`Rocket`][rocket], optionally
+//! restricting the allowed MIME types with a managed `AllowedTypes`.
+//!
+//! If the `multer` feature is enabled, `SniffFields` walks the fields of a
+//! [`multer`][multer] multipart stream, performing detection on each field's content within a
+//! configurable byte budget. This works with any framework that can provide a multipart body as
+//! a stream of `Bytes`.
+//!
+//! ## Sidecars
+//!
+//! If the `server` feature is enabled, `Server` exposes detection over a small HTTP API backed
+//! by a [`Pool`], and the `mojique-server` binary runs it as a standalone process. This allows
+//! services written in other languages to share a single libmagic deployment.
+//!
+//! If the `daemon` feature is enabled, `Daemon` serves detection requests over a Unix socket
+//! instead, and the `mojique-daemon` binary runs it as a standalone process. `Client` implements
+//! [`Backend`] by sending content or open file descriptors to the daemon, which allows detection
+//! to be privilege separated: the daemon can run sandboxed as an unprivileged user, since it
+//! never opens files itself.
+//!
+//! ## Command line tool
+//!
+//! If the `cli` feature is enabled, the `mojique` binary mirrors the common options of `file(1)`,
+//! such as `--mime-type`, `-k`, `-z`, `-L`, and `-m`, and describes each file with a handle from a
+//! [`Pool`]. Results can also be written as JSON, JSON Lines, or CSV with `--format`, or separated
+//! by NUL bytes with `--print0`, so that scripts don't have to parse libmagic's descriptions.
+//! `--files-from` and `--files0-from` read paths from a file or stdin, such as the output of
+//! `find -print0`, which avoids the limits on the length of the command line. `--recursive`
+//! describes every file within directories in parallel with a [`Scanner`], showing a progress bar
+//! with [`indicatif`][indicatif] if the `indicatif` feature is enabled. `--expect` fails
+//! unless every file matches one of the given MIME type patterns, for use in CI and pre-commit
+//! hooks.
+//!
+//! ## Validating uploads
+//!
+//! [`Validator`] applies a policy of allowed and denied MIME types to untrusted content, and can
+//! also cross-check the file name extension and `Content-Type` claimed by an uploader against the
+//! content. Every problem found is reported in a [`ValidationReport`].
+//!
+//! ## Scanning directories
+//!
+//! [`Scanner`] walks one or more directory trees and performs detection on every file with a pool
+//! of worker threads, returning the results as a [`Scan`] that can be iterated over or received
+//! from another thread. Glob patterns and extensions can restrict which files are scanned, such as
+//! to skip `node_modules` directories, or to only scan `**/*.bin`. A callback can follow the
+//! [`ScanProgress`] of a scan, which would otherwise be opaque until it completes. Hardlinks can be
+//! deduplicated, so that each underlying file is only read once, and files over a maximum size
+//! can be skipped or only have a prefix read.
+//!
+//! [`ScanReport`] writes the results of a scan to any number of [`ScanSink`]s, such as JSON Lines
+//! or CSV, and summarises the number of files of each type in a [`ScanSummary`].
+//!
+//! If the `blake3`, `sha2`, or `xxhash` feature is enabled, `Scanner::with_hash` also hashes each
+//! file with [`blake3`][blake3], [`sha2`][sha2], or [`xxhash-rust`][xxhash-rust] as it's read for
+//! detection, so inventories don't need to read every file twice.
+//!
+//! ## Scanning with io_uring
+//!
+//! If the `uring` feature is enabled, which is only supported on Linux, `UringBatch` performs
+//! detection on batches of files by reading a prefix of each file with [`io_uring`][io-uring] into
+//! registered buffers, rather than leaving libmagic to open and read each file in turn. This
+//! dramatically improves throughput when scanning millions of small files on fast storage.
+//!
+//! If the `blake3`, `sha2`, or `xxhash` feature is enabled, `UringBatch::with_dedup` hashes each
+//! prefix with [`blake3`][blake3], [`sha2`][sha2], or [`xxhash-rust`][xxhash-rust], and only
+//! performs detection once for each unique prefix, which avoids redundant work on corpora that
+//! contain many identical files.
+//!
+//! ## Archives
+//!
+//! libmagic only identifies archives themselves. If the `zip` feature is enabled,
+//! `Handle::zip_entries` iterates over the members of a ZIP archive using [`zip`][zip], and
+//! performs detection on the start of each member's decompressed content.
+//!
+//! Similarly, if the `tar` feature is enabled, `Handle::tar_entries` streams the files within a
+//! tar archive through libmagic using [`tar`][tar], without extracting them to disk. The `flate2`,
+//! `xz2`, and `zstd` features add transparent support for archives compressed with
+//! [`flate2`][flate2], [`xz2`][xz2], and [`zstd`][zstd].
+//!
+//! With either feature enabled, `Handle::deep_detect` descends into archives within archives,
+//! returning a tree of detections. Since archives can decompress to far more than their own size,
+//! or even contain themselves, the depth, total decompressed bytes, and number of members examined
+//! are bounded by a `DeepBudget`, and any archive that couldn't be fully examined is marked as
+//! truncated.
+//!
+//! ## Writing results
+//!
+//! [`JsonLinesWriter`] and [`CsvWriter`] write `(path, Detection)` records to any
+//! [`Write`](std::io::Write), which is useful for batch jobs that need to record what they found.
+//!
+//! ## Extension fallback
+//!
+//! If the `mime_guess` feature is enabled, `Handle::detect_with_fallback` guesses a MIME type from
+//! a file's extension using [`mime_guess`][mime_guess] when libmagic can only identify the file as
+//! generic data, which gives a best effort answer for formats that libmagic doesn't know.
+//!
+//! ## Reloading databases
+//!
+//! [`Pool::reload`] replaces every handle in a pool with one that has loaded the magic databases
+//! again, which allows changes to custom databases to be picked up without restarting. If the
+//! `watch` feature is enabled, `Pool::watch` uses [`notify`][notify] to watch the databases that
+//! the pool was configured with, and reloads the pool automatically when they change.
+//!
+//! ## Metrics
+//!
+//! If the `metrics` feature is enabled, pools and handles will emit counters, gauges, and
+//! histograms via the [`metrics`][metrics] facade, covering handle creation and reuse, the number
+//! of idle handles in pools, detection latency, and the size of buffers and readers that detection
+//! is performed on. All metric names are prefixed with `mojique_`.
+//!
+//! Detections are also counted in `mojique_detections_total`, with an `outcome` label of the top
+//! level MIME type detected (such as `image` or `text`), `unknown` if the handle isn't configured
+//! to return MIME types, `timeout` if reading the input timed out, or `error` for other failures.
+//! The number of idle handles is recorded in `mojique_pool_idle_handles`, with a `pool` label if
+//! the pool was given a name with [`PoolOptions::name`].
+//!
+//! ## Logging
+//!
+//! If the `log` feature is enabled, debug and trace messages are emitted via the [`log`][log]
+//! facade when pools create, reuse, discard, and evict handles, when magic databases are loaded
+//! (including how long loading took), and when input is copied to libmagic by [`Handle::read`].
+//!
+//! ## Diagnostics
+//!
+//! If the `miette` feature is enabled, [`Error`] implements [`miette`][miette]'s `Diagnostic`
+//! trait, with an error code for each variant, help text where there's something useful to
+//! suggest, and any warnings from a failed check attached as related diagnostics.
+//!
+//! If the `serde` feature is enabled, [`Error`] implements `Serialize`, producing the variant
+//! name, `errno`, message, and path, which is suitable for structured logs and error responses.
+//! Similarly, [`Detection`] and [`ValidationReport`] implement `Serialize` and `Deserialize`, so
+//! results can be included directly in API responses, queued, or written to audit logs.
+//!
+//! If the `backtrace` feature is enabled, errors from libmagic, creating cookies, and driving the
+//! anonymous pipes used by [`Handle::read`] capture a backtrace when they're created, which can be
+//! retrieved with [`Error::backtrace`].
+//!
+//! ## Declarative configuration
+//!
+//! If the `serde` feature is enabled, `ConfigSpec` can be deserialised from application
+//! configuration files to describe the magic database source, flags, and pool limits, and then
+//! converted into the matching configuration.
+//!
+//! Separately, [`Flag`] and [`FlagSet`] can be parsed from human readable names such as `mime` and
+//! `no-check-text`, and `Flag` implements `clap::ValueEnum` if the `clap` feature is enabled.
+//!
+//! ## Runtime capabilities
+//!
+//! The `v5-*` features control which flags are available at compile time, but the libmagic in use
+//! at runtime may be older. [`capabilities`] and [`Flag::is_supported`] check against the runtime
+//! version, and building a handle or pool with an unsupported flag returns
+//! [`Error::UnsupportedFlag`].
+//!
+//! ## Loading libmagic at runtime
+//!
+//! If the `dlopen` feature is enabled, libmagic is loaded when the first handle is created, rather
+//! than being a dynamic dependency of the binary. This allows the same binary to run on systems
+//! with different libmagic sonames, and to degrade gracefully on systems without libmagic at all:
+//! building a handle or pool returns [`Error::LibraryLoad`] instead of the binary failing to start.
+//!
+//! By default, `libmagic.so.1`, `libmagic.so`, `libmagic.1.dylib`, and `libmagic.dylib` are tried
+//! in that order. The `MOJIQUE_LIBMAGIC` environment variable can be set to a library name or path
+//! to load instead.
+//!
+//! Older versions of libmagic are accepted even if they lack functions that were added after 5.0.
+//! Operations that need one of those functions, such as loading databases from buffers, return
+//! [`Error::UnsupportedFunction`] instead.
+//!
+//! Note that [`magic_sys`] is still a build dependency, so libmagic needs to be available when
+//! building, even though it isn't needed when running.
+//!
+//! To keep working without libmagic, code can perform detection through the [`Backend`] trait,
+//! which [`Pool`] implements. If the `infer` feature is enabled, `InferBackend` implements the
+//! same trait in pure Rust using [`infer`][infer], and can be selected at runtime when building a
+//! pool fails.
+//!
+//! [actix-web]: https://crates.io/crates/actix-web
+//! [async-std]: https://crates.io/crates/async-std
+//! [axum]: https://crates.io/crates/axum
+//! [bb8]: https://crates.io/crates/bb8
+//! [blake3]: https://crates.io/crates/blake3
+//! [blocking]: https://crates.io/crates/blocking
+//! [deadpool]: https://crates.io/crates/deadpool
+//! [flate2]: https://crates.io/crates/flate2
+//! [http-body]: https://crates.io/crates/http-body
+//! [indicatif]: https://crates.io/crates/indicatif
+//! [infer]: https://crates.io/crates/infer
+//! [io-uring]: https://crates.io/crates/io-uring
 //! [libmagic]: https://www.darwinsys.com/file/
+//! [log]: https://crates.io/crates/log
+//! [metrics]: https://crates.io/crates/metrics
+//! [miette]: https://crates.io/crates/miette
+//! [moka]: https://crates.io/crates/moka
+//! [mime_guess]: https://crates.io/crates/mime_guess
+//! [multer]: https://crates.io/crates/multer
+//! [notify]: https://crates.io/crates/notify
+//! [r2d2]: https://crates.io/crates/r2d2
+//! [reqwest]: https://crates.io/crates/reqwest
+//! [rocket]: https://crates.io/crates/rocket
+//! [sha2]: https://crates.io/crates/sha2
+//! [smol]: https://crates.io/crates/smol
+//! [tar]: https://crates.io/crates/tar
+//! [tower]: https://crates.io/crates/tower
+//! [xxhash-rust]: https://crates.io/crates/xxhash-rust
+//! [xz2]: https://crates.io/crates/xz2
+//! [zip]: https://crates.io/crates/zip
+//! [zstd]: https://crates.io/crates/zstd
 
 pub use magic_sys;
-use std::ffi::c_int;
+use std::{
+    ffi::{OsStr, c_int},
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+};
 
 pub use crate::{
-    config::{BufferConfig, Config, DefaultConfig, FileConfig},
-    error::Error,
-    ffi::Flag,
-    handle::{Handle, ResultType},
-    pool::Pool,
+    backend::Backend,
+    cache::CachedPool,
+    capabilities::{Capabilities, capabilities},
+    check::CheckWarning,
+    compile::compile,
+    config::{
+        BufferConfig, CombinedConfig, Config, DefaultConfig, DirectoryConfig, EnvConfig,
+        FileConfig, ReaderConfig,
+    },
+    database::Database,
+    detection::Detection,
+    error::{Description, Error, ErrorKind},
+    ffi::{Check, Flag, FlagSet},
+    handle::{BYTES_MAX, Handle, ResultType},
+    output::{CsvWriter, JsonLinesWriter},
+    pool::{Pool, PoolOptions, PoolOrder, PooledHandle, WeakPool},
+    report::{ScanReport, ScanSink, ScanSummary},
+    scanner::{LargeFilePolicy, Scan, ScanProgress, Scanner, SymlinkPolicy},
+    validator::{Claims, ValidationReport, Validator, Violation},
+    version::Version,
 };
 
+#[cfg(feature = "http")]
+pub use crate::body::ReplayBody;
+
+#[cfg(feature = "daemon")]
+pub use crate::{client::Client, daemon::Daemon};
+
+#[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+pub use crate::{
+    dedup::{Digest, HashAlgorithm},
+    scanner::HashedResult,
+};
+
+#[cfg(feature = "disk-cache")]
+pub use crate::disk_cache::DiskCache;
+
+#[cfg(feature = "infer")]
+pub use crate::backend::InferBackend;
+
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "r2d2"))]
+pub use crate::manager::Manager;
+
+#[cfg(feature = "serde")]
+pub use crate::spec::{ConfigSpec, LimitsSpec, SourceSpec};
+
+#[cfg(feature = "actix")]
+pub use crate::web::actix::{FieldDetections, Sniff, SniffMiddleware, Sniffed, SniffedFields};
+
+#[cfg(feature = "axum")]
+pub use crate::web::axum::{Allowlist, AllowlistLayer, Detected, DetectedRejection};
+
+#[cfg(feature = "mime_guess")]
+pub use crate::detection::DetectionSource;
+
+#[cfg(feature = "multer")]
+pub use crate::multipart::SniffFields;
+
+#[cfg(feature = "reqwest")]
+pub use crate::response::{ResponseBody, SniffedResponse};
+
+#[cfg(feature = "rocket")]
+pub use crate::web::rocket::{AllowedTypes, TypedData, TypedDataError};
+
+#[cfg(feature = "server")]
+pub use crate::server::Server;
+
+#[cfg(feature = "uring")]
+pub use crate::uring::UringBatch;
+
+#[cfg(feature = "watch")]
+pub use crate::watch::{ReloadEvent, WatchOptions, Watcher};
+
+#[cfg(feature = "tower")]
+pub use crate::service::{DetectRequest, DetectService, DetectStream};
+
+#[cfg(feature = "tokio")]
+pub use crate::sniff::AsyncSniffReader;
+
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub use crate::archive::deep::{BudgetLimit, DeepBudget, DeepDetection};
+
+#[cfg(feature = "zip")]
+pub use crate::archive::zip::ZipEntries;
+
+#[cfg(all(feature = "bytes", feature = "tokio"))]
+pub use crate::stream::Remainder;
+
+#[cfg(any(feature = "tar", feature = "zip"))]
+mod archive;
+mod backend;
+#[cfg(feature = "http")]
+mod body;
+mod cache;
+mod capabilities;
+mod check;
+#[cfg(feature = "daemon")]
+mod client;
+mod compile;
 mod config;
+#[cfg(feature = "daemon")]
+mod daemon;
+mod database;
+#[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+mod dedup;
+mod detection;
+#[cfg(feature = "miette")]
+mod diagnostic;
+#[cfg(feature = "disk-cache")]
+mod disk_cache;
 mod error;
 mod ffi;
+mod glob;
 mod handle;
+mod instrument;
+#[cfg(any(feature = "bb8", feature = "deadpool", feature = "r2d2"))]
+mod manager;
+#[cfg(feature = "multer")]
+mod multipart;
+mod output;
 mod pool;
+mod report;
+#[cfg(feature = "reqwest")]
+mod response;
+mod scanner;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "tower")]
+mod service;
+#[cfg(feature = "tokio")]
+mod sniff;
+#[cfg(feature = "serde")]
+mod spec;
+#[cfg(all(feature = "bytes", feature = "tokio"))]
+mod stream;
+mod sys;
+#[cfg(feature = "uring")]
+mod uring;
+mod validator;
+mod version;
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(any(feature = "actix", feature = "axum", feature = "rocket"))]
+mod web;
 
 /// Returns the libmagic version.
+///
+/// If the `dlopen` feature is enabled and libmagic can't be loaded, or the loaded libmagic is too
+/// old to report its version, this returns 0.
+///
+/// [`Version::current`] returns the same version, split into its components.
 pub fn version() -> c_int {
-    unsafe { magic_sys::magic_version() }
+    unsafe { sys::magic_version() }
+}
+
+/// Returns the path(s) of the default magic database, as understood by libmagic.
+///
+/// This takes the `MAGIC` environment variable into account, so it reflects the database(s) that
+/// [`DefaultConfig`] will load. libmagic will use a compiled `.mgc` file next to each path in
+/// preference to the path itself, if one exists.
+pub fn default_database_path() -> Vec<PathBuf> {
+    config::raw_default_database_path()
+        .map(|path| std::env::split_paths(OsStr::from_bytes(path.as_bytes())).collect())
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
@@ -71,4 +495,9 @@ mod tests {
     fn version() {
         assert!(super::version() != 0)
     }
+
+    #[test]
+    fn default_database_path() {
+        assert!(!super::default_database_path().is_empty())
+    }
 }
@@ -0,0 +1,91 @@
+use std::ffi::c_int;
+
+use crate::{Config, Error, Handle, pool::Source};
+
+/// A manager that creates [`Handle`]s from a [`Config`] on behalf of a third party pool
+/// implementation.
+///
+/// This is an alternative to [`Pool`][crate::Pool] for applications that already use another
+/// pooling crate and want to manage libmagic handles with the same tooling. The following pools
+/// are supported, each behind a feature of the same name:
+///
+/// - [`bb8`](https://crates.io/crates/bb8)
+/// - [`deadpool`](https://crates.io/crates/deadpool)
+/// - [`r2d2`](https://crates.io/crates/r2d2)
+///
+/// Note that handles are created synchronously, which may block for some time while the magic
+/// database is loaded. For the async pools, you may want to create the pool with a minimum size
+/// so that this happens up front.
+#[derive(Debug)]
+pub struct Manager {
+    flags: c_int,
+    source: Source,
+}
+
+impl Manager {
+    /// Creates a new manager from the given configuration.
+    pub fn new(config: impl Config) -> Result<Self, Error> {
+        Ok(Self {
+            flags: config.flags(),
+            source: config.source()?,
+        })
+    }
+
+    fn create_handle(&self) -> Result<Handle, Error> {
+        self.source.create_handle(self.flags)
+    }
+}
+
+#[cfg(feature = "bb8")]
+impl bb8::ManageConnection for Manager {
+    type Connection = Handle;
+    type Error = Error;
+
+    async fn connect(&self) -> Result<Handle, Error> {
+        self.create_handle()
+    }
+
+    async fn is_valid(&self, handle: &mut Handle) -> Result<(), Error> {
+        handle.probe()
+    }
+
+    fn has_broken(&self, handle: &mut Handle) -> bool {
+        handle.is_broken()
+    }
+}
+
+#[cfg(feature = "deadpool")]
+impl deadpool::managed::Manager for Manager {
+    type Type = Handle;
+    type Error = Error;
+
+    async fn create(&self) -> Result<Handle, Error> {
+        self.create_handle()
+    }
+
+    async fn recycle(
+        &self,
+        handle: &mut Handle,
+        _metrics: &deadpool::managed::Metrics,
+    ) -> deadpool::managed::RecycleResult<Error> {
+        Ok(handle.probe()?)
+    }
+}
+
+#[cfg(feature = "r2d2")]
+impl r2d2::ManageConnection for Manager {
+    type Connection = Handle;
+    type Error = Error;
+
+    fn connect(&self) -> Result<Handle, Error> {
+        self.create_handle()
+    }
+
+    fn is_valid(&self, handle: &mut Handle) -> Result<(), Error> {
+        handle.probe()
+    }
+
+    fn has_broken(&self, handle: &mut Handle) -> bool {
+        handle.is_broken()
+    }
+}
@@ -0,0 +1,86 @@
+use multer::{Field, Multipart};
+
+use crate::{BYTES_MAX, Detection, Error, Pool, Remainder};
+
+/// Walks the fields of a [`multer::Multipart`] stream, performing detection on the content of
+/// each field as it's reached.
+///
+/// Each field is returned as a [`Remainder`] that replays the data buffered for detection,
+/// followed by the rest of the field, so the content can still be streamed elsewhere. The
+/// original [`Field`] (and its name, file name, and declared content type) is available through
+/// [`Remainder::get_ref`].
+///
+/// ```no_run
+/// # async fn upload(multipart: multer::Multipart<'static>) -> Result<(), mojique::Error> {
+/// use mojique::{Config, DefaultConfig, Flag, SniffFields};
+///
+/// let pool = DefaultConfig::default().set_flag(Flag::MimeType).build_pool()?;
+/// let mut fields = SniffFields::new(pool, multipart).with_budget(64 * 1024);
+/// while let Some((field, detection)) = fields.next_field().await? {
+///     println!("{:?}: {detection}", field.get_ref().name());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SniffFields<'r> {
+    budget: usize,
+    multipart: Multipart<'r>,
+    pool: Pool,
+}
+
+impl<'r> SniffFields<'r> {
+    /// Creates a new multipart walker that performs detection using the given pool.
+    ///
+    /// By default, up to [`BYTES_MAX`] bytes of each field are used for detection.
+    pub fn new(pool: Pool, multipart: Multipart<'r>) -> Self {
+        Self {
+            budget: BYTES_MAX,
+            multipart,
+            pool,
+        }
+    }
+
+    /// Sets the number of bytes of each field that are buffered and used for detection.
+    ///
+    /// Whole chunks are buffered until at least `budget` bytes have been read or the field ends,
+    /// and detection is then performed on the first `budget` bytes. Budgets larger than
+    /// [`BYTES_MAX`] don't improve detection, since libmagic only examines that much of a buffer.
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Returns the next field, along with the result of detection on its content, or `None` if
+    /// there are no more fields.
+    ///
+    /// As with [`Multipart::next_field`], the previous field must be dropped before this is
+    /// called. Errors from the multipart stream are returned as [`Error::ReadInput`].
+    pub async fn next_field(&mut self) -> Result<Option<(Remainder<Field<'r>>, Detection)>, Error> {
+        let Some(mut field) = self.multipart.next_field().await.map_err(read_input)? else {
+            return Ok(None);
+        };
+
+        let prefix = crate::stream::read_prefix(&mut field, self.budget)
+            .await
+            .map_err(read_input)?;
+        let detection = self
+            .pool
+            .buffer_async(prefix.slice(..prefix.len().min(self.budget)))
+            .await?;
+
+        Ok(Some((
+            Remainder::new(Some(prefix), field),
+            detection.into(),
+        )))
+    }
+
+    /// Returns the underlying multipart stream.
+    pub fn into_inner(self) -> Multipart<'r> {
+        self.multipart
+    }
+}
+
+fn read_input(e: multer::Error) -> Error {
+    Error::ReadInput(std::io::Error::other(e))
+}
@@ -0,0 +1,195 @@
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{Detection, Error};
+
+/// Writes detection results as [JSON Lines](https://jsonlines.org/), with one object per line.
+///
+/// Each object has a `path` and a `description`, along with a `mime_type` that is `null` if the
+/// description isn't a MIME type. Detections that were only performed on a prefix of the input, as
+/// per [`Detection::is_prefix`], also have a `prefix` of `true`. Paths that aren't valid UTF-8 are
+/// converted lossily.
+///
+/// ```
+/// use mojique::{Detection, JsonLinesWriter};
+///
+/// let mut writer = JsonLinesWriter::new(Vec::new());
+/// writer.write("a.pdf".as_ref(), &Detection::new("application/pdf"))?;
+/// assert_eq!(
+///     String::from_utf8(writer.into_inner())?,
+///     "{\"path\":\"a.pdf\",\"description\":\"application/pdf\",\"mime_type\":\"application/pdf\"}\n"
+/// );
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug)]
+pub struct JsonLinesWriter<W> {
+    inner: W,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    /// Creates a new writer.
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    /// Writes a single result.
+    pub fn write(&mut self, path: &Path, detection: &Detection) -> io::Result<()> {
+        let mut line = String::from("{\"path\":");
+        push_json_string(&mut line, &path.to_string_lossy());
+        line.push_str(",\"description\":");
+        push_json_string(&mut line, detection.description());
+        line.push_str(",\"mime_type\":");
+        match detection.mime_type() {
+            Some(mime_type) => push_json_string(&mut line, mime_type),
+            None => line.push_str("null"),
+        }
+        if detection.is_prefix() {
+            line.push_str(",\"prefix\":true");
+        }
+        line.push_str("}\n");
+
+        self.inner.write_all(line.as_bytes())
+    }
+
+    /// Writes a single error, as an object with a `path` and an `error` message.
+    pub fn write_error(&mut self, path: &Path, error: &Error) -> io::Result<()> {
+        let mut line = String::from("{\"path\":");
+        push_json_string(&mut line, &path.to_string_lossy());
+        line.push_str(",\"error\":");
+        push_json_string(&mut line, &error.to_string());
+        line.push_str("}\n");
+
+        self.inner.write_all(line.as_bytes())
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Writes detection results as CSV, as described by
+/// [RFC 4180](https://www.rfc-editor.org/rfc/rfc4180).
+///
+/// Each record has `path`, `description`, `mime_type`, and `prefix` fields, with `mime_type` left
+/// empty if the description isn't a MIME type, and `prefix` being `true` if the detection was only
+/// performed on a prefix of the input, as per [`Detection::is_prefix`], or `false` otherwise. By
+/// default, a header record is written before the first result. Paths that aren't valid UTF-8 are
+/// converted lossily.
+///
+/// ```
+/// use mojique::{CsvWriter, Detection};
+///
+/// let mut writer = CsvWriter::new(Vec::new());
+/// writer.write("a, b.txt".as_ref(), &Detection::new("ASCII text"))?;
+/// assert_eq!(
+///     String::from_utf8(writer.into_inner())?,
+///     "path,description,mime_type,prefix\r\n\"a, b.txt\",ASCII text,,false\r\n"
+/// );
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug)]
+pub struct CsvWriter<W> {
+    header: bool,
+    inner: W,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Creates a new writer.
+    pub fn new(inner: W) -> Self {
+        Self {
+            header: true,
+            inner,
+        }
+    }
+
+    /// Sets whether a header record is written before the first result.
+    ///
+    /// This defaults to `true`.
+    pub fn with_header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Writes a single result.
+    pub fn write(&mut self, path: &Path, detection: &Detection) -> io::Result<()> {
+        let mut record = String::new();
+        if std::mem::take(&mut self.header) {
+            record.push_str("path,description,mime_type,prefix\r\n");
+        }
+
+        push_csv_field(&mut record, &path.to_string_lossy());
+        record.push(',');
+        push_csv_field(&mut record, detection.description());
+        record.push(',');
+        push_csv_field(&mut record, detection.mime_type().unwrap_or_default());
+        record.push(',');
+        record.push_str(if detection.is_prefix() {
+            "true"
+        } else {
+            "false"
+        });
+        record.push_str("\r\n");
+
+        self.inner.write_all(record.as_bytes())
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+fn push_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn push_csv_field(out: &mut String, s: &str) {
+    if s.contains([',', '"', '\r', '\n']) {
+        out.push('"');
+        out.push_str(&s.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        let mut json = String::new();
+        push_json_string(&mut json, "a \"b\"\\\n\u{1}");
+        assert_eq!(json, r#""a \"b\"\\\n\u0001""#);
+
+        let mut csv = String::new();
+        push_csv_field(&mut csv, "a \"b\"\n");
+        assert_eq!(csv, "\"a \"\"b\"\"\n\"");
+    }
+}
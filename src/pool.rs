@@ -1,14 +1,24 @@
 use std::{
-    ffi::{CString, c_int, c_void},
+    cell::RefCell,
+    collections::VecDeque,
+    ffi::{CStr, CString, OsStr, c_int, c_void},
     fmt::Debug,
-    sync::{Arc, Mutex},
+    ops::{Deref, DerefMut},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, PoisonError, Weak,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
-use magic_sys::*;
-
 use crate::{
-    Error,
+    CheckWarning, Database, Error, check,
+    config::{default_database_base, join_paths},
     handle::{Cookie, Handle},
+    instrument,
+    sys::{self, magic_load, magic_load_buffers},
 };
 
 /// A thread-safe pool of [`Handle`] instances.
@@ -22,54 +32,1036 @@ pub struct Pool(Arc<Inner>);
 struct Inner {
     flags: c_int,
     source: Source,
+    options: PoolOptions,
 
     // This is about the stupidest possible way of implementing a free pool of handles, but it does
-    // work. We'll keep a reference to the reservoir in each handle, and then hand the cookie
+    // work. We'll keep a reference to the pool in each PooledHandle, and then hand the cookie
     // within the handle back to the reservoir on Drop.
-    reservoir: Arc<Mutex<Reservoir>>,
+    reservoir: Mutex<Reservoir>,
+
+    // Notified whenever a handle is returned to the pool or a handle creation finishes, which
+    // allows Pool::close_and_wait() and callers waiting on a creation slot to wake up.
+    changed: Condvar,
+
+    // The same, but for async callers waiting for capacity, which shouldn't tie up a thread to
+    // wait on the condition variable.
+    #[cfg(feature = "tower")]
+    capacity: tokio::sync::Notify,
+
+    // Incremented each time the pool is reloaded. Handles created before a reload are discarded
+    // rather than being reused.
+    generation: AtomicU64,
+}
+
+impl Inner {
+    /// Reports the number of idle handles in the reservoir, if it has changed.
+    fn idle_changed(&self, reservoir: &mut Reservoir) {
+        let idle = reservoir.unused.len();
+        instrument::idle_handles(self.options.name.as_deref(), reservoir.reported_idle, idle);
+        reservoir.reported_idle = idle;
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // The idle handles are about to be dropped along with the reservoir.
+        let reservoir = self
+            .reservoir
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        instrument::idle_handles(self.options.name.as_deref(), reservoir.reported_idle, 0);
+    }
 }
 
 impl Pool {
-    pub(crate) fn new(flags: c_int, source: Source) -> Result<Self, Error> {
-        Ok(Self(Arc::new(Inner {
+    pub(crate) fn new(flags: c_int, source: Source, options: PoolOptions) -> Result<Self, Error> {
+        let idle_timeout = options.idle_timeout;
+        let pool = Self(Arc::new(Inner {
             flags,
             source,
+            options,
             reservoir: Default::default(),
-        })))
+            changed: Default::default(),
+            #[cfg(feature = "tower")]
+            capacity: Default::default(),
+            generation: Default::default(),
+        }));
+
+        if let Some(idle_timeout) = idle_timeout {
+            spawn_reaper(pool.downgrade(), idle_timeout)?;
+        }
+
+        Ok(pool)
+    }
+
+    /// Creates a [`WeakPool`] that refers to this pool without keeping it alive.
+    pub fn downgrade(&self) -> WeakPool {
+        WeakPool(Arc::downgrade(&self.0))
     }
 
-    /// Returns a [`Handle`], instantiating a new one if necessary.
+    /// Returns a [`PooledHandle`], instantiating a new handle if necessary.
+    ///
+    /// The handle will be returned to the pool when the [`PooledHandle`] is dropped.
     ///
     /// Users of async runtimes may want to consider running this on a blocking task, as loading
     /// and parsing a database — especially from disk — may cause significant blocking.
-    pub fn handle(&self) -> Result<Handle, Error> {
+    ///
+    /// Once the pool has been closed, this will return [`Error::PoolClosed`].
+    pub fn handle(&self) -> Result<PooledHandle, Error> {
+        let mut reservoir = self.0.reservoir.lock()?;
+
+        // Cookies may have expired while sitting idle, in which case we'll discard them once we're
+        // no longer holding the lock.
+        let mut expired = Vec::new();
+        let reused = loop {
+            if reservoir.closed {
+                return Err(Error::PoolClosed);
+            }
+
+            let entry = match self.0.options.order {
+                PoolOrder::Lifo => reservoir.unused.pop_back(),
+                PoolOrder::Fifo => reservoir.unused.pop_front(),
+            };
+
+            match entry {
+                Some((cookie, usage)) if self.0.options.is_expired(&usage) => {
+                    instrument::handle_discarded("expired while idle");
+                    expired.push(cookie);
+                }
+                Some(entry) => break Some(entry),
+                None if self.0.options.can_create(&reservoir) => break None,
+                // Wait for either a handle to be returned, or another creation to finish.
+                None => reservoir = self.0.changed.wait(reservoir)?,
+            }
+        };
+
+        reservoir.outstanding += 1;
+        if reused.is_none() {
+            reservoir.creating += 1;
+        }
+        // This is read while the lock is held, since reloads update the generation while holding
+        // it, so a handle created concurrently with a reload is treated as stale.
+        let generation = self.0.generation.load(Ordering::Acquire);
+        self.0.idle_changed(&mut reservoir);
+        drop(reservoir);
+        drop(expired);
+
+        let (handle, mut usage) = match reused {
+            Some((cookie, usage)) => {
+                instrument::handle_reused();
+                (self.0.options.configure(Handle::new(cookie)), usage)
+            }
+            None => {
+                let start = Instant::now();
+                let result = self.0.source.create_handle(self.0.flags);
+                instrument::handle_created(start.elapsed());
+                self.creation_finished();
+
+                (
+                    self.0
+                        .options
+                        .configure(result.inspect_err(|_| self.release(None, true))?),
+                    Usage::new(generation),
+                )
+            }
+        };
+        usage.uses += 1;
+
+        Ok(PooledHandle {
+            handle: Some(handle),
+            usage,
+            pool: self.clone(),
+        })
+    }
+
+    /// Returns a textual description of the given buffer, using a handle acquired from the pool.
+    ///
+    /// This is equivalent to calling [`Handle::buffer`] on a handle returned from
+    /// [`Pool::handle`]; the handle is returned to the pool afterwards regardless of the result.
+    pub fn buffer(&self, buf: &[u8]) -> Result<String, Error> {
+        self.handle()?.buffer(buf)
+    }
+
+    /// Returns a textual description of the given file, using a handle acquired from the pool.
+    ///
+    /// This is equivalent to calling [`Handle::file`] on a handle returned from [`Pool::handle`];
+    /// the handle is returned to the pool afterwards regardless of the result.
+    pub fn file(&self, path: impl AsRef<Path>) -> Result<String, Error> {
+        self.handle()?.file(path)
+    }
+
+    /// Returns a textual description of the given buffer, without blocking the async runtime.
+    ///
+    /// Acquiring a handle may block while waiting for one to become available or while a new
+    /// handle loads its magic database, so both that and the detection itself are performed via
+    /// [`tokio::task::spawn_blocking`]. This must therefore be called within a Tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub async fn buffer_async(
+        &self,
+        buf: impl AsRef<[u8]> + Send + 'static,
+    ) -> Result<String, Error> {
+        let pool = self.clone();
+        tokio::task::spawn_blocking(move || pool.buffer(buf.as_ref()))
+            .await
+            .map_err(|_| Error::TaskJoin)?
+    }
+
+    /// Returns a textual description of the given file, without blocking the async runtime.
+    ///
+    /// As with [`Pool::buffer_async`], acquiring the handle and the detection itself are both
+    /// performed via [`tokio::task::spawn_blocking`].
+    #[cfg(feature = "tokio")]
+    pub async fn file_async(&self, path: impl AsRef<Path>) -> Result<String, Error> {
+        let pool = self.clone();
+        let path = path.as_ref().to_path_buf();
+        tokio::task::spawn_blocking(move || pool.file(path))
+            .await
+            .map_err(|_| Error::TaskJoin)?
+    }
+
+    /// Acquires a handle from the pool, without blocking the async runtime.
+    ///
+    /// Acquiring a handle may block while waiting for one to become available or while a new
+    /// handle loads its magic database, so this is performed on the thread pool used by
+    /// [`blocking::unblock`]. This works with any async runtime, including `async-std` and `smol`.
+    #[cfg(feature = "smol")]
+    pub async fn handle_unblock(&self) -> Result<PooledHandle, Error> {
+        let pool = self.clone();
+        blocking::unblock(move || pool.handle()).await
+    }
+
+    /// Returns a textual description of the given buffer, without blocking the async runtime.
+    ///
+    /// This is the equivalent of `Pool::buffer_async` for applications using `async-std` or
+    /// `smol`: acquiring the handle and the detection itself are both performed via
+    /// [`blocking::unblock`].
+    #[cfg(feature = "smol")]
+    pub async fn buffer_unblock(
+        &self,
+        buf: impl AsRef<[u8]> + Send + 'static,
+    ) -> Result<String, Error> {
+        let pool = self.clone();
+        blocking::unblock(move || pool.buffer(buf.as_ref())).await
+    }
+
+    /// Returns a textual description of the given file, without blocking the async runtime.
+    ///
+    /// This is the equivalent of `Pool::file_async` for applications using `async-std` or
+    /// `smol`: acquiring the handle and the detection itself are both performed via
+    /// [`blocking::unblock`].
+    #[cfg(feature = "smol")]
+    pub async fn file_unblock(&self, path: impl AsRef<Path>) -> Result<String, Error> {
+        let pool = self.clone();
+        let path = path.as_ref().to_path_buf();
+        blocking::unblock(move || pool.file(path)).await
+    }
+
+    /// Performs detection on the start of a stream of [`Bytes`][bytes::Bytes], without blocking
+    /// the async runtime, and returns a stream that replays the entire input.
+    ///
+    /// Whole chunks are read until at least [`BYTES_MAX`][crate::BYTES_MAX] bytes have been
+    /// buffered or the stream ends, and then detection is performed as per
+    /// [`Pool::buffer_async`]. The returned [`Remainder`][crate::Remainder] yields the buffered
+    /// data, followed by the rest of the stream, so the input can be forwarded after it has been
+    /// classified.
+    ///
+    /// Errors from the stream are returned as [`Error::ReadInput`].
+    #[cfg(all(feature = "bytes", feature = "tokio"))]
+    pub async fn sniff_stream<S, E>(
+        &self,
+        mut stream: S,
+    ) -> Result<(crate::Detection, crate::Remainder<S>), Error>
+    where
+        S: futures_core::Stream<Item = Result<bytes::Bytes, E>> + Unpin,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let prefix = crate::stream::read_prefix(&mut stream, crate::BYTES_MAX)
+            .await
+            .map_err(|e| Error::ReadInput(std::io::Error::other(e)))?;
+        let detection = self.buffer_async(prefix.clone()).await?;
+
+        Ok((
+            detection.into(),
+            crate::Remainder::new(Some(prefix), stream),
+        ))
+    }
+
+    /// Performs detection on the start of an [`http_body::Body`], without blocking the async
+    /// runtime, and returns a body that replays the entire input.
+    ///
+    /// This works with any body type from the [`http`][http_body] ecosystem, including hyper's
+    /// `Incoming`, and is intended for proxies that need to fill in or correct the `Content-Type`
+    /// of a response or request before forwarding it. Whole data frames are read until at least
+    /// [`BYTES_MAX`][crate::BYTES_MAX] bytes have been buffered or the body ends, and then
+    /// detection is performed as per [`Pool::buffer_async`].
+    ///
+    /// Errors from the body are returned as [`Error::ReadInput`].
+    #[cfg(feature = "http")]
+    pub async fn sniff_body<B>(
+        &self,
+        body: B,
+    ) -> Result<(crate::Detection, crate::ReplayBody<B>), Error>
+    where
+        B: http_body::Body + Unpin,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let (prefix, body) = crate::body::read_prefix(body)
+            .await
+            .map_err(|e| Error::ReadInput(std::io::Error::other(e)))?;
+        let detection = self.buffer_async(prefix).await?;
+
+        Ok((detection.into(), body))
+    }
+
+    /// Performs detection on the start of a [`reqwest::Response`] body, without blocking the async
+    /// runtime, and returns the response with a body that replays the entire input.
+    ///
+    /// Whole chunks of the body are read until at least [`BYTES_MAX`][crate::BYTES_MAX] bytes
+    /// have been buffered or the body ends, so only the minimal prefix of a streaming response is
+    /// read before detection is performed as per [`Pool::buffer_async`]. This is useful for
+    /// crawlers and link checkers, which need to know what a response actually contains rather
+    /// than what the server claims: the claimed `Content-Type` can be compared with
+    /// [`SniffedResponse::content_type_matches`][crate::SniffedResponse::content_type_matches].
+    ///
+    /// Errors from the body are returned as [`Error::ReadInput`].
+    #[cfg(feature = "reqwest")]
+    pub async fn sniff_response(
+        &self,
+        response: reqwest::Response,
+    ) -> Result<crate::SniffedResponse, Error> {
+        crate::response::sniff(self, response).await
+    }
+
+    /// Watches the magic databases that the pool was configured with, and calls [`Pool::reload`]
+    /// whenever they change, using the default [`WatchOptions`][crate::WatchOptions].
+    ///
+    /// Watching continues until the returned [`Watcher`][crate::Watcher] is dropped. The watcher
+    /// only holds a weak reference to the pool, so it doesn't keep the pool alive.
+    ///
+    /// If the compile cache is enabled, the compiled databases within the cache are watched,
+    /// rather than the databases that they were compiled from. Pools built from buffers can't be
+    /// watched, and return [`Error::WatchBuffers`].
+    #[cfg(feature = "watch")]
+    pub fn watch(&self) -> Result<crate::Watcher, Error> {
+        self.watch_with(crate::WatchOptions::default())
+    }
+
+    /// Watches the magic databases that the pool was configured with, as per [`Pool::watch`],
+    /// with the given options.
+    #[cfg(feature = "watch")]
+    pub fn watch_with(&self, options: crate::WatchOptions) -> Result<crate::Watcher, Error> {
+        crate::watch::watch(self.downgrade(), self.0.source.paths()?, options)
+    }
+
+    /// Invokes `f` with a [`Handle`] that is cached in a thread local for this pool, acquiring
+    /// one from the pool first if this thread doesn't already have one.
+    ///
+    /// This avoids the overhead of acquiring and returning a handle from the shared pool on each
+    /// call, which can be noticeable when many small detections are performed from a fixed set of
+    /// threads (for example, within a rayon thread pool).
+    ///
+    /// Cached handles don't count as being in use for the purposes of [`Pool::close_and_wait`],
+    /// and are only returned to the pool when the thread exits. If the pool is closed or dropped,
+    /// the cached handle is discarded on the next call to this method on that thread, or when the
+    /// thread exits, whichever happens first.
+    ///
+    /// Calling `local_handle` from within `f` for the same pool will acquire a second handle.
+    pub fn local_handle<F, R>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut Handle) -> Result<R, Error>,
+    {
+        // We take the cached handle out of the thread local while it's in use, rather than holding
+        // a borrow across the call to f, so that reentrant calls don't panic.
+        let cached = LOCAL_HANDLES.with_borrow_mut(|locals| {
+            locals.retain(|local| local.pool.strong_count() > 0);
+            locals
+                .iter()
+                .position(|local| std::ptr::eq(local.pool.as_ptr(), Arc::as_ptr(&self.0)))
+                .map(|i| locals.swap_remove(i))
+        });
+
+        let mut local = match cached {
+            Some(local) if !self.is_stale(&local.usage) && !self.is_closed()? => local,
+            _ => {
+                let pooled = self.handle()?;
+                let usage = pooled.usage;
+                LocalHandle {
+                    pool: Arc::downgrade(&self.0),
+                    handle: Some(pooled.detach()),
+                    usage,
+                }
+            }
+        };
+
+        let result = f(local.handle.as_mut().expect("local handle is present"));
+        local.usage.uses += 1;
+        LOCAL_HANDLES.with_borrow_mut(|locals| locals.push(local));
+
+        result
+    }
+
+    /// Closes the pool.
+    ///
+    /// Idle handles are dropped immediately, subsequent calls to [`Pool::handle`] will return
+    /// [`Error::PoolClosed`], and any handles that are still in use will be dropped when they are
+    /// returned, rather than being put back into the pool.
+    ///
+    /// Closing a pool affects all clones of the pool, and closing an already closed pool is a
+    /// no-op.
+    pub fn close(&self) -> Result<(), Error> {
+        let unused = {
+            let mut reservoir = self.0.reservoir.lock()?;
+            reservoir.closed = true;
+            let unused = std::mem::take(&mut reservoir.unused);
+            self.0.idle_changed(&mut reservoir);
+            unused
+        };
+
+        // Closing cookies isn't free, so let's do it without holding the lock.
+        drop(unused);
+
+        // Wake up anyone waiting for a handle so they can see that the pool is closed.
+        self.notify_changed();
+
+        Ok(())
+    }
+
+    /// Closes the pool as per [`Pool::close`], and then waits for any handles that are still in
+    /// use to be returned.
+    ///
+    /// If `timeout` is `None`, this will wait indefinitely. Returns `true` if all handles were
+    /// returned, or `false` if the timeout elapsed first.
+    pub fn close_and_wait(&self, timeout: Option<Duration>) -> Result<bool, Error> {
+        self.close()?;
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
         let mut reservoir = self.0.reservoir.lock()?;
+        while reservoir.outstanding > 0 {
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(false);
+                    }
+
+                    reservoir = self
+                        .0
+                        .changed
+                        .wait_timeout(reservoir, deadline - now)
+                        .map_err(|_| Error::PoolPoisoned)?
+                        .0;
+                }
+                None => {
+                    reservoir = self.0.changed.wait(reservoir)?;
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Returns `true` if a handle can currently be acquired without waiting for one to be
+    /// returned.
+    #[cfg(feature = "tower")]
+    pub(crate) fn has_capacity(&self) -> Result<bool, Error> {
+        let reservoir = self.0.reservoir.lock()?;
+        if reservoir.closed {
+            return Err(Error::PoolClosed);
+        }
+
+        Ok(self.0.options.has_capacity(&reservoir))
+    }
+
+    /// Waits until a handle can be acquired without waiting for one to be returned.
+    ///
+    /// This doesn't block a thread while waiting, so dropping the future stops waiting.
+    #[cfg(feature = "tower")]
+    pub(crate) async fn wait_for_capacity(&self) -> Result<(), Error> {
+        loop {
+            // The notification has to be enabled before checking the capacity, otherwise a handle
+            // returned in between would be missed.
+            let mut notified = std::pin::pin!(self.0.capacity.notified());
+            notified.as_mut().enable();
+
+            if self.has_capacity()? {
+                return Ok(());
+            }
+            notified.await;
+        }
+    }
+
+    /// Returns `true` if the pool has been closed.
+    pub fn is_closed(&self) -> Result<bool, Error> {
+        Ok(self.0.reservoir.lock()?.closed)
+    }
+
+    /// Returns the number of idle handles currently held by the pool.
+    pub fn idle_count(&self) -> Result<usize, Error> {
+        Ok(self.0.reservoir.lock()?.unused.len())
+    }
+
+    /// Returns the number of handles currently in use.
+    ///
+    /// This includes handles that are in the process of being created, but not handles that have
+    /// been [detached][PooledHandle::detach] or are cached by [`Pool::local_handle`].
+    pub fn in_use(&self) -> Result<usize, Error> {
+        Ok(self.0.reservoir.lock()?.outstanding)
+    }
+
+    /// Returns the maximum number of handles that may be in use at once, as set by
+    /// [`PoolOptions::max_size`], or `None` if the pool is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.0.options.max_size
+    }
 
-        if let Some(cookie) = reservoir.unused.pop() {
-            Ok(Handle::new(cookie, Some(self.0.reservoir.clone())))
-        } else {
-            // We don't need to hold the lock while we create a handle.
-            drop(reservoir);
+    /// Closes any idle handles that have been idle for at least `idle_for`, returning the number
+    /// of handles that were closed.
+    ///
+    /// This is called periodically if [`PoolOptions::idle_timeout`] is set, but may also be called
+    /// manually.
+    pub fn evict_idle(&self, idle_for: Duration) -> Result<usize, Error> {
+        let mut reservoir = self.0.reservoir.lock()?;
 
-            self.0
-                .source
-                .create_handle(self.0.flags, Some(self.0.reservoir.clone()))
+        // Cookies are always returned to the back of the reservoir, so regardless of the pool
+        // order, the front of the reservoir always has the cookie that has been idle the longest.
+        let mut evicted = Vec::new();
+        while reservoir
+            .unused
+            .front()
+            .is_some_and(|(_, usage)| usage.idle_since.elapsed() >= idle_for)
+        {
+            evicted.extend(reservoir.unused.pop_front());
+        }
+        self.0.idle_changed(&mut reservoir);
+        drop(reservoir);
+
+        instrument::handles_evicted(evicted.len());
+        Ok(evicted.len())
+    }
+
+    /// Reloads the magic database(s), replacing every handle in the pool.
+    ///
+    /// This is useful when the databases a pool was configured with have changed on disk, and is
+    /// called automatically by `Pool::watch` if the `watch` feature is enabled. A new handle is
+    /// created first, so if the databases can't be loaded, the error is returned and the existing
+    /// handles continue to be used. Otherwise, idle handles are closed immediately, and handles
+    /// that are currently in use continue to use the old databases until they're returned, at
+    /// which point they're discarded rather than being reused.
+    ///
+    /// Pools built from buffers are reloaded from the same buffers, so this has no visible effect.
+    /// Pools that share a database loaded from files, as per
+    /// [`FileConfig::with_shared_database`][crate::FileConfig::with_shared_database], read the
+    /// files again, and share the new database between subsequent handles.
+    pub fn reload(&self) -> Result<(), Error> {
+        let start = Instant::now();
+        let handle = self.0.source.reload_handle(self.0.flags)?;
+        instrument::handle_created(start.elapsed());
+        let cookie = handle.into_cookie().ok_or(Error::CookieNommed)?;
+
+        let mut reservoir = self.0.reservoir.lock()?;
+        if reservoir.closed {
+            return Err(Error::PoolClosed);
+        }
+        let generation = self.0.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        let stale = std::mem::take(&mut reservoir.unused);
+        reservoir.unused.push_back((cookie, Usage::new(generation)));
+        self.0.idle_changed(&mut reservoir);
+        drop(reservoir);
+
+        for _ in &stale {
+            instrument::handle_discarded("reloaded");
+        }
+        drop(stale);
+        self.notify_changed();
+
+        Ok(())
+    }
+
+    /// Accounts for a handle being returned to the pool, putting its cookie back into the
+    /// reservoir if the pool is still open and the cookie is still usable.
+    ///
+    /// `checked_out` should be `true` if the handle was counted as being in use.
+    fn release(&self, returned: Option<(Cookie, Usage)>, checked_out: bool) {
+        let returned = returned.and_then(|(mut cookie, usage)| {
+            if self.0.options.is_expired(&usage) {
+                instrument::handle_discarded("expired");
+                None
+            } else if self.0.options.validate_on_return && cookie.probe().is_err() {
+                instrument::handle_discarded("failed validation");
+                None
+            } else {
+                Some((cookie, usage))
+            }
+        });
+
+        // This is called when handles are dropped, which may be while a thread is panicking, so
+        // a poisoned lock is recovered rather than panicking again. The counts are still kept
+        // accurate, but the cookie is discarded rather than returned to the pool.
+        let (mut reservoir, poisoned) = self.lock_reservoir();
+        if checked_out {
+            reservoir.outstanding -= 1;
+        }
+        if let Some((cookie, mut usage)) = returned {
+            if reservoir.closed {
+                instrument::handle_discarded("pool closed");
+            } else if usage.generation != self.0.generation.load(Ordering::Acquire) {
+                instrument::handle_discarded("reloaded");
+            } else if poisoned {
+                instrument::handle_discarded("pool lock poisoned");
+            } else if self.0.options.is_full(&reservoir) {
+                instrument::handle_discarded("pool full");
+            } else {
+                usage.idle_since = Instant::now();
+                reservoir.unused.push_back((cookie, usage));
+            }
+        }
+        self.0.idle_changed(&mut reservoir);
+        drop(reservoir);
+
+        self.notify_changed();
+    }
+
+    /// Returns `true` if a handle with the given usage has expired, or was created before the
+    /// pool was last reloaded.
+    fn is_stale(&self, usage: &Usage) -> bool {
+        self.0.options.is_expired(usage)
+            || usage.generation != self.0.generation.load(Ordering::Acquire)
+    }
+
+    fn creation_finished(&self) {
+        self.lock_reservoir().0.creating -= 1;
+
+        self.notify_changed();
+    }
+
+    /// Wakes anything waiting for a handle to be returned or created, or the pool to be closed.
+    fn notify_changed(&self) {
+        self.0.changed.notify_all();
+        #[cfg(feature = "tower")]
+        self.0.capacity.notify_waiters();
+    }
+
+    /// Locks the reservoir, recovering it if the lock is poisoned.
+    ///
+    /// The returned boolean is `true` if the lock was poisoned.
+    fn lock_reservoir(&self) -> (MutexGuard<'_, Reservoir>, bool) {
+        match self.0.reservoir.lock() {
+            Ok(reservoir) => (reservoir, false),
+            Err(e) => (e.into_inner(), true),
         }
     }
 }
 
 impl Debug for Pool {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Pool")
-            .field("flags", &self.0.flags)
+        let mut f = f.debug_struct("Pool");
+        f.field("flags", &self.0.flags)
             .field("source", &self.0.source)
+            .field("options", &self.0.options);
+
+        if let Ok(reservoir) = self.0.reservoir.lock() {
+            f.field("idle_count", &reservoir.unused.len())
+                .field("in_use", &reservoir.outstanding);
+        }
+
+        f.field("capacity", &self.0.options.max_size).finish()
+    }
+}
+
+/// A weak reference to a [`Pool`], created with [`Pool::downgrade`].
+///
+/// This is useful for background tasks that need to refer to a pool, but shouldn't keep the pool
+/// (and all of its handles and databases) alive once the rest of the application is done with it.
+#[derive(Clone)]
+pub struct WeakPool(Weak<Inner>);
+
+impl WeakPool {
+    /// Attempts to upgrade to a [`Pool`], returning `None` if the pool has since been dropped.
+    pub fn upgrade(&self) -> Option<Pool> {
+        self.0.upgrade().map(Pool)
+    }
+}
+
+impl Debug for WeakPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeakPool")
+            .field("alive", &(self.0.strong_count() > 0))
+            .finish()
+    }
+}
+
+/// Options controlling the behaviour of a [`Pool`].
+///
+/// These can be provided to [`Config::build_pool_with_options`][crate::Config::build_pool_with_options].
+#[derive(Debug, Clone, Default)]
+pub struct PoolOptions {
+    idle_timeout: Option<Duration>,
+    max_concurrent_creations: Option<usize>,
+    max_cookie_age: Option<Duration>,
+    max_cookie_uses: Option<usize>,
+    max_size: Option<usize>,
+    name: Option<String>,
+    order: PoolOrder,
+    prefix_read: bool,
+    read_buffer_size: Option<usize>,
+    validate_on_return: bool,
+}
+
+impl PoolOptions {
+    /// Sets the maximum time a handle may sit idle in the pool before being closed.
+    ///
+    /// If set, a background thread will be spawned that periodically closes handles that have
+    /// been idle for longer than this, which allows the pool to shrink again after a spike in
+    /// traffic. The thread exits once the pool is closed or dropped. By default, idle handles are
+    /// kept indefinitely.
+    ///
+    /// If the `tokio` feature is enabled and the pool is built within a tokio runtime, a task is
+    /// spawned on that runtime instead of a thread, which also exits if the runtime shuts down.
+    /// Runtimes without their time driver enabled can't run the task, so a thread is used for
+    /// those as well.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum number of handles that may be created concurrently.
+    ///
+    /// When a pool without idle handles receives a burst of requests, each caller would otherwise
+    /// load the magic database simultaneously. With this set, callers beyond the limit instead
+    /// wait for either an existing creation to finish or a handle to be returned to the pool. By
+    /// default, creation is unlimited.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn max_concurrent_creations(mut self, max: usize) -> Self {
+        assert!(max > 0, "max_concurrent_creations must be non-zero");
+        self.max_concurrent_creations = Some(max);
+        self
+    }
+
+    /// Sets the maximum age of a handle, after which it will be closed and replaced with a newly
+    /// created handle rather than being reused.
+    ///
+    /// This bounds the impact of any slow memory growth within libmagic in long running
+    /// processes. By default, handles are reused indefinitely.
+    pub fn max_cookie_age(mut self, age: Duration) -> Self {
+        self.max_cookie_age = Some(age);
+        self
+    }
+
+    /// Sets the maximum number of times a handle will be acquired from the pool, after which it
+    /// will be closed and replaced with a newly created handle rather than being reused.
+    ///
+    /// By default, handles are reused indefinitely.
+    pub fn max_cookie_uses(mut self, uses: usize) -> Self {
+        self.max_cookie_uses = Some(uses);
+        self
+    }
+
+    /// Sets the maximum number of handles that may be in use at once.
+    ///
+    /// Once this many handles are in use, [`Pool::handle`] will block until a handle is returned
+    /// to the pool. Handles that have been [detached][PooledHandle::detach] or are cached by
+    /// [`Pool::local_handle`] don't count towards this limit. By default, pools are unbounded.
+    ///
+    /// The pool never holds more handles than this, whether idle or in use: if a handle is
+    /// returned when the pool is already full, such as when a thread with a handle cached by
+    /// [`Pool::local_handle`] exits, that handle is closed rather than being kept idle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn max_size(mut self, max: usize) -> Self {
+        assert!(max > 0, "max_size must be non-zero");
+        self.max_size = Some(max);
+        self
+    }
+
+    /// Sets the name of the pool.
+    ///
+    /// This is only used to label the `mojique_pool_idle_handles` gauge emitted if the `metrics`
+    /// feature is enabled, which allows the idle handles of each pool to be told apart. Unnamed
+    /// pools share a single unlabelled gauge, which records the total across all of them.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the order in which idle handles are reused.
+    ///
+    /// This defaults to [`PoolOrder::Lifo`].
+    pub fn order(mut self, order: PoolOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Sets whether [`Handle::file`] reads the start of regular files itself, as per
+    /// [`Handle::set_prefix_read`], for every handle acquired from the pool.
+    ///
+    /// This also applies to detection performed via the pool by other types, such as
+    /// `Scanner`. This defaults to `false`.
+    pub fn prefix_read(mut self, prefix_read: bool) -> Self {
+        self.prefix_read = prefix_read;
+        self
+    }
+
+    /// Sets the size of the chunks that [`Handle::read`] copies data in, as per
+    /// [`Handle::set_read_buffer_size`], for every handle acquired from the pool.
+    ///
+    /// The buffer is kept with each handle while it's idle, so it's only allocated once per
+    /// handle rather than on each read. This defaults to 64 KiB.
+    pub fn read_buffer_size(mut self, size: usize) -> Self {
+        self.read_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets whether handles are validated before being returned to the pool.
+    ///
+    /// If enabled, each handle is used to identify an empty buffer when it is returned, and is
+    /// discarded instead of being reused if that fails. This guards against a handle that has been
+    /// left in a bad state being handed to the next caller, at the cost of an extra (cheap) libmagic
+    /// call each time a handle is returned.
+    ///
+    /// This defaults to `false`.
+    pub fn validate_on_return(mut self, validate: bool) -> Self {
+        self.validate_on_return = validate;
+        self
+    }
+
+    /// Applies the options that affect handles themselves to a newly acquired handle.
+    fn configure(&self, mut handle: Handle) -> Handle {
+        handle.set_prefix_read(self.prefix_read);
+        if let Some(size) = self.read_buffer_size {
+            handle.set_read_buffer_size(size);
+        }
+
+        handle
+    }
+
+    fn can_create(&self, reservoir: &Reservoir) -> bool {
+        self.max_concurrent_creations
+            .is_none_or(|max| reservoir.creating < max)
+            && self.max_size.is_none_or(|max| reservoir.outstanding < max)
+    }
+
+    #[cfg(feature = "tower")]
+    fn has_capacity(&self, reservoir: &Reservoir) -> bool {
+        !reservoir.unused.is_empty() || self.can_create(reservoir)
+    }
+
+    /// Returns `true` if the pool already holds as many handles as [`PoolOptions::max_size`]
+    /// allows, counting both idle handles and those in use.
+    fn is_full(&self, reservoir: &Reservoir) -> bool {
+        self.max_size
+            .is_some_and(|max| reservoir.unused.len() + reservoir.outstanding >= max)
+    }
+
+    fn is_expired(&self, usage: &Usage) -> bool {
+        self.max_cookie_age
+            .is_some_and(|age| usage.created.elapsed() >= age)
+            || self.max_cookie_uses.is_some_and(|uses| usage.uses >= uses)
+    }
+}
+
+/// The order in which a [`Pool`] reuses idle handles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PoolOrder {
+    /// The most recently returned handle is reused first.
+    ///
+    /// This tends to keep a small number of handles in use, which leaves the rest idle for long
+    /// enough to be evicted by [`PoolOptions::idle_timeout`].
+    #[default]
+    Lifo,
+
+    /// The least recently returned handle is reused first.
+    ///
+    /// This cycles through all handles evenly, which pairs well with
+    /// [`PoolOptions::max_cookie_age`] and [`PoolOptions::max_cookie_uses`].
+    Fifo,
+}
+
+/// A [`Handle`] that has been acquired from a [`Pool`], and which will be returned to that pool
+/// when dropped.
+///
+/// `PooledHandle` dereferences to [`Handle`], so all detection methods are available directly.
+pub struct PooledHandle {
+    // This is only ever None once the handle has been detached or dropped.
+    handle: Option<Handle>,
+    usage: Usage,
+    pool: Pool,
+}
+
+impl PooledHandle {
+    /// Detaches the handle from its pool, returning a free-standing [`Handle`].
+    ///
+    /// The handle will no longer be returned to the pool when dropped, and won't count towards
+    /// the handles that [`Pool::close_and_wait`] waits for.
+    pub fn detach(mut self) -> Handle {
+        self.handle
+            .take()
+            .expect("pooled handle is present until dropped")
+    }
+}
+
+impl Debug for PooledHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PooledHandle")
+            .field("handle", &self.handle)
             .finish()
     }
 }
 
+impl Deref for PooledHandle {
+    type Target = Handle;
+
+    fn deref(&self) -> &Self::Target {
+        self.handle
+            .as_ref()
+            .expect("pooled handle is present until dropped")
+    }
+}
+
+impl DerefMut for PooledHandle {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.handle
+            .as_mut()
+            .expect("pooled handle is present until dropped")
+    }
+}
+
+impl Drop for PooledHandle {
+    fn drop(&mut self) {
+        self.pool.release(
+            self.handle
+                .take()
+                .and_then(Handle::into_cookie)
+                .map(|cookie| (cookie, self.usage)),
+            true,
+        );
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct Reservoir {
-    pub(crate) unused: Vec<Cookie>,
+    unused: VecDeque<(Cookie, Usage)>,
+    outstanding: usize,
+    creating: usize,
+    closed: bool,
+
+    // The number of idle handles last reported to the instrumentation hooks.
+    reported_idle: usize,
+}
+
+/// Tracks how long a cookie has existed and how often it has been used, for the purposes of
+/// recycling.
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    created: Instant,
+    idle_since: Instant,
+    uses: usize,
+    generation: u64,
+}
+
+impl Usage {
+    fn new(generation: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            created: now,
+            idle_since: now,
+            uses: 0,
+            generation,
+        }
+    }
+}
+
+thread_local! {
+    static LOCAL_HANDLES: RefCell<Vec<LocalHandle>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A handle cached by [`Pool::local_handle`].
+///
+/// This only holds a weak reference to the pool, since thread locals may live considerably longer
+/// than the pool itself.
+struct LocalHandle {
+    pool: Weak<Inner>,
+    // This is only ever None once the handle has been dropped.
+    handle: Option<Handle>,
+    usage: Usage,
+}
+
+impl Drop for LocalHandle {
+    fn drop(&mut self) {
+        if let Some(inner) = self.pool.upgrade()
+            && let Some(cookie) = self.handle.take().and_then(Handle::into_cookie)
+        {
+            Pool(inner).release(Some((cookie, self.usage)), false);
+        }
+    }
+}
+
+/// Creates the interval that the reaper task ticks on, or returns `None` if the current tokio
+/// runtime doesn't have its time driver enabled.
+///
+/// tokio doesn't provide a way to check for the time driver, and instead panics when a timer is
+/// created without it, so the panic is caught here. This has to happen while the pool is being
+/// built, since a panic within the task would only be seen once it's too late to use a thread.
+#[cfg(feature = "tokio")]
+fn tokio_interval(interval: Duration) -> Option<tokio::time::Interval> {
+    std::panic::catch_unwind(|| {
+        let start = tokio::time::Instant::now() + interval;
+        let mut ticks = tokio::time::interval_at(start, interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticks
+    })
+    .ok()
+}
+
+fn spawn_reaper(pool: WeakPool, idle_timeout: Duration) -> Result<(), Error> {
+    // There's no need to be particularly precise here: checking twice per timeout period means
+    // that no handle will live for more than 1.5x the timeout once it's idle.
+    let interval = (idle_timeout / 2).max(Duration::from_millis(1));
+
+    // Within a tokio runtime, there's no need to tie up a thread just to sleep most of the time.
+    #[cfg(feature = "tokio")]
+    if let Ok(runtime) = tokio::runtime::Handle::try_current()
+        && let Some(mut ticks) = tokio_interval(interval)
+    {
+        runtime.spawn(async move {
+            loop {
+                ticks.tick().await;
+                if !reap(&pool, idle_timeout) {
+                    break;
+                }
+            }
+        });
+        return Ok(());
+    }
+
+    std::thread::Builder::new()
+        .name("mojique-reaper".into())
+        .spawn(move || {
+            loop {
+                std::thread::sleep(interval);
+                if !reap(&pool, idle_timeout) {
+                    break;
+                }
+            }
+        })
+        .map_err(Error::ReaperSpawn)?;
+
+    Ok(())
+}
+
+/// Evicts handles that have been idle for longer than the timeout, returning `false` once the
+/// pool has been closed or dropped and the reaper should exit.
+fn reap(pool: &WeakPool, idle_timeout: Duration) -> bool {
+    // Only hold a strong reference while we're actually doing something, otherwise the reaper
+    // would keep the pool alive forever.
+    let Some(pool) = pool.upgrade() else {
+        return false;
+    };
+    matches!(pool.is_closed(), Ok(false)) && pool.evict_idle(idle_timeout).is_ok()
 }
 
 #[derive(Debug)]
@@ -77,36 +1069,230 @@ pub(crate) enum Source {
     Default,
     Buffers(Buffers),
     Files(CString),
+    Shared(SharedFiles),
 }
 
 impl Source {
-    pub(crate) fn create_handle(
+    pub(crate) fn create_handle(&self, flags: c_int) -> Result<Handle, Error> {
+        self.open(flags, |cookie| {
+            match &self {
+                Source::Buffers(buffers) => load_buffers(cookie, buffers)?,
+                Source::Files(filename) => {
+                    cookie
+                        .raw(|cookie| unsafe { magic_load(cookie, filename.as_ptr()) })
+                        .map_err(|e| diagnose_load(flags, filename, e))?;
+                }
+                Source::Shared(shared) => {
+                    let buffers = shared.buffers()?;
+                    load_buffers(cookie, &buffers)?;
+                }
+                Source::Default => {
+                    cookie
+                        .raw(|cookie| unsafe { magic_load(cookie, std::ptr::null()) })
+                        .map_err(load_failed)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Creates a handle for [`Pool::reload`], which reads shared databases from disk again.
+    ///
+    /// The new database only replaces the shared one once a handle has loaded it successfully, so
+    /// a broken database doesn't affect handles created afterwards.
+    fn reload_handle(&self, flags: c_int) -> Result<Handle, Error> {
+        let Source::Shared(shared) = self else {
+            return self.create_handle(flags);
+        };
+
+        let buffers = Arc::new(shared.load()?);
+        let handle = self.open(flags, |cookie| load_buffers(cookie, &buffers))?;
+        shared.replace(buffers);
+
+        Ok(handle)
+    }
+
+    /// Opens a cookie, and then loads a database into it with `load`.
+    fn open(
         &self,
         flags: c_int,
-        reservoir: Option<Arc<Mutex<Reservoir>>>,
+        load: impl FnOnce(&mut Cookie) -> Result<(), Error>,
     ) -> Result<Handle, Error> {
-        let mut cookie = Cookie::try_from(unsafe { magic_open(flags) })?;
+        let mut cookie = Cookie::open(flags)?;
+        let start = Instant::now();
+        load(&mut cookie)?;
+        instrument::database_loaded(self.kind(), start.elapsed());
 
-        match &self {
-            Source::Buffers(buffers) => {
-                cookie.raw(|cookie| unsafe {
-                    magic_load_buffers(cookie, buffers.buffers(), buffers.sizes(), buffers.len())
-                })?;
-            }
-            Source::Files(filename) => {
-                cookie.raw(|cookie| unsafe { magic_load(cookie, filename.as_ptr()) })?;
-            }
+        Ok(Handle::new(cookie))
+    }
+
+    /// Returns the paths of the magic databases that handles are loaded from.
+    #[cfg(feature = "watch")]
+    fn paths(&self) -> Result<Vec<PathBuf>, Error> {
+        let paths = match self {
             Source::Default => {
-                cookie.raw(|cookie| unsafe { magic_load(cookie, std::ptr::null()) })?;
+                crate::config::raw_default_database_path().ok_or(Error::NoDefaultDatabase)?
             }
+            Source::Buffers(_) => return Err(Error::WatchBuffers),
+            Source::Files(filename) => filename.clone(),
+            Source::Shared(shared) => shared.filename()?,
+        };
+
+        Ok(paths
+            .to_bytes()
+            .split(|b| *b == b':')
+            .filter(|path| !path.is_empty())
+            .map(|path| PathBuf::from(OsStr::from_bytes(path)))
+            .collect())
+    }
+
+    /// Returns a short description of the source, for use in log messages.
+    fn kind(&self) -> &'static str {
+        match self {
+            Source::Default => "default",
+            Source::Buffers(_) => "buffered",
+            Source::Files(_) => "file",
+            Source::Shared(_) => "shared",
+        }
+    }
+
+    pub(crate) fn check(&self, flags: c_int) -> Result<Vec<CheckWarning>, Error> {
+        let mut cookie = Cookie::open(flags)?;
+
+        match &self {
+            Source::Buffers(_) => Err(Error::CheckBuffers),
+            Source::Files(filename) => check::check(&mut cookie, Some(filename)),
+            Source::Shared(shared) => check::check(&mut cookie, Some(&shared.filename()?)),
+            Source::Default => check::check(&mut cookie, None),
         }
+    }
+}
 
-        Ok(Handle::new(cookie, reservoir))
+fn load_buffers(cookie: &mut Cookie, buffers: &Buffers) -> Result<(), Error> {
+    sys::require("magic_load_buffers").map_err(load_failed)?;
+    cookie
+        .raw(|cookie| unsafe {
+            magic_load_buffers(cookie, buffers.buffers(), buffers.sizes(), buffers.len())
+        })
+        .map_err(load_failed)?;
+    cookie.retain_buffers(&buffers.storage);
+
+    Ok(())
+}
+
+/// Wraps an error from libmagic while loading a database, so that it's classified as a database
+/// error rather than by its `errno`.
+fn load_failed(error: Error) -> Error {
+    Error::Load {
+        source: Box::new(error),
+    }
+}
+
+/// Magic databases on the filesystem that are loaded into memory when the first handle is
+/// created, and then shared between every handle until the pool is reloaded.
+#[derive(Debug)]
+pub(crate) struct SharedFiles {
+    default_database: bool,
+    paths: Vec<PathBuf>,
+
+    // Held while the databases are loaded, so that handles created concurrently with the first
+    // don't all load them. Loading can't panic, so a poisoned lock is safe to recover.
+    loaded: Mutex<Option<Arc<Buffers>>>,
+}
+
+impl SharedFiles {
+    pub(crate) fn new(default_database: bool, paths: Vec<PathBuf>) -> Self {
+        Self {
+            default_database,
+            paths,
+            loaded: Mutex::default(),
+        }
+    }
+
+    /// Returns the shared databases, loading them first if necessary.
+    fn buffers(&self) -> Result<Arc<Buffers>, Error> {
+        let mut loaded = self.loaded.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(buffers) = &*loaded {
+            return Ok(buffers.clone());
+        }
+
+        let buffers = Arc::new(self.load()?);
+        *loaded = Some(buffers.clone());
+        Ok(buffers)
+    }
+
+    /// Reads and, if necessary, compiles the databases.
+    fn load(&self) -> Result<Buffers, Error> {
+        let database = Database::from_files(self.default_database, &self.paths)?;
+        Ok(database.buffers().to_vec().into())
+    }
+
+    fn replace(&self, buffers: Arc<Buffers>) {
+        *self.loaded.lock().unwrap_or_else(PoisonError::into_inner) = Some(buffers);
+    }
+
+    /// Returns the paths in the colon separated form that libmagic expects.
+    fn filename(&self) -> Result<CString, Error> {
+        join_paths(default_database_base(self.default_database)?, &self.paths)
+    }
+}
+
+/// Works out which of the paths in a failed `magic_load` call caused the failure.
+///
+/// libmagic only fails to load a set of paths if none of them are valid, and then only reports that
+/// it couldn't find any valid magic files, so each path is loaded individually with a new cookie to
+/// collect the actual errors. If every path loads individually, the original error is returned
+/// wrapped in [`Error::Load`].
+fn diagnose_load(flags: c_int, filename: &CStr, error: Error) -> Error {
+    let failures: Vec<_> = filename
+        .to_bytes()
+        .split(|b| *b == b':')
+        .filter(|path| !path.is_empty())
+        .filter_map(|path| {
+            let result = CString::new(path)
+                .map_err(|_| Error::EmbeddedNuls)
+                .and_then(|path| {
+                    Cookie::open(flags)?.raw(|cookie| unsafe { magic_load(cookie, path.as_ptr()) })
+                });
+
+            result
+                .err()
+                .map(|e| (PathBuf::from(OsStr::from_bytes(path)), e))
+        })
+        .collect();
+
+    if failures.is_empty() {
+        load_failed(error)
+    } else {
+        Error::DatabaseLoad { failures }
+    }
+}
+
+/// A magic database buffer that can be shared between configurations and pools without copying.
+#[derive(Clone)]
+pub(crate) struct SharedBuffer(Arc<dyn AsRef<[u8]> + Send + Sync>);
+
+impl SharedBuffer {
+    pub(crate) fn new(buffer: impl AsRef<[u8]> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(buffer))
+    }
+
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        self.0.as_ref().as_ref()
+    }
+}
+
+impl Debug for SharedBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedBuffer")
+            .field("len", &self.as_slice().len())
+            .finish()
     }
 }
 
 pub(crate) struct Buffers {
-    storage: Vec<Vec<u8>>,
+    storage: Vec<SharedBuffer>,
 
     buffers: Vec<*const c_void>,
     sizes: Vec<usize>,
@@ -140,12 +1326,12 @@ impl Buffers {
     }
 }
 
-impl From<Vec<Vec<u8>>> for Buffers {
-    fn from(value: Vec<Vec<u8>>) -> Self {
-        let sizes = value.iter().map(|buf| buf.len()).collect();
+impl From<Vec<SharedBuffer>> for Buffers {
+    fn from(value: Vec<SharedBuffer>) -> Self {
+        let sizes = value.iter().map(|buf| buf.as_slice().len()).collect();
         let buffers = value
             .iter()
-            .map(|buf| buf.as_ptr() as *const c_void)
+            .map(|buf| buf.as_slice().as_ptr() as *const c_void)
             .collect();
 
         Self {
@@ -155,3 +1341,32 @@ impl From<Vec<Vec<u8>>> for Buffers {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, DefaultConfig};
+
+    #[test]
+    fn release_poisoned() -> anyhow::Result<()> {
+        let pool = DefaultConfig::default().build_pool()?;
+        let handle = pool.handle()?;
+
+        // Poison the reservoir lock, then ensure that dropping the handle doesn't panic.
+        let poisoner = pool.clone();
+        std::thread::spawn(move || {
+            let _reservoir = poisoner.0.reservoir.lock();
+            panic!("poisoning the reservoir");
+        })
+        .join()
+        .expect_err("thread panicked");
+
+        drop(handle);
+
+        let (reservoir, poisoned) = pool.lock_reservoir();
+        assert!(poisoned);
+        assert_eq!(reservoir.outstanding, 0);
+        assert!(reservoir.unused.is_empty());
+
+        Ok(())
+    }
+}
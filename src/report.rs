@@ -0,0 +1,168 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Display},
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{CsvWriter, Detection, Error, JsonLinesWriter, Scan};
+
+/// A destination for the results of a [`Scan`], as used by [`ScanReport`].
+pub trait ScanSink {
+    /// Records the result of detection on a single file.
+    fn record(&mut self, path: &Path, result: &Result<Detection, Error>) -> io::Result<()>;
+
+    /// Called once every result has been recorded.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Records each result as a line, including errors, which are written as per
+/// [`JsonLinesWriter::write_error`].
+impl<W: Write> ScanSink for JsonLinesWriter<W> {
+    fn record(&mut self, path: &Path, result: &Result<Detection, Error>) -> io::Result<()> {
+        match result {
+            Ok(detection) => self.write(path, detection),
+            Err(e) => self.write_error(path, e),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Records each successful result as a record. Errors are skipped, since they don't fit the
+/// fields of the record, but are still counted by the [`ScanSummary`].
+impl<W: Write> ScanSink for CsvWriter<W> {
+    fn record(&mut self, path: &Path, result: &Result<Detection, Error>) -> io::Result<()> {
+        match result {
+            Ok(detection) => self.write(path, detection),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// A summary of a scan, counting the files of each type.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanSummary {
+    /// The number of files that were detected successfully.
+    pub files: usize,
+
+    /// The number of files that couldn't be detected.
+    pub errors: usize,
+
+    /// The number of files of each type, keyed by MIME type, or by the full description if the
+    /// description isn't a MIME type.
+    pub types: BTreeMap<String, usize>,
+}
+
+impl ScanSink for ScanSummary {
+    fn record(&mut self, _path: &Path, result: &Result<Detection, Error>) -> io::Result<()> {
+        match result {
+            Ok(detection) => {
+                self.files += 1;
+                let key = detection.mime_type().unwrap_or(detection.description());
+                *self.types.entry(key.to_string()).or_default() += 1;
+            }
+            Err(_) => self.errors += 1,
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats the summary as one line per type, from the most to the least common, followed by the
+/// total number of files and errors.
+impl Display for ScanSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut types: Vec<_> = self.types.iter().collect();
+        types.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+
+        let width = types
+            .first()
+            .map_or(1, |(_, count)| count.to_string().len());
+        for (type_, count) in types {
+            writeln!(f, "{count:>width$} {type_}")?;
+        }
+        write!(f, "{} files, {} errors", self.files, self.errors)
+    }
+}
+
+/// Writes the results of a [`Scan`] to any number of [`ScanSink`]s, while summarising them.
+///
+/// ```no_run
+/// use mojique::{Config, DefaultConfig, Flag, ScanReport};
+///
+/// let scan = DefaultConfig::default()
+///     .set_flag(Flag::MimeType)
+///     .build_scanner()?
+///     .with_root("/srv/bucket")
+///     .scan()?;
+///
+/// let summary = ScanReport::new()
+///     .with_json_lines(std::fs::File::create("inventory.jsonl")?)
+///     .run(scan)?;
+/// println!("{summary}");
+/// # anyhow::Ok(())
+/// ```
+#[derive(Default)]
+pub struct ScanReport<'a> {
+    sinks: Vec<Box<dyn ScanSink + 'a>>,
+}
+
+impl<'a> ScanReport<'a> {
+    /// Creates a report with no sinks, which only summarises the scan.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sink that results are written to.
+    pub fn with_sink(mut self, sink: impl ScanSink + 'a) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Writes results to the given writer as JSON Lines, as per [`JsonLinesWriter`].
+    pub fn with_json_lines(self, writer: impl Write + 'a) -> Self {
+        self.with_sink(JsonLinesWriter::new(writer))
+    }
+
+    /// Writes results to the given writer as CSV, as per [`CsvWriter`].
+    pub fn with_csv(self, writer: impl Write + 'a) -> Self {
+        self.with_sink(CsvWriter::new(writer))
+    }
+
+    /// Writes every result of the scan to each sink in turn, returning a summary once the scan is
+    /// complete.
+    ///
+    /// Errors writing to a sink stop the report, and are returned as [`Error::ScanOutput`].
+    pub fn run(mut self, scan: Scan) -> Result<ScanSummary, Error> {
+        let mut summary = ScanSummary::default();
+        for (path, result) in scan {
+            summary.record(&path, &result).map_err(Error::ScanOutput)?;
+            for sink in &mut self.sinks {
+                sink.record(&path, &result).map_err(Error::ScanOutput)?;
+            }
+        }
+
+        for sink in &mut self.sinks {
+            sink.finish().map_err(Error::ScanOutput)?;
+        }
+
+        Ok(summary)
+    }
+}
+
+impl Debug for ScanReport<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScanReport")
+            .field("sinks", &self.sinks.len())
+            .finish()
+    }
+}
@@ -0,0 +1,88 @@
+use std::pin::Pin;
+
+use bytes::Bytes;
+use futures_core::Stream;
+use reqwest::{StatusCode, header::HeaderMap};
+
+use crate::{Detection, Error, Pool, Remainder};
+
+/// The body of a [`SniffedResponse`].
+///
+/// This yields the data that was read for detection, followed by the rest of the response body.
+pub type ResponseBody = Remainder<Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>>;
+
+/// A [`reqwest::Response`] that has had detection performed on the start of its body, as returned
+/// by [`Pool::sniff_response`].
+#[derive(Debug)]
+pub struct SniffedResponse {
+    body: ResponseBody,
+    detection: Detection,
+    headers: HeaderMap,
+    status: StatusCode,
+}
+
+impl SniffedResponse {
+    /// Returns the status of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the result of detection on the start of the response body.
+    pub fn detection(&self) -> &Detection {
+        &self.detection
+    }
+
+    /// Returns the `Content-Type` claimed by the server, if any.
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// Returns whether the MIME type claimed in the `Content-Type` header matches the detected MIME
+    /// type, or `None` if the server didn't claim one.
+    ///
+    /// Parameters such as `charset` are ignored. If the pool isn't configured to return MIME
+    /// types, this always returns `Some(false)` when there's a `Content-Type`.
+    pub fn content_type_matches(&self) -> Option<bool> {
+        let essence = self.content_type()?.split(';').next()?.trim();
+        Some(
+            self.detection
+                .mime_type()
+                .is_some_and(|mime_type| mime_type.eq_ignore_ascii_case(essence)),
+        )
+    }
+
+    /// Returns the response body, which replays the entire input.
+    pub fn into_body(self) -> ResponseBody {
+        self.body
+    }
+
+    /// Returns the result of detection, along with the response body.
+    pub fn into_parts(self) -> (Detection, ResponseBody) {
+        (self.detection, self.body)
+    }
+}
+
+pub(crate) async fn sniff(
+    pool: &Pool,
+    response: reqwest::Response,
+) -> Result<SniffedResponse, Error> {
+    let status = response.status();
+    let headers = response.headers().clone();
+    let stream: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>> =
+        Box::pin(response.bytes_stream());
+    let (detection, body) = pool.sniff_stream(stream).await?;
+
+    Ok(SniffedResponse {
+        body,
+        detection,
+        headers,
+        status,
+    })
+}
@@ -0,0 +1,1137 @@
+use std::{
+    collections::{HashMap, HashSet, hash_map::Entry},
+    fmt::Debug,
+    fs::Metadata,
+    io::Read,
+    num::NonZero,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+    },
+};
+
+use crate::{BYTES_MAX, Detection, Error, Handle, Pool, PooledHandle, glob::Glob};
+#[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+use crate::{Digest, HashAlgorithm};
+
+/// The number of paths and results that can be queued for each worker thread.
+const QUEUE_PER_THREAD: usize = 64;
+
+/// The default number of bytes read from files larger than the maximum file size.
+const PREFIX_BYTES: usize = 1024 * 1024;
+
+/// Walks one or more directory trees, performing detection on every file with a pool of worker
+/// threads.
+///
+/// A single thread walks the roots, and queues every entry that isn't a directory for detection
+/// by the workers, each of which holds a handle from the pool for as long as it runs. Results are
+/// returned by [`Scan`] as they become available, so they aren't in any particular order.
+///
+/// Files can be filtered by glob patterns and extensions with [`Scanner::with_include`],
+/// [`Scanner::with_exclude`], and [`Scanner::with_extensions`]. The progress of a scan can be
+/// followed with [`Scanner::with_progress`] or [`Scan::progress`].
+///
+/// Backup trees and package mirrors often contain many hardlinks to the same file. With
+/// [`Scanner::with_dedup_hardlinks`], detection is only performed on the first path found for
+/// each file, and the other paths are returned with the same detection, and listed by
+/// [`Scan::aliases`].
+///
+/// Huge files, such as in media archives, can be skipped or only have a prefix read with
+/// [`Scanner::with_max_file_size`].
+///
+/// By default, symlinks are passed to libmagic like any other entry, which describes the link
+/// itself unless the pool was configured with [`Flag::Symlink`][crate::Flag::Symlink], and symlinks
+/// to directories are never walked. [`Scanner::with_symlinks`] can instead follow or skip them,
+/// regardless of that flag. Roots that are directories are always walked, however, so a root can
+/// be a symlink to a directory.
+///
+/// ```no_run
+/// use mojique::{Config, DefaultConfig, Flag};
+///
+/// let scanner = DefaultConfig::default()
+///     .set_flag(Flag::MimeType)
+///     .build_scanner()?
+///     .with_root("/srv/uploads");
+///
+/// for (path, result) in scanner.scan()? {
+///     match result {
+///         Ok(detection) => println!("{}: {detection}", path.display()),
+///         Err(e) => eprintln!("{e}"),
+///     }
+/// }
+/// # anyhow::Ok(())
+/// ```
+#[derive(Clone)]
+pub struct Scanner {
+    dedup_hardlinks: bool,
+    exclude: Vec<String>,
+    extensions: Vec<String>,
+    #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+    hash: Option<HashAlgorithm>,
+    include: Vec<String>,
+    large_files: Option<(u64, LargeFilePolicy)>,
+    on_progress: Option<ProgressCallback>,
+    pool: Pool,
+    prefix_bytes: usize,
+    roots: Vec<PathBuf>,
+    symlinks: SymlinkPolicy,
+    threads: usize,
+}
+
+impl Scanner {
+    /// Creates a scanner that performs detection using the given pool.
+    ///
+    /// By default, there are no roots, and one worker thread is used for each CPU.
+    pub fn new(pool: Pool) -> Self {
+        Self {
+            dedup_hardlinks: false,
+            exclude: Vec::new(),
+            extensions: Vec::new(),
+            #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+            hash: None,
+            include: Vec::new(),
+            large_files: None,
+            on_progress: None,
+            pool,
+            prefix_bytes: PREFIX_BYTES,
+            roots: Vec::new(),
+            symlinks: SymlinkPolicy::Report,
+            threads: std::thread::available_parallelism().map_or(1, NonZero::get),
+        }
+    }
+
+    /// Adds a directory or file to scan.
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.roots.push(root.into());
+        self
+    }
+
+    /// Adds directories or files to scan.
+    pub fn with_roots<I>(mut self, roots: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+    {
+        self.roots.extend(roots.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds glob patterns that files must match at least one of to be scanned.
+    ///
+    /// Patterns are matched against the path of each file relative to the root it was found in.
+    /// `*` and `?` match within a single path component, `**` matches any number of components,
+    /// and `[a-z]` and `[!a-z]` match sets of characters. A pattern without a `/` matches the file
+    /// name in any directory, so `*.bin` is equivalent to `**/*.bin`, and a leading `/` anchors the
+    /// pattern to the root. Matching is case sensitive.
+    ///
+    /// If no include patterns are added, every file is scanned, subject to
+    /// [`Scanner::with_exclude`] and [`Scanner::with_extensions`]. Invalid patterns result in
+    /// [`Error::InvalidGlob`] when the scan starts.
+    pub fn with_include<I>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.include.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds glob patterns for files and directories that shouldn't be scanned.
+    ///
+    /// Patterns are as per [`Scanner::with_include`]. Excluded directories aren't walked at all,
+    /// so `node_modules` skips every `node_modules` directory and everything within it. Exclude
+    /// patterns take precedence over include patterns.
+    pub fn with_exclude<I>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.exclude.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds file name extensions, without the leading `.`, that files must have one of to be
+    /// scanned.
+    ///
+    /// Extensions are matched case insensitively. If no extensions are added, files are scanned
+    /// regardless of their extension.
+    pub fn with_extensions<I>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.extensions
+            .extend(extensions.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets whether detection is only performed once for each file with more than one hardlink.
+    ///
+    /// Files are identified by their device and inode number. The first path found for a file is
+    /// scanned as usual, and every other path is returned with the same detection, without reading
+    /// the file again, and listed by [`Scan::aliases`]. If detection fails, each path is scanned
+    /// separately instead. By default, every path is scanned.
+    pub fn with_dedup_hardlinks(mut self, dedup: bool) -> Self {
+        self.dedup_hardlinks = dedup;
+        self
+    }
+
+    /// Sets an algorithm to hash each regular file with, as it's read for detection.
+    ///
+    /// Hashed files are read in full by the worker threads, rather than by libmagic, and detection
+    /// is performed on the first [`BYTES_MAX`] bytes as per [`Handle::buffer`], so some files,
+    /// such as empty files, are described differently than they otherwise would be. The digests
+    /// are returned by [`Scan::hashed`]. Files that only have a prefix read, as per
+    /// [`LargeFilePolicy::Prefix`], and anything other than a regular file aren't hashed.
+    #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+    pub fn with_hash(mut self, algorithm: HashAlgorithm) -> Self {
+        self.hash = Some(algorithm);
+        self
+    }
+
+    /// Sets how regular files larger than `max_size` bytes are handled.
+    ///
+    /// libmagic reads up to [`BYTES_MAX`] bytes of each file, but some magic can also seek
+    /// elsewhere within it, which is slow for multi-gigabyte files on slow storage. Skipped files
+    /// aren't returned by the scan, and are counted by [`ScanProgress::skipped`]. By default, every
+    /// file is scanned in full.
+    pub fn with_max_file_size(mut self, max_size: u64, policy: LargeFilePolicy) -> Self {
+        self.large_files = Some((max_size, policy));
+        self
+    }
+
+    /// Sets the number of bytes read from files larger than the maximum file size with
+    /// [`LargeFilePolicy::Prefix`], which defaults to 1 MiB.
+    ///
+    /// libmagic ignores anything past [`BYTES_MAX`], so a larger value only wastes memory.
+    pub fn with_prefix_bytes(mut self, prefix_bytes: usize) -> Self {
+        self.prefix_bytes = prefix_bytes;
+        self
+    }
+
+    /// Sets a callback that is invoked whenever the progress of a scan changes.
+    ///
+    /// The callback is invoked on the threads performing the scan, once for each file that is
+    /// discovered or scanned and each error, so it should return promptly, and throttle anything
+    /// expensive such as redrawing a progress bar.
+    pub fn with_progress<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&ScanProgress) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets how symlinks found while walking the roots are handled, which defaults to
+    /// [`SymlinkPolicy::Report`].
+    pub fn with_symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// Sets the number of worker threads that perform detection.
+    ///
+    /// Each worker holds a handle for the duration of the scan, so this should be no more than
+    /// the maximum size of the pool. A value of zero is treated as one.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Returns the underlying pool.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    /// Starts scanning the roots, returning an iterator over the results.
+    ///
+    /// Errors reading a directory or performing detection on a file are returned within the
+    /// results as [`Error::Detect`], and don't stop the rest of the scan. Dropping the [`Scan`]
+    /// stops the scan once the threads notice that nobody is waiting for their results.
+    pub fn scan(&self) -> Result<Scan, Error> {
+        let progress = Arc::new(Progress {
+            callback: self.on_progress.clone(),
+            ..Progress::default()
+        });
+        let hardlinks = Arc::new(Hardlinks::default());
+        #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+        let hashes = Arc::new(Hashes::default());
+        let prefixed = Arc::new(Prefixed::default());
+        let walker = Walker {
+            filter: Filter::new(self)?,
+            hardlinks: self.dedup_hardlinks.then(|| hardlinks.clone()),
+            #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+            hashes: hashes.clone(),
+            large_files: self.large_files,
+            prefixed: prefixed.clone(),
+            progress: progress.clone(),
+            roots: self.roots.clone(),
+            symlinks: self.symlinks,
+        };
+
+        let (paths_tx, paths_rx) = mpsc::sync_channel(self.threads * QUEUE_PER_THREAD);
+        let (results_tx, results_rx) = mpsc::sync_channel(self.threads * QUEUE_PER_THREAD);
+
+        let paths_rx = Arc::new(Mutex::new(paths_rx));
+        for i in 0..self.threads {
+            let paths_rx = paths_rx.clone();
+            let worker = Worker {
+                handle: None,
+                hardlinks: hardlinks.clone(),
+                #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+                hash: self.hash,
+                #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+                hashes: hashes.clone(),
+                pool: self.pool.clone(),
+                prefix_bytes: self.prefix_bytes,
+                prefixed: prefixed.clone(),
+                progress: progress.clone(),
+                results: results_tx.clone(),
+            };
+
+            std::thread::Builder::new()
+                .name(format!("mojique-scanner-{i}"))
+                .spawn(move || worker.run(paths_rx))
+                .map_err(Error::ScannerSpawn)?;
+        }
+
+        std::thread::Builder::new()
+            .name("mojique-scanner-walker".into())
+            .spawn(move || walker.walk(paths_tx, results_tx))
+            .map_err(Error::ScannerSpawn)?;
+
+        Ok(Scan {
+            hardlinks,
+            #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+            hashes,
+            prefixed,
+            progress,
+            results: results_rx,
+        })
+    }
+}
+
+impl Debug for Scanner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("Scanner");
+        debug
+            .field("dedup_hardlinks", &self.dedup_hardlinks)
+            .field("exclude", &self.exclude)
+            .field("extensions", &self.extensions);
+        #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+        debug.field("hash", &self.hash);
+        debug
+            .field("include", &self.include)
+            .field("large_files", &self.large_files)
+            .field("on_progress", &self.on_progress.is_some())
+            .field("pool", &self.pool)
+            .field("prefix_bytes", &self.prefix_bytes)
+            .field("roots", &self.roots)
+            .field("symlinks", &self.symlinks)
+            .field("threads", &self.threads)
+            .finish()
+    }
+}
+
+/// How [`Scanner::with_max_file_size`] handles files that are larger than the maximum size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LargeFilePolicy {
+    /// Only a prefix of the file is read, as set by [`Scanner::with_prefix_bytes`], and detection
+    /// is performed on that. These detections are marked by [`Detection::is_prefix`], and the
+    /// files are also listed by [`Scan::prefixed`].
+    Prefix,
+
+    /// The file isn't scanned at all.
+    Skip,
+}
+
+/// How [`Scanner::with_symlinks`] handles symlinks found while walking the roots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SymlinkPolicy {
+    /// Symlinks to directories are walked, and detection is performed on the targets of symlinks
+    /// to files, which are returned with the path of the symlink.
+    ///
+    /// Each directory is only walked once, so symlinks that form a cycle, or that point to a
+    /// directory that has already been walked, are ignored. Broken symlinks are returned as
+    /// errors.
+    Follow,
+
+    /// Symlinks are passed to libmagic as they are, which describes the link itself unless the
+    /// pool was configured with [`Flag::Symlink`][crate::Flag::Symlink]. Symlinks to directories
+    /// aren't walked.
+    Report,
+
+    /// Symlinks are ignored, including roots that are symlinks to files.
+    Skip,
+}
+
+/// The progress of a scan, as passed to the callback given to [`Scanner::with_progress`], and
+/// returned by [`Scan::progress`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanProgress {
+    /// The number of files found while walking the roots, which are scanned in turn.
+    pub discovered: u64,
+
+    /// The number of files that detection has been performed on, whether or not it succeeded,
+    /// including hardlinks that shared the detection of another path.
+    pub scanned: u64,
+
+    /// The number of scanned files that were hardlinks sharing the detection of another path, as
+    /// per [`Scanner::with_dedup_hardlinks`].
+    pub aliases: u64,
+
+    /// The number of files that weren't scanned because they were larger than the maximum size,
+    /// as per [`LargeFilePolicy::Skip`]. These aren't included in the discovered files.
+    pub skipped: u64,
+
+    /// The number of bytes available to libmagic in the scanned files, which is the size of each
+    /// regular file up to [`BYTES_MAX`].
+    pub bytes: u64,
+
+    /// The number of errors, including errors reading directories.
+    pub errors: u64,
+}
+
+type ProgressCallback = Arc<dyn Fn(&ScanProgress) + Send + Sync>;
+
+/// The progress of a scan, shared between its threads.
+#[derive(Default)]
+struct Progress {
+    aliases: AtomicU64,
+    bytes: AtomicU64,
+    callback: Option<ProgressCallback>,
+    discovered: AtomicU64,
+    errors: AtomicU64,
+    scanned: AtomicU64,
+    skipped: AtomicU64,
+}
+
+impl Progress {
+    fn snapshot(&self) -> ScanProgress {
+        ScanProgress {
+            discovered: self.discovered.load(Ordering::Relaxed),
+            scanned: self.scanned.load(Ordering::Relaxed),
+            aliases: self.aliases.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Applies an update to the counters, and then notifies the callback.
+    fn update(&self, f: impl FnOnce(&Self)) {
+        f(self);
+        if let Some(callback) = &self.callback {
+            callback(&self.snapshot());
+        }
+    }
+
+    /// Counts a file that shared the detection of another path as scanned.
+    fn alias(&self) {
+        self.update(|progress| {
+            progress.scanned.fetch_add(1, Ordering::Relaxed);
+            progress.aliases.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+}
+
+/// A scan in progress, as returned by [`Scanner::scan`].
+///
+/// This yields the path of each file along with the result of detection, in the order that
+/// detection completes. Iteration ends once every file has been scanned.
+pub struct Scan {
+    hardlinks: Arc<Hardlinks>,
+    #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+    hashes: Arc<Hashes>,
+    prefixed: Arc<Prefixed>,
+    progress: Arc<Progress>,
+    results: Receiver<(PathBuf, Result<Detection, Error>)>,
+}
+
+impl Scan {
+    /// Returns each hardlink that was returned with the detection of another path so far, along
+    /// with that path, sorted by the path of the hardlink.
+    ///
+    /// This is always empty unless [`Scanner::with_dedup_hardlinks`] was set.
+    pub fn aliases(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.hardlinks.aliases()
+    }
+
+    /// Returns an iterator over the remaining results, along with the digest of each file that was
+    /// hashed, as per [`Scanner::with_hash`].
+    #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+    pub fn hashed(&mut self) -> impl Iterator<Item = HashedResult> + '_ {
+        std::iter::from_fn(|| {
+            let (path, result) = self.next()?;
+            let digest = self.hashes.get(&path);
+            Some((path, result, digest))
+        })
+    }
+
+    /// Returns the files that detection was only performed on a prefix of so far, as per
+    /// [`LargeFilePolicy::Prefix`], sorted by path.
+    pub fn prefixed(&self) -> Vec<PathBuf> {
+        self.prefixed.paths()
+    }
+
+    /// Returns the progress of the scan so far.
+    pub fn progress(&self) -> ScanProgress {
+        self.progress.snapshot()
+    }
+
+    /// Returns the channel that results are sent to, which can be handed to another thread.
+    pub fn into_receiver(self) -> Receiver<(PathBuf, Result<Detection, Error>)> {
+        self.results
+    }
+}
+
+impl Debug for Scan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scan").finish_non_exhaustive()
+    }
+}
+
+impl Iterator for Scan {
+    type Item = (PathBuf, Result<Detection, Error>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.results.recv().ok()
+    }
+}
+
+type Results = SyncSender<(PathBuf, Result<Detection, Error>)>;
+
+/// A result returned by [`Scan::hashed`].
+#[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+pub type HashedResult = (PathBuf, Result<Detection, Error>, Option<Digest>);
+
+/// A file queued for detection.
+struct Job {
+    /// The inode whose other hardlinks share the result, if any.
+    inode: Option<Inode>,
+    path: PathBuf,
+
+    /// Whether only a prefix of the file is read, as per [`LargeFilePolicy::Prefix`].
+    prefix: bool,
+
+    /// The file that detection is performed on, if the path is a symlink that was followed.
+    target: Option<PathBuf>,
+}
+
+/// The device and inode number that identify a file.
+type Inode = (u64, u64);
+
+/// The hardlinks found during a scan, shared between its threads.
+#[derive(Default)]
+struct Hardlinks {
+    // Nothing can panic while the lock is held, so a poisoned lock is safe to recover.
+    links: Mutex<HashMap<Inode, Link>>,
+}
+
+/// The first path found for a file with more than one hardlink, and the other paths to it.
+struct Link {
+    aliases: Vec<PathBuf>,
+    path: PathBuf,
+    state: LinkState,
+}
+
+/// The progress of detection on the first path to a file.
+enum LinkState {
+    /// Detection hasn't completed, so the aliases are waiting for its result.
+    Pending,
+
+    /// Detection succeeded, and the aliases share its result.
+    Detected(Detection),
+
+    /// Detection failed, so the other paths are scanned separately.
+    Failed,
+}
+
+/// What the walker does with a path to a file with more than one hardlink.
+enum Linked {
+    /// This is the first path to the file, so it's queued for detection.
+    First,
+
+    /// The path will be sent with the result of detection on the first path, once it completes.
+    Waiting,
+
+    /// Detection on the first path, which is included, succeeded, so the path is sent with the
+    /// same detection.
+    Shared(Detection, PathBuf),
+
+    /// Detection on the first path failed, so the path is queued for detection separately.
+    Failed,
+}
+
+impl Hardlinks {
+    fn aliases(&self) -> Vec<(PathBuf, PathBuf)> {
+        let links = self.links.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut aliases: Vec<_> = links
+            .values()
+            .filter(|link| matches!(link.state, LinkState::Detected(_)))
+            .flat_map(|link| {
+                link.aliases
+                    .iter()
+                    .map(|alias| (alias.clone(), link.path.clone()))
+            })
+            .collect();
+        aliases.sort();
+        aliases
+    }
+
+    /// Records a path found by the walker.
+    fn link(&self, inode: Inode, path: &Path) -> Linked {
+        let mut links = self.links.lock().unwrap_or_else(PoisonError::into_inner);
+        let link = match links.entry(inode) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(Link {
+                    aliases: Vec::new(),
+                    path: path.to_path_buf(),
+                    state: LinkState::Pending,
+                });
+                return Linked::First;
+            }
+        };
+
+        match &link.state {
+            LinkState::Pending => {
+                link.aliases.push(path.to_path_buf());
+                Linked::Waiting
+            }
+            LinkState::Detected(detection) => {
+                let detection = detection.clone();
+                link.aliases.push(path.to_path_buf());
+                Linked::Shared(detection, link.path.clone())
+            }
+            LinkState::Failed => Linked::Failed,
+        }
+    }
+
+    /// Records the result of detection on the first path to a file, returning the paths that were
+    /// waiting for it.
+    fn complete(&self, inode: Inode, result: &Result<Detection, Error>) -> Vec<PathBuf> {
+        let mut links = self.links.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(link) = links.get_mut(&inode) else {
+            return Vec::new();
+        };
+
+        match result {
+            Ok(detection) => {
+                link.state = LinkState::Detected(detection.clone());
+                link.aliases.clone()
+            }
+            Err(_) => {
+                link.state = LinkState::Failed;
+                std::mem::take(&mut link.aliases)
+            }
+        }
+    }
+}
+
+/// The digests of the files that were hashed, shared between the threads of a scan.
+#[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+#[derive(Default)]
+struct Hashes(Mutex<HashMap<PathBuf, Digest>>);
+
+#[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+impl Hashes {
+    fn get(&self, path: &Path) -> Option<Digest> {
+        let hashes = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        hashes.get(path).copied()
+    }
+
+    fn insert(&self, path: &Path, digest: Digest) {
+        let mut hashes = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        hashes.insert(path.to_path_buf(), digest);
+    }
+
+    /// Gives a hardlink the same digest as the path whose detection it shares.
+    fn share(&self, original: &Path, alias: &Path) {
+        let mut hashes = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(digest) = hashes.get(original).copied() {
+            hashes.insert(alias.to_path_buf(), digest);
+        }
+    }
+}
+
+/// The files that detection was only performed on a prefix of, shared between the threads of a
+/// scan.
+#[derive(Default)]
+struct Prefixed(Mutex<Vec<PathBuf>>);
+
+impl Prefixed {
+    fn paths(&self) -> Vec<PathBuf> {
+        let mut paths = self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        paths.sort();
+        paths
+    }
+
+    fn push(&self, path: &Path) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(path.to_path_buf());
+    }
+}
+
+/// The state of the thread that walks the roots.
+struct Walker {
+    filter: Filter,
+    hardlinks: Option<Arc<Hardlinks>>,
+    #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+    hashes: Arc<Hashes>,
+    large_files: Option<(u64, LargeFilePolicy)>,
+    prefixed: Arc<Prefixed>,
+    progress: Arc<Progress>,
+    roots: Vec<PathBuf>,
+    symlinks: SymlinkPolicy,
+}
+
+impl Walker {
+    /// Walks the roots, queueing every file that passes the filter for the workers.
+    ///
+    /// Sending only fails once the scan has been dropped, at which point there's nothing left to
+    /// do.
+    fn walk(self, paths: SyncSender<Job>, results: Results) {
+        let mut dirs = Vec::new();
+        let mut walked = HashSet::new();
+        for root in &self.roots {
+            match std::fs::metadata(root) {
+                Ok(metadata) if metadata.is_dir() => {
+                    if self.is_unwalked(&mut walked, root) {
+                        dirs.push(root.clone());
+                    }
+                }
+                Ok(_) => {
+                    // Roots that are files are filtered by their name.
+                    let name = root.file_name().map_or(root.as_path(), Path::new);
+                    let is_symlink =
+                        std::fs::symlink_metadata(root).is_ok_and(|metadata| metadata.is_symlink());
+                    if self.filter.is_file_included(name)
+                        && !self.file(&paths, &results, root.clone(), is_symlink)
+                    {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    if !self.report(&results, root.clone(), e) {
+                        return;
+                    }
+                }
+            }
+
+            while let Some(dir) = dirs.pop() {
+                let entries = match std::fs::read_dir(&dir) {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        if !self.report(&results, dir, e) {
+                            return;
+                        }
+                        continue;
+                    }
+                };
+
+                for entry in entries {
+                    let (path, file_type) =
+                        match entry.and_then(|entry| Ok((entry.path(), entry.file_type()?))) {
+                            Ok(entry) => entry,
+                            Err(e) => {
+                                if !self.report(&results, dir.clone(), e) {
+                                    return;
+                                }
+                                continue;
+                            }
+                        };
+
+                    let is_dir = if file_type.is_symlink() && self.symlinks == SymlinkPolicy::Follow
+                    {
+                        match std::fs::metadata(&path) {
+                            Ok(metadata) => metadata.is_dir(),
+                            Err(e) => {
+                                if !self.report(&results, path, e) {
+                                    return;
+                                }
+                                continue;
+                            }
+                        }
+                    } else {
+                        file_type.is_dir()
+                    };
+
+                    let relative = path.strip_prefix(root).unwrap_or(&path);
+                    if is_dir {
+                        if !self.filter.is_excluded(relative)
+                            && self.is_unwalked(&mut walked, &path)
+                        {
+                            dirs.push(path);
+                        }
+                    } else if self.filter.is_file_included(relative)
+                        && !self.file(&paths, &results, path, file_type.is_symlink())
+                    {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if a directory hasn't been walked yet, recording that it will be.
+    ///
+    /// Directories can only be reached more than once by following symlinks, so they're only
+    /// tracked if symlinks are followed.
+    fn is_unwalked(&self, walked: &mut HashSet<Inode>, dir: &Path) -> bool {
+        // If the directory can't be examined, reading it will report the error.
+        self.symlinks != SymlinkPolicy::Follow
+            || std::fs::metadata(dir).map_or(true, |metadata| {
+                walked.insert((metadata.dev(), metadata.ino()))
+            })
+    }
+
+    /// Handles a file according to the symlink policy, returning `false` if the scan has been
+    /// dropped.
+    fn file(
+        &self,
+        paths: &SyncSender<Job>,
+        results: &Results,
+        path: PathBuf,
+        is_symlink: bool,
+    ) -> bool {
+        if !is_symlink {
+            return self.queue(paths, results, path, None);
+        }
+
+        match self.symlinks {
+            SymlinkPolicy::Follow => match std::fs::canonicalize(&path) {
+                Ok(target) => self.queue(paths, results, path, Some(target)),
+                Err(e) => self.report(results, path, e),
+            },
+            SymlinkPolicy::Report => self.queue(paths, results, path, None),
+            SymlinkPolicy::Skip => true,
+        }
+    }
+
+    /// Queues a file for detection, or sends the detection of another hardlink to it, returning
+    /// `false` if the scan has been dropped.
+    fn queue(
+        &self,
+        paths: &SyncSender<Job>,
+        results: &Results,
+        path: PathBuf,
+        target: Option<PathBuf>,
+    ) -> bool {
+        // Files are only examined if something depends on their metadata.
+        let metadata = (self.hardlinks.is_some() || self.large_files.is_some())
+            .then(|| std::fs::symlink_metadata(target.as_ref().unwrap_or(&path)).ok())
+            .flatten()
+            .filter(Metadata::is_file);
+
+        let prefix = match (self.large_files, &metadata) {
+            (Some((max_size, policy)), Some(metadata)) if metadata.len() > max_size => match policy
+            {
+                LargeFilePolicy::Prefix => true,
+                LargeFilePolicy::Skip => {
+                    self.progress.update(|progress| {
+                        progress.skipped.fetch_add(1, Ordering::Relaxed);
+                    });
+                    return true;
+                }
+            },
+            _ => false,
+        };
+
+        self.progress.update(|progress| {
+            progress.discovered.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let mut job = Job {
+            inode: None,
+            path,
+            prefix,
+            target,
+        };
+        let Some((hardlinks, inode)) = self
+            .hardlinks
+            .as_ref()
+            .zip(metadata.as_ref().and_then(inode))
+        else {
+            return paths.send(job).is_ok();
+        };
+
+        match hardlinks.link(inode, &job.path) {
+            Linked::First => {
+                job.inode = Some(inode);
+                paths.send(job).is_ok()
+            }
+            Linked::Waiting => true,
+            #[cfg_attr(
+                not(any(feature = "blake3", feature = "sha2", feature = "xxhash")),
+                allow(unused_variables)
+            )]
+            Linked::Shared(detection, original) => {
+                #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+                self.hashes.share(&original, &job.path);
+                self.progress.alias();
+                if prefix {
+                    self.prefixed.push(&job.path);
+                }
+                results.send((job.path, Ok(detection))).is_ok()
+            }
+            Linked::Failed => paths.send(job).is_ok(),
+        }
+    }
+
+    /// Sends an error reading a path, returning `false` if the scan has been dropped.
+    fn report(&self, results: &Results, path: PathBuf, source: std::io::Error) -> bool {
+        self.progress.update(|progress| {
+            progress.errors.fetch_add(1, Ordering::Relaxed);
+        });
+        results.send(read_error(path, source)).is_ok()
+    }
+}
+
+/// Decides which paths are scanned, based on their path relative to their root.
+struct Filter {
+    exclude: Vec<Glob>,
+    extensions: Vec<String>,
+    include: Vec<Glob>,
+}
+
+impl Filter {
+    fn new(scanner: &Scanner) -> Result<Self, Error> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| Glob::new(pattern))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(Self {
+            exclude: compile(&scanner.exclude)?,
+            extensions: scanner.extensions.clone(),
+            include: compile(&scanner.include)?,
+        })
+    }
+
+    fn is_excluded(&self, relative: &Path) -> bool {
+        self.exclude.iter().any(|glob| glob.is_match(relative))
+    }
+
+    fn is_file_included(&self, relative: &Path) -> bool {
+        !self.is_excluded(relative)
+            && (self.include.is_empty() || self.include.iter().any(|glob| glob.is_match(relative)))
+            && (self.extensions.is_empty()
+                || relative.extension().is_some_and(|extension| {
+                    self.extensions
+                        .iter()
+                        .any(|allowed| extension.eq_ignore_ascii_case(allowed))
+                }))
+    }
+}
+
+/// The state of a thread that performs detection.
+struct Worker {
+    handle: Option<PooledHandle>,
+    hardlinks: Arc<Hardlinks>,
+    #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+    hash: Option<HashAlgorithm>,
+    #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+    hashes: Arc<Hashes>,
+    pool: Pool,
+    prefix_bytes: usize,
+    prefixed: Arc<Prefixed>,
+    progress: Arc<Progress>,
+    results: Results,
+}
+
+impl Worker {
+    /// Performs detection on queued paths until the walker has finished.
+    fn run(mut self, paths: Arc<Mutex<Receiver<Job>>>) {
+        loop {
+            // Nothing can panic while the lock is held, so a poisoned lock is safe to recover.
+            let job = paths.lock().unwrap_or_else(PoisonError::into_inner).recv();
+            let Ok(job) = job else {
+                return;
+            };
+
+            let target = job.target.as_ref().unwrap_or(&job.path);
+            let result = self.detect(&job.path, target, job.prefix);
+            let aliases = job
+                .inode
+                .map_or_else(Vec::new, |inode| self.hardlinks.complete(inode, &result));
+            let shared = result
+                .as_ref()
+                .ok()
+                .filter(|_| !aliases.is_empty())
+                .cloned();
+            #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+            if shared.is_some() {
+                for alias in &aliases {
+                    self.hashes.share(&job.path, alias);
+                }
+            }
+            if self.results.send((job.path, result)).is_err() {
+                return;
+            }
+
+            for alias in aliases {
+                let result = match &shared {
+                    Some(detection) => {
+                        self.progress.alias();
+                        if job.prefix {
+                            self.prefixed.push(&alias);
+                        }
+                        Ok(detection.clone())
+                    }
+                    None => self.detect(&alias, &alias, job.prefix),
+                };
+                if self.results.send((alias, result)).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Performs detection on the target of a path, which is the path itself unless it's a symlink
+    /// that was followed.
+    fn detect(&mut self, path: &Path, target: &Path, prefix: bool) -> Result<Detection, Error> {
+        let metadata = std::fs::symlink_metadata(target)
+            .ok()
+            .filter(Metadata::is_file);
+
+        // If a handle can't be acquired, the error is attributed to the current path, and the next
+        // path tries again.
+        let result = match self.handle.take().map_or_else(|| self.pool.handle(), Ok) {
+            Ok(mut handle) => {
+                let result = self.examine(&mut handle, path, target, prefix, metadata.is_some());
+                self.handle = Some(handle);
+                result
+            }
+            Err(e) => Err(e),
+        }
+        .map(|description| Detection::new(description).with_prefix(prefix));
+
+        if prefix && result.is_ok() {
+            self.prefixed.push(path);
+        }
+
+        // libmagic reads at most BYTES_MAX bytes of each file, and nothing from anything else.
+        let limit = if prefix { self.prefix_bytes } else { BYTES_MAX };
+        let bytes = metadata.map_or(0, |metadata| metadata.len().min(limit as u64));
+        self.progress.update(|progress| {
+            progress.scanned.fetch_add(1, Ordering::Relaxed);
+            progress.bytes.fetch_add(bytes, Ordering::Relaxed);
+            if result.is_err() {
+                progress.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+        result
+    }
+
+    /// Performs detection on a file in whichever way the scan requires, recording its digest if
+    /// it's hashed.
+    #[cfg_attr(
+        not(any(feature = "blake3", feature = "sha2", feature = "xxhash")),
+        allow(unused_variables)
+    )]
+    fn examine(
+        &self,
+        handle: &mut Handle,
+        path: &Path,
+        target: &Path,
+        prefix: bool,
+        is_file: bool,
+    ) -> Result<String, Error> {
+        if prefix {
+            return detect_prefix(handle, target, self.prefix_bytes);
+        }
+
+        #[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+        if let Some(algorithm) = self.hash
+            && is_file
+        {
+            let (description, digest) = detect_hashed(handle, target, algorithm)?;
+            self.hashes.insert(path, digest);
+            return Ok(description);
+        }
+
+        handle.file(target)
+    }
+}
+
+/// Performs detection on up to `len` bytes from the start of a file.
+///
+/// As with [`Handle::file`], any error is wrapped in [`Error::Detect`].
+fn detect_prefix(handle: &mut Handle, path: &Path, len: usize) -> Result<String, Error> {
+    let mut buf = Vec::new();
+    std::fs::File::open(path)
+        .and_then(|file| file.take(len as u64).read_to_end(&mut buf))
+        .map_err(Error::ReadInput)
+        .and_then(|_| handle.buffer(&buf))
+        .map_err(|source| Error::Detect {
+            path: path.to_path_buf(),
+            source: Box::new(source),
+        })
+}
+
+/// Reads the whole of a file to hash it, performing detection on the first [`BYTES_MAX`] bytes.
+///
+/// As with [`Handle::file`], any error is wrapped in [`Error::Detect`].
+#[cfg(any(feature = "blake3", feature = "sha2", feature = "xxhash"))]
+fn detect_hashed(
+    handle: &mut Handle,
+    path: &Path,
+    algorithm: HashAlgorithm,
+) -> Result<(String, Digest), Error> {
+    let mut hasher = algorithm.hasher();
+    let mut buf = Vec::new();
+    let mut chunk = vec![0; 64 * 1024];
+
+    let result = std::fs::File::open(path).and_then(|mut file| {
+        loop {
+            let len = match file.read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(len) => len,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            hasher.update(&chunk[..len]);
+            let keep = len.min(BYTES_MAX - buf.len());
+            buf.extend_from_slice(&chunk[..keep]);
+        }
+    });
+
+    result
+        .map_err(Error::ReadInput)
+        .and_then(|()| handle.buffer(&buf))
+        .map(|description| (description, hasher.finish()))
+        .map_err(|source| Error::Detect {
+            path: path.to_path_buf(),
+            source: Box::new(source),
+        })
+}
+
+/// Returns the device and inode number of a regular file with more than one hardlink.
+fn inode(metadata: &Metadata) -> Option<Inode> {
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+fn read_error(path: PathBuf, source: std::io::Error) -> (PathBuf, Result<Detection, Error>) {
+    let error = Error::Detect {
+        path: path.clone(),
+        source: Box::new(Error::ReadInput(source)),
+    };
+
+    (path, Err(error))
+}
@@ -0,0 +1,161 @@
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde::Deserialize;
+use tokio::net::TcpListener;
+
+use crate::{Detection, Error, ErrorKind, Pool};
+
+/// A small HTTP server that exposes detection to other processes, backed by a [`Pool`].
+///
+/// The server has a single `/detect` endpoint:
+///
+/// * `POST /detect` performs detection on the request body. Only the start of the body is read,
+///   so arbitrarily large bodies can be posted.
+/// * `GET /detect?path=<path>` performs detection on a local file. This is disabled unless a root
+///   directory is configured with [`Server::with_root`]. Paths must be relative to the root, and
+///   paths that are absolute, contain `..`, or resolve to outside of the root are rejected with
+///   `403 Forbidden`.
+///
+/// Successful responses are the serialised [`Detection`]; errors are the serialised [`Error`],
+/// with a status code derived from [`Error::kind`]. Failures to load the magic database are
+/// always `500 Internal Server Error`.
+///
+/// The `mojique-server` binary runs this server as a standalone sidecar.
+///
+/// ```no_run
+/// # async fn serve() -> anyhow::Result<()> {
+/// use mojique::{Config, DefaultConfig, Flag, Server};
+///
+/// let pool = DefaultConfig::default().set_flag(Flag::MimeType).build_pool()?;
+/// let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await?;
+/// Server::new(pool).with_root("/srv/uploads").serve(listener).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Server {
+    pool: Pool,
+    root: Option<PathBuf>,
+}
+
+impl Server {
+    /// Creates a new server that performs detection using the given pool.
+    pub fn new(pool: Pool) -> Self {
+        Self { pool, root: None }
+    }
+
+    /// Enables `GET /detect?path=`, allowing detection on files within the given directory.
+    ///
+    /// Relative paths in requests are resolved against the root.
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Returns an axum [`Router`] for the server, which can be nested within a larger
+    /// application.
+    pub fn into_router(self) -> Router {
+        Router::new()
+            .route("/detect", post(detect_body).get(detect_path))
+            .with_state(self)
+    }
+
+    /// Serves requests on the given listener until an I/O error occurs.
+    pub async fn serve(self, listener: TcpListener) -> io::Result<()> {
+        axum::serve(listener, self.into_router()).await
+    }
+
+    /// Resolves a requested path against the root, ensuring that it doesn't escape the root.
+    fn resolve(&self, path: &Path) -> Result<PathBuf, Error> {
+        let Some(root) = &self.root else {
+            return Err(Error::ReadInput(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "path lookups are disabled",
+            )));
+        };
+
+        let outside = || {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "path is outside of the server root",
+            )
+        };
+
+        // Paths that could escape the root are rejected before touching the filesystem, since
+        // otherwise the difference between a missing file and one outside the root would reveal
+        // which files exist elsewhere on the host.
+        let resolve = || {
+            if !path
+                .components()
+                .all(|component| matches!(component, Component::CurDir | Component::Normal(_)))
+            {
+                return Err(outside());
+            }
+
+            let root = root.canonicalize()?;
+            let resolved = root.join(path).canonicalize()?;
+            if resolved.starts_with(&root) {
+                Ok(resolved)
+            } else {
+                Err(outside())
+            }
+        };
+
+        resolve().map_err(|e| Error::Detect {
+            path: path.to_path_buf(),
+            source: Box::new(Error::ReadInput(e)),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PathQuery {
+    path: PathBuf,
+}
+
+async fn detect_body(State(server): State<Server>, body: Body) -> Response {
+    respond(
+        server
+            .pool
+            .sniff_stream(body.into_data_stream())
+            .await
+            .map(|(detection, _)| detection),
+    )
+}
+
+async fn detect_path(State(server): State<Server>, Query(query): Query<PathQuery>) -> Response {
+    let result = match server.resolve(&query.path) {
+        Ok(path) => server.pool.file_async(path).await.map(Detection::from),
+        Err(e) => Err(e),
+    };
+
+    respond(result)
+}
+
+fn respond(result: Result<Detection, Error>) -> Response {
+    match result {
+        Ok(detection) => Json(detection).into_response(),
+        Err(e) => {
+            let status = match e.kind() {
+                ErrorKind::NotFound => StatusCode::NOT_FOUND,
+                ErrorKind::Permission => StatusCode::FORBIDDEN,
+                ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+                _ if matches!(e, Error::ReadInput(_)) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+
+            (status, Json(e)).into_response()
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    fmt::Debug,
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_core::Stream;
+use tower_service::Service;
+
+use crate::{Error, Pool};
+
+/// A boxed stream of [`Bytes`], as used by [`DetectRequest::Stream`].
+pub type DetectStream =
+    Pin<Box<dyn Stream<Item = Result<Bytes, Box<dyn std::error::Error + Send + Sync>>> + Send>>;
+
+/// An input to be detected by a [`DetectService`].
+pub enum DetectRequest {
+    /// An in-memory buffer.
+    Buffer(Bytes),
+
+    /// A file on the filesystem.
+    Path(PathBuf),
+
+    /// A stream, such as a request body. Only the start of the stream is consumed; see
+    /// [`Handle::stream`][crate::Handle::stream].
+    Stream(DetectStream),
+}
+
+impl Debug for DetectRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Buffer(buf) => f.debug_tuple("Buffer").field(&buf.len()).finish(),
+            Self::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            Self::Stream(_) => f.debug_tuple("Stream").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl From<Bytes> for DetectRequest {
+    fn from(value: Bytes) -> Self {
+        Self::Buffer(value)
+    }
+}
+
+impl From<Vec<u8>> for DetectRequest {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Buffer(value.into())
+    }
+}
+
+impl From<PathBuf> for DetectRequest {
+    fn from(value: PathBuf) -> Self {
+        Self::Path(value)
+    }
+}
+
+/// A [`tower_service::Service`] that performs detection using handles from a [`Pool`].
+///
+/// The service is only ready when the pool has an idle handle or is able to create a new one, so
+/// tower middleware such as load shedding and concurrency limits can respond to pool capacity.
+/// Readiness doesn't reserve a handle, however, so a call may still have to wait for one if other
+/// users of the pool acquire handles in the meantime.
+///
+/// Waiting for the pool to have capacity doesn't occupy a thread, so the service can be dropped
+/// while it isn't ready without leaving anything behind. Both acquiring the handle and the
+/// detection itself are performed on blocking threads via [`tokio::task::spawn_blocking`], so the
+/// service must be used within a Tokio runtime.
+pub struct DetectService {
+    pool: Pool,
+    waiting: Option<Waiting>,
+}
+
+/// A future that resolves once the pool has capacity.
+type Waiting = Pin<Box<dyn Future<Output = Result<(), Error>> + Send>>;
+
+impl DetectService {
+    /// Creates a new service that acquires handles from the given pool.
+    pub fn new(pool: Pool) -> Self {
+        Self {
+            pool,
+            waiting: None,
+        }
+    }
+
+    /// Returns the pool that the service acquires handles from.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+}
+
+// Clones shouldn't share the wait for capacity, since each clone will be polled separately.
+impl Clone for DetectService {
+    fn clone(&self) -> Self {
+        Self::new(self.pool.clone())
+    }
+}
+
+impl Debug for DetectService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DetectService")
+            .field("pool", &self.pool)
+            .field("waiting", &self.waiting.is_some())
+            .finish()
+    }
+}
+
+impl Service<DetectRequest> for DetectService {
+    type Response = String;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<String, Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let waiting = match self.waiting.as_mut() {
+            Some(waiting) => waiting,
+            None if self.pool.has_capacity()? => return Poll::Ready(Ok(())),
+            None => {
+                let pool = self.pool.clone();
+                self.waiting
+                    .insert(Box::pin(async move { pool.wait_for_capacity().await }))
+            }
+        };
+
+        let result = std::task::ready!(waiting.as_mut().poll(cx));
+        self.waiting = None;
+        Poll::Ready(result)
+    }
+
+    fn call(&mut self, request: DetectRequest) -> Self::Future {
+        let pool = self.pool.clone();
+        Box::pin(async move {
+            match request {
+                DetectRequest::Buffer(buf) => pool.buffer_async(buf).await,
+                DetectRequest::Path(path) => pool.file_async(path).await,
+                DetectRequest::Stream(stream) => {
+                    // The handle is held while the stream is read, and detection then needs
+                    // another blocking thread, so avoid occupying one while waiting for capacity.
+                    pool.wait_for_capacity().await?;
+                    let mut handle = tokio::task::spawn_blocking(move || pool.handle())
+                        .await
+                        .map_err(|_| Error::TaskJoin)??;
+                    let (description, _) = handle.stream(stream).await?;
+                    Ok(description)
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    fmt::Debug,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+use crate::{BYTES_MAX, Error, Handle};
+
+/// An [`AsyncRead`] that performs detection on the start of another `AsyncRead`, and then replays
+/// the data that was read for detection before continuing with the rest of the input.
+///
+/// This allows an input to be classified and then forwarded elsewhere without having to buffer
+/// it in its entirety.
+///
+/// Up to [`BYTES_MAX`] bytes are held in memory until they have been replayed.
+pub struct AsyncSniffReader<R> {
+    description: String,
+    prefix: Arc<Vec<u8>>,
+    position: usize,
+    inner: R,
+}
+
+impl<R> AsyncSniffReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Reads the start of `read` and performs detection on it using the given handle.
+    ///
+    /// As with [`Handle::read_async`], detection is performed via
+    /// [`tokio::task::spawn_blocking`], so this must be called within a Tokio runtime.
+    pub async fn new(handle: &mut Handle, mut read: R) -> Result<Self, Error> {
+        let mut prefix = Vec::new();
+        (&mut read)
+            .take(BYTES_MAX as u64)
+            .read_to_end(&mut prefix)
+            .await
+            .map_err(Error::ReadInput)?;
+
+        let prefix = Arc::new(prefix);
+        let buf = prefix.clone();
+        let description = handle
+            .run_blocking(move |handle| handle.buffer(&buf))
+            .await?;
+
+        Ok(Self {
+            description,
+            prefix,
+            position: 0,
+            inner: read,
+        })
+    }
+}
+
+impl<R> AsyncSniffReader<R> {
+    /// Returns the textual description of the start of the input.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns a reference to the underlying reader.
+    ///
+    /// Note that reading from the underlying reader directly will skip any data that hasn't yet
+    /// been replayed.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R> Debug for AsyncSniffReader<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncSniffReader")
+            .field("description", &self.description)
+            .field("prefix_len", &self.prefix.len())
+            .field("position", &self.position)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R> AsyncRead for AsyncSniffReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = &mut *self;
+        if this.position < this.prefix.len() {
+            let len = buf.remaining().min(this.prefix.len() - this.position);
+            buf.put_slice(&this.prefix[this.position..this.position + len]);
+            this.position += len;
+            return Poll::Ready(Ok(()));
+        }
+
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
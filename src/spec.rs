@@ -0,0 +1,272 @@
+use std::{path::PathBuf, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{
+    CombinedConfig, Config, DefaultConfig, DirectoryConfig, EnvConfig, Error, FileConfig, Flag,
+    Handle, Pool, PoolOptions,
+};
+
+/// A declarative description of a configuration, suitable for deserialising from application
+/// configuration files.
+///
+/// For example, in TOML:
+///
+/// ```toml
+/// flags = ["mime-type", "symlink"]
+///
+/// [source]
+/// type = "files"
+/// paths = ["/etc/magic.d/local"]
+/// default_database = true
+///
+/// [limits]
+/// max_size = 16
+/// idle_timeout_secs = 300
+/// ```
+///
+/// A spec can be converted into the matching concrete configuration with [`TryFrom`], or used
+/// directly with [`ConfigSpec::build_handle`] and [`ConfigSpec::build_pool`].
+///
+/// Flags are given by their [`Flag::name`], and are parsed as by [`FromStr`][std::str::FromStr].
+/// They are set in addition to the default flags of the configuration, so [`Flag::Error`] is
+/// always set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigSpec {
+    pub source: SourceSpec,
+    pub flags: Vec<Flag>,
+    pub limits: LimitsSpec,
+}
+
+impl ConfigSpec {
+    /// Builds a single [`Handle`] from the spec.
+    pub fn build_handle(&self) -> Result<Handle, Error> {
+        match &self.source {
+            SourceSpec::Default => DefaultConfig::try_from(self.clone())?.build_handle(),
+            SourceSpec::Env => EnvConfig::try_from(self.clone())?.build_handle(),
+            SourceSpec::Files { .. } => FileConfig::try_from(self.clone())?.build_handle(),
+            SourceSpec::Directory { .. } => DirectoryConfig::try_from(self.clone())?.build_handle(),
+            SourceSpec::Combined { .. } => CombinedConfig::try_from(self.clone())?.build_handle(),
+        }
+    }
+
+    /// Builds a [`Pool`] from the spec, applying any limits.
+    pub fn build_pool(&self) -> Result<Pool, Error> {
+        let options = self.limits.pool_options()?;
+
+        match &self.source {
+            SourceSpec::Default => {
+                DefaultConfig::try_from(self.clone())?.build_pool_with_options(options)
+            }
+            SourceSpec::Env => EnvConfig::try_from(self.clone())?.build_pool_with_options(options),
+            SourceSpec::Files { .. } => {
+                FileConfig::try_from(self.clone())?.build_pool_with_options(options)
+            }
+            SourceSpec::Directory { .. } => {
+                DirectoryConfig::try_from(self.clone())?.build_pool_with_options(options)
+            }
+            SourceSpec::Combined { .. } => {
+                CombinedConfig::try_from(self.clone())?.build_pool_with_options(options)
+            }
+        }
+    }
+
+    fn apply_flags<C: Config>(&self, config: C) -> C {
+        config.set_flags(self.flags.clone())
+    }
+}
+
+/// The magic database(s) described by a [`ConfigSpec`].
+///
+/// This is internally tagged with a `type` field.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SourceSpec {
+    /// Equivalent to [`DefaultConfig`].
+    #[default]
+    Default,
+
+    /// Equivalent to [`EnvConfig`].
+    Env,
+
+    /// Equivalent to [`FileConfig`].
+    Files {
+        paths: Vec<PathBuf>,
+        #[serde(default)]
+        default_database: bool,
+    },
+
+    /// Equivalent to [`DirectoryConfig`].
+    Directory {
+        path: PathBuf,
+        #[serde(default)]
+        extensions: Vec<String>,
+        #[serde(default)]
+        exclude: Vec<String>,
+    },
+
+    /// Equivalent to [`CombinedConfig`], except that buffers cannot be provided.
+    Combined {
+        #[serde(default)]
+        paths: Vec<PathBuf>,
+        #[serde(default = "default_true")]
+        default_database: bool,
+    },
+}
+
+impl SourceSpec {
+    fn kind(&self) -> &'static str {
+        match self {
+            SourceSpec::Default => "default",
+            SourceSpec::Env => "env",
+            SourceSpec::Files { .. } => "files",
+            SourceSpec::Directory { .. } => "directory",
+            SourceSpec::Combined { .. } => "combined",
+        }
+    }
+}
+
+/// Pool limits described by a [`ConfigSpec`], which map onto [`PoolOptions`].
+///
+/// Unset limits use the [`PoolOptions`] defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsSpec {
+    pub idle_timeout_secs: Option<u64>,
+    pub max_concurrent_creations: Option<usize>,
+    pub max_cookie_age_secs: Option<u64>,
+    pub max_cookie_uses: Option<usize>,
+    pub max_size: Option<usize>,
+}
+
+impl LimitsSpec {
+    /// Converts the limits into [`PoolOptions`].
+    ///
+    /// Unlike the [`PoolOptions`] setters, limits that must be non-zero return
+    /// [`Error::InvalidLimit`] rather than panicking.
+    pub fn pool_options(&self) -> Result<PoolOptions, Error> {
+        let mut options = PoolOptions::default();
+
+        if let Some(secs) = self.idle_timeout_secs {
+            options = options.idle_timeout(Duration::from_secs(secs));
+        }
+        if let Some(max) = self.max_concurrent_creations {
+            options = options.max_concurrent_creations(non_zero("max_concurrent_creations", max)?);
+        }
+        if let Some(secs) = self.max_cookie_age_secs {
+            options = options.max_cookie_age(Duration::from_secs(secs));
+        }
+        if let Some(max) = self.max_cookie_uses {
+            options = options.max_cookie_uses(max);
+        }
+        if let Some(max) = self.max_size {
+            options = options.max_size(non_zero("max_size", max)?);
+        }
+
+        Ok(options)
+    }
+}
+
+impl TryFrom<ConfigSpec> for DefaultConfig {
+    type Error = Error;
+
+    fn try_from(spec: ConfigSpec) -> Result<Self, Self::Error> {
+        match &spec.source {
+            SourceSpec::Default => Ok(spec.apply_flags(DefaultConfig::default())),
+            source => Err(mismatch("default", source)),
+        }
+    }
+}
+
+impl TryFrom<ConfigSpec> for EnvConfig {
+    type Error = Error;
+
+    fn try_from(spec: ConfigSpec) -> Result<Self, Self::Error> {
+        match &spec.source {
+            SourceSpec::Env => Ok(spec.apply_flags(EnvConfig::default())),
+            source => Err(mismatch("env", source)),
+        }
+    }
+}
+
+impl TryFrom<ConfigSpec> for FileConfig {
+    type Error = Error;
+
+    fn try_from(spec: ConfigSpec) -> Result<Self, Self::Error> {
+        match &spec.source {
+            SourceSpec::Files {
+                paths,
+                default_database,
+            } => Ok(spec.apply_flags(
+                FileConfig::default()
+                    .with_default_database(*default_database)
+                    .with_files(paths.iter().cloned()),
+            )),
+            source => Err(mismatch("files", source)),
+        }
+    }
+}
+
+impl TryFrom<ConfigSpec> for DirectoryConfig {
+    type Error = Error;
+
+    fn try_from(spec: ConfigSpec) -> Result<Self, Self::Error> {
+        match &spec.source {
+            SourceSpec::Directory {
+                path,
+                extensions,
+                exclude,
+            } => {
+                let config = extensions
+                    .iter()
+                    .fold(DirectoryConfig::new(path), |config, ext| {
+                        config.with_extension(ext)
+                    });
+                let config = exclude
+                    .iter()
+                    .fold(config, |config, name| config.without_file(name));
+
+                Ok(spec.apply_flags(config))
+            }
+            source => Err(mismatch("directory", source)),
+        }
+    }
+}
+
+impl TryFrom<ConfigSpec> for CombinedConfig {
+    type Error = Error;
+
+    fn try_from(spec: ConfigSpec) -> Result<Self, Self::Error> {
+        match &spec.source {
+            SourceSpec::Combined {
+                paths,
+                default_database,
+            } => Ok(spec.apply_flags(
+                CombinedConfig::default()
+                    .with_default_database(*default_database)
+                    .with_files(paths.iter().cloned()),
+            )),
+            source => Err(mismatch("combined", source)),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn mismatch(expected: &'static str, source: &SourceSpec) -> Error {
+    Error::SpecSourceMismatch {
+        expected,
+        actual: source.kind(),
+    }
+}
+
+fn non_zero(name: &'static str, value: usize) -> Result<usize, Error> {
+    if value == 0 {
+        Err(Error::InvalidLimit(name))
+    } else {
+        Ok(value)
+    }
+}
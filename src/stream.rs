@@ -0,0 +1,94 @@
+use std::{
+    fmt::Debug,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_core::Stream;
+
+/// The part of a stream that wasn't consumed by [`Handle::stream`][crate::Handle::stream].
+///
+/// This yields any data from the last consumed chunk that was beyond the detection limit, and
+/// then the rest of the original stream.
+pub struct Remainder<S> {
+    pending: Option<Bytes>,
+    stream: S,
+}
+
+impl<S> Remainder<S> {
+    pub(crate) fn new(pending: Option<Bytes>, stream: S) -> Self {
+        Self { pending, stream }
+    }
+
+    /// Returns a reference to the original stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns the unconsumed data from the last consumed chunk, if any, along with the original
+    /// stream.
+    pub fn into_parts(self) -> (Option<Bytes>, S) {
+        (self.pending, self.stream)
+    }
+}
+
+impl<S> Debug for Remainder<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Remainder")
+            .field("pending", &self.pending.as_ref().map(Bytes::len))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, E> Stream for Remainder<S>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.pending.take() {
+            Some(pending) => Poll::Ready(Some(Ok(pending))),
+            None => Pin::new(&mut self.stream).poll_next(cx),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = usize::from(self.pending.is_some());
+        let (lower, upper) = self.stream.size_hint();
+        (
+            lower.saturating_add(pending),
+            upper.and_then(|upper| upper.checked_add(pending)),
+        )
+    }
+}
+
+/// Reads whole chunks from the stream until at least `limit` bytes have been read or the stream
+/// ends, returning them as a single buffer.
+///
+/// Unlike [`Handle::stream`][crate::Handle::stream], chunks are never split, so the result can be
+/// replayed in front of the rest of the stream with a [`Remainder`].
+pub(crate) async fn read_prefix<S, E>(stream: &mut S, limit: usize) -> Result<Bytes, E>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+{
+    let mut chunks = Vec::new();
+    let mut len = 0;
+    while len < limit {
+        match std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await {
+            Some(chunk) => {
+                let chunk = chunk?;
+                len += chunk.len();
+                chunks.push(chunk);
+            }
+            None => break,
+        }
+    }
+
+    Ok(if chunks.len() == 1 {
+        chunks.swap_remove(0)
+    } else {
+        chunks.concat().into()
+    })
+}
@@ -0,0 +1,232 @@
+//! The libmagic functions used within mojique.
+//!
+//! By default, these are re-exported directly from [`magic_sys`], and libmagic is linked when the
+//! binary is built. If the `dlopen` feature is enabled, libmagic is instead loaded the first time
+//! it's needed, and the functions below are resolved from it at that point.
+
+pub(crate) use magic_sys::magic_t;
+
+#[cfg(not(feature = "dlopen"))]
+pub(crate) use magic_sys::{
+    magic_buffer, magic_check, magic_close, magic_compile, magic_descriptor, magic_errno,
+    magic_error, magic_file, magic_getpath, magic_load, magic_load_buffers, magic_open,
+    magic_version,
+};
+
+#[cfg(all(not(feature = "dlopen"), feature = "v5-21"))]
+pub(crate) use magic_sys::magic_getflags;
+
+#[cfg(feature = "dlopen")]
+pub(crate) use self::dynamic::*;
+
+/// Ensures that libmagic is available.
+///
+/// When libmagic is linked at build time, this always succeeds.
+#[cfg(not(feature = "dlopen"))]
+pub(crate) fn load() -> Result<(), crate::Error> {
+    Ok(())
+}
+
+/// Ensures that libmagic provides the given function.
+///
+/// When libmagic is linked at build time, every function is known to exist, so this always
+/// succeeds.
+#[cfg(not(feature = "dlopen"))]
+pub(crate) fn require(_name: &'static str) -> Result<(), crate::Error> {
+    Ok(())
+}
+
+#[cfg(feature = "dlopen")]
+mod dynamic {
+    use std::{
+        ffi::{OsString, c_char, c_int, c_void},
+        sync::OnceLock,
+    };
+
+    use libloading::Library;
+    use magic_sys::magic_t;
+
+    use crate::Error;
+
+    /// The environment variable that can be used to override the libmagic library to load.
+    const LIBRARY_ENV: &str = "MOJIQUE_LIBMAGIC";
+
+    /// The library names to try, in order, if [`LIBRARY_ENV`] isn't set.
+    const LIBRARY_NAMES: &[&str] = &[
+        "libmagic.so.1",
+        "libmagic.so",
+        "libmagic.1.dylib",
+        "libmagic.dylib",
+    ];
+
+    // The library is never unloaded once it has been loaded, since cookies may live for the rest
+    // of the process. If loading fails, the error is kept so that it can be returned each time.
+    static LIBRARY: OnceLock<Result<Loaded, String>> = OnceLock::new();
+
+    struct Loaded {
+        // Only held to keep the resolved functions valid.
+        _library: Library,
+        functions: Functions,
+    }
+
+    // Each function is declared with the value that it returns if libmagic couldn't be loaded.
+    // Since cookies can only be created once libmagic has loaded, most of these will never be
+    // returned in practice, but they're chosen to look like libmagic errors regardless.
+    //
+    // Functions that were added to libmagic after 5.0 are optional: if the loaded library doesn't
+    // export them, the rest of libmagic remains usable, and calling them returns the fallback.
+    // Callers that need to report this should check with [`require`] first.
+    macro_rules! functions {
+        (
+            $(
+                $(#[$attr:meta])*
+                fn $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty = $fallback:expr;
+            )*
+            optional {
+                $(
+                    $(#[$opt_attr:meta])*
+                    fn $opt_name:ident($($opt_arg:ident: $opt_ty:ty),*) -> $opt_ret:ty
+                        = $opt_fallback:expr;
+                )*
+            }
+        ) => {
+            struct Functions {
+                $(
+                    $(#[$attr])*
+                    $name: unsafe extern "C" fn($($ty),*) -> $ret,
+                )*
+                $(
+                    $(#[$opt_attr])*
+                    $opt_name: Option<unsafe extern "C" fn($($opt_ty),*) -> $opt_ret>,
+                )*
+            }
+
+            impl Functions {
+                fn resolve(library: &Library) -> Result<Self, libloading::Error> {
+                    Ok(Self {
+                        $(
+                            $(#[$attr])*
+                            $name: unsafe {
+                                *library.get(concat!(stringify!($name), "\0").as_bytes())?
+                            },
+                        )*
+                        $(
+                            $(#[$opt_attr])*
+                            $opt_name: unsafe {
+                                library
+                                    .get(concat!(stringify!($opt_name), "\0").as_bytes())
+                                    .ok()
+                                    .map(|symbol| *symbol)
+                            },
+                        )*
+                    })
+                }
+
+                fn has(&self, name: &str) -> bool {
+                    match name {
+                        $(
+                            $(#[$opt_attr])*
+                            stringify!($opt_name) => self.$opt_name.is_some(),
+                        )*
+                        _ => unreachable!("{name} isn't an optional libmagic function"),
+                    }
+                }
+            }
+
+            $(
+                $(#[$attr])*
+                pub(crate) unsafe fn $name($($arg: $ty),*) -> $ret {
+                    match functions() {
+                        Ok(functions) => unsafe { (functions.$name)($($arg),*) },
+                        Err(_) => $fallback,
+                    }
+                }
+            )*
+
+            $(
+                $(#[$opt_attr])*
+                pub(crate) unsafe fn $opt_name($($opt_arg: $opt_ty),*) -> $opt_ret {
+                    match functions().map(|functions| functions.$opt_name) {
+                        Ok(Some(function)) => unsafe { function($($opt_arg),*) },
+                        _ => $opt_fallback,
+                    }
+                }
+            )*
+        };
+    }
+
+    functions! {
+        fn magic_buffer(cookie: magic_t, buffer: *const u8, length: usize) -> *const c_char
+            = std::ptr::null();
+        fn magic_check(cookie: magic_t, filename: *const c_char) -> c_int = -1;
+        fn magic_close(cookie: magic_t) -> () = ();
+        fn magic_compile(cookie: magic_t, filename: *const c_char) -> c_int = -1;
+        fn magic_descriptor(cookie: magic_t, fd: c_int) -> *const c_char = std::ptr::null();
+        fn magic_errno(cookie: magic_t) -> c_int = 0;
+        fn magic_error(cookie: magic_t) -> *const c_char = std::ptr::null();
+        fn magic_file(cookie: magic_t, filename: *const c_char) -> *const c_char
+            = std::ptr::null();
+        fn magic_load(cookie: magic_t, filename: *const c_char) -> c_int = -1;
+        fn magic_open(flags: c_int) -> magic_t = std::ptr::null_mut();
+
+        optional {
+            #[cfg(feature = "v5-21")]
+            fn magic_getflags(cookie: magic_t) -> c_int = -1;
+            fn magic_getpath(magicfile: *const c_char, action: c_int) -> *const c_char
+                = std::ptr::null();
+            fn magic_load_buffers(
+                cookie: magic_t,
+                buffers: *mut *mut c_void,
+                sizes: *mut usize,
+                nbuffers: usize
+            ) -> c_int = -1;
+            fn magic_version() -> c_int = 0;
+        }
+    }
+
+    /// Ensures that libmagic has been loaded, loading it if this is the first call.
+    pub(crate) fn load() -> Result<(), Error> {
+        functions().map(|_| ())
+    }
+
+    /// Ensures that the loaded libmagic exports the given optional function, returning
+    /// [`Error::UnsupportedFunction`] if it doesn't.
+    pub(crate) fn require(name: &'static str) -> Result<(), Error> {
+        if functions()?.has(name) {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedFunction(name))
+        }
+    }
+
+    fn functions() -> Result<&'static Functions, Error> {
+        match LIBRARY.get_or_init(open) {
+            Ok(loaded) => Ok(&loaded.functions),
+            Err(e) => Err(Error::LibraryLoad(e.clone())),
+        }
+    }
+
+    fn open() -> Result<Loaded, String> {
+        let names = match std::env::var_os(LIBRARY_ENV) {
+            Some(name) if !name.is_empty() => vec![name],
+            _ => LIBRARY_NAMES.iter().copied().map(OsString::from).collect(),
+        };
+
+        let mut errors = Vec::new();
+        for name in names {
+            let result = unsafe { Library::new(&name) }.and_then(|library| {
+                Ok(Loaded {
+                    functions: Functions::resolve(&library)?,
+                    _library: library,
+                })
+            });
+
+            match result {
+                Ok(loaded) => return Ok(loaded),
+                Err(e) => errors.push(format!("{}: {e}", name.display())),
+            }
+        }
+
+        Err(errors.join("; "))
+    }
+}
@@ -0,0 +1,238 @@
+use std::path::Path;
+
+use crate::{Detection, Error, Pool};
+
+/// Validates untrusted content, such as uploads, against a policy of allowed and denied MIME
+/// types.
+///
+/// The [`Pool`] used for detection must be configured with
+/// [`Flag::MimeType`][crate::Flag::MimeType] (or [`Flag::Mime`][crate::Flag::Mime]), since
+/// patterns are matched against the detected MIME type with [`Detection::matches`].
+///
+/// Optionally, the file name extension and `Content-Type` claimed by the uploader can also be
+/// cross-checked against the content. The result of validation is a [`ValidationReport`], which
+/// lists every [`Violation`] that was found, rather than stopping at the first.
+///
+/// ```
+/// use mojique::{Claims, Config, DefaultConfig, Flag, Validator};
+///
+/// let pool = DefaultConfig::default().set_flag(Flag::MimeType).build_pool()?;
+/// let validator = Validator::new(pool)
+///     .with_allowed(["image/*", "application/pdf"])
+///     .with_content_type_check(true);
+///
+/// let report = validator.validate(
+///     b"#!/bin/sh\n",
+///     &Claims {
+///         content_type: Some("application/pdf"),
+///         ..Default::default()
+///     },
+/// )?;
+/// assert!(!report.is_valid());
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Validator {
+    allowed: Vec<String>,
+    check_content_type: bool,
+    denied: Vec<String>,
+    extensions: Option<Pool>,
+    pool: Pool,
+}
+
+impl Validator {
+    /// Creates a new validator that performs detection using the given pool.
+    ///
+    /// By default, every MIME type is allowed, and no claims are cross-checked.
+    pub fn new(pool: Pool) -> Self {
+        Self {
+            allowed: Vec::new(),
+            check_content_type: false,
+            denied: Vec::new(),
+            extensions: None,
+            pool,
+        }
+    }
+
+    /// Adds MIME type patterns that content must match at least one of.
+    ///
+    /// If no allowed patterns are added, any MIME type is allowed, subject to
+    /// [`Validator::with_denied`]. Content that can't be identified as a MIME type never matches.
+    pub fn with_allowed<I>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.allowed.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds MIME type patterns that content must not match.
+    ///
+    /// Denied patterns take precedence over allowed patterns, so `image/svg+xml` can be denied
+    /// while still allowing `image/*`.
+    pub fn with_denied<I>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.denied.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets whether the claimed `Content-Type` is cross-checked against the detected MIME type.
+    ///
+    /// Any parameters in the claimed `Content-Type`, such as `charset`, are ignored. This
+    /// defaults to `false`.
+    pub fn with_content_type_check(mut self, check: bool) -> Self {
+        self.check_content_type = check;
+        self
+    }
+
+    /// Enables cross-checking the extension of the claimed file name against the content, using
+    /// a pool configured with [`Flag::Extension`][crate::Flag::Extension].
+    ///
+    /// libmagic doesn't know the extensions for every type that it can identify. If it doesn't
+    /// know the extensions for some content, the claimed extension isn't checked.
+    pub fn with_extension_check(mut self, pool: Pool) -> Self {
+        self.extensions = Some(pool);
+        self
+    }
+
+    /// Validates a buffer against the policy and the given claims.
+    ///
+    /// Errors are only returned if detection fails; policy violations are reported in the
+    /// returned [`ValidationReport`].
+    pub fn validate(&self, buf: &[u8], claims: &Claims) -> Result<ValidationReport, Error> {
+        let detection = Detection::from(self.pool.buffer(buf)?);
+        let mut violations = Vec::new();
+
+        if let Some(pattern) = self
+            .denied
+            .iter()
+            .find(|pattern| detection.matches(pattern))
+        {
+            violations.push(Violation::Denied {
+                pattern: pattern.clone(),
+            });
+        } else if !self.allowed.is_empty()
+            && !self
+                .allowed
+                .iter()
+                .any(|pattern| detection.matches(pattern))
+        {
+            violations.push(Violation::NotAllowed);
+        }
+
+        if self.check_content_type
+            && let Some(claimed) = claims.content_type
+        {
+            let essence = claimed.split(';').next().unwrap_or_default().trim();
+            if !detection
+                .mime_type()
+                .is_some_and(|mime_type| mime_type.eq_ignore_ascii_case(essence))
+            {
+                violations.push(Violation::ContentTypeMismatch {
+                    claimed: claimed.to_string(),
+                });
+            }
+        }
+
+        let mut extensions = Vec::new();
+        if let Some(pool) = &self.extensions {
+            // libmagic returns a slash separated list of extensions, or ??? if it doesn't know
+            // any for the content.
+            extensions = pool
+                .buffer(buf)?
+                .split('/')
+                .filter(|extension| !extension.is_empty() && *extension != "???")
+                .map(str::to_string)
+                .collect();
+
+            if let Some(claimed) = claims
+                .file_name
+                .and_then(|file_name| Path::new(file_name).extension())
+                && !extensions.is_empty()
+                && !extensions
+                    .iter()
+                    .any(|extension| claimed.eq_ignore_ascii_case(extension))
+            {
+                violations.push(Violation::ExtensionMismatch {
+                    claimed: claimed.to_string_lossy().into_owned(),
+                });
+            }
+        }
+
+        Ok(ValidationReport {
+            detection,
+            extensions,
+            violations,
+        })
+    }
+}
+
+/// The claims made about content by whoever provided it, such as the file name and
+/// `Content-Type` of an upload.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Claims<'a> {
+    /// The `Content-Type` claimed for the content.
+    pub content_type: Option<&'a str>,
+
+    /// The file name claimed for the content. Only the extension is used.
+    pub file_name: Option<&'a str>,
+}
+
+/// The result of [`Validator::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct ValidationReport {
+    detection: Detection,
+    extensions: Vec<String>,
+    violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    /// Returns the result of detection on the content.
+    pub fn detection(&self) -> &Detection {
+        &self.detection
+    }
+
+    /// Returns the extensions that libmagic expects for the content.
+    ///
+    /// This is empty if [`Validator::with_extension_check`] wasn't used, or if libmagic doesn't
+    /// know any extensions for the content.
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// Returns the policy violations that were found.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Returns `true` if no violations were found.
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// A policy violation found by a [`Validator`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum Violation {
+    /// The claimed `Content-Type` doesn't match the detected MIME type.
+    ContentTypeMismatch { claimed: String },
+
+    /// The detected MIME type matches a denied pattern.
+    Denied { pattern: String },
+
+    /// The claimed file name extension isn't one that libmagic expects for the content.
+    ExtensionMismatch { claimed: String },
+
+    /// The detected MIME type doesn't match any of the allowed patterns.
+    NotAllowed,
+}
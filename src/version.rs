@@ -0,0 +1,48 @@
+use std::{ffi::c_int, fmt::Display};
+
+/// A libmagic version.
+///
+/// libmagic reports its version as a single integer, such as `545` for 5.45, which is what
+/// [`crate::version`] returns. This splits that into its components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl Version {
+    /// Returns the version of the libmagic in use.
+    pub fn current() -> Self {
+        Self::from(crate::version())
+    }
+}
+
+impl From<c_int> for Version {
+    fn from(version: c_int) -> Self {
+        let version = version.unsigned_abs();
+        Self {
+            major: version / 100,
+            minor: version % 100,
+        }
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:02}", self.major, self.minor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version() {
+        let version = Version::from(504);
+        assert_eq!(version, Version { major: 5, minor: 4 });
+        assert_eq!(version.to_string(), "5.04");
+        assert!(version < Version::from(545));
+        assert_eq!(Version::current(), Version::from(crate::version()));
+    }
+}
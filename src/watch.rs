@@ -0,0 +1,221 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::{Error, WeakPool};
+
+/// Options for [`Pool::watch_with`][crate::Pool::watch_with].
+pub struct WatchOptions {
+    debounce: Duration,
+    on_reload: Option<Box<dyn FnMut(ReloadEvent) + Send>>,
+}
+
+impl WatchOptions {
+    /// Sets how long to wait for further changes after a database changes before reloading.
+    ///
+    /// Editors and build tools often write a file in several steps, so reloading is delayed until
+    /// no changes have been seen for this long. This defaults to 250 milliseconds.
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Sets a callback that is invoked after each reload, whether or not it succeeded.
+    ///
+    /// The callback is invoked on the thread that watches for changes, so it should return
+    /// promptly.
+    pub fn on_reload<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(ReloadEvent) + Send + 'static,
+    {
+        self.on_reload = Some(Box::new(f));
+        self
+    }
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(250),
+            on_reload: None,
+        }
+    }
+}
+
+impl Debug for WatchOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchOptions")
+            .field("debounce", &self.debounce)
+            .field("on_reload", &self.on_reload.is_some())
+            .finish()
+    }
+}
+
+/// A reload triggered by a [`Watcher`], as passed to [`WatchOptions::on_reload`].
+#[derive(Debug)]
+pub struct ReloadEvent {
+    /// The paths that changed.
+    pub paths: Vec<PathBuf>,
+
+    /// The result of [`Pool::reload`][crate::Pool::reload].
+    pub result: Result<(), Error>,
+}
+
+/// Watches the magic databases used by a [`Pool`][crate::Pool], and reloads the pool when they
+/// change.
+///
+/// This is returned by [`Pool::watch`][crate::Pool::watch]. Watching stops when the watcher is
+/// dropped, or once the pool has been dropped.
+pub struct Watcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl Debug for Watcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watcher").finish_non_exhaustive()
+    }
+}
+
+pub(crate) fn watch(
+    pool: WeakPool,
+    paths: Vec<PathBuf>,
+    options: WatchOptions,
+) -> Result<Watcher, Error> {
+    let databases = Databases::new(paths)?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(watch_error)?;
+    for dir in &databases.dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+    }
+
+    let WatchOptions {
+        debounce,
+        mut on_reload,
+    } = options;
+
+    std::thread::Builder::new()
+        .name("mojique-watcher".into())
+        .spawn(move || {
+            // The channel disconnects when the watcher is dropped, which ends the thread.
+            while let Ok(event) = rx.recv() {
+                let mut changed = BTreeSet::new();
+                databases.collect(event, &mut changed);
+                if changed.is_empty() {
+                    continue;
+                }
+
+                loop {
+                    match rx.recv_timeout(debounce) {
+                        Ok(event) => databases.collect(event, &mut changed),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                // As with the reaper, only hold a strong reference while reloading.
+                let Some(pool) = pool.upgrade() else {
+                    break;
+                };
+                let result = pool.reload();
+                drop(pool);
+
+                if let Some(on_reload) = &mut on_reload {
+                    on_reload(ReloadEvent {
+                        paths: changed.into_iter().collect(),
+                        result,
+                    });
+                }
+            }
+        })
+        .map_err(Error::Watch)?;
+
+    Ok(Watcher { _watcher: watcher })
+}
+
+/// The databases being watched.
+///
+/// Editors frequently replace files rather than modifying them in place, which would stop a
+/// watch on the file itself from seeing further changes, so the directories containing the
+/// databases are watched instead, and events are filtered down to the databases.
+struct Databases {
+    // The directories to watch.
+    dirs: BTreeSet<PathBuf>,
+
+    // Databases that are directories, within which every file is a database.
+    database_dirs: BTreeSet<PathBuf>,
+
+    // Databases that are files.
+    files: BTreeSet<PathBuf>,
+}
+
+impl Databases {
+    fn new(paths: Vec<PathBuf>) -> Result<Self, Error> {
+        let mut dirs = BTreeSet::new();
+        let mut database_dirs = BTreeSet::new();
+        let mut files = BTreeSet::new();
+        for path in paths {
+            let path = std::path::absolute(&path).map_err(Error::Watch)?;
+            if path.is_dir() {
+                dirs.insert(path.clone());
+                database_dirs.insert(path);
+                continue;
+            }
+
+            if let Some(parent) = path.parent() {
+                dirs.insert(parent.to_path_buf());
+            }
+
+            // libmagic loads the compiled version of a database in preference to the database
+            // itself, if one exists.
+            let mut compiled = path.clone().into_os_string();
+            compiled.push(".mgc");
+            files.insert(compiled.into());
+            files.insert(path);
+        }
+
+        Ok(Self {
+            dirs,
+            database_dirs,
+            files,
+        })
+    }
+
+    /// Adds any databases affected by the event to `changed`.
+    fn collect(&self, event: notify::Result<notify::Event>, changed: &mut BTreeSet<PathBuf>) {
+        // Errors from the watcher don't tell us anything about which databases changed, so the
+        // best we can do is to continue watching.
+        let Ok(event) = event else {
+            return;
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+
+        changed.extend(
+            event
+                .paths
+                .into_iter()
+                .filter(|path| self.is_database(path)),
+        );
+    }
+
+    fn is_database(&self, path: &Path) -> bool {
+        self.files.contains(path)
+            || path
+                .parent()
+                .is_some_and(|dir| self.database_dirs.contains(dir))
+    }
+}
+
+fn watch_error(e: notify::Error) -> Error {
+    Error::Watch(std::io::Error::other(e))
+}
@@ -0,0 +1,255 @@
+use std::{
+    future::{Future, Ready, ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_multipart::{Field, Multipart, MultipartError};
+use actix_web::{
+    FromRequest, HttpMessage, HttpRequest,
+    dev::{BoxedPayloadStream, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::{ErrorBadRequest, ErrorInternalServerError},
+};
+use futures_core::Stream;
+
+use crate::{BYTES_MAX, Detection, Error, Pool, Remainder};
+
+/// actix-web middleware that performs detection on request bodies using a [`Pool`], and attaches
+/// the resulting [`Detection`] to the request extensions.
+///
+/// Only the start of the body is buffered for detection, after which the entire body is replayed
+/// to the wrapped service. The detection can be retrieved in handlers with the [`Sniffed`]
+/// extractor.
+///
+/// Multipart payloads are detected as a whole by this middleware, which is rarely what's wanted,
+/// so the [`SniffedFields`] extractor can be used instead to perform detection on each field with
+/// the middleware's pool.
+///
+/// ```no_run
+/// use actix_web::{App, web};
+/// use mojique::{Config, DefaultConfig, Flag, Sniff, Sniffed};
+///
+/// async fn upload(Sniffed(detection): Sniffed, body: web::Bytes) -> String {
+///     format!("received {} bytes of {detection}", body.len())
+/// }
+///
+/// let pool = DefaultConfig::default().set_flag(Flag::MimeType).build_pool()?;
+/// let app = App::new()
+///     .wrap(Sniff::new(pool))
+///     .route("/upload", web::post().to(upload));
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sniff {
+    pool: Pool,
+}
+
+impl Sniff {
+    /// Creates the middleware, performing detection with handles from the given pool.
+    pub fn new(pool: Pool) -> Self {
+        Self { pool }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Sniff
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = SniffMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SniffMiddleware {
+            service: Rc::new(service),
+            pool: self.pool.clone(),
+        }))
+    }
+}
+
+/// The service created by the [`Sniff`] middleware.
+#[derive(Debug)]
+pub struct SniffMiddleware<S> {
+    service: Rc<S>,
+    pool: Pool,
+}
+
+impl<S, B> Service<ServiceRequest> for SniffMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let pool = self.pool.clone();
+
+        Box::pin(async move {
+            req.extensions_mut().insert(SniffPool(pool.clone()));
+
+            let (detection, remainder) = match pool.sniff_stream(req.take_payload()).await {
+                Ok(result) => result,
+                Err(e @ Error::ReadInput(_)) => return Err(ErrorBadRequest(e)),
+                Err(e) => return Err(ErrorInternalServerError(e)),
+            };
+
+            req.extensions_mut().insert(detection);
+            req.set_payload(Payload::from(Box::pin(remainder) as BoxedPayloadStream));
+            service.call(req).await
+        })
+    }
+}
+
+/// An actix-web extractor for the [`Detection`] attached to the request by the [`Sniff`]
+/// middleware.
+///
+/// Extraction fails with `500 Internal Server Error` if the middleware isn't in use.
+#[derive(Debug, Clone)]
+pub struct Sniffed(pub Detection);
+
+impl FromRequest for Sniffed {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Detection>()
+                .cloned()
+                .map(Self)
+                .ok_or_else(|| {
+                    ErrorInternalServerError("request body wasn't sniffed; is Sniff in use?")
+                }),
+        )
+    }
+}
+
+/// An actix-web extractor that walks the fields of a `multipart/form-data` request body,
+/// performing detection on the content of each field as it's reached, using the pool of the
+/// [`Sniff`] middleware.
+///
+/// Each field is returned as a [`Remainder`] that replays the data buffered for detection,
+/// followed by the rest of the field, and the original [`Field`] is available through
+/// [`Remainder::get_ref`]. The detections are also attached to the request extensions as
+/// [`FieldDetections`] as each field is reached, so that middleware can inspect them once the
+/// handler has run.
+///
+/// Extraction fails with `500 Internal Server Error` if the middleware isn't in use.
+///
+/// ```no_run
+/// use actix_web::error::ErrorBadRequest;
+/// use mojique::SniffedFields;
+///
+/// async fn upload(mut fields: SniffedFields) -> actix_web::Result<String> {
+///     let mut types = Vec::new();
+///     while let Some((field, detection)) = fields.next_field().await.map_err(ErrorBadRequest)? {
+///         types.push(format!("{:?}: {detection}", field.get_ref().name()));
+///     }
+///     Ok(types.join("\n"))
+/// }
+/// ```
+pub struct SniffedFields {
+    budget: usize,
+    multipart: Multipart,
+    pool: Pool,
+    req: HttpRequest,
+}
+
+impl SniffedFields {
+    /// Sets the number of bytes of each field that are buffered and used for detection, as per
+    /// [`SniffFields::with_budget`][crate::SniffFields::with_budget].
+    ///
+    /// By default, up to [`BYTES_MAX`] bytes of each field are used for detection.
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Returns the next field, along with the result of detection on its content, or `None` if
+    /// there are no more fields.
+    ///
+    /// The previous field must be dropped before this is called. Errors from the multipart
+    /// stream, including a request that isn't `multipart/form-data`, are returned as
+    /// [`Error::ReadInput`].
+    pub async fn next_field(&mut self) -> Result<Option<(Remainder<Field>, Detection)>, Error> {
+        let next = std::future::poll_fn(|cx| Pin::new(&mut self.multipart).poll_next(cx)).await;
+        let Some(mut field) = next.transpose().map_err(read_input)? else {
+            return Ok(None);
+        };
+
+        let prefix = crate::stream::read_prefix(&mut field, self.budget)
+            .await
+            .map_err(read_input)?;
+        let detection = Detection::from(
+            self.pool
+                .buffer_async(prefix.slice(..prefix.len().min(self.budget)))
+                .await?,
+        );
+
+        let entry = (field.name().map(str::to_string), detection.clone());
+        let mut extensions = self.req.extensions_mut();
+        match extensions.get_mut::<FieldDetections>() {
+            Some(fields) => fields.0.push(entry),
+            None => {
+                extensions.insert(FieldDetections(vec![entry]));
+            }
+        }
+
+        Ok(Some((Remainder::new(Some(prefix), field), detection)))
+    }
+}
+
+impl std::fmt::Debug for SniffedFields {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SniffedFields")
+            .field("budget", &self.budget)
+            .field("pool", &self.pool)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FromRequest for SniffedFields {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let Some(SniffPool(pool)) = req.extensions().get::<SniffPool>().cloned() else {
+            return ready(Err(ErrorInternalServerError(
+                "request wasn't sniffed; is Sniff in use?",
+            )));
+        };
+
+        ready(Ok(Self {
+            budget: BYTES_MAX,
+            multipart: Multipart::new(req.headers(), payload.take()),
+            pool,
+            req: req.clone(),
+        }))
+    }
+}
+
+/// The name and detection of each multipart field walked by [`SniffedFields`], in the order that
+/// they were reached, which is attached to the request extensions.
+#[derive(Debug, Clone, Default)]
+pub struct FieldDetections(pub Vec<(Option<String>, Detection)>);
+
+/// The pool of the [`Sniff`] middleware, attached to the request extensions for
+/// [`SniffedFields`].
+#[derive(Clone)]
+struct SniffPool(Pool);
+
+fn read_input(e: MultipartError) -> Error {
+    // Multipart errors aren't necessarily Send, so only the message is kept.
+    Error::ReadInput(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        e.to_string(),
+    ))
+}
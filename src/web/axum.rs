@@ -0,0 +1,191 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRef, FromRequest, Request, rejection::BytesRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use thiserror::Error;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{Detection, Error, Pool};
+
+/// An axum extractor that buffers the request body and performs detection on it, using the
+/// [`Pool`] in the application state.
+///
+/// The pool must be extractable from the state via [`FromRef`], and the body is subject to axum's
+/// usual body limit.
+///
+/// ```no_run
+/// use axum::{Router, routing::post};
+/// use mojique::{Config, DefaultConfig, Detected, Flag};
+///
+/// async fn upload(Detected { data, detection }: Detected) -> String {
+///     format!("received {} bytes of {detection}", data.len())
+/// }
+///
+/// let pool = DefaultConfig::default().set_flag(Flag::MimeType).build_pool()?;
+/// let app: Router = Router::new().route("/upload", post(upload)).with_state(pool);
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Detected<T = Bytes> {
+    /// The request body.
+    pub data: T,
+
+    /// The result of detection on the request body.
+    pub detection: Detection,
+}
+
+impl<S> FromRequest<S> for Detected<Bytes>
+where
+    S: Send + Sync,
+    Pool: FromRef<S>,
+{
+    type Rejection = DetectedRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let data = Bytes::from_request(req, state).await?;
+        let detection = Pool::from_ref(state)
+            .buffer_async(data.clone())
+            .await
+            .map_err(DetectedRejection::Detect)?;
+
+        Ok(Self {
+            data,
+            detection: detection.into(),
+        })
+    }
+}
+
+/// The rejection returned by the [`Detected`] extractor.
+#[derive(Debug, Error)]
+pub enum DetectedRejection {
+    #[error(transparent)]
+    Body(#[from] BytesRejection),
+
+    #[error("detecting request body: {0}")]
+    Detect(#[source] Error),
+}
+
+impl IntoResponse for DetectedRejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Body(rejection) => rejection.into_response(),
+            Self::Detect(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+            }
+        }
+    }
+}
+
+/// A [`Layer`] that performs detection on request bodies, rejecting any request whose detected
+/// MIME type doesn't match one of the allowed patterns with `415 Unsupported Media Type`.
+///
+/// Patterns are matched with [`Detection::matches`], so the pool should be configured with
+/// [`Flag::MimeType`][crate::Flag::MimeType].
+///
+/// Only the start of the body is buffered for detection, after which the entire body is passed
+/// on to the inner service unchanged. The [`Detection`] is also inserted into the request
+/// extensions, so handlers can retrieve it with `Extension<Detection>`.
+///
+/// ```no_run
+/// use axum::{Router, routing::post};
+/// use mojique::{AllowlistLayer, Config, DefaultConfig, Flag};
+///
+/// let pool = DefaultConfig::default().set_flag(Flag::MimeType).build_pool()?;
+/// let app: Router = Router::new()
+///     .route("/images", post(|| async { "thanks" }))
+///     .layer(AllowlistLayer::new(pool, ["image/*"]));
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct AllowlistLayer {
+    pool: Pool,
+    allowed: Arc<[String]>,
+}
+
+impl AllowlistLayer {
+    /// Creates a new layer that performs detection with the given pool, and allows requests that
+    /// match any of the given MIME type patterns.
+    pub fn new<I>(pool: Pool, allowed: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self {
+            pool,
+            allowed: allowed.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl<S> Layer<S> for AllowlistLayer {
+    type Service = Allowlist<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Allowlist {
+            inner,
+            pool: self.pool.clone(),
+            allowed: self.allowed.clone(),
+        }
+    }
+}
+
+/// The service created by [`AllowlistLayer`].
+#[derive(Debug, Clone)]
+pub struct Allowlist<S> {
+    inner: S,
+    pool: Pool,
+    allowed: Arc<[String]>,
+}
+
+impl<S> Service<Request> for Allowlist<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // The inner service is the one that was driven to readiness, so we need to take it rather
+        // than a fresh clone.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let pool = self.pool.clone();
+        let allowed = self.allowed.clone();
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+            let (detection, remainder) = match pool.sniff_stream(body.into_data_stream()).await {
+                Ok(result) => result,
+                Err(e @ Error::ReadInput(_)) => {
+                    return Ok((StatusCode::BAD_REQUEST, e.to_string()).into_response());
+                }
+                Err(e) => {
+                    return Ok((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response());
+                }
+            };
+            if !allowed.iter().any(|pattern| detection.matches(pattern)) {
+                return Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+            }
+
+            parts.extensions.insert(detection);
+            let body = Body::from_stream(remainder);
+            inner.call(Request::from_parts(parts, body)).await
+        })
+    }
+}
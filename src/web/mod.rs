@@ -0,0 +1,8 @@
+//! Integrations with web frameworks, each behind a feature of the same name.
+
+#[cfg(feature = "actix")]
+pub(crate) mod actix;
+#[cfg(feature = "axum")]
+pub(crate) mod axum;
+#[cfg(feature = "rocket")]
+pub(crate) mod rocket;
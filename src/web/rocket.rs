@@ -0,0 +1,146 @@
+use std::io;
+
+use rocket::{
+    Request,
+    data::{self, Data, FromData, ToByteUnit},
+    http::Status,
+    outcome::Outcome,
+};
+use thiserror::Error;
+
+use crate::{Detection, Error, Pool};
+
+/// A Rocket data guard that reads a request body of up to `MAX` bytes and performs detection on
+/// it, using the [`Pool`] managed by Rocket.
+///
+/// If [`AllowedTypes`] is also managed, bodies whose detected MIME type doesn't match one of the
+/// allowed patterns are rejected with `415 Unsupported Media Type`. Bodies larger than `MAX` are
+/// rejected with `413 Payload Too Large`.
+///
+/// ```no_run
+/// use mojique::{AllowedTypes, Config, DefaultConfig, Flag, TypedData};
+/// use rocket::{post, routes};
+///
+/// #[post("/upload", data = "<upload>")]
+/// fn upload(upload: TypedData<{ 1024 * 1024 }>) -> String {
+///     format!("received {} bytes of {}", upload.data.len(), upload.detection)
+/// }
+///
+/// let pool = DefaultConfig::default().set_flag(Flag::MimeType).build_pool()?;
+/// let rocket = rocket::build()
+///     .manage(pool)
+///     .manage(AllowedTypes::new(["image/*"]))
+///     .mount("/", routes![upload]);
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct TypedData<const MAX: usize> {
+    /// The request body.
+    pub data: Vec<u8>,
+
+    /// The result of detection on the request body.
+    pub detection: Detection,
+}
+
+#[rocket::async_trait]
+impl<'r, const MAX: usize> FromData<'r> for TypedData<MAX> {
+    type Error = TypedDataError;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        match Self::read(req, data).await {
+            Ok(typed) => Outcome::Success(typed),
+            Err(e) => Outcome::Error((e.status(), e)),
+        }
+    }
+}
+
+impl<const MAX: usize> TypedData<MAX> {
+    async fn read(req: &Request<'_>, data: Data<'_>) -> Result<Self, TypedDataError> {
+        let pool = req
+            .rocket()
+            .state::<Pool>()
+            .ok_or(TypedDataError::NoPool)?
+            .clone();
+
+        let capped = data
+            .open(MAX.bytes())
+            .into_bytes()
+            .await
+            .map_err(TypedDataError::Read)?;
+        if !capped.is_complete() {
+            return Err(TypedDataError::TooLarge(MAX));
+        }
+
+        // The data is moved into the blocking task and back again, rather than being copied.
+        let (description, data) = tokio::task::spawn_blocking(move || {
+            let data = capped.into_inner();
+            (pool.buffer(&data), data)
+        })
+        .await
+        .map_err(|_| TypedDataError::Detect(Error::TaskJoin))?;
+        let detection = Detection::from(description.map_err(TypedDataError::Detect)?);
+
+        if let Some(allowed) = req.rocket().state::<AllowedTypes>()
+            && !allowed.allows(&detection)
+        {
+            return Err(TypedDataError::NotAllowed(detection));
+        }
+
+        Ok(Self { data, detection })
+    }
+}
+
+/// The MIME type patterns accepted by [`TypedData`].
+///
+/// This should be managed by Rocket alongside the [`Pool`]; if it isn't, any type is accepted.
+/// Patterns are matched with [`Detection::matches`], so the pool should be configured with
+/// [`Flag::MimeType`][crate::Flag::MimeType].
+#[derive(Debug, Clone)]
+pub struct AllowedTypes(Vec<String>);
+
+impl AllowedTypes {
+    /// Creates a new set of allowed MIME type patterns.
+    pub fn new<I>(patterns: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self(patterns.into_iter().map(Into::into).collect())
+    }
+
+    /// Returns `true` if the detection matches any of the allowed patterns.
+    pub fn allows(&self, detection: &Detection) -> bool {
+        self.0.iter().any(|pattern| detection.matches(pattern))
+    }
+}
+
+/// The error returned by the [`TypedData`] data guard.
+#[derive(Debug, Error)]
+pub enum TypedDataError {
+    #[error("detecting request body: {0}")]
+    Detect(#[source] Error),
+
+    #[error("detected type {0} is not allowed")]
+    NotAllowed(Detection),
+
+    #[error("no mojique Pool is being managed")]
+    NoPool,
+
+    #[error("reading request body: {0}")]
+    Read(#[source] io::Error),
+
+    #[error("request body is larger than {0} bytes")]
+    TooLarge(usize),
+}
+
+impl TypedDataError {
+    /// Returns the HTTP status that the error results in.
+    pub fn status(&self) -> Status {
+        match self {
+            Self::Detect(_) | Self::NoPool => Status::InternalServerError,
+            Self::NotAllowed(_) => Status::UnsupportedMediaType,
+            Self::Read(_) => Status::BadRequest,
+            Self::TooLarge(_) => Status::PayloadTooLarge,
+        }
+    }
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "actix")]
+
+use actix_web::{
+    App, HttpMessage, HttpRequest,
+    error::ErrorBadRequest,
+    http::StatusCode,
+    test::{self, TestRequest},
+    web,
+};
+use futures::TryStreamExt;
+use insta::assert_snapshot;
+use mojique::{Config, DefaultConfig, FieldDetections, Flag, Sniff, Sniffed, SniffedFields};
+
+async fn upload(Sniffed(detection): Sniffed, body: web::Bytes) -> String {
+    format!("{} {detection}", body.len())
+}
+
+async fn fields(req: HttpRequest, mut fields: SniffedFields) -> actix_web::Result<String> {
+    let mut lines = Vec::new();
+    while let Some((field, detection)) = fields.next_field().await.map_err(ErrorBadRequest)? {
+        // The whole field should still be available after detection.
+        let name = field.get_ref().name().unwrap_or_default().to_string();
+        let len = field
+            .try_fold(0, |len, chunk| async move { Ok(len + chunk.len()) })
+            .await?;
+        lines.push(format!("{name} {len} {detection}"));
+    }
+
+    // As should the detections, via the request extensions.
+    let extensions = req.extensions();
+    let recorded = extensions
+        .get::<FieldDetections>()
+        .expect("field detections");
+    lines.push(format!("{} recorded", recorded.0.len()));
+
+    Ok(lines.join("\n"))
+}
+
+#[actix_web::test]
+async fn sniff() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+    let app = test::init_service(
+        App::new()
+            .wrap(Sniff::new(pool))
+            .route("/", web::post().to(upload)),
+    )
+    .await;
+
+    // The body should be replayed in its entirety after detection.
+    let request = TestRequest::post()
+        .uri("/")
+        .set_payload("#!/bin/sh\n")
+        .to_request();
+    let body = test::call_and_read_body(&app, request).await;
+    assert_snapshot!(std::str::from_utf8(&body)?, @"10 text/x-shellscript");
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn sniffed_without_middleware() -> anyhow::Result<()> {
+    let app = test::init_service(App::new().route("/", web::post().to(upload))).await;
+
+    let request = TestRequest::post().uri("/").set_payload("").to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    Ok(())
+}
+
+#[actix_web::test]
+async fn sniffed_fields() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+    let app = test::init_service(
+        App::new()
+            .wrap(Sniff::new(pool))
+            .route("/", web::post().to(fields)),
+    )
+    .await;
+
+    let body = concat!(
+        "--boundary\r\n",
+        "Content-Disposition: form-data; name=\"script\"; filename=\"a.txt\"\r\n",
+        "Content-Type: text/plain\r\n\r\n",
+        "#!/bin/sh\n\r\n",
+        "--boundary\r\n",
+        "Content-Disposition: form-data; name=\"document\"; filename=\"b.txt\"\r\n",
+        "Content-Type: text/plain\r\n\r\n",
+        "%PDF-1.4\n\r\n",
+        "--boundary--\r\n",
+    );
+    let request = TestRequest::post()
+        .uri("/")
+        .insert_header(("Content-Type", "multipart/form-data; boundary=boundary"))
+        .set_payload(body)
+        .to_request();
+    let body = test::call_and_read_body(&app, request).await;
+    assert_snapshot!(std::str::from_utf8(&body)?, @r"
+    script 10 text/x-shellscript
+    document 9 application/pdf
+    2 recorded
+    ");
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "axum")]
+
+use std::future::poll_fn;
+
+use axum::{
+    Extension, Router,
+    body::{Body, Bytes, to_bytes},
+    http::{Request, StatusCode},
+    response::Response,
+    routing::post,
+};
+use insta::assert_snapshot;
+use mojique::{AllowlistLayer, Config, DefaultConfig, Detected, Detection, Flag, Pool};
+use tower_service::Service;
+
+async fn send(app: &mut Router, body: &'static [u8]) -> anyhow::Result<(StatusCode, String)> {
+    poll_fn(|cx| <Router as Service<Request<Body>>>::poll_ready(app, cx)).await?;
+    let response: Response = app.call(Request::post("/").body(Body::from(body))?).await?;
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await?;
+
+    Ok((status, String::from_utf8(body.to_vec())?))
+}
+
+fn pool() -> anyhow::Result<Pool> {
+    Ok(DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?)
+}
+
+#[tokio::test]
+async fn detected() -> anyhow::Result<()> {
+    async fn upload(Detected { data, detection }: Detected) -> String {
+        format!("{} {detection}", data.len())
+    }
+
+    let mut app = Router::new().route("/", post(upload)).with_state(pool()?);
+
+    let (status, body) = send(&mut app, b"#!/bin/sh\n").await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_snapshot!(body, @"10 text/x-shellscript");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn allowlist() -> anyhow::Result<()> {
+    async fn upload(Extension(detection): Extension<Detection>, body: Bytes) -> String {
+        format!("{} {detection}", body.len())
+    }
+
+    let mut app = Router::new()
+        .route("/", post(upload))
+        .layer(AllowlistLayer::new(pool()?, ["text/*", "application/pdf"]));
+
+    // Allowed requests should have their entire body passed through.
+    let (status, body) = send(&mut app, b"#!/bin/sh\n").await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_snapshot!(body, @"10 text/x-shellscript");
+
+    let (status, body) = send(&mut app, b"%PDF-1.4\n").await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_snapshot!(body, @"9 application/pdf");
+
+    // Anything else should be rejected.
+    let (status, _) = send(&mut app, b"GIF89a").await?;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use common::*;
+use mojique::{Backend, Config, DefaultConfig, Error, Flag};
+
+mod common;
+
+fn backends() -> anyhow::Result<Vec<Box<dyn Backend>>> {
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+
+    Ok(vec![
+        Box::new(pool),
+        #[cfg(feature = "infer")]
+        Box::new(mojique::InferBackend),
+    ])
+}
+
+#[test]
+fn backend() -> anyhow::Result<()> {
+    for backend in backends()? {
+        let name = backend.name();
+        assert_eq!(
+            backend.buffer(b"%PDF-1.4\n")?.description(),
+            "application/pdf",
+            "{name}"
+        );
+        assert_eq!(
+            backend.buffer(b"")?.description(),
+            "application/x-empty",
+            "{name}"
+        );
+        assert!(
+            backend
+                .file(&manifest_dir().join("tests/data/LICENSE.zst"))?
+                .matches("application/*"),
+            "{name}"
+        );
+        assert!(
+            matches!(
+                backend.file(&manifest_dir().join("tests/data/missing")),
+                Err(Error::Detect { .. })
+            ),
+            "{name}"
+        );
+    }
+
+    Ok(())
+}
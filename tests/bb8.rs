@@ -0,0 +1,23 @@
+#![cfg(feature = "bb8")]
+
+use bb8::Pool;
+use mojique::{DefaultConfig, Manager};
+
+#[tokio::test]
+async fn bb8() -> anyhow::Result<()> {
+    let pool = Pool::builder()
+        .max_size(2)
+        .test_on_check_out(true)
+        .build(Manager::new(DefaultConfig::default())?)
+        .await?;
+
+    let mut handle = pool.get().await?;
+    assert_eq!(handle.buffer(b"")?, "empty");
+    drop(handle);
+
+    // Checking the handle out again should validate it, and leave it usable.
+    let mut handle = pool.get().await?;
+    assert_eq!(handle.buffer(b"")?, "empty");
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use common::*;
+use insta::assert_debug_snapshot;
+use mojique::{Backend, CachedPool, Config, DefaultConfig, Flag};
+
+mod common;
+
+#[test]
+fn cached() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+    let cache = CachedPool::new(pool.clone());
+    assert!(cache.is_empty());
+
+    let license = manifest_dir().join("tests/data/LICENSE.zst");
+    assert_eq!(
+        cache.buffer(b"%PDF-1.4\n")?.description(),
+        "application/pdf"
+    );
+    assert_eq!(cache.file(&license)?.description(), "application/zstd");
+    assert_eq!(cache.len(), 2);
+
+    // Once the pool is closed, only cached detections can be returned.
+    pool.close()?;
+    assert_eq!(
+        cache.buffer(b"%PDF-1.4\n")?.description(),
+        "application/pdf"
+    );
+    assert_eq!(cache.file(&license)?.description(), "application/zstd");
+    assert_debug_snapshot!(cache.buffer(b"#!/bin/sh\n").expect_err("pool is closed"), @"PoolClosed");
+
+    // Clones share the same cache.
+    let cloned = cache.clone();
+    assert_eq!(
+        Backend::buffer(&cloned, b"%PDF-1.4\n")?.description(),
+        "application/pdf"
+    );
+
+    cache.clear();
+    assert!(cloned.is_empty());
+    assert!(cache.buffer(b"%PDF-1.4\n").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn errors() -> anyhow::Result<()> {
+    let cache = CachedPool::new(DefaultConfig::default().build_pool()?);
+
+    assert!(
+        cache
+            .file(manifest_dir().join("tests/data/missing"))
+            .is_err()
+    );
+    assert!(cache.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn modified() -> anyhow::Result<()> {
+    let cache = CachedPool::new(
+        DefaultConfig::default()
+            .set_flag(Flag::MimeType)
+            .build_pool()?,
+    );
+
+    let dir = TempDir::new("cache")?;
+    let path = dir.join("file");
+
+    std::fs::write(&path, b"%PDF-1.4\n")?;
+    assert_eq!(cache.file(&path)?.description(), "application/pdf");
+
+    // The size changes, so the cached detection can't be used.
+    std::fs::write(&path, b"#!/bin/sh\n\n")?;
+    assert_eq!(cache.file(&path)?.description(), "text/x-shellscript");
+
+    Ok(())
+}
+
+#[test]
+fn capacity() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+
+    let cache = CachedPool::new(pool.clone()).with_capacity(1);
+    cache.buffer(b"%PDF-1.4\n")?;
+    cache.buffer(b"#!/bin/sh\n")?;
+    assert_eq!(cache.len(), 1);
+
+    let cache = CachedPool::new(pool).with_capacity(0);
+    cache.buffer(b"%PDF-1.4\n")?;
+    assert!(cache.is_empty());
+
+    Ok(())
+}
+
+#[cfg(not(feature = "moka"))]
+#[test]
+fn least_recently_used() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+    let cache = CachedPool::new(pool.clone()).with_capacity(2);
+
+    cache.buffer(b"%PDF-1.4\n")?;
+    cache.buffer(b"#!/bin/sh\n")?;
+    cache.buffer(b"%PDF-1.4\n")?;
+    cache.buffer(b"")?;
+
+    // The shell script was used least recently, so it should have been evicted.
+    pool.close()?;
+    assert!(cache.buffer(b"%PDF-1.4\n").is_ok());
+    assert!(cache.buffer(b"").is_ok());
+    assert!(cache.buffer(b"#!/bin/sh\n").is_err());
+
+    Ok(())
+}
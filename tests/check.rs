@@ -0,0 +1,35 @@
+use common::*;
+use mojique::{BufferConfig, Config, Error, FileConfig};
+
+mod common;
+
+#[test]
+fn check() -> anyhow::Result<()> {
+    let custom = manifest_dir().join("tests/data/custom.magic");
+    let invalid = manifest_dir().join("tests/data/invalid-magic");
+
+    assert_eq!(FileConfig::default().with_file(&custom).check()?, vec![]);
+
+    let warnings = FileConfig::default()
+        .with_file(&custom)
+        .with_file(&invalid)
+        .check()?;
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].path.as_deref(), Some(invalid.as_path()));
+    assert_eq!(warnings[0].line, Some(3));
+    assert!(warnings[0].message.contains("strnig"));
+
+    // Checking only the invalid file should fail, since libmagic rejects the whole file, but the
+    // warnings should still be available.
+    match FileConfig::default().with_file(&invalid).check() {
+        Err(Error::Check { warnings, .. }) => assert_eq!(warnings.len(), 1),
+        other => panic!("unexpected result: {other:?}"),
+    }
+
+    assert!(matches!(
+        BufferConfig::default().with_buffer(b"x").check(),
+        Err(Error::CheckBuffers)
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+use common::*;
+
+mod common;
+
+fn mojique(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mojique"))
+        .args(args)
+        .current_dir(manifest_dir().join("tests/data"))
+        .output()
+        .expect("running mojique")
+}
+
+#[test]
+fn describe() {
+    let output = mojique(&["--mime-type", "custom.magic", "LICENSE.zst"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "custom.magic: text/plain\nLICENSE.zst: application/zstd\n"
+    );
+
+    // Short options can be combined, and -m takes a value.
+    let output = mojique(&["-bm", "custom.magic", "--mime-type", "custom.magic"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "text/plain\n");
+}
+
+#[test]
+fn formats() {
+    let output = mojique(&[
+        "--mime-type",
+        "--format",
+        "json",
+        "custom.magic",
+        "LICENSE.zst",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        r#"[
+  {"path":"custom.magic","description":"text/plain","mime_type":"text/plain"},
+  {"path":"LICENSE.zst","description":"application/zstd","mime_type":"application/zstd"}
+]
+"#
+    );
+
+    let output = mojique(&["--mime-type", "--format=jsonl", "custom.magic"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{\"path\":\"custom.magic\",\"description\":\"text/plain\",\"mime_type\":\"text/plain\"}\n"
+    );
+
+    let output = mojique(&["--mime-type", "--format", "csv", "custom.magic"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "path,description,mime_type,prefix\r\ncustom.magic,text/plain,text/plain,false\r\n"
+    );
+
+    let output = mojique(&["--mime-type", "-0", "custom.magic", "LICENSE.zst"]);
+    assert_eq!(
+        output.stdout,
+        b"custom.magic\0text/plain\0LICENSE.zst\0application/zstd\0"
+    );
+
+    // Errors are included in the JSON formats, rather than reported.
+    let output = mojique(&["--format", "jsonl", "missing"]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout).starts_with("{\"path\":\"missing\",\"error\":")
+    );
+    assert!(output.stderr.is_empty());
+
+    let output = mojique(&["--format", "csv", "-0", "custom.magic"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn files_from() -> anyhow::Result<()> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mojique"))
+        .args(["--mime-type", "--files0-from", "-", "nested.zip"])
+        .current_dir(manifest_dir().join("tests/data"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(b"custom.magic\0LICENSE.zst\0")?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "nested.zip: application/zip\ncustom.magic: text/plain\nLICENSE.zst: application/zstd\n"
+    );
+
+    let output = mojique(&["--files-from", "missing"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn recursive() {
+    let output = mojique(&["-r", "--threads", "2", "--mime-type", "."]);
+    assert!(output.status.success());
+
+    // Results are in the order that detection completes.
+    let mut lines: Vec<_> = std::str::from_utf8(&output.stdout)
+        .expect("output is UTF-8")
+        .lines()
+        .filter(|line| line.contains(".zip") || line.contains("custom.magic"))
+        .collect();
+    lines.sort_unstable();
+    assert_eq!(
+        lines,
+        [
+            "./archive.zip: application/zip",
+            "./custom.magic: text/plain",
+            "./nested.zip: application/zip",
+        ]
+    );
+
+    assert!(
+        !mojique(&["--threads", "2", "custom.magic"])
+            .status
+            .success()
+    );
+    assert!(!mojique(&["-r", "-"]).status.success());
+}
+
+#[test]
+fn expect() {
+    let output = mojique(&["--expect", "text/*", "custom.magic"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "custom.magic: text/plain\n"
+    );
+
+    let output = mojique(&["--expect", "text/*", "custom.magic", "archive.zip"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "mojique: archive.zip: application/zip doesn't match any expected type\n"
+    );
+
+    let output = mojique(&[
+        "--expect",
+        "text/*",
+        "--expect=application/zip",
+        "custom.magic",
+        "archive.zip",
+    ]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn errors() {
+    // Missing files are reported, but don't stop the remaining files from being described.
+    let output = mojique(&["--mime-type", "missing", "custom.magic"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "custom.magic: text/plain\n"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing"));
+
+    let output = mojique(&["-x", "custom.magic"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("unknown option: -x\n"));
+}
@@ -0,0 +1,40 @@
+use common::*;
+use insta::assert_snapshot;
+use mojique::{CombinedConfig, Config, FileConfig};
+
+mod common;
+
+#[test]
+fn combined() -> anyhow::Result<()> {
+    let custom = manifest_dir().join("tests/data/custom.magic");
+    let zstd = manifest_dir().join("tests/data/LICENSE.zst");
+
+    // With the default database, both our custom rules and the stock rules should be used.
+    let mut handle = CombinedConfig::default()
+        .with_file(&custom)
+        .build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+    assert_snapshot!(handle.file(&zstd)?, @"Zstandard compressed data (v0.8+), Dictionary ID: None");
+
+    // Whereas without, only our custom rules should be used, which is equivalent to a
+    // FileConfig.
+    let mut handle = CombinedConfig::default()
+        .with_default_database(false)
+        .with_file(&custom)
+        .build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+    assert_snapshot!(handle.file(&zstd)?, @"data");
+
+    let mut handle = FileConfig::default().with_file(&custom).build_handle()?;
+    assert_snapshot!(handle.file(&zstd)?, @"data");
+
+    // FileConfig can also opt into the default database.
+    let mut handle = FileConfig::default()
+        .with_default_database(true)
+        .with_file(&custom)
+        .build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+    assert_snapshot!(handle.file(&zstd)?, @"Zstandard compressed data (v0.8+), Dictionary ID: None");
+
+    Ok(())
+}
@@ -1,6 +1,13 @@
 #![allow(dead_code)]
 
-use std::{path::Path, sync::LazyLock};
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{
+        LazyLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
 static MANIFEST_DIR: LazyLock<&'static Path> =
     LazyLock::new(|| Path::new(env!("CARGO_MANIFEST_DIR")));
@@ -8,3 +15,45 @@ static MANIFEST_DIR: LazyLock<&'static Path> =
 pub(crate) fn manifest_dir() -> &'static Path {
     &MANIFEST_DIR
 }
+
+/// A temporary directory that's removed when dropped.
+///
+/// Each directory is named after the process and a counter, so tests can use the same name
+/// without colliding with each other, whether they run in the same test binary or not.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new(name: &str) -> std::io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "mojique-{name}-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path)?;
+
+        Ok(Self(path))
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // Failing to clean up shouldn't fail the test, and may be because the test already has.
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
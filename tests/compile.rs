@@ -0,0 +1,132 @@
+use common::*;
+use insta::assert_snapshot;
+use mojique::{BufferConfig, Config, Error, FileConfig};
+
+mod common;
+
+#[test]
+fn compile() -> anyhow::Result<()> {
+    let custom = manifest_dir().join("tests/data/custom.magic");
+    let output_dir = TempDir::new("compile")?;
+    let cwd = std::env::current_dir()?;
+
+    let compiled = mojique::compile([&custom], &output_dir)?;
+    assert_eq!(compiled, vec![output_dir.join("custom.magic.mgc")]);
+    assert_eq!(std::env::current_dir()?, cwd);
+
+    // The compiled database should be loadable from a buffer, which requires it to be compiled.
+    let mut handle = BufferConfig::default()
+        .with_owned_buffer(std::fs::read(&compiled[0])?)
+        .build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+
+    assert!(matches!(
+        mojique::compile([custom.with_file_name("missing.magic")], &output_dir),
+        Err(Error::Compile { path, .. }) if path.ends_with("missing.magic")
+    ));
+    assert_eq!(std::env::current_dir()?, cwd);
+
+    Ok(())
+}
+
+#[test]
+fn compile_cache() -> anyhow::Result<()> {
+    let custom = manifest_dir().join("tests/data/custom.magic");
+    let cache_dir = TempDir::new("compile-cache")?;
+
+    let config = FileConfig::default()
+        .with_file(&custom)
+        .with_compile_cache_dir(cache_dir.to_path_buf());
+    let mut handle = config.build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+
+    // There should now be exactly one compiled database in the cache, which is reused when
+    // building again.
+    let entries = || -> anyhow::Result<Vec<_>> {
+        Ok(std::fs::read_dir(&cache_dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?)
+    };
+    let cached = entries()?;
+    assert_eq!(cached.len(), 1);
+    assert!(cached[0].join("custom.magic.mgc").is_file());
+
+    let pool = config.build_pool()?;
+    assert_snapshot!(pool.buffer(b"MOJIQUE")?, @"mojique test data");
+    assert_eq!(entries()?, cached);
+
+    // The same contents under a different name get their own entry, since the compiled database
+    // is named after the source file.
+    let source_dir = TempDir::new("compile-source")?;
+    let renamed = source_dir.join("renamed.magic");
+    std::fs::copy(&custom, &renamed)?;
+    let mut handle = FileConfig::default()
+        .with_file(&renamed)
+        .with_compile_cache_dir(cache_dir.to_path_buf())
+        .build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+    let cached = entries()?;
+    assert_eq!(cached.len(), 2);
+    assert!(
+        cached
+            .iter()
+            .any(|entry| entry.join("renamed.magic.mgc").is_file())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn buffered_loading() -> anyhow::Result<()> {
+    let custom = manifest_dir().join("tests/data/custom.magic");
+    let output_dir = TempDir::new("buffered:colons")?;
+
+    // Text databases are compiled on the way in.
+    let mut handle = FileConfig::default()
+        .with_buffered_loading(true)
+        .with_file(&custom)
+        .build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+
+    // Compiled databases can be loaded even if their paths contain colons, which libmagic can't
+    // otherwise handle.
+    mojique::compile([&custom], &output_dir)?;
+    let config = FileConfig::default().with_file(output_dir.join("custom.magic"));
+    assert!(matches!(
+        config.clone().with_buffered_loading(false).build_handle(),
+        Err(Error::EmbeddedColons)
+    ));
+
+    let config = config.with_buffered_loading(true);
+    config.validate()?;
+    let mut handle = config.build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+
+    Ok(())
+}
+
+#[test]
+fn compiled_buffer() -> anyhow::Result<()> {
+    let custom = manifest_dir().join("tests/data/custom.magic");
+    let output_dir = TempDir::new("compiled")?;
+
+    let compiled = mojique::compile([&custom], &output_dir)?;
+    let mut handle = BufferConfig::default()
+        .with_compiled_buffer(std::fs::read(&compiled[0])?)?
+        .build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+
+    // Text databases can't be loaded from buffers...
+    assert!(matches!(
+        BufferConfig::default().with_compiled_buffer(std::fs::read(&custom)?),
+        Err(Error::UncompiledBuffer)
+    ));
+
+    // ...and compiled databases can't be compiled again.
+    assert!(matches!(
+        mojique::compile(&compiled, &output_dir),
+        Err(Error::AlreadyCompiled(path)) if path == compiled[0]
+    ));
+
+    Ok(())
+}
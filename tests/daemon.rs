@@ -0,0 +1,70 @@
+#![cfg(feature = "daemon")]
+
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    os::unix::net::UnixListener,
+};
+
+use common::*;
+use mojique::{Backend, Client, Config, Daemon, DefaultConfig, Error, Flag};
+
+mod common;
+
+#[test]
+fn daemon() -> anyhow::Result<()> {
+    let dir = TempDir::new("daemon")?;
+    let socket = dir.join("mojique.sock");
+
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+    let listener = UnixListener::bind(&socket)?;
+    std::thread::spawn(move || Daemon::new(pool).serve(listener));
+
+    let client = Client::new(&socket);
+    assert_eq!(
+        client.buffer(b"#!/bin/sh\n")?.description(),
+        "text/x-shellscript"
+    );
+    assert_eq!(client.buffer(b"")?.description(), "application/x-empty");
+
+    // Files are opened by the client and passed to the daemon.
+    let custom = manifest_dir().join("tests/data/custom.magic");
+    assert!(client.file(&custom)?.matches("text/*"));
+    assert!(client.fd(File::open(&custom)?)?.matches("text/*"));
+    assert!(matches!(
+        client.file(&dir.join("missing")),
+        Err(Error::Detect { source, .. }) if matches!(*source, Error::ReadInput(_))
+    ));
+
+    // Failing to connect should be reported as such.
+    assert!(matches!(
+        Client::new(dir.join("missing.sock")).buffer(b""),
+        Err(Error::DaemonConnection(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn oversized_response() -> anyhow::Result<()> {
+    let dir = TempDir::new("daemon-oversized")?;
+    let socket = dir.join("mojique.sock");
+
+    // A misbehaving daemon that claims a 4 GiB response shouldn't make the client allocate it.
+    let listener = UnixListener::bind(&socket)?;
+    std::thread::spawn(move || -> std::io::Result<()> {
+        let (mut stream, _) = listener.accept()?;
+        let mut request = [0; 6];
+        stream.read_exact(&mut request)?;
+        stream.write_all(&[0, 0xff, 0xff, 0xff, 0xff])
+    });
+
+    assert!(matches!(
+        Client::new(&socket).buffer(b"M"),
+        Err(Error::DaemonConnection(e)) if e.kind() == ErrorKind::InvalidData
+    ));
+
+    Ok(())
+}
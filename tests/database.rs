@@ -0,0 +1,32 @@
+use common::*;
+use insta::assert_snapshot;
+use mojique::{BufferConfig, CombinedConfig, Config, Database};
+
+mod common;
+
+#[test]
+fn database() -> anyhow::Result<()> {
+    // Text databases should be compiled on the way in, since they'll be loaded as buffers.
+    let custom = Database::from_file(manifest_dir().join("tests/data/custom.magic"))?;
+
+    let mut handle = BufferConfig::from(custom.clone()).build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+    assert_snapshot!(handle.buffer(b"#include <stdio.h>")?, @"ASCII text, with no line terminators");
+
+    let pool = BufferConfig::default()
+        .with_database(&custom)
+        .build_pool()?;
+    assert_snapshot!(pool.buffer(b"MOJIQUE")?, @"mojique test data");
+
+    let database = Database::default_database()?.with_database(&custom);
+    let mut handle = BufferConfig::from(database).build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+    assert_snapshot!(handle.buffer(b"#include <stdio.h>")?, @"C source, ASCII text, with no line terminators");
+
+    let mut handle = CombinedConfig::default()
+        .with_database(&custom)
+        .build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+#![cfg(feature = "deadpool")]
+
+use deadpool::managed::Pool;
+use mojique::{DefaultConfig, Manager};
+
+#[tokio::test]
+async fn deadpool() -> anyhow::Result<()> {
+    let pool = Pool::builder(Manager::new(DefaultConfig::default())?)
+        .max_size(2)
+        .build()?;
+
+    let mut handle = pool.get().await?;
+    assert_eq!(handle.buffer(b"")?, "empty");
+    drop(handle);
+
+    // Recycling the handle should succeed, and leave it usable.
+    let mut handle = pool.get().await?;
+    assert_eq!(handle.buffer(b"")?, "empty");
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+#![cfg(any(feature = "zip", all(feature = "tar", feature = "flate2")))]
+
+use std::fmt::Write;
+
+use mojique::{Config, DeepBudget, DeepDetection, DefaultConfig, Flag, Handle};
+
+mod common;
+
+/// Renders the tree with one indented line per member, which is easier to compare than the
+/// `Debug` output.
+fn outline(tree: &DeepDetection) -> String {
+    fn visit(tree: &DeepDetection, depth: usize, out: &mut String) {
+        if let Some(limit) = tree.truncated {
+            writeln!(
+                out,
+                "{:depth$}(truncated: {limit:?})",
+                "",
+                depth = depth * 2
+            )
+            .unwrap();
+        }
+        for member in &tree.members {
+            match member {
+                Ok((name, child)) => {
+                    writeln!(
+                        out,
+                        "{:depth$}{name}: {}",
+                        "",
+                        child.detection,
+                        depth = depth * 2
+                    )
+                    .unwrap();
+                    visit(child, depth + 1, out);
+                }
+                Err(e) => writeln!(out, "{:depth$}error: {e}", "", depth = depth * 2).unwrap(),
+            }
+        }
+    }
+
+    let mut out = format!("{}\n", tree.detection);
+    visit(tree, 1, &mut out);
+    out
+}
+
+fn handle() -> anyhow::Result<Handle> {
+    Ok(DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_handle()?)
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn deep_detect_zip() -> anyhow::Result<()> {
+    use insta::assert_snapshot;
+
+    let mut handle = handle()?;
+    let buf = std::fs::read(common::manifest_dir().join("tests/data/nested.zip"))?;
+
+    assert_snapshot!(outline(&handle.deep_detect(&buf, DeepBudget::new())?), @r"
+    application/zip
+      readme.txt: text/plain
+      archive.zip: application/zip
+        document.pdf: application/pdf
+        scripts/run.sh: text/x-shellscript
+    ");
+
+    assert_snapshot!(
+        outline(&handle.deep_detect(&buf, DeepBudget::new().with_max_depth(1))?),
+        @r"
+    application/zip
+      readme.txt: text/plain
+      archive.zip: application/zip
+        (truncated: Depth)
+    "
+    );
+
+    assert_snapshot!(
+        outline(&handle.deep_detect(&buf, DeepBudget::new().with_max_entries(3))?),
+        @r"
+    application/zip
+      readme.txt: text/plain
+      archive.zip: application/zip
+        (truncated: Entries)
+        document.pdf: application/pdf
+    "
+    );
+
+    // The inner archive fits, but only the first of its members does.
+    assert_snapshot!(
+        outline(&handle.deep_detect(&buf, DeepBudget::new().with_max_bytes(400))?),
+        @r"
+    application/zip
+      readme.txt: text/plain
+      archive.zip: application/zip
+        (truncated: Bytes)
+        document.pdf: application/pdf
+    "
+    );
+
+    // Anything that isn't an archive is only detected itself.
+    let tree = handle.deep_detect(b"%PDF-1.4\n", DeepBudget::new())?;
+    assert_eq!(tree.detection.description(), "application/pdf");
+    assert!(tree.members.is_empty());
+    assert_eq!(tree.truncated, None);
+
+    Ok(())
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn deep_detect_unreadable_members() -> anyhow::Result<()> {
+    use mojique::BudgetLimit;
+
+    // Builds a zip archive of empty members that use a compression method that doesn't exist, so
+    // that none of them can be opened.
+    let mut buf = Vec::new();
+    let mut directory = Vec::new();
+    let count: u16 = 5;
+    for i in 0..count {
+        let name = format!("member-{i}");
+        let offset = buf.len() as u32;
+
+        buf.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        buf.extend_from_slice(&[20, 0, 0, 0, 0x34, 0x12]);
+        buf.extend_from_slice(&[0; 16]);
+        buf.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        buf.extend_from_slice(&[0; 2]);
+        buf.extend_from_slice(name.as_bytes());
+
+        directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        directory.extend_from_slice(&[20, 0, 20, 0, 0, 0, 0x34, 0x12]);
+        directory.extend_from_slice(&[0; 16]);
+        directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        directory.extend_from_slice(&[0; 12]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+    let directory_offset = buf.len() as u32;
+    buf.extend_from_slice(&directory);
+    buf.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    buf.extend_from_slice(&[0; 4]);
+    buf.extend_from_slice(&count.to_le_bytes());
+    buf.extend_from_slice(&count.to_le_bytes());
+    buf.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&directory_offset.to_le_bytes());
+    buf.extend_from_slice(&[0; 2]);
+
+    let tree = handle()?.deep_detect(&buf, DeepBudget::new())?;
+    assert_eq!(tree.members.len(), usize::from(count));
+    assert!(tree.members.iter().all(Result::is_err));
+    assert_eq!(tree.truncated, None);
+
+    // Members that can't be opened still count towards the budget.
+    let tree = handle()?.deep_detect(&buf, DeepBudget::new().with_max_entries(2))?;
+    assert_eq!(tree.members.len(), 2);
+    assert_eq!(tree.truncated, Some(BudgetLimit::Entries));
+
+    Ok(())
+}
+
+#[cfg(all(feature = "tar", feature = "flate2"))]
+#[test]
+fn deep_detect_tar() -> anyhow::Result<()> {
+    use insta::assert_snapshot;
+
+    let mut handle = handle()?;
+    let buf = std::fs::read(common::manifest_dir().join("tests/data/archive.tar.gz"))?;
+
+    assert_snapshot!(outline(&handle.deep_detect(&buf, DeepBudget::new())?), @r"
+    application/gzip
+      document.pdf: application/pdf
+      scripts/run.sh: text/x-shellscript
+    ");
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use common::*;
+use insta::assert_snapshot;
+use mojique::{Config, DirectoryConfig, Error};
+
+mod common;
+
+#[test]
+fn directory() -> anyhow::Result<()> {
+    let data = manifest_dir().join("tests/data");
+
+    let mut handle = DirectoryConfig::new(&data)
+        .with_extension("magic")
+        .build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+    assert_snapshot!(handle.buffer(b"#include <stdio.h>")?, @"ASCII text, with no line terminators");
+
+    // Excluding the only matching file should leave nothing to load.
+    let config = DirectoryConfig::new(&data)
+        .with_extension("magic")
+        .without_file("custom.magic");
+    assert!(matches!(config.build_handle(), Err(Error::NoDatabases(_))));
+
+    assert!(matches!(
+        DirectoryConfig::new(data.join("missing")).build_handle(),
+        Err(Error::ReadDatabase { .. })
+    ));
+
+    Ok(())
+}
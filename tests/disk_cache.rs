@@ -0,0 +1,100 @@
+#![cfg(feature = "disk-cache")]
+
+use common::*;
+use mojique::{Config, DefaultConfig, DiskCache, Flag};
+
+mod common;
+
+#[test]
+fn persistent() -> anyhow::Result<()> {
+    let dir = TempDir::new("disk-cache")?;
+    let cache_path = dir.join("cache");
+    let file = dir.join("file");
+    std::fs::write(&file, b"%PDF-1.4\n")?;
+    let license = manifest_dir().join("tests/data/LICENSE.zst");
+
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+    let cache = DiskCache::open(pool.clone(), &cache_path)?;
+    assert_eq!(cache.file(&file)?.description(), "application/pdf");
+    assert_eq!(cache.file(&license)?.description(), "application/zstd");
+    assert!(cache.file(dir.join("missing")).is_err());
+    assert_eq!(cache.len(), 2);
+    drop(cache);
+
+    // Reopening the cache should find both files without touching the pool.
+    pool.close()?;
+    let cache = DiskCache::open(pool, &cache_path)?;
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.file(&file)?.description(), "application/pdf");
+    assert_eq!(cache.file(&license)?.description(), "application/zstd");
+
+    // Files that have changed, or have been invalidated, are detected again.
+    std::fs::write(&file, b"#!/bin/sh\n\n")?;
+    assert!(cache.file(&file).is_err());
+    assert!(cache.invalidate(&license)?);
+    assert!(!cache.invalidate(&license)?);
+    assert!(cache.file(&license).is_err());
+    drop(cache);
+
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+    let cache = DiskCache::open(pool, &cache_path)?;
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.file(&file)?.description(), "text/x-shellscript");
+
+    cache.clear()?;
+    assert!(cache.is_empty());
+    drop(cache);
+
+    let cache = DiskCache::open(DefaultConfig::default().build_pool()?, &cache_path)?;
+    assert!(cache.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn max_entries() -> anyhow::Result<()> {
+    let dir = TempDir::new("disk-cache-max")?;
+    let cache_path = dir.join("cache");
+
+    let pool = DefaultConfig::default().build_pool()?;
+    let cache = DiskCache::open(pool.clone(), &cache_path)?.with_max_entries(1);
+    cache.file(manifest_dir().join("tests/data/LICENSE.zst"))?;
+    cache.file(manifest_dir().join("tests/data/custom.magic"))?;
+    assert_eq!(cache.len(), 1);
+    cache.compact()?;
+    drop(cache);
+
+    let cache = DiskCache::open(pool, &cache_path)?;
+    assert_eq!(cache.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn corrupt() -> anyhow::Result<()> {
+    let dir = TempDir::new("disk-cache-bad")?;
+    let cache_path = dir.join("cache");
+    let pool = DefaultConfig::default().build_pool()?;
+
+    // Files that aren't caches are replaced.
+    std::fs::write(&cache_path, "not a cache\n")?;
+    let cache = DiskCache::open(pool.clone(), &cache_path)?;
+    assert!(cache.is_empty());
+    cache.file(manifest_dir().join("tests/data/LICENSE.zst"))?;
+    drop(cache);
+
+    // A partially written record at the end of the cache is discarded.
+    let mut contents = std::fs::read_to_string(&cache_path)?;
+    contents.push_str("+ 1 2 3");
+    std::fs::write(&cache_path, contents)?;
+    let cache = DiskCache::open(pool, &cache_path)?;
+    assert_eq!(cache.len(), 1);
+    drop(cache);
+    assert!(std::fs::read_to_string(&cache_path)?.ends_with('\n'));
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+#![cfg(feature = "dlopen")]
+
+use mojique::{Config, DefaultConfig, Error};
+
+// This is the only test in this file, since libmagic is only loaded once per process, and the
+// environment variable affects the whole test process.
+#[test]
+fn missing_library() -> anyhow::Result<()> {
+    unsafe { std::env::set_var("MOJIQUE_LIBMAGIC", "libmojique-missing.so") };
+
+    assert!(matches!(
+        DefaultConfig::default().build_handle(),
+        Err(Error::LibraryLoad(message)) if message.contains("libmojique-missing.so")
+    ));
+
+    // Pools create handles lazily, so the error only appears once a handle is needed.
+    let pool = DefaultConfig::default().build_pool()?;
+    assert!(matches!(pool.handle(), Err(Error::LibraryLoad(_))));
+
+    assert_eq!(mojique::version(), 0);
+
+    Ok(())
+}
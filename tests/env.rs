@@ -0,0 +1,28 @@
+use common::*;
+use insta::assert_snapshot;
+use mojique::{Config, EnvConfig, Error};
+
+mod common;
+
+// This is the only test in this file, since it modifies the environment of the whole test
+// process.
+#[test]
+fn env() -> anyhow::Result<()> {
+    let custom = manifest_dir().join("tests/data/custom.magic");
+
+    unsafe { std::env::set_var("MAGIC", &custom) };
+    let mut handle = EnvConfig::default().build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"mojique test data");
+
+    unsafe { std::env::set_var("MAGIC", custom.with_file_name("missing.magic")) };
+    assert!(matches!(
+        EnvConfig::default().build_handle(),
+        Err(Error::EnvDatabaseMissing(path)) if path.ends_with("missing.magic")
+    ));
+
+    unsafe { std::env::remove_var("MAGIC") };
+    let mut handle = EnvConfig::default().build_handle()?;
+    assert_snapshot!(handle.buffer(b"")?, @"empty");
+
+    Ok(())
+}
@@ -1,9 +1,14 @@
-use std::collections::BTreeSet;
+use std::{
+    collections::BTreeSet,
+    fs::{File, Permissions},
+    os::unix::fs::PermissionsExt,
+    path::Path,
+};
 
 use common::*;
 use insta::{assert_debug_snapshot, assert_snapshot};
 use itertools::Itertools;
-use mojique::{Config, DefaultConfig, Flag};
+use mojique::{Config, DefaultConfig, Error, ErrorKind, Flag, PoolOptions};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 mod common;
@@ -66,14 +71,46 @@ fn not_exist() -> anyhow::Result<()> {
     let e = handle
         .file("this-file-should-not-exist")
         .expect_err("file not found");
+    // The backtrace can't be snapshotted if it's actually captured.
+    #[cfg(not(feature = "backtrace"))]
     assert_debug_snapshot!(e, @r#"
-    Magic {
-        errno: 2,
-        message: Message(
-            "cannot stat `this-file-should-not-exist' (No such file or directory)",
-        ),
+    Detect {
+        path: "this-file-should-not-exist",
+        source: Magic {
+            errno: 2,
+            message: Message(
+                "cannot stat `this-file-should-not-exist' (No such file or directory)",
+            ),
+            backtrace: <disabled>,
+        },
     }
     "#);
+    assert_eq!(e.path(), Some(Path::new("this-file-should-not-exist")));
+    assert_eq!(e.kind(), ErrorKind::NotFound);
+    assert!(!e.is_retryable());
+
+    let os_error = e.os_error().expect("errno is set");
+    assert_eq!(os_error.raw_os_error(), Some(2));
+    assert_eq!(os_error.kind(), std::io::ErrorKind::NotFound);
+
+    // libmagic's errno should carry through when converting into an io::Error.
+    assert_eq!(std::io::Error::from(e).kind(), std::io::ErrorKind::NotFound);
+
+    Ok(())
+}
+
+#[cfg(feature = "backtrace")]
+#[test]
+fn backtrace() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default().build_handle()?;
+    let e = handle
+        .file("this-file-should-not-exist")
+        .expect_err("file not found");
+    let backtrace = e.backtrace().expect("backtrace").to_string();
+    assert!(
+        backtrace.contains("mojique::handle::Cookie::raw"),
+        "{backtrace}"
+    );
 
     Ok(())
 }
@@ -105,6 +142,83 @@ fn symlink() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn prefix_read() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default().build_handle()?;
+    assert!(!handle.prefix_read());
+    handle.set_prefix_read(true);
+    assert!(handle.prefix_read());
+
+    // Regular files are read by us, which shouldn't change their description.
+    let magic_type = handle.file(manifest_dir().join("LICENSE"))?;
+    assert_snapshot!(magic_type, @"ASCII text");
+
+    // Whereas anything else is still left to libmagic.
+    let magic_type = handle.file(manifest_dir().join("tests/data"))?;
+    assert_snapshot!(magic_type, @"directory");
+
+    let magic_type = handle.file(manifest_dir().join("tests/data/symlink"))?;
+    assert_snapshot!(magic_type, @"symbolic link to LICENSE.zst");
+
+    // Errors still include the path.
+    let e = handle
+        .file("this-file-should-not-exist")
+        .expect_err("file not found");
+    assert_eq!(e.path(), Some(Path::new("this-file-should-not-exist")));
+    assert_eq!(e.kind(), ErrorKind::NotFound);
+
+    Ok(())
+}
+
+#[test]
+fn prefix_read_errors() -> anyhow::Result<()> {
+    let dir = TempDir::new("prefix-read")?;
+    let path = dir.join("unreadable");
+    std::fs::write(&path, b"%PDF-1.4\n")?;
+    std::fs::set_permissions(&path, Permissions::from_mode(0o000))?;
+
+    // Permissions aren't enforced for root, in which case there's nothing to test.
+    if File::open(&path).is_err() {
+        let mut handle = DefaultConfig::default().build_handle()?;
+        handle.set_prefix_read(true);
+        let e = handle.file(&path).expect_err("file is unreadable");
+        assert!(
+            matches!(&e, Error::Detect { source, .. } if matches!(**source, Error::ReadInput(_))),
+            "{e:?}"
+        );
+        assert_eq!(e.kind(), ErrorKind::Permission);
+        assert_eq!(e.path(), Some(path.as_path()));
+
+        // Preserving access times leaves the file to libmagic, even when reading prefixes, and
+        // libmagic describes unreadable files rather than failing.
+        let mut handle = DefaultConfig::default()
+            .set_flag(Flag::PreserveAccessTime)
+            .build_handle()?;
+        handle.set_prefix_read(true);
+        assert_snapshot!(handle.file(&path)?, @"regular file, no read permission");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn prefix_read_pooled() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default()
+        .build_pool_with_options(PoolOptions::default().max_size(1).prefix_read(true))?;
+
+    // The option should apply to every acquisition, including after a handle has been changed and
+    // returned to the pool.
+    let mut handle = pool.handle()?;
+    assert!(handle.prefix_read());
+    handle.set_prefix_read(false);
+    drop(handle);
+
+    assert!(pool.handle()?.prefix_read());
+    assert_snapshot!(pool.file(manifest_dir().join("LICENSE"))?, @"ASCII text");
+
+    Ok(())
+}
+
 #[test]
 fn parallel() -> anyhow::Result<()> {
     const ITERATIONS: usize = 1000;
@@ -130,3 +244,28 @@ fn parallel() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn parallel_pool_file() -> anyhow::Result<()> {
+    const ITERATIONS: usize = 1000;
+
+    let pool = DefaultConfig::default().build_pool()?;
+
+    // As above, but using the pool's own detection methods, which acquire a handle for each call.
+    let types: BTreeSet<String> = (0..ITERATIONS)
+        .map(|_| manifest_dir().join("LICENSE"))
+        .collect_vec()
+        .into_par_iter()
+        .map(|path| pool.file(path))
+        .collect::<Vec<_>>()
+        .into_iter()
+        .try_collect()?;
+
+    assert_debug_snapshot!(types, @r#"
+    {
+        "ASCII text",
+    }
+    "#);
+
+    Ok(())
+}
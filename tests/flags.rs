@@ -0,0 +1,103 @@
+use mojique::{Check, Config, DefaultConfig, Flag};
+
+#[test]
+fn capabilities() -> anyhow::Result<()> {
+    let capabilities = mojique::capabilities();
+    assert_eq!(capabilities.version(), mojique::version());
+    assert!(capabilities.supports(Flag::Error));
+    assert!(capabilities.supports(Flag::Mime));
+    assert_eq!(
+        capabilities.supports(Flag::Extension),
+        Flag::Extension.is_supported()
+    );
+
+    let config = DefaultConfig::default().try_set_flag(Flag::MimeType)?;
+    assert_eq!(config.build_handle()?.buffer(b"")?, "application/x-empty");
+
+    Ok(())
+}
+
+#[test]
+fn flags() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default()
+        .set_flags(Flag::MimeType | Flag::Symlink)
+        .build_handle()?;
+
+    let flags = handle.flags()?;
+    assert!(flags.contains(Flag::Error | Flag::MimeType | Flag::Symlink));
+    assert!(!flags.contains(Flag::MimeEncoding));
+    assert_eq!(handle.buffer(b"")?, "application/x-empty");
+
+    Ok(())
+}
+
+#[test]
+fn shortcuts() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default()
+        .mime()
+        .follow_symlinks()
+        .decompress()
+        .preserve_atime()
+        .build_handle()?;
+
+    let flags = handle.flags()?;
+    assert!(flags.contains(Flag::Mime | Flag::Symlink | Flag::Compress | Flag::PreserveAccessTime));
+    assert_eq!(handle.buffer(b"")?, "application/x-empty; charset=binary");
+
+    let mut handle = DefaultConfig::default().extension().build_handle()?;
+    assert_eq!(handle.buffer(b"GIF89a")?, "gif");
+
+    Ok(())
+}
+
+#[test]
+fn checks() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default().build_handle()?;
+    assert_eq!(handle.buffer(b"{\"a\": 1}\n")?, "JSON text data");
+
+    let mut handle = DefaultConfig::default()
+        .disable_check(Check::Json)
+        .build_handle()?;
+    assert!(handle.flags()?.contains(Flag::NoCheckJSON));
+    assert_eq!(handle.buffer(b"{\"a\": 1}\n")?, "ASCII text");
+
+    // With only the magic database being consulted, plain text isn't recognised at all.
+    let mut handle = DefaultConfig::default()
+        .disable_check(Check::Soft)
+        .disable_all_checks_except([Check::Soft])
+        .build_handle()?;
+    let flags = handle.flags()?;
+    assert!(flags.contains(Flag::NoCheckText | Flag::NoCheckEncoding | Flag::NoCheckTar));
+    assert!(!flags.contains(Flag::NoCheckSoft));
+    assert_eq!(handle.buffer(b"hello world\n")?, "data");
+
+    Ok(())
+}
+
+#[cfg(feature = "v5-44")]
+#[test]
+fn no_compress_fork() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default()
+        .set_flags(Flag::Compress | Flag::NoCompressFork)
+        .build_handle()?;
+    assert!(handle.flags()?.contains(Flag::NoCompressFork));
+
+    Ok(())
+}
+
+#[test]
+fn merge_flags() -> anyhow::Result<()> {
+    let tenant = DefaultConfig::default().set_flags([Flag::MimeType, Flag::Symlink]);
+    let config = DefaultConfig::default()
+        .set_flags(vec![Flag::Compress])
+        .merge_flags(&tenant);
+    assert_eq!(
+        config.flag_set(),
+        Flag::Error | Flag::MimeType | Flag::Symlink | Flag::Compress
+    );
+
+    let mut handle = config.build_handle()?;
+    assert_eq!(handle.buffer(b"")?, "application/x-empty");
+
+    Ok(())
+}
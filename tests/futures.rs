@@ -0,0 +1,26 @@
+#![cfg(feature = "futures-io")]
+
+use common::*;
+use futures::{executor::block_on, io::AllowStdIo};
+use insta::assert_snapshot;
+use mojique::{Config, DefaultConfig};
+
+mod common;
+
+#[test]
+fn read_futures() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default().build_handle()?;
+
+    let license = std::fs::read(manifest_dir().join("LICENSE"))?;
+    let magic_type = block_on(handle.read_futures(license.as_slice()))?;
+    assert_snapshot!(magic_type, @"ASCII text");
+
+    let magic_type = block_on(handle.read_futures(futures::io::empty()))?;
+    assert_snapshot!(magic_type, @"empty");
+
+    // Inputs beyond the limit should only be partially read.
+    let magic_type = block_on(handle.read_futures(AllowStdIo::new(std::io::repeat(0))))?;
+    assert_snapshot!(magic_type, @"data");
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+#![cfg(feature = "miette")]
+
+use common::*;
+use miette::{Diagnostic, Severity};
+use mojique::{Config, Error, FileConfig};
+
+mod common;
+
+#[test]
+fn diagnostic() -> anyhow::Result<()> {
+    let error = Error::NoDefaultDatabase;
+    assert_eq!(
+        error.code().map(|code| code.to_string()).as_deref(),
+        Some("mojique::no_default_database")
+    );
+    assert!(error.help().is_some());
+
+    // Check failures should carry their warnings as related diagnostics.
+    let invalid = manifest_dir().join("tests/data/invalid-magic");
+    let Err(error) = FileConfig::default().with_file(&invalid).check() else {
+        panic!("expected the check to fail");
+    };
+    let related: Vec<_> = error.related().into_iter().flatten().collect();
+    assert_eq!(related.len(), 1);
+    assert_eq!(related[0].severity(), Some(Severity::Warning));
+    assert!(related[0].to_string().contains("strnig"));
+
+    Ok(())
+}
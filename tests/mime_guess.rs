@@ -0,0 +1,34 @@
+#![cfg(feature = "mime_guess")]
+
+use common::*;
+use mojique::{Config, DefaultConfig, DetectionSource, Flag};
+
+mod common;
+
+#[test]
+fn detect_with_fallback() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_handle()?;
+    let dir = TempDir::new("fallback")?;
+
+    // Content that libmagic doesn't recognise should fall back to the extension, if known.
+    let unknown = [0x8f, 0x03, 0xa7, 0x11, 0xd2, 0x5e, 0x90, 0xc4];
+    std::fs::write(dir.join("clip.mp4"), unknown)?;
+    let (detection, source) = handle.detect_with_fallback(dir.join("clip.mp4"))?;
+    assert_eq!(detection.description(), "video/mp4");
+    assert_eq!(source, DetectionSource::Extension);
+
+    std::fs::write(dir.join("clip.xyzzy"), unknown)?;
+    let (detection, source) = handle.detect_with_fallback(dir.join("clip.xyzzy"))?;
+    assert_eq!(detection.description(), "application/octet-stream");
+    assert_eq!(source, DetectionSource::Libmagic);
+
+    // Content that libmagic does recognise should never fall back.
+    std::fs::write(dir.join("script.mp4"), "#!/bin/sh\n")?;
+    let (detection, source) = handle.detect_with_fallback(dir.join("script.mp4"))?;
+    assert_eq!(detection.description(), "text/x-shellscript");
+    assert_eq!(source, DetectionSource::Libmagic);
+
+    Ok(())
+}
@@ -0,0 +1,414 @@
+use std::time::Duration;
+
+use common::*;
+use insta::assert_debug_snapshot;
+use magic_sys::magic_load;
+use mojique::{Config, DefaultConfig, FileConfig, PoolOptions, PoolOrder};
+
+mod common;
+
+#[test]
+fn close() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+    let mut handle = pool.handle()?;
+    assert!(!pool.is_closed()?);
+
+    pool.close()?;
+    assert!(pool.is_closed()?);
+
+    // Handles that were already checked out should continue to work.
+    assert_eq!(handle.buffer(b"")?, "empty");
+
+    let e = pool.handle().expect_err("pool is closed");
+    assert_debug_snapshot!(e, @"PoolClosed");
+
+    // Closing is idempotent, and affects clones of the pool.
+    let cloned = pool.clone();
+    cloned.close()?;
+    assert!(cloned.handle().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn close_and_wait() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+
+    // With no outstanding handles, this should return immediately.
+    assert!(pool.close_and_wait(None)?);
+
+    let pool = DefaultConfig::default().build_pool()?;
+    let handle = pool.handle()?;
+
+    // A handle that hasn't been returned should result in a timeout.
+    assert!(!pool.close_and_wait(Some(Duration::from_millis(10)))?);
+
+    // Whereas returning it from another thread should unblock the wait.
+    let thread = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        drop(handle);
+    });
+    assert!(pool.close_and_wait(Some(Duration::from_secs(10)))?);
+    thread.join().expect("join thread");
+
+    Ok(())
+}
+
+#[test]
+fn detach() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+    let mut handle = pool.handle()?.detach();
+
+    // A detached handle no longer belongs to the pool, so closing shouldn't wait for it, and it
+    // should keep working after the pool is gone.
+    assert!(pool.close_and_wait(Some(Duration::from_millis(10)))?);
+    drop(pool);
+
+    assert_eq!(handle.buffer(b"")?, "empty");
+
+    Ok(())
+}
+
+#[test]
+fn validate_on_return() -> anyhow::Result<()> {
+    for validate in [false, true] {
+        let pool = DefaultConfig::default()
+            .build_pool_with_options(PoolOptions::default().validate_on_return(validate))?;
+
+        // A handle that was merely left with an error is still usable, so it's always kept.
+        let mut handle = pool.handle()?;
+        assert!(handle.file("this-file-should-not-exist").is_err());
+        drop(handle);
+        assert_eq!(pool.idle_count()?, 1);
+
+        // Whereas failing to load a database leaves the cookie without any magic at all, which
+        // only validation notices.
+        let mut handle = pool.handle()?;
+        assert!(
+            handle
+                .raw(|cookie| unsafe { magic_load(cookie, c"this-file-should-not-exist".as_ptr()) })
+                .is_err()
+        );
+        drop(handle);
+        assert_eq!(
+            pool.idle_count()?,
+            usize::from(!validate),
+            "validate: {validate}"
+        );
+
+        if validate {
+            assert_eq!(pool.handle()?.buffer(b"")?, "empty");
+        } else {
+            assert!(pool.handle()?.buffer(b"").is_err());
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn recycling() -> anyhow::Result<()> {
+    // We can't directly observe whether a cookie was recycled, but we can at least ensure that
+    // handles continue to work as cookies are retired.
+    let pool = DefaultConfig::default().build_pool_with_options(
+        PoolOptions::default()
+            .max_cookie_age(Duration::from_millis(1))
+            .max_cookie_uses(2),
+    )?;
+
+    for _ in 0..5 {
+        assert_eq!(pool.handle()?.buffer(b"")?, "empty");
+        std::thread::sleep(Duration::from_millis(2));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn evict_idle() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+    let first = pool.handle()?;
+    let second = pool.handle()?;
+    drop(first);
+    drop(second);
+
+    assert_eq!(pool.evict_idle(Duration::from_secs(60))?, 0);
+    assert_eq!(pool.evict_idle(Duration::ZERO)?, 2);
+    assert_eq!(pool.evict_idle(Duration::ZERO)?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn idle_timeout() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default()
+        .build_pool_with_options(PoolOptions::default().idle_timeout(Duration::from_millis(10)))?;
+    drop(pool.handle()?);
+
+    // Give the reaper plenty of time to run, at which point there should be nothing left to
+    // evict.
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(pool.evict_idle(Duration::ZERO)?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn local_handle() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+
+    assert_eq!(pool.local_handle(|handle| handle.buffer(b""))?, "empty");
+
+    // The same handle should be reused, and reentrant calls should still work.
+    let desc = pool.local_handle(|outer| {
+        let inner = pool.local_handle(|inner| inner.buffer(b""))?;
+        Ok(format!("{} {inner}", outer.buffer(b"")?))
+    })?;
+    assert_eq!(desc, "empty empty");
+
+    // Cached handles don't count as being in use.
+    assert!(pool.close_and_wait(Some(Duration::from_millis(10)))?);
+
+    let e = pool
+        .local_handle(|handle| handle.buffer(b""))
+        .expect_err("pool is closed");
+    assert_debug_snapshot!(e, @"PoolClosed");
+
+    Ok(())
+}
+
+#[test]
+fn detection() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+
+    assert_eq!(pool.buffer(b"")?, "empty");
+    assert!(pool.file("this-file-should-not-exist").is_err());
+
+    // Neither success nor failure should leave a handle checked out.
+    assert!(pool.close_and_wait(Some(Duration::from_millis(10)))?);
+
+    Ok(())
+}
+
+#[test]
+fn max_concurrent_creations() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default()
+        .build_pool_with_options(PoolOptions::default().max_concurrent_creations(1))?;
+
+    // Acquire handles from several threads at once. We can't easily observe the serialisation,
+    // but every thread should still end up with a working handle.
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..4).map(|_| scope.spawn(|| pool.buffer(b""))).collect();
+
+        for thread in threads {
+            assert_eq!(thread.join().expect("join thread")?, "empty");
+        }
+
+        anyhow::Ok(())
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn weak() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+    let weak = pool.downgrade();
+
+    let upgraded = weak.upgrade().expect("pool is still alive");
+    assert_eq!(upgraded.buffer(b"")?, "empty");
+
+    drop(upgraded);
+    drop(pool);
+    assert!(weak.upgrade().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn fifo() -> anyhow::Result<()> {
+    for (order, idle) in [(PoolOrder::Fifo, 1), (PoolOrder::Lifo, 2)] {
+        let pool = DefaultConfig::default()
+            .build_pool_with_options(PoolOptions::default().order(order).max_cookie_uses(3))?;
+
+        // Use one handle twice and another once, returning the more used handle first.
+        drop(pool.handle()?);
+        let first = pool.handle()?;
+        let second = pool.handle()?;
+        drop(first);
+        drop(second);
+        assert_eq!(pool.idle_count()?, 2);
+
+        // Reusing the first handle that was returned uses it for the third time, so it's retired
+        // rather than being returned, whereas reusing the last handle that was returned doesn't.
+        drop(pool.handle()?);
+        assert_eq!(pool.idle_count()?, idle, "{order:?}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn introspection() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+    assert_eq!(pool.capacity(), None);
+    assert_eq!(pool.idle_count()?, 0);
+    assert_eq!(pool.in_use()?, 0);
+
+    let first = pool.handle()?;
+    let second = pool.handle()?;
+    assert_eq!(pool.idle_count()?, 0);
+    assert_eq!(pool.in_use()?, 2);
+
+    drop(first);
+    assert_eq!(pool.idle_count()?, 1);
+    assert_eq!(pool.in_use()?, 1);
+
+    drop(second);
+    assert_eq!(pool.idle_count()?, 2);
+    assert_eq!(pool.in_use()?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn max_size() -> anyhow::Result<()> {
+    let pool =
+        DefaultConfig::default().build_pool_with_options(PoolOptions::default().max_size(1))?;
+    assert_eq!(pool.capacity(), Some(1));
+
+    let handle = pool.handle()?;
+    let thread = std::thread::spawn({
+        let pool = pool.clone();
+        move || pool.buffer(b"")
+    });
+
+    // The thread can't make progress until we return our handle.
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(!thread.is_finished());
+    drop(handle);
+
+    assert_eq!(thread.join().expect("join thread")?, "empty");
+    assert_eq!(pool.idle_count()?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn max_size_local_handle() -> anyhow::Result<()> {
+    let pool =
+        DefaultConfig::default().build_pool_with_options(PoolOptions::default().max_size(1))?;
+
+    // Cache a handle on another thread, and then fill the pool before that thread exits and
+    // returns its handle.
+    let (cached_tx, cached_rx) = std::sync::mpsc::channel();
+    let (exit_tx, exit_rx) = std::sync::mpsc::channel::<()>();
+    let thread = std::thread::spawn({
+        let pool = pool.clone();
+        move || -> Result<(), mojique::Error> {
+            pool.local_handle(|handle| handle.buffer(b""))?;
+            cached_tx.send(()).expect("send cached");
+            exit_rx.recv().expect("receive exit");
+            Ok(())
+        }
+    });
+    cached_rx.recv()?;
+    let handle = pool.handle()?;
+
+    // The returned handle would take the pool over its maximum size, so it's closed instead.
+    exit_tx.send(())?;
+    thread.join().expect("join thread")?;
+    assert_eq!(pool.idle_count()?, 0);
+    assert_eq!(pool.in_use()?, 1);
+
+    drop(handle);
+    assert_eq!(pool.idle_count()?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn reuse_config() -> anyhow::Result<()> {
+    let config = DefaultConfig::default();
+
+    // Pools built from the same configuration should be independent of one another.
+    let first = config.build_pool()?;
+    let second = config.build_pool()?;
+    first.close()?;
+    assert_eq!(second.buffer(b"")?, "empty");
+    assert_eq!(config.build_handle()?.buffer(b"")?, "empty");
+
+    Ok(())
+}
+
+#[test]
+fn reload() -> anyhow::Result<()> {
+    let dir = TempDir::new("reload")?;
+    let magic = dir.join("test.magic");
+    std::fs::write(&magic, "0\tstring\tMOJIQUE\tbefore reload\n")?;
+
+    let pool = FileConfig::default().with_file(&magic).build_pool()?;
+    assert_eq!(
+        pool.local_handle(|handle| handle.buffer(b"MOJIQUE"))?,
+        "before reload"
+    );
+    let mut held = pool.handle()?;
+    assert_eq!(pool.idle_count()?, 0);
+
+    std::fs::write(&magic, "0\tstring\tMOJIQUE\tafter reload\n")?;
+    pool.reload()?;
+    assert_eq!(pool.buffer(b"MOJIQUE")?, "after reload");
+    assert_eq!(
+        pool.local_handle(|handle| handle.buffer(b"MOJIQUE"))?,
+        "after reload"
+    );
+
+    // Handles that were in use should continue to use the old database, but shouldn't be reused
+    // once they're returned.
+    assert_eq!(held.buffer(b"MOJIQUE")?, "before reload");
+    let idle = pool.idle_count()?;
+    drop(held);
+    assert_eq!(pool.idle_count()?, idle);
+
+    // If the database can no longer be loaded, the existing handles should be kept.
+    std::fs::remove_file(&magic)?;
+    assert!(pool.reload().is_err());
+    assert_eq!(
+        pool.local_handle(|handle| handle.buffer(b"MOJIQUE"))?,
+        "after reload"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn shared_database() -> anyhow::Result<()> {
+    let dir = TempDir::new("shared")?;
+    let magic = dir.join("test.magic");
+    std::fs::write(&magic, "0\tstring\tMOJIQUE\tbefore reload\n")?;
+
+    let pool = FileConfig::default()
+        .with_file(&magic)
+        .with_shared_database(true)
+        .build_pool()?;
+    let mut first = pool.handle()?;
+    assert_eq!(first.buffer(b"MOJIQUE")?, "before reload");
+
+    // Once the database has been loaded, new handles share it rather than reading it again.
+    std::fs::write(&magic, "0\tstring\tMOJIQUE\tafter reload\n")?;
+    let mut second = pool.handle()?;
+    assert_eq!(second.buffer(b"MOJIQUE")?, "before reload");
+
+    pool.reload()?;
+    let mut third = pool.handle()?;
+    assert_eq!(third.buffer(b"MOJIQUE")?, "after reload");
+
+    // If the database can no longer be loaded, new handles continue to share the previous one.
+    std::fs::remove_file(&magic)?;
+    assert!(pool.reload().is_err());
+    let mut fourth = pool.handle()?;
+    assert_eq!(fourth.buffer(b"MOJIQUE")?, "after reload");
+
+    drop((first, second, third, fourth));
+    Ok(())
+}
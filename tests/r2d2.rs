@@ -0,0 +1,21 @@
+#![cfg(feature = "r2d2")]
+
+use mojique::{DefaultConfig, Manager};
+use r2d2::Pool;
+
+#[test]
+fn r2d2() -> anyhow::Result<()> {
+    let pool = Pool::builder()
+        .max_size(2)
+        .build(Manager::new(DefaultConfig::default())?)?;
+
+    let mut handle = pool.get()?;
+    assert_eq!(handle.buffer(b"")?, "empty");
+    drop(handle);
+
+    // Checking the handle out again should validate it, and leave it usable.
+    let mut handle = pool.get()?;
+    assert_eq!(handle.buffer(b"")?, "empty");
+
+    Ok(())
+}
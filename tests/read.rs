@@ -28,3 +28,25 @@ fn read() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn read_buffer_size() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default().build_handle()?;
+    assert_eq!(handle.read_buffer_size(), 64 * 1024);
+
+    // Small chunks mean many writes to the pipe, but shouldn't change the result, including after
+    // the buffer has been reused.
+    handle.set_read_buffer_size(3);
+    assert_eq!(handle.read_buffer_size(), 3);
+    for _ in 0..2 {
+        let file = File::open(manifest_dir().join("LICENSE"))?;
+        assert_snapshot!(handle.read(file)?, @"ASCII text");
+    }
+
+    // A zero sized buffer would never read anything.
+    handle.set_read_buffer_size(0);
+    assert_eq!(handle.read_buffer_size(), 1);
+    assert_snapshot!(handle.read(b"%PDF-1.4\n".as_slice())?, @"PDF document, version 1.4");
+
+    Ok(())
+}
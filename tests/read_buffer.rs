@@ -0,0 +1,69 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use mojique::{Config, DefaultConfig, PoolOptions};
+
+// An unusual size, so that we can count the allocations of the read buffer without being confused
+// by anything else the process allocates.
+const SIZE: usize = 12_345;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() == SIZE {
+            ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        }
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if layout.size() == SIZE {
+            ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        }
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+#[test]
+fn pooled() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default()
+        .build_pool_with_options(PoolOptions::default().max_size(1).read_buffer_size(SIZE))?;
+
+    // The buffer should only be allocated by the first read, even though each read uses a handle
+    // that has been returned to the pool and acquired again.
+    for _ in 0..3 {
+        let mut handle = pool.handle()?;
+        assert_eq!(handle.read_buffer_size(), SIZE);
+        assert_eq!(
+            handle.read(b"%PDF-1.4\n".as_slice())?,
+            "PDF document, version 1.4"
+        );
+    }
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), 1);
+
+    // Whereas changing the size for one acquisition replaces the buffer, and then the pool's size
+    // applies again once the handle has been returned.
+    let mut handle = pool.handle()?;
+    handle.set_read_buffer_size(SIZE + 1);
+    handle.read(b"%PDF-1.4\n".as_slice())?;
+    drop(handle);
+
+    let mut handle = pool.handle()?;
+    assert_eq!(handle.read_buffer_size(), SIZE);
+    handle.read(b"%PDF-1.4\n".as_slice())?;
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
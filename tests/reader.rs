@@ -0,0 +1,30 @@
+use std::io::{self, Read};
+
+use insta::assert_snapshot;
+use mojique::{Config, Error, ErrorKind, ReaderConfig};
+
+struct FailingReader;
+
+impl Read for FailingReader {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::other("oh no"))
+    }
+}
+
+#[test]
+fn reader() -> anyhow::Result<()> {
+    // Errors from the reader should be surfaced when the reader is added.
+    let err = ReaderConfig::default()
+        .with_reader(FailingReader)
+        .unwrap_err();
+    assert!(matches!(err, Error::ReadDatabaseReader(_)));
+    assert_snapshot!(err, @"reading magic database from reader: oh no");
+
+    // Whereas invalid database contents are only detected once libmagic tries to load them.
+    let config = ReaderConfig::default().with_reader(b"not a database".as_slice())?;
+    let err = config.build_handle().unwrap_err();
+    assert!(matches!(&err, Error::Load { source } if matches!(**source, Error::Magic { .. })));
+    assert_eq!(err.kind(), ErrorKind::DatabaseLoad);
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "rocket")]
+
+use insta::assert_snapshot;
+use mojique::{AllowedTypes, Config, DefaultConfig, Flag, TypedData};
+use rocket::{http::Status, local::asynchronous::Client, post, routes};
+
+#[post("/", data = "<upload>")]
+fn upload(upload: TypedData<16>) -> String {
+    format!("{} {}", upload.data.len(), upload.detection)
+}
+
+async fn client(allowed: &[&str]) -> anyhow::Result<Client> {
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+    let rocket = rocket::build()
+        .manage(pool)
+        .manage(AllowedTypes::new(allowed.iter().copied()))
+        .mount("/", routes![upload]);
+
+    Ok(Client::tracked(rocket).await?)
+}
+
+#[tokio::test]
+async fn typed_data() -> anyhow::Result<()> {
+    let client = client(&["text/*"]).await?;
+
+    let response = client.post("/").body("#!/bin/sh\n").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_snapshot!(response.into_string().await.unwrap(), @"10 text/x-shellscript");
+
+    let response = client.post("/").body("%PDF-1.4\n").dispatch().await;
+    assert_eq!(response.status(), Status::UnsupportedMediaType);
+
+    let response = client
+        .post("/")
+        .body([b'a'; 17].as_slice())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::PayloadTooLarge);
+
+    Ok(())
+}
@@ -0,0 +1,476 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use common::*;
+use insta::assert_snapshot;
+use mojique::{
+    Config, DefaultConfig, Error, Flag, LargeFilePolicy, ScanProgress, ScanReport, Scanner,
+    SymlinkPolicy,
+};
+
+mod common;
+
+#[test]
+fn scan() -> anyhow::Result<()> {
+    let dir = TempDir::new("scanner")?;
+    std::fs::create_dir_all(dir.join("nested/deeper"))?;
+    std::fs::write(dir.join("document.pdf"), b"%PDF-1.4\n")?;
+    std::fs::write(dir.join("nested/script"), b"#!/bin/sh\n")?;
+    std::fs::write(dir.join("nested/deeper/empty"), b"")?;
+
+    let scanner = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_scanner()?
+        .with_threads(2)
+        .with_roots([
+            dir.to_path_buf(),
+            manifest_dir().join("tests/data/LICENSE.zst"),
+        ])
+        .with_root(dir.join("missing"));
+
+    let results: BTreeMap<_, _> = scanner.scan()?.collect();
+    assert_eq!(results.len(), 5);
+    let description = |path: &str| {
+        results[&dir.join(path)]
+            .as_ref()
+            .map(|detection| detection.description().to_string())
+            .ok()
+    };
+    assert_eq!(
+        description("document.pdf").as_deref(),
+        Some("application/pdf")
+    );
+    assert_eq!(
+        description("nested/script").as_deref(),
+        Some("text/x-shellscript")
+    );
+    assert_eq!(
+        description("nested/deeper/empty").as_deref(),
+        Some("inode/x-empty")
+    );
+    assert!(matches!(
+        &results[&dir.join("missing")],
+        Err(Error::Detect { source, .. }) if matches!(**source, Error::ReadInput(_))
+    ));
+    assert!(results[&manifest_dir().join("tests/data/LICENSE.zst")].is_ok());
+
+    // Results can also be received on another thread.
+    let rx = scanner.scan()?.into_receiver();
+    let count = std::thread::spawn(move || rx.iter().count())
+        .join()
+        .unwrap();
+    assert_eq!(count, 5);
+
+    Ok(())
+}
+
+#[test]
+fn dropped() -> anyhow::Result<()> {
+    // Dropping a scan early shouldn't hang or panic.
+    let scanner = Scanner::new(DefaultConfig::default().build_pool()?)
+        .with_threads(1)
+        .with_root(manifest_dir().join("src"));
+    let mut scan = scanner.scan()?;
+    assert!(scan.next().is_some());
+    drop(scan);
+
+    Ok(())
+}
+
+#[test]
+fn filters() -> anyhow::Result<()> {
+    let dir = TempDir::new("scanner-filters")?;
+    std::fs::create_dir_all(dir.join("node_modules/package"))?;
+    std::fs::create_dir_all(dir.join("firmware"))?;
+    std::fs::write(dir.join("node_modules/package/image.bin"), b"")?;
+    std::fs::write(dir.join("firmware/image.bin"), b"")?;
+    std::fs::write(dir.join("firmware/IMAGE.BIN"), b"")?;
+    std::fs::write(dir.join("firmware/notes.txt"), b"")?;
+    std::fs::write(dir.join("image.bin"), b"")?;
+
+    let scanner = Scanner::new(DefaultConfig::default().build_pool()?).with_root(dir.to_path_buf());
+    let scanned = |scanner: Scanner| -> anyhow::Result<Vec<String>> {
+        let mut paths = scanner
+            .scan()?
+            .map(|(path, _)| {
+                path.strip_prefix(&dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        Ok(paths)
+    };
+
+    assert_eq!(
+        scanned(
+            scanner
+                .clone()
+                .with_include(["**/*.bin"])
+                .with_exclude(["node_modules"])
+        )?,
+        ["firmware/image.bin", "image.bin"]
+    );
+    assert_eq!(
+        scanned(scanner.clone().with_include(["/firmware/*"]))?,
+        [
+            "firmware/IMAGE.BIN",
+            "firmware/image.bin",
+            "firmware/notes.txt"
+        ]
+    );
+    assert_eq!(
+        scanned(
+            scanner
+                .clone()
+                .with_extensions(["bin"])
+                .with_exclude(["/image.bin", "node_modules"])
+        )?,
+        ["firmware/IMAGE.BIN", "firmware/image.bin"]
+    );
+
+    assert!(matches!(
+        scanner.with_include(["[bin"]).scan(),
+        Err(Error::InvalidGlob(pattern)) if pattern == "[bin"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn report() -> anyhow::Result<()> {
+    let dir = TempDir::new("scanner-report")?;
+    std::fs::write(dir.join("a.pdf"), b"%PDF-1.4\n")?;
+    std::fs::write(dir.join("b.pdf"), b"%PDF-1.4\n")?;
+    std::fs::write(dir.join("script"), b"#!/bin/sh\n")?;
+
+    let scanner = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_scanner()?
+        .with_roots([dir.to_path_buf(), dir.join("missing")]);
+
+    let mut json = Vec::new();
+    let mut csv = Vec::new();
+    let summary = ScanReport::new()
+        .with_json_lines(&mut json)
+        .with_csv(&mut csv)
+        .run(scanner.scan()?)?;
+
+    assert_eq!(summary.files, 3);
+    assert_eq!(summary.errors, 1);
+    assert_eq!(
+        summary.types,
+        BTreeMap::from([
+            ("application/pdf".to_string(), 2),
+            ("text/x-shellscript".to_string(), 1)
+        ])
+    );
+    assert_snapshot!(summary, @r"
+    2 application/pdf
+    1 text/x-shellscript
+    3 files, 1 errors
+    ");
+
+    let json = String::from_utf8(json)?;
+    assert_eq!(json.lines().count(), 4);
+    assert_eq!(json.matches("\"error\":").count(), 1);
+
+    // Errors don't fit in CSV records, so only the header and files are written.
+    assert_eq!(String::from_utf8(csv)?.lines().count(), 4);
+
+    Ok(())
+}
+
+#[test]
+fn progress() -> anyhow::Result<()> {
+    let dir = TempDir::new("scanner-progress")?;
+    std::fs::write(dir.join("document.pdf"), b"%PDF-1.4\n")?;
+    std::fs::write(dir.join("script"), b"#!/bin/sh\n")?;
+
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let scanner = Scanner::new(DefaultConfig::default().build_pool()?)
+        .with_roots([dir.to_path_buf(), dir.join("missing")])
+        .with_progress({
+            let updates = updates.clone();
+            move |progress| updates.lock().unwrap().push(*progress)
+        });
+
+    let mut scan = scanner.scan()?;
+    assert_eq!(scan.by_ref().count(), 3);
+    let expected = ScanProgress {
+        discovered: 2,
+        scanned: 2,
+        aliases: 0,
+        skipped: 0,
+        bytes: 19,
+        errors: 1,
+    };
+    assert_eq!(scan.progress(), expected);
+
+    // Every change is reported, including the one that reached the final counts.
+    let updates = updates.lock().unwrap();
+    assert_eq!(updates.len(), 5);
+    assert!(updates.contains(&expected));
+
+    Ok(())
+}
+
+#[test]
+fn hardlinks() -> anyhow::Result<()> {
+    let dir = TempDir::new("scanner-links")?;
+    std::fs::create_dir_all(dir.join("nested"))?;
+    std::fs::write(dir.join("document.pdf"), b"%PDF-1.4\n")?;
+    std::fs::hard_link(dir.join("document.pdf"), dir.join("copy.pdf"))?;
+    std::fs::hard_link(dir.join("document.pdf"), dir.join("nested/copy.pdf"))?;
+    std::fs::write(dir.join("script"), b"#!/bin/sh\n")?;
+
+    let scanner = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_scanner()?
+        .with_threads(2)
+        .with_root(dir.to_path_buf())
+        .with_dedup_hardlinks(true);
+
+    let mut scan = scanner.scan()?;
+    let results: BTreeMap<_, _> = scan.by_ref().collect();
+    assert_eq!(results.len(), 4);
+    for path in ["document.pdf", "copy.pdf", "nested/copy.pdf"] {
+        assert_eq!(
+            results[&dir.join(path)].as_ref().unwrap().description(),
+            "application/pdf"
+        );
+    }
+
+    // Whichever path was found first is scanned, and the others are its aliases.
+    let aliases = scan.aliases();
+    assert_eq!(aliases.len(), 2);
+    let original = &aliases[0].1;
+    assert!(
+        aliases
+            .iter()
+            .all(|(alias, of)| of == original && alias != original)
+    );
+
+    let progress = scan.progress();
+    assert_eq!(
+        (progress.scanned, progress.aliases, progress.bytes),
+        (4, 2, 19)
+    );
+
+    // Without deduplication, every path is scanned.
+    let mut scan = scanner.clone().with_dedup_hardlinks(false).scan()?;
+    assert_eq!(scan.by_ref().count(), 4);
+    assert!(scan.aliases().is_empty());
+    assert_eq!(scan.progress().aliases, 0);
+
+    Ok(())
+}
+
+#[test]
+fn large_files() -> anyhow::Result<()> {
+    let dir = TempDir::new("scanner-large")?;
+    std::fs::write(dir.join("script"), b"#!/bin/sh\n")?;
+    let mut large = b"%PDF-1.4\n".to_vec();
+    large.resize(4096, b'\n');
+    std::fs::write(dir.join("large.pdf"), large)?;
+
+    let scanner = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_scanner()?
+        .with_root(dir.to_path_buf());
+
+    // Only the first three bytes are read, which aren't enough to identify a PDF.
+    let mut scan = scanner
+        .clone()
+        .with_max_file_size(1024, LargeFilePolicy::Prefix)
+        .with_prefix_bytes(3)
+        .scan()?;
+    let results: BTreeMap<_, _> = scan.by_ref().collect();
+    assert_eq!(
+        results[&dir.join("large.pdf")]
+            .as_ref()
+            .unwrap()
+            .description(),
+        "text/plain"
+    );
+    assert_eq!(
+        results[&dir.join("script")].as_ref().unwrap().description(),
+        "text/x-shellscript"
+    );
+    assert!(
+        results[&dir.join("large.pdf")]
+            .as_ref()
+            .unwrap()
+            .is_prefix()
+    );
+    assert!(!results[&dir.join("script")].as_ref().unwrap().is_prefix());
+    assert_eq!(scan.prefixed(), [dir.join("large.pdf")]);
+    assert_eq!(scan.progress().bytes, 13);
+
+    // Reports include whether each detection was only performed on a prefix.
+    let mut json = Vec::new();
+    let mut csv = Vec::new();
+    ScanReport::new()
+        .with_json_lines(&mut json)
+        .with_csv(&mut csv)
+        .run(
+            scanner
+                .clone()
+                .with_max_file_size(1024, LargeFilePolicy::Prefix)
+                .with_prefix_bytes(3)
+                .scan()?,
+        )?;
+    let json = String::from_utf8(json)?;
+    assert_eq!(json.matches("\"prefix\":true").count(), 1);
+    assert!(
+        json.lines()
+            .any(|line| line.contains("large.pdf") && line.ends_with(",\"prefix\":true}"))
+    );
+    let csv = String::from_utf8(csv)?;
+    assert!(csv.starts_with("path,description,mime_type,prefix\r\n"));
+    assert!(
+        csv.lines()
+            .any(|line| line.contains("large.pdf") && line.ends_with(",true"))
+    );
+    assert!(
+        csv.lines()
+            .any(|line| line.contains("script") && line.ends_with(",false"))
+    );
+
+    let mut scan = scanner
+        .clone()
+        .with_max_file_size(1024, LargeFilePolicy::Skip)
+        .scan()?;
+    let results: Vec<_> = scan.by_ref().map(|(path, _)| path).collect();
+    assert_eq!(results, [dir.join("script")]);
+    assert!(scan.prefixed().is_empty());
+    let progress = scan.progress();
+    assert_eq!((progress.discovered, progress.skipped), (1, 1));
+
+    // Files at the maximum size are scanned in full.
+    let mut scan = scanner
+        .with_max_file_size(4096, LargeFilePolicy::Skip)
+        .scan()?;
+    assert_eq!(scan.by_ref().count(), 2);
+    assert_eq!(scan.progress().bytes, 4106);
+
+    Ok(())
+}
+
+#[test]
+fn symlinks() -> anyhow::Result<()> {
+    use std::os::unix::fs::symlink;
+
+    let dir = TempDir::new("scanner-symlinks")?;
+    std::fs::create_dir_all(dir.join("nested"))?;
+    std::fs::write(dir.join("document.pdf"), b"%PDF-1.4\n")?;
+    std::fs::write(dir.join("nested/script"), b"#!/bin/sh\n")?;
+    symlink("document.pdf", dir.join("link.pdf"))?;
+    symlink("missing", dir.join("broken"))?;
+    symlink("nested", dir.join("nested-link"))?;
+    symlink("..", dir.join("nested/parent"))?;
+
+    let scanner = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_scanner()?
+        .with_root(dir.to_path_buf());
+    let scan = |policy| -> anyhow::Result<BTreeMap<String, String>> {
+        Ok(scanner
+            .clone()
+            .with_symlinks(policy)
+            .scan()?
+            .map(|(path, result)| {
+                let path = path.strip_prefix(&dir).unwrap().display().to_string();
+                let result = match result {
+                    Ok(detection) => detection.into_description(),
+                    Err(_) => "error".into(),
+                };
+                (path, result)
+            })
+            .collect())
+    };
+
+    // Every symlink is described as a symlink, and none are walked.
+    let results = scan(SymlinkPolicy::Report)?;
+    assert_eq!(
+        results.keys().collect::<Vec<_>>(),
+        [
+            "broken",
+            "document.pdf",
+            "link.pdf",
+            "nested-link",
+            "nested/parent",
+            "nested/script"
+        ]
+    );
+    assert_eq!(results["link.pdf"], "inode/symlink");
+    assert_eq!(results["nested-link"], "inode/symlink");
+
+    // The nested directory is only walked once, via whichever path was found first, and the link
+    // back to the root is ignored.
+    let mut results = scan(SymlinkPolicy::Follow)?;
+    let script = results
+        .remove("nested/script")
+        .or_else(|| results.remove("nested-link/script"));
+    assert_eq!(script.as_deref(), Some("text/x-shellscript"));
+    assert_eq!(
+        results,
+        BTreeMap::from([
+            ("broken".into(), "error".into()),
+            ("document.pdf".into(), "application/pdf".into()),
+            ("link.pdf".into(), "application/pdf".into()),
+        ])
+    );
+
+    let results = scan(SymlinkPolicy::Skip)?;
+    assert_eq!(
+        results.keys().collect::<Vec<_>>(),
+        ["document.pdf", "nested/script"]
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn hashed() -> anyhow::Result<()> {
+    use mojique::HashAlgorithm;
+
+    let dir = TempDir::new("scanner-hashed")?;
+    std::fs::write(dir.join("document.pdf"), b"%PDF-1.4\n")?;
+    std::fs::hard_link(dir.join("document.pdf"), dir.join("copy.pdf"))?;
+    std::fs::write(dir.join("large.pdf"), b"%PDF-1.4\n\n\n")?;
+
+    let mut scan = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_scanner()?
+        .with_root(dir.to_path_buf())
+        .with_dedup_hardlinks(true)
+        .with_max_file_size(10, LargeFilePolicy::Prefix)
+        .with_hash(HashAlgorithm::Sha256)
+        .scan()?;
+
+    let results: BTreeMap<_, _> = scan
+        .hashed()
+        .map(|(path, result, digest)| {
+            let path = path.strip_prefix(&dir).unwrap().display().to_string();
+            (path, (result.unwrap().into_description(), digest))
+        })
+        .collect();
+
+    let digest = "e5c62df5dab5c87b6a015ef3d43597074d1eec433b15f51aec63b8582d0e4ab4";
+    for path in ["document.pdf", "copy.pdf"] {
+        let (description, hash) = &results[path];
+        assert_eq!(description, "application/pdf");
+        assert_eq!(hash.unwrap().algorithm(), HashAlgorithm::Sha256);
+        assert_eq!(hash.unwrap().to_string(), digest);
+    }
+
+    // Files that only have a prefix read aren't hashed.
+    assert_eq!(results["large.pdf"].1, None);
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "serde")]
+
+use common::*;
+use insta::assert_snapshot;
+use mojique::{Config, ConfigSpec, DefaultConfig, Error, FileConfig, Flag};
+
+mod common;
+
+#[test]
+fn spec() -> anyhow::Result<()> {
+    let custom = manifest_dir().join("tests/data/custom.magic");
+
+    let spec: ConfigSpec = serde_json::from_value(serde_json::json!({
+        "source": {
+            "type": "files",
+            "paths": [custom],
+        },
+        "flags": ["mime-type"],
+        "limits": {
+            "max_size": 2,
+        },
+    }))?;
+
+    let mut handle = spec.build_handle()?;
+    assert_snapshot!(handle.buffer(b"MOJIQUE")?, @"application/x-mojique");
+
+    let pool = spec.build_pool()?;
+    assert_eq!(pool.capacity(), Some(2));
+    assert_snapshot!(pool.buffer(b"MOJIQUE")?, @"application/x-mojique");
+
+    // Flags are parsed as by FromStr, so all of the libmagic style names work.
+    let spec: ConfigSpec =
+        serde_json::from_str(r#"{"flags": ["no-check-text", "preserve-atime", "NO_CHECK_CDF"]}"#)?;
+    assert_eq!(
+        spec.flags,
+        [
+            Flag::NoCheckText,
+            Flag::PreserveAccessTime,
+            Flag::NoCheckCDF
+        ]
+    );
+    let e =
+        serde_json::from_str::<ConfigSpec>(r#"{"flags": ["MimeType"]}"#).expect_err("unknown flag");
+    assert!(e.to_string().contains("MimeType"), "{e}");
+
+    // Converting into the wrong kind of configuration should fail.
+    let spec: ConfigSpec = serde_json::from_str("{}")?;
+    assert!(matches!(
+        FileConfig::try_from(spec.clone()),
+        Err(Error::SpecSourceMismatch {
+            expected: "files",
+            actual: "default"
+        })
+    ));
+
+    // As should limits that would otherwise panic.
+    let spec: ConfigSpec = serde_json::from_str(r#"{"limits": {"max_size": 0}}"#)?;
+    assert!(matches!(
+        spec.build_pool(),
+        Err(Error::InvalidLimit("max_size"))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn error() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default().build_handle()?;
+    let e = handle
+        .file("this-file-should-not-exist")
+        .expect_err("file not found");
+    assert_snapshot!(serde_json::to_string_pretty(&e)?, @r#"
+    {
+      "variant": "detect",
+      "errno": 2,
+      "message": "detecting this-file-should-not-exist: [2] cannot stat `this-file-should-not-exist' (No such file or directory)",
+      "path": "this-file-should-not-exist"
+    }
+    "#);
+
+    // Errors without an errno or path should still serialise those fields.
+    assert_snapshot!(serde_json::to_string(&Error::NoDefaultDatabase)?, @r#"{"variant":"no_default_database","errno":null,"message":"unable to locate the default magic database","path":null}"#);
+
+    Ok(())
+}
+
+#[test]
+fn results() -> anyhow::Result<()> {
+    use mojique::{Claims, Detection, Flag, ValidationReport, Validator};
+
+    let detection = Detection::new("image/gif");
+    let json = serde_json::to_string(&detection)?;
+    assert_snapshot!(json, @r#"{"description":"image/gif"}"#);
+    assert_eq!(serde_json::from_str::<Detection>(&json)?, detection);
+
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+    let report = Validator::new(pool)
+        .with_allowed(["text/*"])
+        .with_content_type_check(true)
+        .validate(
+            b"GIF89a",
+            &Claims {
+                content_type: Some("application/pdf"),
+                ..Default::default()
+            },
+        )?;
+    let json = serde_json::to_string_pretty(&report)?;
+    assert_snapshot!(json, @r#"
+    {
+      "detection": {
+        "description": "image/gif"
+      },
+      "extensions": [],
+      "violations": [
+        {
+          "type": "not_allowed"
+        },
+        {
+          "type": "content_type_mismatch",
+          "claimed": "application/pdf"
+        }
+      ]
+    }
+    "#);
+    assert_eq!(serde_json::from_str::<ValidationReport>(&json)?, report);
+
+    Ok(())
+}
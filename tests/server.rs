@@ -0,0 +1,108 @@
+#![cfg(feature = "server")]
+
+use std::future::poll_fn;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode},
+    response::Response,
+};
+use common::*;
+use insta::assert_snapshot;
+use mojique::{Config, DefaultConfig, Flag, Server};
+use tower_service::Service;
+
+mod common;
+
+async fn send(app: &mut Router, request: Request<Body>) -> anyhow::Result<(StatusCode, String)> {
+    poll_fn(|cx| <Router as Service<Request<Body>>>::poll_ready(app, cx)).await?;
+    let response: Response = app.call(request).await?;
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await?;
+
+    Ok((status, String::from_utf8(body.to_vec())?))
+}
+
+fn server() -> anyhow::Result<Server> {
+    Ok(Server::new(
+        DefaultConfig::default()
+            .set_flag(Flag::MimeType)
+            .build_pool()?,
+    ))
+}
+
+#[tokio::test]
+async fn detect_body() -> anyhow::Result<()> {
+    let mut app = server()?.into_router();
+
+    let (status, body) = send(
+        &mut app,
+        Request::post("/detect").body(Body::from("#!/bin/sh\n"))?,
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_snapshot!(body, @r#"{"description":"text/x-shellscript"}"#);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn detect_path() -> anyhow::Result<()> {
+    // Path lookups should be disabled unless a root is configured.
+    let mut app = server()?.into_router();
+    let (status, _) = send(
+        &mut app,
+        Request::get("/detect?path=custom.magic").body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let mut app = server()?
+        .with_root(manifest_dir().join("tests/data"))
+        .into_router();
+
+    let (status, body) = send(
+        &mut app,
+        Request::get("/detect?path=custom.magic").body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.starts_with(r#"{"description":"text/"#), "{body}");
+
+    let (status, _) = send(
+        &mut app,
+        Request::get("/detect?path=missing").body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Paths outside of the root should be rejected, even if they exist.
+    let (status, body) = send(
+        &mut app,
+        Request::get("/detect?path=../../Cargo.toml").body(Body::empty())?,
+    )
+    .await?;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body.contains(r#""variant":"detect""#), "{body}");
+
+    // Whether or not they exist, so that clients can't probe the rest of the filesystem.
+    for path in [
+        "../../Cargo.toml",
+        "../../this-file-should-not-exist",
+        "../../../../../../etc/passwd",
+        "../../../../../../etc/this-file-should-not-exist",
+        "/etc/passwd",
+        "/etc/this-file-should-not-exist",
+        "./../custom.magic",
+    ] {
+        let (status, _) = send(
+            &mut app,
+            Request::get(format!("/detect?path={path}")).body(Body::empty())?,
+        )
+        .await?;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{path}");
+    }
+
+    Ok(())
+}
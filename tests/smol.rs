@@ -0,0 +1,56 @@
+#![cfg(feature = "smol")]
+
+use common::*;
+use futures::{executor::block_on, io::AllowStdIo};
+use insta::assert_snapshot;
+use mojique::{BYTES_MAX, Config, DefaultConfig, Error};
+
+mod common;
+
+#[test]
+fn read_unblock() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default().build_handle()?;
+
+    let license = std::fs::read(manifest_dir().join("LICENSE"))?;
+    let magic_type = block_on(handle.read_unblock(license.as_slice()))?;
+    assert_snapshot!(magic_type, @"ASCII text");
+
+    let magic_type = block_on(handle.read_unblock(futures::io::empty()))?;
+    assert_snapshot!(magic_type, @"empty");
+
+    // Inputs beyond the limit should only be partially read.
+    let magic_type = block_on(handle.read_unblock(AllowStdIo::new(std::io::repeat(0))))?;
+    assert_snapshot!(magic_type, @"data");
+
+    // The handle should still be usable synchronously afterwards.
+    assert_eq!(handle.buffer(&vec![0; BYTES_MAX + 1])?, "data");
+
+    Ok(())
+}
+
+#[test]
+fn pool_unblock() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+
+    let magic_type = block_on(pool.file_unblock(manifest_dir().join("LICENSE")))?;
+    assert_snapshot!(magic_type, @"ASCII text");
+
+    let magic_type = block_on(pool.buffer_unblock(b""))?;
+    assert_snapshot!(magic_type, @"empty");
+
+    // Errors should be returned as they would be synchronously.
+    let e = block_on(pool.file_unblock("this-file-should-not-exist")).expect_err("file not found");
+    assert!(matches!(e, Error::Detect { .. }));
+
+    // The handle should have been returned to the pool each time.
+    assert_eq!(pool.in_use()?, 0);
+    assert_eq!(pool.idle_count()?, 1);
+
+    let mut handle = block_on(pool.handle_unblock())?;
+    assert_snapshot!(handle.buffer(b"")?, @"empty");
+    assert_eq!(pool.in_use()?, 1);
+    drop(handle);
+    assert_eq!(pool.in_use()?, 0);
+
+    Ok(())
+}
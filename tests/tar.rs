@@ -0,0 +1,52 @@
+#![cfg(feature = "tar")]
+
+use std::io::Cursor;
+
+use mojique::{Config, DefaultConfig, Error, Flag};
+
+mod common;
+
+#[cfg(feature = "flate2")]
+#[test]
+fn tar_entries() -> anyhow::Result<()> {
+    use common::*;
+    use insta::assert_debug_snapshot;
+
+    let mut handle = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_handle()?;
+
+    // The archive also contains a directory and a symlink, which should be skipped.
+    let archive = std::fs::File::open(manifest_dir().join("tests/data/archive.tar.gz"))?;
+    let entries = handle
+        .tar_entries(archive)?
+        .into_iter()
+        .map(|entry| entry.map(|(name, detection)| (name, detection.into_description())))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_debug_snapshot!(entries, @r#"
+    [
+        (
+            "document.pdf",
+            "application/pdf",
+        ),
+        (
+            "scripts/run.sh",
+            "text/x-shellscript",
+        ),
+    ]
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn tar_entries_invalid() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_handle()?;
+
+    let entries = handle.tar_entries(Cursor::new(b"not a tar archive".to_vec()))?;
+    assert!(matches!(entries.as_slice(), [Err(Error::Archive(_))]));
+
+    Ok(())
+}
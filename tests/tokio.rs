@@ -0,0 +1,277 @@
+#![cfg(feature = "tokio")]
+
+use std::time::Duration;
+
+use common::*;
+use insta::assert_snapshot;
+use mojique::{AsyncSniffReader, BYTES_MAX, Config, DefaultConfig, Error, PoolOptions};
+use tokio::io::AsyncReadExt;
+
+mod common;
+
+#[tokio::test]
+async fn read_async() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default().build_handle()?;
+
+    let license = std::fs::read(manifest_dir().join("LICENSE"))?;
+    let magic_type = handle.read_async(license.as_slice()).await?;
+    assert_snapshot!(magic_type, @"ASCII text");
+
+    let magic_type = handle.read_async(tokio::io::empty()).await?;
+    assert_snapshot!(magic_type, @"empty");
+
+    // Inputs beyond the limit should only be partially read.
+    let magic_type = handle.read_async(tokio::io::repeat(0)).await?;
+    assert_snapshot!(magic_type, @"data");
+
+    // The handle should still be usable synchronously afterwards.
+    assert_eq!(handle.buffer(&vec![0; BYTES_MAX + 1])?, "data");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn pool_async() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default().build_pool()?;
+
+    let magic_type = pool.file_async(manifest_dir().join("LICENSE")).await?;
+    assert_snapshot!(magic_type, @"ASCII text");
+
+    let magic_type = pool.buffer_async(b"").await?;
+    assert_snapshot!(magic_type, @"empty");
+
+    // Errors should be returned as they would be synchronously.
+    let e = pool
+        .file_async("this-file-should-not-exist")
+        .await
+        .expect_err("file not found");
+    assert!(matches!(e, Error::Detect { .. }));
+
+    // The handle should have been returned to the pool each time.
+    assert_eq!(pool.in_use()?, 0);
+    assert_eq!(pool.idle_count()?, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn idle_timeout() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default()
+        .build_pool_with_options(PoolOptions::default().idle_timeout(Duration::from_millis(10)))?;
+    drop(pool.handle()?);
+
+    // The reaper is a task on this runtime, so it only runs while this test is waiting.
+    assert_eq!(pool.idle_count()?, 1);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(pool.idle_count()?, 0);
+
+    Ok(())
+}
+
+#[test]
+fn idle_timeout_without_time() -> anyhow::Result<()> {
+    // Without the time driver, the reaper can't be a task, so it falls back to a thread.
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let pool = runtime.block_on(async {
+        DefaultConfig::default()
+            .build_pool_with_options(PoolOptions::default().idle_timeout(Duration::from_millis(10)))
+    })?;
+    drop(pool.handle()?);
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(pool.idle_count()?, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sniff() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default().build_handle()?;
+
+    let license = std::fs::read(manifest_dir().join("LICENSE"))?;
+    let mut reader = AsyncSniffReader::new(&mut handle, license.as_slice()).await?;
+    assert_snapshot!(reader.description(), @"ASCII text");
+
+    // The entire input should be replayed.
+    let mut replayed = Vec::new();
+    reader.read_to_end(&mut replayed).await?;
+    assert_eq!(replayed, license);
+
+    // Including inputs that are longer than the limit.
+    let input: Vec<u8> = (0..BYTES_MAX + 1000).map(|i| i as u8).collect();
+    let mut reader = AsyncSniffReader::new(&mut handle, input.as_slice()).await?;
+    let mut replayed = Vec::new();
+    reader.read_to_end(&mut replayed).await?;
+    assert_eq!(replayed, input);
+
+    Ok(())
+}
+
+#[cfg(feature = "bytes")]
+#[tokio::test]
+async fn stream() -> anyhow::Result<()> {
+    use bytes::Bytes;
+    use futures::{StreamExt, TryStreamExt, stream};
+
+    let mut handle = DefaultConfig::default().build_handle()?;
+
+    let license = Bytes::from(std::fs::read(manifest_dir().join("LICENSE"))?);
+    let chunks = stream::iter(vec![Ok::<_, std::io::Error>(license.clone())]);
+    let (magic_type, remainder) = handle.stream(chunks).await?;
+    assert_snapshot!(magic_type, @"ASCII text");
+    assert!(remainder.collect::<Vec<_>>().await.is_empty());
+
+    // Chunks beyond the limit should be left in the remainder, including the part of the chunk
+    // that crossed the limit.
+    let chunks = stream::iter(vec![
+        Ok::<_, std::io::Error>(Bytes::from(vec![0; BYTES_MAX - 1])),
+        Ok(Bytes::from_static(b"abc")),
+        Ok(Bytes::from_static(b"def")),
+    ]);
+    let (magic_type, remainder) = handle.stream(chunks).await?;
+    assert_snapshot!(magic_type, @"data");
+    let remainder: Vec<_> = remainder.try_collect().await?;
+    assert_eq!(
+        remainder,
+        vec![Bytes::from_static(b"bc"), Bytes::from_static(b"def")]
+    );
+
+    // Errors from the stream should be returned.
+    let chunks = stream::iter(vec![Err::<Bytes, _>(std::io::Error::other("oops"))]);
+    let e = handle.stream(chunks).await.expect_err("stream error");
+    assert!(matches!(e, Error::ReadInput(_)));
+
+    Ok(())
+}
+
+#[cfg(feature = "bytes")]
+#[tokio::test]
+async fn sniff_stream() -> anyhow::Result<()> {
+    use bytes::Bytes;
+    use futures::{TryStreamExt, stream};
+
+    let pool = DefaultConfig::default().build_pool()?;
+
+    // The entire input should be replayed, regardless of where the limit falls.
+    let chunks = vec![
+        Bytes::from_static(b"#!/bin/sh\n"),
+        Bytes::from(vec![b'#'; BYTES_MAX]),
+        Bytes::from_static(b"echo hello\n"),
+    ];
+    let input = stream::iter(chunks.clone().into_iter().map(Ok::<_, std::io::Error>));
+    let (detection, remainder) = pool.sniff_stream(input).await?;
+    assert!(detection.description().starts_with("POSIX shell script"));
+    let replayed: Vec<_> = remainder.try_collect().await?;
+    assert_eq!(replayed.concat(), chunks.concat());
+
+    Ok(())
+}
+
+#[cfg(feature = "http")]
+#[tokio::test]
+async fn sniff_body() -> anyhow::Result<()> {
+    use bytes::Bytes;
+    use futures::stream;
+    use http_body::Frame;
+    use http_body_util::{BodyExt, StreamBody};
+
+    let pool = DefaultConfig::default().build_pool()?;
+
+    // The entire body should be replayed, regardless of where the limit falls.
+    let chunks = vec![
+        Bytes::from_static(b"#!/bin/sh\n"),
+        Bytes::from(vec![b'#'; BYTES_MAX]),
+        Bytes::from_static(b"echo hello\n"),
+    ];
+    let body = StreamBody::new(stream::iter(
+        chunks
+            .clone()
+            .into_iter()
+            .map(|chunk| Ok::<_, std::io::Error>(Frame::data(chunk))),
+    ));
+    let (detection, body) = pool.sniff_body(body).await?;
+    assert!(detection.description().starts_with("POSIX shell script"));
+    assert_eq!(body.collect().await?.to_bytes(), chunks.concat());
+
+    Ok(())
+}
+
+#[cfg(feature = "multer")]
+#[tokio::test]
+async fn sniff_fields() -> anyhow::Result<()> {
+    use bytes::Bytes;
+    use futures::{TryStreamExt, stream};
+    use mojique::{Flag, SniffFields};
+
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+
+    let body = "--X\r\n\
+                Content-Disposition: form-data; name=\"script\"; filename=\"a.sh\"\r\n\r\n\
+                #!/bin/sh\n\r\n\
+                --X\r\n\
+                Content-Disposition: form-data; name=\"document\"; filename=\"b.txt\"\r\n\r\n\
+                %PDF-1.4\n\r\n\
+                --X--\r\n";
+    let multipart = multer::Multipart::new(
+        stream::once(async move { Ok::<_, std::io::Error>(Bytes::from_static(body.as_bytes())) }),
+        "X",
+    );
+
+    let mut fields = SniffFields::new(pool, multipart).with_budget(5);
+    let mut results = Vec::new();
+    while let Some((field, detection)) = fields.next_field().await? {
+        let name = field.get_ref().name().unwrap_or_default().to_string();
+        let content: Vec<_> = field.try_collect().await?;
+        results.push(format!(
+            "{name}: {detection} ({} bytes)",
+            content.concat().len()
+        ));
+    }
+
+    // A five byte budget is enough for the PDF magic, but not the shebang.
+    assert_snapshot!(results.join("\n"), @r"
+    script: text/plain (10 bytes)
+    document: application/pdf (9 bytes)
+    ");
+
+    Ok(())
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn sniff_response() -> anyhow::Result<()> {
+    use futures::TryStreamExt;
+    use mojique::Flag;
+
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+
+    // The server's claim should be compared with the content, and the body replayed.
+    let body = b"%PDF-1.4\n%%EOF\n".to_vec();
+    let response = http::Response::builder()
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(body.clone())?;
+    let sniffed = pool.sniff_response(response.into()).await?;
+    assert_eq!(sniffed.status(), reqwest::StatusCode::OK);
+    assert_eq!(sniffed.detection().description(), "application/pdf");
+    assert_eq!(sniffed.content_type(), Some("text/html; charset=utf-8"));
+    assert_eq!(sniffed.content_type_matches(), Some(false));
+    let replayed: Vec<_> = sniffed.into_body().try_collect().await?;
+    assert_eq!(replayed.concat(), body);
+
+    let response = http::Response::builder()
+        .header("Content-Type", "Application/PDF")
+        .body(body)?;
+    let sniffed = pool.sniff_response(response.into()).await?;
+    assert_eq!(sniffed.content_type_matches(), Some(true));
+
+    // Without a Content-Type, there's nothing to compare against.
+    let response = http::Response::builder().body("%PDF-1.4\n")?;
+    let sniffed = pool.sniff_response(response.into()).await?;
+    assert_eq!(sniffed.content_type_matches(), None);
+
+    Ok(())
+}
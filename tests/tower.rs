@@ -0,0 +1,89 @@
+#![cfg(feature = "tower")]
+
+use std::{future::poll_fn, time::Duration};
+
+use bytes::Bytes;
+use common::*;
+use futures::{FutureExt, stream};
+use insta::assert_snapshot;
+use mojique::{Config, DefaultConfig, DetectRequest, DetectService, PoolOptions};
+use tower_service::Service;
+
+mod common;
+
+#[tokio::test]
+async fn service() -> anyhow::Result<()> {
+    let pool =
+        DefaultConfig::default().build_pool_with_options(PoolOptions::default().max_size(1))?;
+    let mut service = DetectService::new(pool.clone());
+
+    poll_fn(|cx| service.poll_ready(cx)).await?;
+    let magic_type = service.call(manifest_dir().join("LICENSE").into()).await?;
+    assert_snapshot!(magic_type, @"ASCII text");
+
+    poll_fn(|cx| service.poll_ready(cx)).await?;
+    let magic_type = service.call(Bytes::from_static(b"").into()).await?;
+    assert_snapshot!(magic_type, @"empty");
+
+    poll_fn(|cx| service.poll_ready(cx)).await?;
+    let chunks = stream::iter(vec![Ok::<_, Box<dyn std::error::Error + Send + Sync>>(
+        Bytes::from_static(b"#!/bin/sh\n"),
+    )]);
+    let magic_type = service
+        .call(DetectRequest::Stream(Box::pin(chunks)))
+        .await?;
+    assert_snapshot!(magic_type, @"POSIX shell script, ASCII text executable");
+
+    // The service shouldn't be ready while the pool is at capacity.
+    let handle = pool.handle()?;
+    let mut ready = Box::pin(poll_fn(|cx| service.poll_ready(cx)));
+    assert!((&mut ready).now_or_never().is_none());
+
+    // But should become ready once the handle is returned.
+    drop(handle);
+    ready.await?;
+
+    Ok(())
+}
+
+#[test]
+fn saturated_blocking_pool() -> anyhow::Result<()> {
+    // With only one blocking thread, any service parking a thread while it waits for capacity
+    // would prevent the handle from ever being returned below.
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .max_blocking_threads(1)
+        .enable_time()
+        .build()?;
+
+    runtime.block_on(async {
+        let pool =
+            DefaultConfig::default().build_pool_with_options(PoolOptions::default().max_size(1))?;
+        let handle = pool.handle()?;
+
+        let mut services = (0..4)
+            .map(|_| DetectService::new(pool.clone()))
+            .collect::<Vec<_>>();
+        for service in services.iter_mut() {
+            assert!(
+                poll_fn(|cx| service.poll_ready(cx))
+                    .now_or_never()
+                    .is_none()
+            );
+        }
+
+        // Services that are dropped while waiting shouldn't leave anything behind either.
+        services.truncate(2);
+
+        let returned = tokio::task::spawn_blocking(move || drop(handle));
+        tokio::time::timeout(Duration::from_secs(5), returned).await??;
+
+        for service in services.iter_mut() {
+            tokio::time::timeout(Duration::from_secs(5), poll_fn(|cx| service.poll_ready(cx)))
+                .await??;
+            let magic_type = service.call(Bytes::from_static(b"").into()).await?;
+            assert_snapshot!(magic_type, @"empty");
+        }
+
+        Ok::<_, anyhow::Error>(())
+    })
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "uring")]
+
+use common::*;
+use mojique::{Config, DefaultConfig, Error, Flag, UringBatch};
+
+mod common;
+
+#[test]
+fn detect() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+
+    // A depth of 2 ensures that the paths are read over multiple chunks.
+    let mut batch = UringBatch::new(pool).with_depth(2);
+    let paths = [
+        manifest_dir().join("tests/data/LICENSE.zst"),
+        manifest_dir().join("tests/data/missing"),
+        manifest_dir().join("tests/data/custom.magic"),
+        manifest_dir().join("tests/data"),
+        manifest_dir().join("tests/data/LICENSE.zst"),
+    ];
+
+    for _ in 0..2 {
+        let results = batch.detect(&paths)?;
+        assert_eq!(results.len(), paths.len());
+        assert_eq!(
+            results[0].as_ref().unwrap().description(),
+            "application/zstd"
+        );
+        assert!(matches!(
+            &results[1],
+            Err(Error::Detect { source, .. }) if matches!(**source, Error::ReadInput(_))
+        ));
+        assert!(results[2].as_ref().unwrap().matches("text/*"));
+        assert!(matches!(&results[3], Err(Error::Detect { .. })));
+        assert_eq!(results[4].as_ref().unwrap(), results[0].as_ref().unwrap());
+    }
+
+    assert!(batch.detect::<&str>(&[])?.is_empty());
+
+    Ok(())
+}
+
+#[cfg(any(feature = "blake3", feature = "xxhash"))]
+#[test]
+fn dedup() -> anyhow::Result<()> {
+    let algorithms = [
+        #[cfg(feature = "blake3")]
+        mojique::HashAlgorithm::Blake3,
+        #[cfg(feature = "xxhash")]
+        mojique::HashAlgorithm::Xxh3,
+    ];
+
+    for algorithm in algorithms {
+        let pool = DefaultConfig::default()
+            .set_flag(Flag::MimeType)
+            .build_pool()?;
+        let mut batch = UringBatch::new(pool).with_dedup(algorithm);
+        let paths = [
+            manifest_dir().join("tests/data/LICENSE.zst"),
+            manifest_dir().join("tests/data/custom.magic"),
+            manifest_dir().join("tests/data/LICENSE.zst"),
+            manifest_dir().join("tests/data/missing"),
+        ];
+
+        let results = batch.detect(&paths)?;
+        assert_eq!(
+            results[0].as_ref().unwrap().description(),
+            "application/zstd",
+            "{algorithm:?}"
+        );
+        assert!(results[1].as_ref().unwrap().matches("text/*"));
+        assert_eq!(results[2].as_ref().unwrap(), results[0].as_ref().unwrap());
+        assert!(results[3].is_err());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use common::*;
+use mojique::{
+    BufferConfig, CombinedConfig, Config, DefaultConfig, EnvConfig, Error, ErrorKind, FileConfig,
+};
+
+mod common;
+
+#[test]
+fn validate() -> anyhow::Result<()> {
+    let data = manifest_dir().join("tests/data");
+
+    DefaultConfig::default().validate()?;
+    FileConfig::default()
+        .with_file(data.join("custom.magic"))
+        .validate()?;
+    CombinedConfig::default()
+        .with_file(data.join("custom.magic"))
+        .validate()?;
+
+    assert!(matches!(
+        FileConfig::default()
+            .with_file(data.join("missing.magic"))
+            .validate(),
+        Err(Error::ReadDatabase { path, .. }) if path.ends_with("missing.magic")
+    ));
+    assert!(matches!(
+        FileConfig::default().with_file("a:b").validate(),
+        Err(Error::EmbeddedColons)
+    ));
+
+    assert!(matches!(
+        BufferConfig::default().validate(),
+        Err(Error::NoBuffers)
+    ));
+    assert!(matches!(
+        BufferConfig::default()
+            .with_buffer(b"x")
+            .with_buffer(b"")
+            .validate(),
+        Err(Error::EmptyBuffer(1))
+    ));
+
+    // An empty MAGIC means the default database, as it does when building. This is done last,
+    // since the default database is used above, and no other test in this file depends on the
+    // environment.
+    unsafe { std::env::set_var("MAGIC", "") };
+    let result = EnvConfig::default().validate();
+    let pool = EnvConfig::default().build_pool();
+    unsafe { std::env::remove_var("MAGIC") };
+    result?;
+    pool?;
+
+    Ok(())
+}
+
+#[test]
+fn try_with_file() -> anyhow::Result<()> {
+    let data = manifest_dir().join("tests/data");
+
+    let config = FileConfig::default().try_with_file(data.join("custom.magic"))?;
+    let mut handle = config.build_handle()?;
+    assert_eq!(handle.buffer(b"MOJIQUE")?, "mojique test data");
+
+    assert!(matches!(
+        FileConfig::default().try_with_file(data.join("missing.magic")),
+        Err(Error::ReadDatabase { path, .. }) if path.ends_with("missing.magic")
+    ));
+    assert!(matches!(
+        FileConfig::default().try_with_file("a:b"),
+        Err(Error::EmbeddedColons)
+    ));
+    assert_eq!(
+        FileConfig::default()
+            .try_with_file(data.join("missing.magic"))
+            .unwrap_err()
+            .kind(),
+        ErrorKind::DatabaseLoad
+    );
+    assert!(matches!(
+        FileConfig::default().try_with_file("a\0b"),
+        Err(Error::EmbeddedNuls)
+    ));
+    assert!(matches!(
+        CombinedConfig::default().try_with_file(data.join("missing.magic")),
+        Err(Error::ReadDatabase { .. })
+    ));
+
+    // Buffered loading allows colons, so the path is only rejected for not existing.
+    assert!(matches!(
+        FileConfig::default()
+            .with_buffered_loading(true)
+            .try_with_file("a:b"),
+        Err(Error::ReadDatabase { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn database_load() -> anyhow::Result<()> {
+    let data = manifest_dir().join("tests/data");
+
+    // libmagic only fails if none of the databases are valid, at which point each of them should
+    // be reported.
+    let Err(Error::DatabaseLoad { failures }) = FileConfig::default()
+        .with_files([data.join("invalid-magic"), data.join("missing.magic")])
+        .build_handle()
+    else {
+        panic!("expected DatabaseLoad");
+    };
+    let paths: Vec<_> = failures.iter().map(|(path, _)| path.clone()).collect();
+    assert_eq!(
+        paths,
+        vec![data.join("invalid-magic"), data.join("missing.magic")]
+    );
+
+    // Whereas if one database is valid, the others are ignored.
+    FileConfig::default()
+        .with_files([data.join("custom.magic"), data.join("invalid-magic")])
+        .build_handle()?;
+
+    Ok(())
+}
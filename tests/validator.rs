@@ -0,0 +1,105 @@
+use mojique::{Claims, Config, DefaultConfig, Flag, Validator, Violation};
+
+#[test]
+fn validator() -> anyhow::Result<()> {
+    let pool = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_pool()?;
+    let validator = Validator::new(pool)
+        .with_allowed(["text/*", "application/pdf"])
+        .with_denied(["text/x-shellscript"])
+        .with_content_type_check(true);
+
+    let report = validator.validate(b"%PDF-1.4\n", &Claims::default())?;
+    assert!(report.is_valid());
+    assert_eq!(report.detection().description(), "application/pdf");
+
+    // Denied patterns should take precedence over allowed patterns.
+    let report = validator.validate(b"#!/bin/sh\n", &Claims::default())?;
+    assert_eq!(
+        report.violations(),
+        [Violation::Denied {
+            pattern: "text/x-shellscript".into()
+        }]
+    );
+
+    let report = validator.validate(
+        b"GIF89a",
+        &Claims {
+            content_type: Some("application/pdf; name=x"),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(
+        report.violations(),
+        [
+            Violation::NotAllowed,
+            Violation::ContentTypeMismatch {
+                claimed: "application/pdf; name=x".into()
+            }
+        ]
+    );
+
+    // Parameters in the claimed Content-Type should be ignored.
+    let report = validator.validate(
+        b"%PDF-1.4\n",
+        &Claims {
+            content_type: Some("Application/PDF; charset=binary"),
+            ..Default::default()
+        },
+    )?;
+    assert!(report.is_valid());
+
+    Ok(())
+}
+
+#[test]
+fn extension() -> anyhow::Result<()> {
+    let validator = Validator::new(
+        DefaultConfig::default()
+            .set_flag(Flag::MimeType)
+            .build_pool()?,
+    )
+    .with_extension_check(
+        DefaultConfig::default()
+            .set_flag(Flag::Extension)
+            .build_pool()?,
+    );
+
+    let report = validator.validate(
+        b"GIF89a",
+        &Claims {
+            file_name: Some("cat.GIF"),
+            ..Default::default()
+        },
+    )?;
+    assert!(report.is_valid());
+    assert_eq!(report.extensions(), ["gif"]);
+
+    let report = validator.validate(
+        b"GIF89a",
+        &Claims {
+            file_name: Some("cat.pdf"),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(
+        report.violations(),
+        [Violation::ExtensionMismatch {
+            claimed: "pdf".into()
+        }]
+    );
+
+    // Unknown extensions for the content shouldn't be reported as mismatches.
+    let report = validator.validate(
+        b"#!/bin/sh\n",
+        &Claims {
+            file_name: Some("script.exe"),
+            ..Default::default()
+        },
+    )?;
+    assert!(report.extensions().is_empty());
+    assert!(report.is_valid());
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "watch")]
+
+use std::{sync::mpsc, time::Duration};
+
+use common::*;
+use mojique::{BufferConfig, Config, Error, FileConfig, WatchOptions};
+
+mod common;
+
+#[test]
+fn watch() -> anyhow::Result<()> {
+    let dir = TempDir::new("watch")?;
+    let magic = dir.join("test.magic");
+    std::fs::write(&magic, "0\tstring\tMOJIQUE\tbefore reload\n")?;
+
+    let pool = FileConfig::default().with_file(&magic).build_pool()?;
+    assert_eq!(pool.buffer(b"MOJIQUE")?, "before reload");
+
+    let (tx, rx) = mpsc::channel();
+    let watcher = pool.watch_with(
+        WatchOptions::default()
+            .debounce(Duration::from_millis(50))
+            .on_reload(move |event| tx.send(event).expect("send reload event")),
+    )?;
+
+    // Changes to other files in the same directory should be ignored.
+    std::fs::write(dir.join("unrelated"), "")?;
+    assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+
+    std::fs::write(&magic, "0\tstring\tMOJIQUE\tafter reload\n")?;
+    let event = rx.recv_timeout(Duration::from_secs(10))?;
+    assert_eq!(event.paths, vec![magic.clone()]);
+    assert!(event.result.is_ok());
+    assert_eq!(pool.buffer(b"MOJIQUE")?, "after reload");
+
+    drop(watcher);
+
+    // Pools built from buffers have nothing to watch.
+    let compiled = mojique::compile([&magic], &dir)?;
+    let pool = BufferConfig::default()
+        .with_owned_buffer(std::fs::read(&compiled[0])?)
+        .build_pool()?;
+    assert!(matches!(pool.watch(), Err(Error::WatchBuffers)));
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "zip")]
+
+use std::{fs::File, io::Cursor};
+
+use common::*;
+use insta::assert_debug_snapshot;
+use mojique::{Config, DefaultConfig, Error, Flag};
+
+mod common;
+
+#[test]
+fn zip_entries() -> anyhow::Result<()> {
+    let mut handle = DefaultConfig::default()
+        .set_flag(Flag::MimeType)
+        .build_handle()?;
+
+    let archive = File::open(manifest_dir().join("tests/data/archive.zip"))?;
+    let entries = handle
+        .zip_entries(archive)?
+        .map(|entry| entry.map(|(name, detection)| (name, detection.into_description())))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_debug_snapshot!(entries, @r#"
+    [
+        (
+            "document.pdf",
+            "application/pdf",
+        ),
+        (
+            "scripts/run.sh",
+            "text/x-shellscript",
+        ),
+    ]
+    "#);
+
+    // A short prefix should still be enough to recognise the PDF.
+    let archive = File::open(manifest_dir().join("tests/data/archive.zip"))?;
+    let (name, detection) = handle
+        .zip_entries(archive)?
+        .with_prefix_len(5)
+        .next()
+        .expect("archive has members")?;
+    assert_eq!(name, "document.pdf");
+    assert_eq!(detection.description(), "application/pdf");
+
+    assert!(matches!(
+        handle.zip_entries(Cursor::new(b"not a zip archive".to_vec())),
+        Err(Error::Archive(_))
+    ));
+
+    Ok(())
+}